@@ -1,5 +1,7 @@
 #![allow(clippy::upper_case_acronyms)]
 
+use std::collections::BTreeSet;
+
 use anyhow::Result;
 use log::{debug, error, info};
 #[macro_use]
@@ -7,10 +9,44 @@ extern crate clap;
 #[macro_use]
 extern crate anyhow;
 
-use lancelot::{analysis::dis, aspace::AddressSpace, loader::pe::PE, util, RVA, VA};
+use lancelot::{
+    analysis::dis,
+    arch::Arch,
+    aspace::AddressSpace,
+    loader::{pe::PE, raw::Raw},
+    module::{Module, Permissions},
+    search::Pattern,
+    util, RVA, VA,
+};
+use lancelot_bin::parse_va;
+
+fn handle_functions(pe: &PE, pdb: Option<&str>) -> Result<()> {
+    let functions = match pdb {
+        Some(pdb) => {
+            let symbols = lancelot::loader::pe::pdb::load_pdb_symbols(pe, pdb)?;
+            info!("loaded {} procedures from PDB", symbols.procedures.len());
+
+            lancelot::analysis::pe::find_functions_with_pdb(pe, &symbols)?
+                .into_iter()
+                .filter_map(|f| match f {
+                    lancelot::analysis::pe::Function::Local(va) => Some(va),
+                    _ => None,
+                })
+                .collect()
+        }
+        None => lancelot::analysis::pe::get_function_starts(lancelot::analysis::pe::find_functions_and_noret(pe)?.0),
+    };
+
+    info!("found {} functions", functions.len());
+    for va in functions.iter() {
+        println!("{:#x}", va);
+    }
+
+    Ok(())
+}
 
-fn handle_functions(pe: &PE) -> Result<()> {
-    let functions = lancelot::analysis::pe::find_function_starts(pe)?;
+fn handle_raw_functions(raw: &Raw) -> Result<()> {
+    let functions = lancelot::analysis::raw::find_function_starts(raw)?;
 
     info!("found {} functions", functions.len());
     for va in functions.iter() {
@@ -20,6 +56,134 @@ fn handle_functions(pe: &PE) -> Result<()> {
     Ok(())
 }
 
+fn handle_exports(pe: &PE) -> Result<()> {
+    use lancelot::loader::pe::exports::{read_exports, ExportTarget};
+
+    let exports = read_exports(pe)?;
+
+    info!("found {} exports", exports.len());
+    for export in exports.iter() {
+        let target = match &export.target {
+            ExportTarget::Code(va) => format!("{:#x}", va),
+            ExportTarget::Data(va) => format!("{:#x} (data)", va),
+            ExportTarget::Forwarder(forwarder) => format!("-> {}", forwarder),
+        };
+
+        println!(
+            "#{:<5} {:<40} {}",
+            export.ordinal,
+            export.name.as_deref().unwrap_or(""),
+            target
+        );
+    }
+
+    Ok(())
+}
+
+fn handle_debug(pe: &PE) -> Result<()> {
+    use lancelot::loader::pe::debug::{CodeView, DebugData};
+
+    let entries = pe.get_debug_entries()?;
+
+    info!("found {} debug entries", entries.len());
+    for entry in entries.iter() {
+        println!("{:<21} timestamp: {:#010x}", entry.kind_name(), entry.time_date_stamp);
+
+        match &entry.data {
+            Some(DebugData::CodeView(cv)) => {
+                if let CodeView::Pdb70 { guid, .. } = cv {
+                    println!("  guid: {}", guid);
+                }
+                println!("  age:  {}", cv.age());
+                println!("  path: {}", cv.path());
+                println!("  key:  {}", cv.symbol_server_key());
+            }
+            Some(DebugData::Pogo(pogo)) => {
+                for entry in pogo.entries.iter() {
+                    println!("  {:#x} {:#8x} {}", entry.address, entry.size, entry.name);
+                }
+            }
+            Some(DebugData::VcFeature(features)) => {
+                println!("  pre-VC++ 11.00: {}", features.pre_vc11);
+                println!("  C/C++:          {}", features.c_and_cpp);
+                println!("  /GS:            {}", features.gs);
+                println!("  /sdl:           {}", features.sdl);
+                println!("  /guardN:        {}", features.guard_n);
+            }
+            Some(DebugData::Repro(hash)) => {
+                if !hash.is_empty() {
+                    println!("  hash: {}", hex::encode(hash));
+                }
+            }
+            Some(DebugData::ExDllCharacteristics(characteristics)) => {
+                println!("  characteristics: {:#x}", characteristics);
+            }
+            Some(DebugData::Raw(buf)) => {
+                println!("  size: {:#x}", buf.len());
+            }
+            None => {
+                println!("  (data not present)");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_version(pe: &PE) -> Result<()> {
+    use lancelot::loader::pe::rsrc::{manifest::read_manifests, version::read_version_info};
+
+    match read_version_info(pe)? {
+        Some(version_info) => {
+            if let Some(fixed) = version_info.fixed {
+                println!("file version:    {}", fixed.file_version);
+                println!("product version: {}", fixed.product_version);
+            }
+
+            for table in version_info.string_tables.iter() {
+                println!("strings ({}):", table.key);
+                for (key, value) in table.strings.iter() {
+                    println!("  {:<20} {}", key, value);
+                }
+            }
+        }
+        None => {
+            info!("no version info found");
+        }
+    }
+
+    for manifest in read_manifests(pe)?.iter() {
+        if let Some(level) = manifest.requested_execution_level() {
+            println!(
+                "requested execution level: {} (uiAccess: {})",
+                level.level, level.ui_access
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_icons(pe: &PE, output: &str) -> Result<()> {
+    use lancelot::loader::pe::rsrc::{icon::read_icons, NodeIdentifier};
+
+    let icons = read_icons(pe)?;
+    info!("found {} icons", icons.len());
+
+    for icon in icons.iter() {
+        let name = match &icon.name {
+            NodeIdentifier::ID(id) => format!("{}", id),
+            NodeIdentifier::Name(name) => name.clone(),
+        };
+
+        let path = std::path::Path::new(output).join(format!("{}-{}.ico", name, icon.language));
+        std::fs::write(&path, &icon.ico)?;
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
 fn render_insn_buf(buf: &[u8], width: usize) -> String {
     let mut out = String::new();
     for (i, c) in hex::encode(buf).chars().enumerate() {
@@ -49,17 +213,14 @@ fn render_insn(insn: &zydis::ffi::DecodedInstruction, va: VA) -> String {
     format!("{}", buffer)
 }
 
-fn handle_disassemble(pe: &PE, va: VA) -> Result<()> {
-    let cfg = lancelot::analysis::cfg::build_cfg(&pe.module, va)?;
-    let decoder = dis::get_disassembler(&pe.module)?;
+fn handle_disassemble(module: &Module, va: VA, noret: &BTreeSet<VA>) -> Result<()> {
+    let cfg = lancelot::analysis::cfg::build_cfg_with_noret(module, va, noret)?;
+    let decoder = dis::get_disassembler(module)?;
 
     info!("found {} basic blocks", cfg.basic_blocks.len());
     for bb in cfg.basic_blocks.values() {
         // need to over-read the bb buffer, to account for the final instructions.
-        let buf = pe
-            .module
-            .address_space
-            .read_bytes(bb.address, bb.length as usize + 0x10)?;
+        let buf = module.address_space.read_bytes(bb.address, bb.length as usize + 0x10)?;
         for (offset, insn) in dis::linear_disassemble(&decoder, &buf) {
             // because we over-read the bb buffer,
             // discard the instructions found after it.
@@ -69,8 +230,7 @@ fn handle_disassemble(pe: &PE, va: VA) -> Result<()> {
 
             let va = bb.address + offset as RVA;
 
-            let name = &pe
-                .module
+            let name = &module
                 .sections
                 .iter()
                 .find(|sec| sec.virtual_range.contains(&va))
@@ -97,13 +257,50 @@ fn handle_disassemble(pe: &PE, va: VA) -> Result<()> {
     Ok(())
 }
 
-fn parse_va(s: &str) -> Result<VA> {
-    if s.starts_with("0x") {
-        let without_prefix = s.trim_start_matches("0x");
-        Ok(u64::from_str_radix(without_prefix, 16)?)
+fn handle_search(module: &Module, pattern: &str, executable: bool) -> Result<()> {
+    let pattern = Pattern::new(pattern)?;
+    let permissions = if executable {
+        Permissions::X
     } else {
-        Ok(s.parse()?)
+        Permissions::empty()
+    };
+
+    let mut count = 0;
+    for va in module.search(&pattern, permissions) {
+        let name = &module
+            .sections
+            .iter()
+            .find(|sec| sec.virtual_range.contains(&va))
+            .unwrap()
+            .name;
+
+        println!("{}:{:016x}", name, va);
+        count += 1;
     }
+    info!("found {} matches", count);
+
+    Ok(())
+}
+
+/// load the input as raw bytes, such as shellcode, at the given base address.
+/// the bytes are mapped RWX, and, by default, execution starts at the first
+/// byte.
+fn load_raw(buf: &[u8], matches: &clap::ArgMatches) -> Result<Raw> {
+    let arch: Arch = matches.value_of("arch").unwrap().parse()?;
+    let base_address = match matches.value_of("base") {
+        Some(base) => parse_va(base)?,
+        None => 0x0,
+    };
+    let entry_point = match matches.value_of("entry") {
+        Some(entry) => parse_va(entry)?,
+        None => base_address,
+    };
+    debug!(
+        "raw: arch: {:?} base: {:#x} entry: {:#x}",
+        arch, base_address, entry_point
+    );
+
+    Raw::from_bytes(buf, arch, base_address, Permissions::RWX, Some(entry_point))
 }
 
 fn _main() -> Result<()> {
@@ -116,11 +313,40 @@ fn _main() -> Result<()> {
         (@arg quiet: -q --quiet "disable informational messages")
         (@subcommand functions =>
             (about: "find functions")
+            (@arg pdb: --pdb +takes_value "path to matching PDB file")
+            (@arg format: --format +takes_value possible_value[pe raw] default_value("pe") "input file format")
+            (@arg arch: --arch +takes_value possible_value[x32 x64] required_if("format", "raw") "architecture of raw input")
+            (@arg base: --base +takes_value "address at which to load raw input (default: 0x0)")
+            (@arg entry: --entry +takes_value "entry point of raw input (default: base address)")
             (@arg input: +required "path to file to analyze"))
+        (@subcommand exports =>
+            (about: "list exports")
+            (@arg input: +required "path to file to analyze"))
+        (@subcommand debug =>
+            (about: "list debug directory entries, like the PDB reference")
+            (@arg input: +required "path to file to analyze"))
+        (@subcommand version =>
+            (about: "show version info and manifest resources")
+            (@arg input: +required "path to file to analyze"))
+        (@subcommand icons =>
+            (about: "extract icon resources as .ico files")
+            (@arg input: +required "path to file to analyze")
+            (@arg output: +required "directory into which to write .ico files"))
         (@subcommand disassemble =>
             (about: "disassemble function")
+            (@arg format: --format +takes_value possible_value[pe raw] default_value("pe") "input file format")
+            (@arg arch: --arch +takes_value possible_value[x32 x64] required_if("format", "raw") "architecture of raw input")
+            (@arg base: --base +takes_value "address at which to load raw input (default: 0x0)")
             (@arg input: +required "path to file to analyze")
-            (@arg va: +required "VA of function")))
+            (@arg va: +required "VA of function"))
+        (@subcommand search =>
+            (about: "search for a byte pattern, like: 55 8B EC ?? [2-4] C3")
+            (@arg executable: -x --executable "only search executable sections")
+            (@arg format: --format +takes_value possible_value[pe raw] default_value("pe") "input file format")
+            (@arg arch: --arch +takes_value possible_value[x32 x64] required_if("format", "raw") "architecture of raw input")
+            (@arg base: --base +takes_value "address at which to load raw input (default: 0x0)")
+            (@arg pattern: +required "byte pattern, in the syntax of YARA hex strings")
+            (@arg input: +required "path to file to analyze")))
     .get_matches();
 
     // --quiet overrides --verbose
@@ -161,10 +387,58 @@ fn _main() -> Result<()> {
         let filename = matches.value_of("input").unwrap();
         debug!("input: {}", filename);
 
+        let buf = util::read_file(filename)?;
+        if matches.value_of("format") == Some("raw") {
+            if matches.is_present("pdb") {
+                return Err(anyhow!("--pdb is not supported for raw input"));
+            }
+
+            let raw = load_raw(&buf, matches)?;
+            handle_raw_functions(&raw)
+        } else {
+            let pe = PE::from_bytes(&buf)?;
+            handle_functions(&pe, matches.value_of("pdb"))
+        }
+    } else if let Some(matches) = matches.subcommand_matches("exports") {
+        debug!("mode: list exports");
+
+        let filename = matches.value_of("input").unwrap();
+        debug!("input: {}", filename);
+
         let buf = util::read_file(filename)?;
         let pe = PE::from_bytes(&buf)?;
 
-        handle_functions(&pe)
+        handle_exports(&pe)
+    } else if let Some(matches) = matches.subcommand_matches("debug") {
+        debug!("mode: list debug entries");
+
+        let filename = matches.value_of("input").unwrap();
+        debug!("input: {}", filename);
+
+        let buf = util::read_file(filename)?;
+        let pe = PE::from_bytes(&buf)?;
+
+        handle_debug(&pe)
+    } else if let Some(matches) = matches.subcommand_matches("version") {
+        debug!("mode: show version info");
+
+        let filename = matches.value_of("input").unwrap();
+        debug!("input: {}", filename);
+
+        let buf = util::read_file(filename)?;
+        let pe = PE::from_bytes(&buf)?;
+
+        handle_version(&pe)
+    } else if let Some(matches) = matches.subcommand_matches("icons") {
+        debug!("mode: extract icons");
+
+        let filename = matches.value_of("input").unwrap();
+        debug!("input: {}", filename);
+
+        let buf = util::read_file(filename)?;
+        let pe = PE::from_bytes(&buf)?;
+
+        handle_icons(&pe, matches.value_of("output").unwrap())
     } else if let Some(matches) = matches.subcommand_matches("disassemble") {
         debug!("mode: disassemble");
 
@@ -174,9 +448,32 @@ fn _main() -> Result<()> {
         let va = parse_va(matches.value_of("va").unwrap())?;
 
         let buf = util::read_file(filename)?;
-        let pe = PE::from_bytes(&buf)?;
+        if matches.value_of("format") == Some("raw") {
+            let raw = load_raw(&buf, matches)?;
+            handle_disassemble(&raw.module, va, &Default::default())
+        } else {
+            let pe = PE::from_bytes(&buf)?;
+            // don't disassemble past calls to functions that don't return.
+            let (_, noret) = lancelot::analysis::pe::find_functions_and_noret(&pe)?;
+            handle_disassemble(&pe.module, va, &noret)
+        }
+    } else if let Some(matches) = matches.subcommand_matches("search") {
+        debug!("mode: search");
 
-        handle_disassemble(&pe, va)
+        let filename = matches.value_of("input").unwrap();
+        debug!("input: {}", filename);
+
+        let pattern = matches.value_of("pattern").unwrap();
+        let executable = matches.is_present("executable");
+
+        let buf = util::read_file(filename)?;
+        if matches.value_of("format") == Some("raw") {
+            let raw = load_raw(&buf, matches)?;
+            handle_search(&raw.module, pattern, executable)
+        } else {
+            let pe = PE::from_bytes(&buf)?;
+            handle_search(&pe.module, pattern, executable)
+        }
     } else {
         Err(anyhow!("SUBCOMMAND required"))
     }
@@ -13,7 +13,7 @@
 // TODO: function names
 // TODO: flirt function names
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use anyhow::Result;
 use log::{debug, error};
@@ -26,7 +26,12 @@ use ansi_term::Colour as Color;
 use lancelot::{
     aspace::{AbsoluteAddressSpace, AddressSpace},
     loader::pe::{
+        authenticode::{
+            get_certificate_table, is_authenticode_hash_valid, parse_signed_data, read_certificates,
+            WIN_CERT_TYPE_PKCS_SIGNED_DATA,
+        },
         imports::{get_import_directory, read_import_descriptors, read_thunks, IMAGE_THUNK_DATA},
+        rich::{read_rich_header, RichHeader},
         rsrc::{NodeChild, NodeIdentifier, ResourceDataType, ResourceSectionData},
         PE,
     },
@@ -40,6 +45,7 @@ enum Structure {
     /// the file headers.
     Header,
     IMAGE_DOS_HEADER,
+    RichHeader(RichHeader),
     IMAGE_NT_HEADERS,
     Signature,
     IMAGE_FILE_HEADER,
@@ -52,6 +58,8 @@ enum Structure {
     ResourceTable,
     ExceptionTable,
     CertificateTable,
+    /// an Authenticode signature, summarized.
+    Authenticode(String),
     BaseRelocationTable,
     DebugData,
     TlsTable,
@@ -71,6 +79,7 @@ impl std::fmt::Display for Structure {
             Structure::File => write!(f, "file"),
             Structure::Header => write!(f, "headers"),
             Structure::IMAGE_DOS_HEADER => write!(f, "IMAGE_DOS_HEADER"),
+            Structure::RichHeader(_) => write!(f, "Rich header"),
             Structure::IMAGE_NT_HEADERS => write!(f, "IMAGE_NT_HEADERS"),
             Structure::Signature => write!(f, "signature"),
             Structure::IMAGE_FILE_HEADER => write!(f, "IMAGE_FILE_HEADER"),
@@ -82,6 +91,7 @@ impl std::fmt::Display for Structure {
             Structure::ResourceTable => write!(f, "resource table"),
             Structure::ExceptionTable => write!(f, "exception table"),
             Structure::CertificateTable => write!(f, "certificate table"),
+            Structure::Authenticode(s) => write!(f, "Authenticode signature: {}", s),
             Structure::BaseRelocationTable => write!(f, "base relocation table"),
             Structure::DebugData => write!(f, "debug data"),
             Structure::TlsTable => write!(f, "TLS table"),
@@ -322,6 +332,17 @@ fn insert_dos_header_range(ranges: &mut Ranges, pe: &PE) -> Result<()> {
     )
 }
 
+fn insert_rich_header_range(ranges: &mut Ranges, pe: &PE) -> Result<()> {
+    if let Some(rich) = read_rich_header(pe) {
+        ranges.insert(
+            rich.start as FileOffset,
+            rich.end as FileOffset,
+            Structure::RichHeader(rich),
+        )?;
+    }
+    Ok(())
+}
+
 fn insert_signature_range(ranges: &mut Ranges, pe: &PE) -> Result<()> {
     let base_address = pe.module.address_space.base_address;
     let start = base_address + offset_IMAGE_NT_HEADERS(pe);
@@ -372,6 +393,7 @@ fn insert_file_header_range(ranges: &mut Ranges, pe: &PE) -> Result<()> {
         Structure::Header,
     )?;
     insert_dos_header_range(ranges, pe)?;
+    insert_rich_header_range(ranges, pe)?;
     insert_image_nt_headers_range(ranges, pe)?;
     insert_image_file_header_range(ranges, pe)?;
     insert_image_optional_header_range(ranges, pe)?;
@@ -433,10 +455,7 @@ fn insert_data_directory_ranges(ranges: &mut Ranges, pe: &PE) -> Result<()> {
                 Box::new(|| *opt.data_directories.get_exception_table()),
                 Structure::ExceptionTable,
             ),
-            (
-                Box::new(|| *opt.data_directories.get_certificate_table()),
-                Structure::CertificateTable,
-            ),
+            // the certificate table is handled by insert_certificate_table_range
             (
                 Box::new(|| *opt.data_directories.get_base_relocation_table()),
                 Structure::BaseRelocationTable,
@@ -479,6 +498,53 @@ fn insert_data_directory_ranges(ranges: &mut Ranges, pe: &PE) -> Result<()> {
     Ok(())
 }
 
+/// unlike the other data directories, the certificate table is referenced by
+/// file offset, and isn't mapped into memory.
+fn insert_certificate_table_range(ranges: &mut Ranges, pe: &PE) -> Result<()> {
+    if let Some((start, size)) = get_certificate_table(pe) {
+        ranges.insert(
+            start as FileOffset,
+            (start + size) as FileOffset,
+            Structure::CertificateTable,
+        )?;
+
+        for certificate in read_certificates(pe)?.into_iter() {
+            let summary = if certificate.certificate_type == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+                match parse_signed_data(&certificate.data) {
+                    Ok(signature) => {
+                        let signer = signature
+                            .signers
+                            .first()
+                            .and_then(|signer| signature.get_signer_certificate(signer))
+                            .map(|cert| cert.subject.clone())
+                            .unwrap_or_else(|| "unknown signer".to_string());
+
+                        let status = match is_authenticode_hash_valid(pe, &signature) {
+                            Ok(true) => "valid",
+                            Ok(false) => "invalid",
+                            Err(_) => "unknown",
+                        };
+
+                        format!("{}, {} image hash {}", signer, signature.digest_algorithm, status)
+                    }
+                    Err(e) => format!("malformed: {}", e),
+                }
+            } else {
+                format!("certificate type {:#x}", certificate.certificate_type)
+            };
+
+            // the certificate data, following the `WIN_CERTIFICATE` header.
+            let start = certificate.offset + 0x8;
+            ranges.insert(
+                start as FileOffset,
+                (start + certificate.data.len()) as FileOffset,
+                Structure::Authenticode(summary),
+            )?;
+        }
+    }
+    Ok(())
+}
+
 /// in typical binaries compiled by MSVC,
 /// the import table and import address table immediately precede the ASCII
 /// strings of the DLLs and exported names required by the program.
@@ -578,11 +644,9 @@ fn insert_resource_ranges(ranges: &mut Ranges, pe: &PE) -> Result<()> {
 /// add a range for each basic block. these won't be rendered, though.
 /// add a range for each function, from its start through all contiguous basic
 /// blocks. only the function start address will be rendered.
-fn insert_function_ranges(ranges: &mut Ranges, pe: &PE) -> Result<()> {
-    let functions = lancelot::analysis::pe::find_function_starts(pe)?;
-
+fn insert_function_ranges(ranges: &mut Ranges, pe: &PE, functions: &[VA], noret: &BTreeSet<VA>) -> Result<()> {
     for &function in functions.iter() {
-        if let Ok(cfg) = lancelot::analysis::cfg::build_cfg(&pe.module, function) {
+        if let Ok(cfg) = lancelot::analysis::cfg::build_cfg_with_noret(&pe.module, function, noret) {
             let mut end = function;
             for bb in cfg.basic_blocks.values() {
                 if bb.address != end {
@@ -601,7 +665,7 @@ fn insert_function_ranges(ranges: &mut Ranges, pe: &PE) -> Result<()> {
     Ok(())
 }
 
-fn insert_string_ranges(ranges: &mut Ranges, pe: &PE) -> Result<()> {
+fn insert_string_ranges(ranges: &mut Ranges, pe: &PE, functions: &[VA], noret: &BTreeSet<VA>) -> Result<()> {
     let mut section_bufs: Vec<Vec<u8>> = pe
         .module
         .sections
@@ -613,9 +677,9 @@ fn insert_string_ranges(ranges: &mut Ranges, pe: &PE) -> Result<()> {
         })
         .collect();
 
-    for function in lancelot::analysis::pe::find_function_starts(pe)?.into_iter() {
+    for &function in functions.iter() {
         // TODO: handle failure here gracefully.
-        let cfg = lancelot::analysis::cfg::build_cfg(&pe.module, function)?;
+        let cfg = lancelot::analysis::cfg::build_cfg_with_noret(&pe.module, function, noret)?;
 
         for bb in cfg.basic_blocks.values() {
             let (i, sec) = pe
@@ -672,10 +736,14 @@ fn compute_ranges(buf: &[u8], pe: &PE) -> Result<Ranges> {
     insert_section_header_ranges(&mut ranges, pe)?;
     insert_section_ranges(&mut ranges, pe)?;
     insert_data_directory_ranges(&mut ranges, pe)?;
+    insert_certificate_table_range(&mut ranges, pe)?;
     insert_imports_range(&mut ranges, pe)?;
     insert_resource_ranges(&mut ranges, pe)?;
-    insert_function_ranges(&mut ranges, pe)?;
-    insert_string_ranges(&mut ranges, pe)?;
+
+    let (functions, noret) = lancelot::analysis::pe::find_functions_and_noret(pe)?;
+    let functions = lancelot::analysis::pe::get_function_starts(functions);
+    insert_function_ranges(&mut ranges, pe, &functions, &noret)?;
+    insert_string_ranges(&mut ranges, pe, &functions, &noret)?;
 
     Ok(ranges)
 }
@@ -766,6 +834,43 @@ fn format_range_hex(address_space: &AbsoluteAddressSpace, range: &Range) -> Stri
     )
 }
 
+/// render the decoded Rich header entries like:
+///
+///   ┌── 0x000080 Rich header ────
+///   │   prodid  build  count  product
+///   │   0x0102  25711      1  Linker1400 (Visual Studio 2017)
+///   └── 0x0000e0  ────
+fn format_rich_header(range: &Range, rich: &RichHeader) -> String {
+    let mut lines: Vec<String> = vec![String::from(" prodid  build  count  product")];
+    for entry in rich.entries.iter() {
+        let product = match (entry.product_name(), entry.toolchain()) {
+            (Some(name), Some(toolchain)) => format!("{} ({})", name, toolchain),
+            (Some(name), None) => name.to_string(),
+            (None, _) => String::from("?"),
+        };
+        lines.push(format!(
+            " {:#06x} {:>6} {:>6}  {}",
+            entry.prodid, entry.build, entry.count, product
+        ));
+    }
+
+    if rich.is_checksum_valid() {
+        lines.push(format!(" checksum: {:#010x} (valid)", rich.key));
+    } else {
+        lines.push(format!(
+            " checksum: {:#010x} (invalid, computed: {:#010x})",
+            rich.key, rich.checksum
+        ));
+    }
+
+    format!(
+        "{}\n{}\n{}",
+        format_block_start(range),
+        prefix(1, &lines.join("\n")),
+        format_block_end(range)
+    )
+}
+
 fn will_render_as_block<'a>(ranges: &'a Ranges, range: &'a Range) -> bool {
     match &range.structure {
         // these are always rendered inline
@@ -773,6 +878,7 @@ fn will_render_as_block<'a>(ranges: &'a Ranges, range: &'a Range) -> bool {
         Structure::String(_) => false,
         // these are always rendered as a hex dump
         Structure::IMAGE_DOS_HEADER => true,
+        Structure::RichHeader(_) => true,
         Structure::Signature => true,
         Structure::IMAGE_FILE_HEADER => true,
         Structure::IMAGE_OPTIONAL_HEADER => true,
@@ -794,6 +900,7 @@ fn render_range<'a>(
         Structure::Function(s) => prefixln(depth, &format!(" {:#08x}: {}", range.start, s)),
         Structure::String(s) => prefixln(depth, &format!(" {:#08x}: \"{}\"", range.start, s)),
         Structure::IMAGE_DOS_HEADER => prefixln(depth, &format_range_hex(address_space, range)),
+        Structure::RichHeader(rich) => prefixln(depth, &format_rich_header(range, rich)),
         Structure::Signature => prefixln(depth, &format_range_hex(address_space, range)),
         Structure::IMAGE_FILE_HEADER => prefixln(depth, &format_range_hex(address_space, range)),
         Structure::IMAGE_OPTIONAL_HEADER => prefixln(depth, &format_range_hex(address_space, range)),
@@ -7,9 +7,39 @@ use log::{debug, error, info};
 #[macro_use]
 extern crate clap;
 
-use lancelot::{loader::pe::PE, util, VA};
+use lancelot::{
+    arch::Arch,
+    loader::{pe::PE, raw::Raw},
+    module::{Module, Permissions},
+    util, VA,
+};
+use lancelot_bin::parse_va;
 use lancelot_flirt::*;
 
+/// load the input and find its functions,
+/// either as a PE file, or as raw bytes, such as shellcode or carved memory.
+fn load(buf: &[u8], matches: &clap::ArgMatches) -> Result<(Module, Vec<VA>)> {
+    if matches.value_of("format") == Some("raw") {
+        let arch: Arch = matches.value_of("arch").unwrap().parse()?;
+        let base_address = match matches.value_of("base") {
+            Some(base) => parse_va(base)?,
+            None => 0x0,
+        };
+        let entry_point = match matches.value_of("entry") {
+            Some(entry) => parse_va(entry)?,
+            None => base_address,
+        };
+
+        let raw = Raw::from_bytes(buf, arch, base_address, Permissions::RWX, Some(entry_point))?;
+        let functions = lancelot::analysis::raw::find_function_starts(&raw)?;
+        Ok((raw.module, functions))
+    } else {
+        let pe = PE::from_bytes(buf)?;
+        let functions = lancelot::analysis::pe::find_function_starts(&pe)?;
+        Ok((pe.module, functions))
+    }
+}
+
 fn _main() -> Result<()> {
     better_panic::install();
 
@@ -18,6 +48,10 @@ fn _main() -> Result<()> {
         (about: "Binary analysis framework")
         (@arg verbose: -v --verbose +multiple "log verbose messages")
         (@arg quiet: -q --quiet "disable informational messages")
+        (@arg format: --format +takes_value possible_value[pe raw] default_value("pe") "input file format")
+        (@arg arch: --arch +takes_value possible_value[x32 x64] required_if("format", "raw") "architecture of raw input")
+        (@arg base: --base +takes_value "address at which to load raw input (default: 0x0)")
+        (@arg entry: --entry +takes_value "entry point of raw input (default: base address)")
         (@arg input: +required "path to file to analyze")
         (@arg sig: +required +multiple "path to FLIRT sig/pat"))
     .get_matches();
@@ -70,9 +104,7 @@ fn _main() -> Result<()> {
     debug!("input: {}", filename);
 
     let buf = util::read_file(filename)?;
-    let pe = PE::from_bytes(&buf)?;
-
-    let mut functions = lancelot::analysis::pe::find_function_starts(&pe)?;
+    let (module, mut functions) = load(&buf, &matches)?;
     functions.sort_unstable();
     info!("found {} functions", functions.len());
 
@@ -94,7 +126,7 @@ fn _main() -> Result<()> {
     let mut names: BTreeMap<VA, BTreeSet<Name>> = Default::default();
 
     for &va in functions.iter() {
-        for sig in lancelot::analysis::flirt::match_flirt(&pe.module, &sigs, va)
+        for sig in lancelot::analysis::flirt::match_flirt(&module, &sigs, va)
             .unwrap_or_default()
             .iter()
         {
@@ -56,11 +56,12 @@ fn _main() -> Result<()> {
     let buf = util::read_file(filename)?;
     let pe = PE::from_bytes(&buf)?;
 
-    let functions = lancelot::analysis::pe::find_function_starts(&pe)?;
+    let (functions, noret) = lancelot::analysis::pe::find_functions_and_noret(&pe)?;
+    let functions = lancelot::analysis::pe::get_function_starts(functions);
     info!("found {} functions", functions.len());
 
     for &va in functions.iter() {
-        let cfg = lancelot::analysis::cfg::build_cfg(&pe.module, va)?;
+        let cfg = lancelot::analysis::cfg::build_cfg_with_noret(&pe.module, va, &noret)?;
         println!("{:#x}: {} basic blocks", va, cfg.basic_blocks.len());
     }
 
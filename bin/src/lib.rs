@@ -1 +1,15 @@
 extern crate log;
+
+use anyhow::Result;
+use lancelot::VA;
+
+/// parse a virtual address given on the command line,
+/// either as hex with a `0x` prefix, or as decimal.
+pub fn parse_va(s: &str) -> Result<VA> {
+    if s.starts_with("0x") {
+        let without_prefix = s.trim_start_matches("0x");
+        Ok(u64::from_str_radix(without_prefix, 16)?)
+    } else {
+        Ok(s.parse()?)
+    }
+}
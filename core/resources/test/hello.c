@@ -0,0 +1,29 @@
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+int add(int a, int b) {
+    return a + b;
+}
+
+char *greeting(const char *name) {
+    size_t len = strlen(name);
+    char *buf = malloc(len + sizeof("hello, "));
+    if (buf == NULL) {
+        return NULL;
+    }
+    strcpy(buf, "hello, ");
+    strcat(buf, name);
+    return buf;
+}
+
+int main(int argc, char **argv) {
+    char *s = greeting(argc > 1 ? argv[1] : "world");
+    if (s == NULL) {
+        exit(1);
+    }
+    puts(s);
+    free(s);
+    printf("%d\n", add(argc, 2));
+    return 0;
+}
@@ -0,0 +1,45 @@
+# a small COFF object and import library, for testing the COFF loader.
+#
+#   llvm-mc -triple x86_64-pc-windows-msvc -filetype=obj -o hello.obj hello.coff.s
+#   llvm-dlltool -m i386:x86-64 -d kernel32.def -l kernel32.lib
+#   llvm-lib /out:hello.lib hello.obj kernel32.lib
+#
+# where kernel32.def contains:
+#
+#   LIBRARY kernel32.dll
+#   EXPORTS
+#     ExitProcess
+#     GetLastError @12 NONAME
+
+    .text
+    .globl  main
+    .def    main; .scl 2; .type 32; .endef
+main:
+    sub     $0x28, %rsp
+    call    add
+    call    helper
+    mov     %eax, %ecx
+    call    *__imp_ExitProcess(%rip)
+    int3
+
+    .globl  add
+    .def    add; .scl 2; .type 32; .endef
+add:
+    lea     (%rcx,%rdx), %eax
+    ret
+
+    .def    helper; .scl 3; .type 32; .endef
+helper:
+    lea     message(%rip), %rax
+    ret
+
+    .data
+message:
+    .asciz  "hello world"
+counter:
+    .quad   main
+
+    .bss
+    .globl  buffer
+buffer:
+    .zero   0x100
@@ -0,0 +1,495 @@
+#!/usr/bin/env python3
+"""
+build `hello.macho`: a minimal universal (fat) Mach-O with an x86-64 executable,
+laid out like ld64 output, plus an arm64 slice with no load commands.
+
+there's no macOS toolchain in the build environment, so the image is assembled by hand.
+it corresponds to:
+
+    static int helper(int x) { return x + x; }
+    int add(int a, int b) { return a + b; }
+    int main() {
+        puts("hello, world");
+        printf("%d\\n", add(helper(1), 2));
+        exit(0);
+    }
+
+where `exit` is called through its GOT slot, like with `-fno-plt`.
+
+usage: python3 hello.macho.py > hello.macho
+"""
+import struct
+import sys
+
+BASE = 0x100000000
+PAGE = 0x1000
+
+
+def uleb(v):
+    out = b""
+    while True:
+        b = v & 0x7F
+        v >>= 7
+        if v:
+            out += bytes([b | 0x80])
+        else:
+            return out + bytes([b])
+
+
+def pad(b, n):
+    return b + b"\x00" * (n - len(b))
+
+
+def align(v, n):
+    return (v + n - 1) // n * n
+
+
+def cstr_cmd(cmd, offset, s, fixed):
+    s = s.encode() + b"\x00"
+    size = align(8 + len(fixed) + len(s), 8)
+    return pad(struct.pack("<II", cmd, size) + fixed + s, size)
+
+
+# layout of the x86-64 slice, as file offsets (== RVAs within __TEXT and __DATA).
+TEXT_OFF = 0x500
+DATA_SEG_OFF = 0x1000
+LINKEDIT_OFF = 0x2000
+
+# __DATA sections
+GOT_OFF = DATA_SEG_OFF + 0x0  # dyld_stub_binder, _exit
+LA_OFF = DATA_SEG_OFF + 0x10  # _printf, _puts
+DATA_OFF = DATA_SEG_OFF + 0x20  # __dyld_private
+
+# imports, sorted like ld does.
+GOT_SYMBOLS = ["dyld_stub_binder", "_exit"]
+LAZY_SYMBOLS = ["_printf", "_puts"]
+
+
+def build_text():
+    """
+    assemble __text, __stubs, __stub_helper and __cstring,
+    given the fixed address of each.
+    returns their addresses and contents.
+    """
+    # sizes are fixed, so compute the layout first.
+    main_size = 0x40
+    add_size = 0x4
+    helper_size = 0x5
+    text_size = main_size + add_size + helper_size
+    text = TEXT_OFF
+    main = text
+    add = main + main_size
+    helper = add + add_size
+
+    stubs = align(text + text_size, 2)
+    stubs_size = 6 * len(LAZY_SYMBOLS)
+    stub_helper = align(stubs + stubs_size, 4)
+    stub_helper_size = 0x10 + 0xA * len(LAZY_SYMBOLS)
+    cstring = stub_helper + stub_helper_size
+    hello_str = cstring
+    fmt_str = hello_str + len(b"hello, world\x00")
+    cstring_data = b"hello, world\x00%d\n\x00"
+
+    def rel32(src_next, dst):
+        return struct.pack("<i", dst - src_next)
+
+    code = b""
+
+    def emit(b):
+        nonlocal code
+        code += b
+
+    def here():
+        return text + len(code)
+
+    # _main
+    emit(b"\x55")  # push rbp
+    emit(b"\x48\x89\xe5")  # mov rbp, rsp
+    emit(b"\x48\x8d\x3d" + rel32(here() + 7, hello_str))  # lea rdi, [rip+hello]
+    emit(b"\xe8" + rel32(here() + 5, stubs + 6 * LAZY_SYMBOLS.index("_puts")))  # call _puts
+    emit(b"\xbf\x01\x00\x00\x00")  # mov edi, 1
+    emit(b"\xe8" + rel32(here() + 5, helper))  # call helper
+    emit(b"\x89\xc7")  # mov edi, eax
+    emit(b"\xbe\x02\x00\x00\x00")  # mov esi, 2
+    emit(b"\xe8" + rel32(here() + 5, add))  # call _add
+    emit(b"\x48\x8d\x3d" + rel32(here() + 7, fmt_str))  # lea rdi, [rip+fmt]
+    emit(b"\x89\xc6")  # mov esi, eax
+    emit(b"\x31\xc0")  # xor eax, eax
+    emit(b"\xe8" + rel32(here() + 5, stubs + 6 * LAZY_SYMBOLS.index("_printf")))  # call _printf
+    emit(b"\x31\xff")  # xor edi, edi
+    emit(b"\xff\x15" + rel32(here() + 6, GOT_OFF + 8 * GOT_SYMBOLS.index("_exit")))  # call [rip+_exit@GOT]
+    emit(b"\x5d")  # pop rbp
+    emit(b"\xc3")  # ret
+    assert here() == add, hex(here())
+
+    # _add
+    emit(b"\x8d\x04\x37")  # lea eax, [rdi+rsi]
+    emit(b"\xc3")  # ret
+    assert here() == helper
+
+    # helper
+    emit(b"\x89\xf8")  # mov eax, edi
+    emit(b"\x01\xc0")  # add eax, eax
+    emit(b"\xc3")  # ret
+    assert len(code) == text_size
+
+    # __stubs: jmp [rip+lazy pointer]
+    stub_code = b""
+    for i, _ in enumerate(LAZY_SYMBOLS):
+        va = stubs + 6 * i
+        stub_code += b"\xff\x25" + rel32(va + 6, LA_OFF + 8 * i)
+
+    # __stub_helper
+    helper_code = b""
+    helper_code += b"\x4c\x8d\x1d" + rel32(stub_helper + 7, DATA_OFF)  # lea r11, [rip+__dyld_private]
+    helper_code += b"\x41\x53"  # push r11
+    helper_code += b"\xff\x25" + rel32(stub_helper + 15, GOT_OFF + 8 * GOT_SYMBOLS.index("dyld_stub_binder"))
+    helper_code += b"\x90"  # nop
+    # the entries are filled in once the lazy binding info offsets are known.
+
+    return {
+        "text": (text, code),
+        "stubs": (stubs, stub_code),
+        "stub_helper": (stub_helper, helper_code, stub_helper_size),
+        "cstring": (cstring, cstring_data),
+        "functions": [main, add, helper],
+        "main": main,
+        "add": add,
+    }
+
+
+def build_x64():
+    t = build_text()
+    text_addr, text_code = t["text"]
+    stubs_addr, stubs_code = t["stubs"]
+    stub_helper_addr, stub_helper_code, stub_helper_size = t["stub_helper"]
+    cstring_addr, cstring_data = t["cstring"]
+
+    # lazy binding info, one record per lazy pointer.
+    lazy_bind = b""
+    lazy_offsets = []
+    for i, name in enumerate(LAZY_SYMBOLS):
+        lazy_offsets.append(len(lazy_bind))
+        lazy_bind += b"\x72" + uleb(LA_OFF - DATA_SEG_OFF + 8 * i)  # SET_SEGMENT_AND_OFFSET_ULEB(2)
+        lazy_bind += b"\x11"  # SET_DYLIB_ORDINAL_IMM(1)
+        lazy_bind += b"\x40" + name.encode() + b"\x00"  # SET_SYMBOL_TRAILING_FLAGS_IMM(0)
+        lazy_bind += b"\x90"  # DO_BIND
+        lazy_bind += b"\x00"  # DONE
+
+    # stub helper entries: push lazy binding offset; jmp stub_helper
+    for i, off in enumerate(lazy_offsets):
+        va = stub_helper_addr + 0x10 + 0xA * i
+        stub_helper_code += b"\x68" + struct.pack("<I", off)
+        stub_helper_code += b"\xe9" + struct.pack("<i", stub_helper_addr - (va + 10))
+    assert len(stub_helper_code) == stub_helper_size
+
+    # non-lazy binding info, for the GOT.
+    bind = b""
+    bind += b"\x11"  # SET_DYLIB_ORDINAL_IMM(1)
+    bind += b"\x40" + GOT_SYMBOLS[0].encode() + b"\x00"
+    bind += b"\x51"  # SET_TYPE_IMM(POINTER)
+    bind += b"\x72" + uleb(GOT_OFF - DATA_SEG_OFF)
+    bind += b"\x90"  # DO_BIND
+    bind += b"\x40" + GOT_SYMBOLS[1].encode() + b"\x00"
+    bind += b"\x90"  # DO_BIND
+    bind += b"\x00"  # DONE
+
+    # rebase info, for the lazy pointers, which initially point into the stub helper.
+    rebase = b""
+    rebase += b"\x11"  # SET_TYPE_IMM(POINTER)
+    rebase += b"\x22" + uleb(LA_OFF - DATA_SEG_OFF)  # SET_SEGMENT_AND_OFFSET_ULEB(2)
+    rebase += bytes([0x50 | len(LAZY_SYMBOLS)])  # DO_REBASE_IMM_TIMES(n)
+    rebase += b"\x00"  # DONE
+
+    # function starts, as ULEB128 deltas from the start of __TEXT.
+    function_starts = b""
+    prev = 0
+    for f in t["functions"]:
+        function_starts += uleb(f - prev)
+        prev = f
+    function_starts += b"\x00"
+
+    # symbol table: locals (none), external definitions, undefined externals.
+    # the `helper` function is static, and stripped.
+    strtab = b" \x00"
+    symbols = []
+
+    def add_str(s):
+        nonlocal strtab
+        off = len(strtab)
+        strtab += s.encode() + b"\x00"
+        return off
+
+    N_EXT = 0x1
+    N_SECT = 0xE
+    REFERENCED_DYNAMICALLY = 0x10
+    extdefs = [
+        ("__mh_execute_header", 1, BASE, REFERENCED_DYNAMICALLY),
+        ("_add", 1, BASE + t["add"], 0),
+        ("_main", 1, BASE + t["main"], 0),
+    ]
+    undefs = sorted(GOT_SYMBOLS + LAZY_SYMBOLS)
+    for name, sect, value, desc in extdefs:
+        symbols.append(struct.pack("<IBBHQ", add_str(name), N_SECT | N_EXT, sect, desc, value))
+    for name in undefs:
+        # library ordinal 1, in the high byte of n_desc.
+        symbols.append(struct.pack("<IBBHQ", add_str(name), N_EXT, 0, 1 << 8, 0))
+    strtab = pad(strtab, align(len(strtab), 8))
+
+    # indirect symbols: __stubs, __got, __la_symbol_ptr
+    indirect = []
+    stubs_indirect_index = len(indirect)
+    indirect += [len(extdefs) + undefs.index(s) for s in LAZY_SYMBOLS]
+    got_indirect_index = len(indirect)
+    indirect += [len(extdefs) + undefs.index(s) for s in GOT_SYMBOLS]
+    la_indirect_index = len(indirect)
+    indirect += [len(extdefs) + undefs.index(s) for s in LAZY_SYMBOLS]
+
+    # __LINKEDIT layout
+    linkedit = b""
+
+    def add_linkedit(b):
+        nonlocal linkedit
+        off = LINKEDIT_OFF + len(linkedit)
+        linkedit += pad(b, align(len(b), 8))
+        return off, len(b)
+
+    rebase_off, rebase_size = add_linkedit(rebase)
+    bind_off, bind_size = add_linkedit(bind)
+    lazy_bind_off, lazy_bind_size = add_linkedit(lazy_bind)
+    function_starts_off, function_starts_size = add_linkedit(function_starts)
+    symoff, _ = add_linkedit(b"".join(symbols))
+    indirect_off, _ = add_linkedit(b"".join(struct.pack("<I", i) for i in indirect))
+    stroff, strsize = add_linkedit(strtab)
+    linkedit_size = len(linkedit)
+
+    # __DATA contents
+    data = bytearray(PAGE)
+    for i, _ in enumerate(LAZY_SYMBOLS):
+        struct.pack_into("<Q", data, LA_OFF - DATA_SEG_OFF + 8 * i, BASE + stub_helper_addr + 0x10 + 0xA * i)
+
+    # load commands
+    def section(sectname, segname, addr, size, offset, align_pow, flags, reserved1=0, reserved2=0):
+        return struct.pack(
+            "<16s16sQQIIIIIIII",
+            sectname.encode(),
+            segname.encode(),
+            BASE + addr,
+            size,
+            offset,
+            align_pow,
+            0,
+            0,
+            flags,
+            reserved1,
+            reserved2,
+            0,
+        )
+
+    def segment(segname, vmaddr, vmsize, fileoff, filesize, maxprot, initprot, sections):
+        return (
+            struct.pack(
+                "<II16sQQQQiiII",
+                0x19,
+                72 + 80 * len(sections),
+                segname.encode(),
+                vmaddr,
+                vmsize,
+                fileoff,
+                filesize,
+                maxprot,
+                initprot,
+                len(sections),
+                0,
+            )
+            + b"".join(sections)
+        )
+
+    S_ATTR_PURE_INSTRUCTIONS = 0x80000000
+    S_ATTR_SOME_INSTRUCTIONS = 0x400
+    S_CSTRING_LITERALS = 0x2
+    S_NON_LAZY_SYMBOL_POINTERS = 0x6
+    S_LAZY_SYMBOL_POINTERS = 0x7
+    S_SYMBOL_STUBS = 0x8
+    code_flags = S_ATTR_PURE_INSTRUCTIONS | S_ATTR_SOME_INSTRUCTIONS
+
+    cmds = []
+    cmds.append(segment("__PAGEZERO", 0, BASE, 0, 0, 0, 0, []))
+    cmds.append(
+        segment(
+            "__TEXT",
+            BASE,
+            PAGE,
+            0,
+            PAGE,
+            5,
+            5,
+            [
+                section("__text", "__TEXT", text_addr, len(text_code), text_addr, 4, code_flags),
+                section(
+                    "__stubs",
+                    "__TEXT",
+                    stubs_addr,
+                    len(stubs_code),
+                    stubs_addr,
+                    1,
+                    code_flags | S_SYMBOL_STUBS,
+                    stubs_indirect_index,
+                    6,
+                ),
+                section(
+                    "__stub_helper", "__TEXT", stub_helper_addr, len(stub_helper_code), stub_helper_addr, 2, code_flags
+                ),
+                section("__cstring", "__TEXT", cstring_addr, len(cstring_data), cstring_addr, 0, S_CSTRING_LITERALS),
+            ],
+        )
+    )
+    cmds.append(
+        segment(
+            "__DATA",
+            BASE + DATA_SEG_OFF,
+            PAGE,
+            DATA_SEG_OFF,
+            PAGE,
+            3,
+            3,
+            [
+                section(
+                    "__got",
+                    "__DATA",
+                    GOT_OFF,
+                    8 * len(GOT_SYMBOLS),
+                    GOT_OFF,
+                    3,
+                    S_NON_LAZY_SYMBOL_POINTERS,
+                    got_indirect_index,
+                ),
+                section(
+                    "__la_symbol_ptr",
+                    "__DATA",
+                    LA_OFF,
+                    8 * len(LAZY_SYMBOLS),
+                    LA_OFF,
+                    3,
+                    S_LAZY_SYMBOL_POINTERS,
+                    la_indirect_index,
+                ),
+                section("__data", "__DATA", DATA_OFF, 8, DATA_OFF, 3, 0),
+            ],
+        )
+    )
+    cmds.append(
+        segment("__LINKEDIT", BASE + LINKEDIT_OFF, align(linkedit_size, PAGE), LINKEDIT_OFF, linkedit_size, 1, 1, [])
+    )
+    # LC_DYLD_INFO_ONLY
+    cmds.append(
+        struct.pack(
+            "<IIIIIIIIIIII",
+            0x80000022,
+            48,
+            rebase_off,
+            rebase_size,
+            bind_off,
+            bind_size,
+            0,
+            0,
+            lazy_bind_off,
+            lazy_bind_size,
+            0,
+            0,
+        )
+    )
+    # LC_SYMTAB
+    cmds.append(struct.pack("<IIIIII", 0x2, 24, symoff, len(symbols), stroff, strsize))
+    # LC_DYSYMTAB
+    cmds.append(
+        struct.pack(
+            "<II18I",
+            0xB,
+            80,
+            0,
+            0,
+            0,
+            len(extdefs),
+            len(extdefs),
+            len(undefs),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            indirect_off,
+            len(indirect),
+            0,
+            0,
+            0,
+            0,
+        )
+    )
+    # LC_LOAD_DYLINKER
+    cmds.append(cstr_cmd(0xE, 12, "/usr/lib/dyld", struct.pack("<I", 12)))
+    # LC_MAIN
+    cmds.append(struct.pack("<IIQQ", 0x80000028, 24, t["main"], 0))
+    # LC_LOAD_DYLIB
+    cmds.append(
+        cstr_cmd(0xC, 24, "/usr/lib/libSystem.B.dylib", struct.pack("<IIII", 24, 2, 0x05276403, 0x00010000))
+    )
+    # LC_FUNCTION_STARTS
+    cmds.append(struct.pack("<IIII", 0x26, 16, function_starts_off, function_starts_size))
+
+    sizeofcmds = sum(len(c) for c in cmds)
+    MH_NOUNDEFS = 0x1
+    MH_DYLDLINK = 0x4
+    MH_TWOLEVEL = 0x80
+    MH_PIE = 0x200000
+    header = struct.pack(
+        "<IiiIIIII",
+        0xFEEDFACF,
+        0x01000007,
+        3,
+        2,
+        len(cmds),
+        sizeofcmds,
+        MH_NOUNDEFS | MH_DYLDLINK | MH_TWOLEVEL | MH_PIE,
+        0,
+    )
+
+    image = bytearray(LINKEDIT_OFF + linkedit_size)
+    hdr = header + b"".join(cmds)
+    assert len(hdr) <= text_addr
+    image[0 : len(hdr)] = hdr
+    image[text_addr : text_addr + len(text_code)] = text_code
+    image[stubs_addr : stubs_addr + len(stubs_code)] = stubs_code
+    image[stub_helper_addr : stub_helper_addr + len(stub_helper_code)] = stub_helper_code
+    image[cstring_addr : cstring_addr + len(cstring_data)] = cstring_data
+    image[DATA_SEG_OFF : DATA_SEG_OFF + PAGE] = data
+    image[LINKEDIT_OFF:] = linkedit
+    return bytes(image)
+
+
+def build_arm64():
+    # just a header, so that the loader has to pick the right slice.
+    return struct.pack("<IiiIIIII", 0xFEEDFACF, 0x0100000C, 0, 2, 0, 0, 0, 0)
+
+
+def build_fat():
+    x64 = build_x64()
+    arm64 = build_arm64()
+
+    X64_OFF = 0x1000
+    ARM64_OFF = align(X64_OFF + len(x64), 0x4000)
+
+    header = struct.pack(">II", 0xCAFEBABE, 2)
+    header += struct.pack(">iiIII", 0x01000007, 3, X64_OFF, len(x64), 12)
+    header += struct.pack(">iiIII", 0x0100000C, 0, ARM64_OFF, len(arm64), 14)
+
+    out = bytearray(ARM64_OFF + len(arm64))
+    out[0 : len(header)] = header
+    out[X64_OFF : X64_OFF + len(x64)] = x64
+    out[ARM64_OFF:] = arm64
+    return bytes(out)
+
+
+if __name__ == "__main__":
+    sys.stdout.buffer.write(build_fat())
@@ -0,0 +1,280 @@
+#!/usr/bin/env python3
+"""
+build `nop.pdb`: a minimal PDB that matches `nop.exe`.
+
+the original PDB isn't available and there's no MSVC toolchain in the build environment,
+so the file is assembled by hand: an MSF 7.00 container with the PDB information, DBI,
+section header, global symbol, and module symbol streams.
+the GUID and age come from the CodeView record of `nop.exe`,
+and the section headers are copied from its PE header.
+
+it describes:
+
+  - public symbols for `_main`, `_printf`, `_mainCRTStartup` (code) and `__iob` (data),
+  - global data `_iob`,
+  - procedures `main` (nop.obj), `printf` (printf.obj), and `_amsg_exit` (crt0dat.obj).
+
+usage: python3 nop.pdb.py > nop.pdb
+"""
+import struct
+import sys
+
+BLOCK_SIZE = 0x200
+
+GUID = (0x53A2B882, 0x2B26, 0x43F2, bytes.fromhex("81499933CD022A46"))
+AGE = 1
+
+# (name, virtual size, virtual address, raw size, raw offset, characteristics)
+SECTIONS = [
+    (b".text", 0x4F48, 0x1000, 0x5000, 0x1000, 0x60000020),
+    (b".rdata", 0x12FE, 0x6000, 0x2000, 0x6000, 0x40000040),
+    (b".data", 0x1C68, 0x8000, 0x1000, 0x8000, 0xC0000040),
+]
+
+NAMES_STREAM = 5
+
+S_END = 0x0006
+S_GDATA32 = 0x110D
+S_PUB32 = 0x110E
+S_GPROC32 = 0x1110
+
+CVPSF_CODE = 0x1
+CVPSF_FUNCTION = 0x2
+
+T_32PVOID = 0x0403
+
+
+def align(v, n):
+    return (v + n - 1) // n * n
+
+
+def pad(b, n):
+    return b + b"\x00" * (n - len(b))
+
+
+def record(kind, body):
+    # the length excludes itself, and records are aligned to four bytes.
+    data = pad(struct.pack("<H", kind) + body, align(2 + len(body) + 2, 4) - 2)
+    return struct.pack("<H", len(data)) + data
+
+
+def section_offset(va):
+    """translate the VA into a (segment, offset) pair, with one-based segments."""
+    rva = va - 0x400000
+    for i, (_, vsize, vaddr, _, _, _) in enumerate(SECTIONS):
+        if vaddr <= rva < vaddr + vsize:
+            return i + 1, rva - vaddr
+    raise ValueError(hex(va))
+
+
+def s_pub32(va, name, flags):
+    segment, offset = section_offset(va)
+    return record(S_PUB32, struct.pack("<IIH", flags, offset, segment) + name + b"\x00")
+
+
+def s_gdata32(va, name, type_index):
+    segment, offset = section_offset(va)
+    return record(S_GDATA32, struct.pack("<IIH", type_index, offset, segment) + name + b"\x00")
+
+
+def s_gproc32(va, length, name, end):
+    segment, offset = section_offset(va)
+    # parent, end, next, length, debug start, debug end, type, offset, segment, flags.
+    body = struct.pack("<IIIIIIIIHB", 0, end, 0, length, 0, length - 1, 0, offset, segment, 0) + name + b"\x00"
+    return record(S_GPROC32, body)
+
+
+def module_stream(procedures):
+    # the C13 signature, then each procedure is closed by an `S_END`.
+    symbols = struct.pack("<I", 4)
+    for va, length, name in procedures:
+        end = len(symbols) + len(s_gproc32(va, length, name, 0))
+        symbols += s_gproc32(va, length, name, end)
+        symbols += record(S_END, b"")
+    # no line numbers, and no global references.
+    return symbols, symbols + struct.pack("<I", 0)
+
+
+def info_stream():
+    version = 20000404
+    signature = 0x5A2D4F6E
+    data1, data2, data3, data4 = GUID
+    buf = struct.pack("<IIIIHH", version, signature, AGE, data1, data2, data3) + data4
+    # the named stream map, with just `/names`:
+    # the name buffer, then size, capacity, present and deleted bit vectors, and the entries.
+    names = b"/names\x00"
+    buf += struct.pack("<I", len(names)) + names
+    buf += struct.pack("<IIIII", 1, 1, 1, 0b1, 0)
+    buf += struct.pack("<II", 0, NAMES_STREAM)
+    # feature: VC140
+    buf += struct.pack("<I", 20140508)
+    return buf
+
+
+def string_table():
+    # an empty string table: signature, version, the buffer with just the empty string,
+    # one hash bucket, and the name count.
+    return struct.pack("<III", 0xEFFEEFFE, 1, 1) + b"\x00" + struct.pack("<III", 1, 0, 0)
+
+
+def tpi_stream():
+    # a header with no type records.
+    return struct.pack(
+        "<IIIIIHHIIiIiIiI",
+        20040203,  # version
+        56,  # header size
+        0x1000,  # first type index
+        0x1000,  # last type index
+        0,  # type record bytes
+        0xFFFF,  # hash stream
+        0xFFFF,  # hash aux stream
+        4,  # hash key size
+        0x3FFFF,  # hash buckets
+        0,
+        0,  # hash values
+        0,
+        0,  # index offsets
+        0,
+        0,  # hash adjusters
+    )
+
+
+def dbi_stream(modules, symbol_records_stream, section_headers_stream):
+    module_list = b""
+    for name, object_name, stream, symbols_size in modules:
+        module_list += struct.pack("<I", 0)
+        # section contribution: section, padding, offset, size, characteristics, module, padding, CRCs.
+        module_list += struct.pack("<HHIIIHHII", 0, 0, 0, 0, 0, 0, 0, 0, 0)
+        module_list += struct.pack("<HHIIIHHIII", 0, stream, symbols_size, 0, 0, 0, 0, 0, 0, 0)
+        module_list += name + b"\x00" + object_name + b"\x00"
+        module_list = pad(module_list, align(len(module_list), 4))
+
+    # V60, and no contributions.
+    section_contributions = struct.pack("<I", 0xEFFE0000 + 19970605)
+
+    # the number of modules, no source files, and the per-module index and count.
+    file_info = struct.pack("<HH", len(modules), 0)
+    file_info += b"".join(struct.pack("<H", 0) for _ in modules)
+    file_info += b"".join(struct.pack("<H", 0) for _ in modules)
+    file_info = pad(file_info, align(len(file_info), 4))
+
+    # the names of the edit-and-continue source files and PDBs.
+    ec_names = string_table()
+
+    # the optional debug streams, where only the section headers are present.
+    debug_header = [0xFFFF] * 11
+    debug_header[5] = section_headers_stream
+    debug_header = struct.pack("<11H", *debug_header)
+
+    header = struct.pack(
+        "<IIIHHHHHHIIIIIIIIHHI",
+        0xFFFFFFFF,  # signature
+        19990903,  # version: V70
+        AGE,
+        0xFFFF,  # global symbols stream
+        0x8E1D,  # version of the pdb dll
+        0xFFFF,  # public symbols stream
+        0x6D7F,  # build version of the pdb dll
+        symbol_records_stream,
+        0,  # rbld version of the pdb dll
+        len(module_list),
+        len(section_contributions),
+        0,  # section map
+        len(file_info),
+        0,  # type server map
+        0,  # mfc type server index
+        len(debug_header),
+        len(ec_names),
+        0,  # flags
+        0x14C,  # machine: i386
+        0,
+    )
+    return header + module_list + section_contributions + file_info + ec_names + debug_header
+
+
+def section_headers_stream():
+    buf = b""
+    for name, vsize, vaddr, rsize, roffset, characteristics in SECTIONS:
+        buf += pad(name, 8) + struct.pack("<IIIIIIHHI", vsize, vaddr, rsize, roffset, 0, 0, 0, 0, characteristics)
+    return buf
+
+
+def symbol_records_stream():
+    return b"".join(
+        [
+            s_pub32(0x401000, b"_main", CVPSF_CODE | CVPSF_FUNCTION),
+            s_pub32(0x40102B, b"_printf", CVPSF_CODE | CVPSF_FUNCTION),
+            s_pub32(0x401081, b"_mainCRTStartup", CVPSF_CODE | CVPSF_FUNCTION),
+            s_pub32(0x408050, b"__iob", 0),
+            s_gdata32(0x408050, b"_iob", T_32PVOID),
+        ]
+    )
+
+
+def msf(streams):
+    """lay out the streams in an MSF 7.00 container."""
+    # blocks 0-2 are the superblock and the two free block maps.
+    blocks = [b"", b"", b""]
+    stream_blocks = []
+    for stream in streams:
+        indices = []
+        for i in range(0, len(stream), BLOCK_SIZE):
+            indices.append(len(blocks))
+            blocks.append(stream[i : i + BLOCK_SIZE])
+        stream_blocks.append(indices)
+
+    directory = struct.pack("<I", len(streams))
+    directory += b"".join(struct.pack("<I", len(stream)) for stream in streams)
+    directory += b"".join(struct.pack("<I", index) for indices in stream_blocks for index in indices)
+    directory_blocks = []
+    for i in range(0, len(directory), BLOCK_SIZE):
+        directory_blocks.append(len(blocks))
+        blocks.append(directory[i : i + BLOCK_SIZE])
+
+    block_map = len(blocks)
+    blocks.append(b"".join(struct.pack("<I", index) for index in directory_blocks))
+
+    # a set bit marks a free block.
+    fpm = bytearray(b"\xFF" * BLOCK_SIZE)
+    for i in range(len(blocks)):
+        fpm[i // 8] &= ~(1 << (i % 8)) & 0xFF
+    blocks[1] = bytes(fpm)
+
+    blocks[0] = b"Microsoft C/C++ MSF 7.00\r\n\x1aDS\x00\x00\x00" + struct.pack(
+        "<IIIIII", BLOCK_SIZE, 1, len(blocks), len(directory), 0, block_map
+    )
+
+    return b"".join(pad(block, BLOCK_SIZE) for block in blocks)
+
+
+def main():
+    procedures = [
+        [(0x401000, 0x2B, b"main")],
+        [(0x40102B, 0x31, b"printf")],
+        [(0x40105C, 0x25, b"_amsg_exit")],
+    ]
+    modules = []
+    module_streams = []
+    for i, (object_name, procs) in enumerate(zip([b"nop.obj", b"printf.obj", b"crt0dat.obj"], procedures)):
+        symbols, stream = module_stream(procs)
+        name = object_name if i == 0 else b"f:\\dd\\vctools\\crt_bld\\SELF_X86\\crt\\src\\build\\INTEL\\mt_obj\\" + object_name
+        library = object_name if i == 0 else b"LIBCMT.lib"
+        modules.append((name, library, 8 + i, len(symbols)))
+        module_streams.append(stream)
+
+    streams = [
+        b"",  # 0: the old directory
+        info_stream(),  # 1
+        tpi_stream(),  # 2
+        dbi_stream(modules, 7, 6),  # 3
+        tpi_stream(),  # 4: IPI
+        string_table(),  # 5: /names
+        section_headers_stream(),  # 6
+        symbol_records_stream(),  # 7
+    ] + module_streams  # 8..
+
+    sys.stdout.buffer.write(msf(streams))
+
+
+if __name__ == "__main__":
+    main()
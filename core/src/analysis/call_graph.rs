@@ -4,7 +4,7 @@ use anyhow::Result;
 use log::debug;
 
 use crate::{
-    analysis::{cfg, dis},
+    analysis::{cfg, dataflow::Annotations, dis},
     aspace::AddressSpace,
     module::Module,
     RVA, VA,
@@ -30,7 +30,26 @@ pub struct CallGraph {
     pub call_instruction_functions: BTreeMap<VA, Vec<VA>>,
 }
 
+impl CallGraph {
+    fn add_call(&mut self, function: VA, va: VA, target: VA) {
+        self.calls_from.entry(va).or_default().push(target);
+        self.calls_to.entry(target).or_default().push(va);
+        self.function_call_instructions.entry(function).or_default().push(va);
+        self.call_instruction_functions.entry(va).or_default().push(function);
+    }
+}
+
 pub fn build_call_graph(module: &Module, cfgs: &BTreeMap<VA, cfg::CFG>) -> Result<CallGraph> {
+    build_call_graph_with_annotations(module, cfgs, &Default::default())
+}
+
+/// like `build_call_graph`, but also use the targets of indirect calls,
+/// like `call eax`, from `dataflow::analyze_functions`.
+pub fn build_call_graph_with_annotations(
+    module: &Module,
+    cfgs: &BTreeMap<VA, cfg::CFG>,
+    annotations: &Annotations,
+) -> Result<CallGraph> {
     debug!("call graph");
 
     let mut cg: CallGraph = Default::default();
@@ -54,16 +73,23 @@ pub fn build_call_graph(module: &Module, cfgs: &BTreeMap<VA, cfg::CFG>) -> Resul
                         let va = basic_block.address + offset as RVA;
                         for flow in cfg::get_call_insn_flow(module, va, &insn)?.iter() {
                             if let cfg::Flow::Call(target) = *flow {
-                                cg.calls_from.entry(va).or_default().push(target);
-                                cg.calls_to.entry(target).or_default().push(va);
-                                cg.function_call_instructions.entry(function).or_default().push(va);
-                                cg.call_instruction_functions.entry(va).or_default().push(function);
+                                cg.add_call(function, va, target);
                             }
                         }
+
+                        if let Some(&target) = annotations.targets.get(&va) {
+                            cg.add_call(function, va, target);
+                        }
                     }
                 }
             }
         }
+
+        // tail calls, found when the CFG was built with function boundaries,
+        // are jumps that act like calls.
+        for (&va, &target) in cfg.tail_calls.iter() {
+            cg.add_call(function, va, target);
+        }
     }
 
     Ok(cg)
@@ -138,4 +164,50 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn annotations() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let mut cfgs: BTreeMap<VA, CFG> = Default::default();
+        cfgs.insert(0x447817, crate::analysis::cfg::build_cfg(&pe.module, 0x447817)?);
+
+        // .text:004478CA  mov     [ebp+var_118], offset sub_4476E7
+        // ...
+        // .text:00447996  call    [ebp+var_118]
+        let cg = call_graph::build_call_graph(&pe.module, &cfgs)?;
+        assert!(!cg.calls_from.contains_key(&0x447996));
+
+        let annotations = crate::analysis::dataflow::analyze_functions(&pe.module, &cfgs)?;
+        let cg = call_graph::build_call_graph_with_annotations(&pe.module, &cfgs, &annotations)?;
+        assert_eq!(cg.calls_from[&0x447996], vec![0x4476E7]);
+        assert!(cg.function_call_instructions[&0x447817].contains(&0x447996));
+
+        Ok(())
+    }
+
+    #[test]
+    fn tail_calls() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        let boundaries = crate::analysis::cfg::functions::FunctionBoundaries::from_pe(&pe)?;
+
+        let mut cfgs: BTreeMap<VA, CFG> = Default::default();
+        for &function in boundaries.function_starts.iter() {
+            if let Ok(cfg) =
+                crate::analysis::cfg::build_cfg_with_boundaries(&pe.module, function, &Default::default(), &boundaries)
+            {
+                cfgs.insert(function, cfg);
+            }
+        }
+
+        let cg = call_graph::build_call_graph(&pe.module, &cfgs)?;
+
+        // .text:0000000180072A6B  jmp     sub_180072A70
+        assert_eq!(cg.calls_from[&0x1_8007_2A6B], vec![0x1_8007_2A70]);
+        assert!(cg.call_instruction_functions[&0x1_8007_2A6B].contains(&0x1_8007_2A20));
+
+        Ok(())
+    }
 }
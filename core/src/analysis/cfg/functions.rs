@@ -0,0 +1,223 @@
+//! Decide where a function ends and the next begins,
+//! so that the CFG of a function doesn't swallow the functions it tail calls.
+//!
+//! A jump to another function, like `jmp free` at the end of a destructor,
+//! is recorded as a tail call rather than followed.
+//! When the function is described by `.pdata` (x64 PE),
+//! a jump that leaves the ranges of the function is a tail call.
+//! Otherwise, a jump to the start of another known function is a tail call.
+//!
+//! A function's instructions needn't be contiguous: compilers move rarely
+//! executed code out of line, into chunks that are reached by jumps
+//! (on x64, described by chained UNWIND_INFO).
+//! So, a `FunctionBody` describes its chunks as well as its overall extent.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/cpp/build/exception-handling-x64#chained-unwind-info-structures
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
+
+use anyhow::Result;
+
+use crate::{
+    analysis::{
+        cfg::{self, CFG},
+        pe::{self, Function},
+    },
+    loader::pe::PE,
+    module::Module,
+    VA,
+};
+
+/// what's known about the locations of the functions in a module.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionBoundaries {
+    /// the start addresses of the local functions and thunks.
+    pub function_starts: BTreeSet<VA>,
+
+    /// the address ranges of functions described by `.pdata`,
+    /// indexed by function start.
+    pub chunks: BTreeMap<VA, Vec<Range<VA>>>,
+}
+
+impl FunctionBoundaries {
+    /// use the functions found by a pass like `analysis::pe::find_functions`,
+    /// or the equivalent for other formats.
+    pub fn new(functions: &[Function]) -> FunctionBoundaries {
+        FunctionBoundaries {
+            function_starts: functions
+                .iter()
+                .filter_map(|function| match function {
+                    Function::Local(va) => Some(*va),
+                    Function::Thunk(thunk) => Some(thunk.address),
+                    Function::Import(_) => None,
+                })
+                .collect(),
+            chunks:          Default::default(),
+        }
+    }
+
+    /// use the functions found by `analysis::pe::find_functions`,
+    /// and the function ranges from `.pdata`, if any.
+    /// the secondary chunks of a function aren't function starts,
+    /// even when another pass, like call target discovery, reports them.
+    pub fn from_pe(pe: &PE) -> Result<FunctionBoundaries> {
+        let mut boundaries = FunctionBoundaries::new(&pe::find_functions(pe)?);
+        boundaries.chunks = pe::runtime_functions::find_pe_runtime_function_chunks(pe)?;
+        for ranges in boundaries.chunks.values() {
+            for range in ranges.iter().skip(1) {
+                boundaries.function_starts.remove(&range.start);
+            }
+        }
+        Ok(boundaries)
+    }
+
+    /// is a jump from the given function to the given target a tail call?
+    pub fn is_tail_call(&self, function: VA, target: VA) -> bool {
+        if target == function {
+            return false;
+        }
+
+        match self.chunks.get(&function) {
+            Some(ranges) => !ranges.iter().any(|range| range.contains(&target)),
+            None => self.function_starts.contains(&target),
+        }
+    }
+}
+
+pub struct FunctionBody {
+    /// the start address of the function.
+    pub address: VA,
+
+    pub cfg: CFG,
+
+    /// the contiguous ranges of instructions in the function, in order.
+    /// the first chunk doesn't necessarily contain the start of the function.
+    pub chunks: Vec<Range<VA>>,
+}
+
+impl FunctionBody {
+    /// the range of addresses from the start of the first chunk
+    /// to the end of the last chunk.
+    /// may contain other functions, or data, between the chunks.
+    pub fn extent(&self) -> Range<VA> {
+        match (self.chunks.first(), self.chunks.last()) {
+            (Some(first), Some(last)) => first.start..last.end,
+            _ => self.address..self.address,
+        }
+    }
+
+    /// does the function have an instruction at the given address?
+    pub fn contains(&self, va: VA) -> bool {
+        // find the last chunk that starts at or before the address.
+        let index = match self.chunks.binary_search_by_key(&va, |chunk| chunk.start) {
+            Ok(_) => return true,
+            Err(0) => return false,
+            Err(index) => index - 1,
+        };
+        self.chunks[index].contains(&va)
+    }
+}
+
+/// coalesce adjacent basic blocks into chunks.
+fn compute_chunks(cfg: &CFG) -> Vec<Range<VA>> {
+    let mut chunks: Vec<Range<VA>> = vec![];
+
+    // basic blocks are ordered by address.
+    for bb in cfg.basic_blocks.values() {
+        match chunks.last_mut() {
+            Some(chunk) if chunk.end == bb.address => chunk.end = bb.address + bb.length,
+            _ => chunks.push(bb.address..bb.address + bb.length),
+        }
+    }
+
+    chunks
+}
+
+/// build the CFG of the function at the given address,
+/// stopping at its boundaries, and describe its chunks.
+///
+/// `noret` is the set of functions that don't return,
+/// like from `noret::find_noret_functions`, which may be empty.
+pub fn build_function(
+    module: &Module,
+    va: VA,
+    noret: &BTreeSet<VA>,
+    boundaries: &FunctionBoundaries,
+) -> Result<FunctionBody> {
+    let cfg = cfg::build_cfg_with_boundaries(module, va, noret, boundaries)?;
+    let chunks = compute_chunks(&cfg);
+
+    Ok(FunctionBody {
+        address: va,
+        cfg,
+        chunks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analysis::cfg::{build_cfg, functions::*},
+        rsrc::*,
+        test::*,
+    };
+    use anyhow::Result;
+
+    #[test]
+    fn tail_call() -> Result<()> {
+        // 0x0:  85 c0             test   eax, eax
+        // 0x2:  74 03             jz     0x7
+        // 0x4:  eb 02             jmp    0x8
+        // 0x6:  90                nop
+        // 0x7:  c3                ret
+        // 0x8:  31 c0             xor    eax, eax
+        // 0xA:  c3                ret
+        let module = load_shellcode32(b"\x85\xC0\x74\x03\xEB\x02\x90\xC3\x31\xC0\xC3");
+
+        // without boundaries, the CFG includes the function at 0x8.
+        let cfg = build_cfg(&module, 0x0)?;
+        assert_eq!(cfg.basic_blocks.len(), 4);
+
+        let boundaries = FunctionBoundaries::new(&[Function::Local(0x0), Function::Local(0x8)]);
+        let f = build_function(&module, 0x0, &Default::default(), &boundaries)?;
+        assert_eq!(f.cfg.basic_blocks.len(), 3);
+        assert_eq!(f.cfg.tail_calls[&0x4], 0x8);
+        assert!(f.cfg.basic_blocks[&0x4].successors.is_empty());
+
+        // the nop isn't reachable.
+        assert_eq!(f.chunks, vec![0x0..0x6, 0x7..0x8]);
+        assert_eq!(f.extent(), 0x0..0x8);
+        assert!(f.contains(0x7));
+        assert!(!f.contains(0x6));
+        assert!(!f.contains(0x8));
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        let boundaries = FunctionBoundaries::from_pe(&pe)?;
+        assert!(boundaries.function_starts.contains(&0x1_8000_1068));
+        assert!(!boundaries.function_starts.contains(&0x1_8002_291E));
+
+        // the primary range, and the chained range with code moved out of line.
+        assert_eq!(
+            boundaries.chunks[&0x1_8000_1068],
+            vec![0x1_8000_1068..0x1_8000_130E, 0x1_8002_291E..0x1_8002_2A92]
+        );
+
+        let f = build_function(&pe.module, 0x1_8000_1068, &Default::default(), &boundaries)?;
+        assert!(f.cfg.tail_calls.is_empty());
+        assert!(f.contains(0x1_8000_1068));
+        assert!(f.contains(0x1_8002_291E));
+        assert_eq!(f.extent().start, 0x1_8000_1068);
+        assert!(f.extent().end > 0x1_8002_291E);
+
+        Ok(())
+    }
+}
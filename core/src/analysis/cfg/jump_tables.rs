@@ -0,0 +1,771 @@
+//! Recover the targets of jump tables, like those emitted for `switch`
+//! statements, so that `build_cfg` can follow each of the cases.
+//!
+//! Starting at an indirect `jmp`, we step backwards through the instructions
+//! that fall through to it, looking for:
+//!
+//!   1. the load of the table entry, which provides the table base and the
+//!      entry size,
+//!   2. the values of the registers used to address the table, like the image
+//!      base in `lea rdx, [rip + __ImageBase]`, and
+//!   3. the bounds check of the index, like `cmp eax, 0xC; ja default`, which
+//!      provides the number of entries.
+//!
+//! We recognize the following forms:
+//!
+//! ```text
+//!     ; x32: a table of absolute addresses.
+//!     cmp    eax, 0x8
+//!     ja     default
+//!     jmp    dword ptr [eax*4 + table]
+//!
+//!     ; x64 (MSVC): a table of RVAs relative to `__ImageBase`.
+//!     cmp    eax, 0xC
+//!     ja     default
+//!     lea    rdx, [rip + __ImageBase]
+//!     mov    ecx, dword ptr [rdx + rax*4 + table_rva]
+//!     add    rcx, rdx
+//!     jmp    rcx
+//!
+//!     ; x64 (Clang): a table of offsets relative to the table itself.
+//!     lea    rcx, [rip + table]
+//!     movsxd rax, dword ptr [rcx + rax*4]
+//!     add    rax, rcx
+//!     jmp    rax
+//! ```
+//!
+//! as well as the indirect forms, which first look up the index into the
+//! jump table from a table of bytes, so that many cases can share a target:
+//!
+//! ```text
+//!     cmp    eax, 0x1C
+//!     ja     default
+//!     movzx  eax, byte ptr [eax + index_table]
+//!     jmp    dword ptr [eax*4 + table]
+//! ```
+//!
+//! We only step backwards along fallthrough flows, so the bounds check and
+//! register definitions must be found in the same straight-line sequence of
+//! instructions as the `jmp`. The exception is the image base on x64,
+//! which MSVC may compute once, early in the function.
+//! If anything else is out of place, or any target isn't executable,
+//! we don't guess: the table isn't recovered.
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+};
+
+use log::debug;
+use smallvec::SmallVec;
+
+use crate::{
+    analysis::cfg::{va_add_signed, Flow},
+    arch::Arch,
+    aspace::AddressSpace,
+    module::{Module, Permissions},
+    VA,
+};
+
+/// the number of instructions to step backwards from the `jmp`.
+const MAX_BACKTRACK: usize = 32;
+
+/// the largest table we'll recover,
+/// to guard against a bounds check that we've misidentified.
+const MAX_ENTRIES: u64 = 0x1000;
+
+#[derive(Debug, Clone)]
+pub struct JumpTable {
+    /// the address of the indirect `jmp` instruction.
+    pub address: VA,
+
+    /// the region containing the table entries.
+    pub table: Range<VA>,
+
+    /// the size of each table entry, in bytes.
+    pub entry_size: u8,
+
+    /// the region containing the byte indices into the table, if any.
+    pub index_table: Option<Range<VA>>,
+
+    /// the target of each table entry, in order.
+    /// many entries may share the same target, like the default case.
+    pub targets: Vec<VA>,
+}
+
+impl JumpTable {
+    /// the regions of data that make up the jump table.
+    pub fn data_regions(&self) -> Vec<Range<VA>> {
+        let mut regions = vec![self.table.clone()];
+        if let Some(index_table) = &self.index_table {
+            regions.push(index_table.clone());
+        }
+        regions
+    }
+
+    /// the unique targets of the table, as flows from the `jmp`.
+    pub fn flows(&self) -> SmallVec<[Flow; 2]> {
+        let mut targets = self.targets.clone();
+        targets.sort_unstable();
+        targets.dedup();
+        targets.into_iter().map(Flow::UnconditionalJump).collect()
+    }
+}
+
+/// how the table entries are translated into targets.
+#[derive(Debug, Clone, Copy)]
+enum Entries {
+    /// the entries are addresses.
+    Absolute,
+    /// the entries are unsigned offsets from the value of the given register.
+    Unsigned(zydis::Register),
+    /// the entries are signed offsets from the value of the given register.
+    Signed(zydis::Register),
+}
+
+fn get_machine_mode(module: &Module) -> zydis::MachineMode {
+    match module.arch {
+        Arch::X32 => zydis::MachineMode::LEGACY_32,
+        Arch::X64 => zydis::MachineMode::LONG_64,
+    }
+}
+
+/// the operands as they'd be listed by a disassembler, including implicit
+/// ones like `eax` in the short form of `cmp eax, 0xF5` (`3D F5 00 00 00`).
+fn visible_operands(insn: &zydis::DecodedInstruction) -> impl Iterator<Item = &zydis::DecodedOperand> {
+    insn.operands[..insn.operand_count as usize].iter().filter(|op| {
+        op.visibility == zydis::OperandVisibility::EXPLICIT || op.visibility == zydis::OperandVisibility::IMPLICIT
+    })
+}
+
+/// the register written by the instruction's first operand,
+/// like `eax` in `mov eax, ecx`, as the largest enclosing register (`rax`).
+fn get_written_register(mode: zydis::MachineMode, insn: &zydis::DecodedInstruction) -> Option<zydis::Register> {
+    let op = visible_operands(insn).next()?;
+    if op.ty == zydis::OperandType::REGISTER
+        && op
+            .action
+            .intersects(zydis::OperandAction::WRITE | zydis::OperandAction::CONDWRITE)
+    {
+        Some(op.reg.get_largest_enclosing(mode))
+    } else {
+        None
+    }
+}
+
+/// does the instruction write to the given register, explicitly or not?
+/// the register should be the largest enclosing register, like `rax`.
+fn writes_register(mode: zydis::MachineMode, insn: &zydis::DecodedInstruction, reg: zydis::Register) -> bool {
+    insn.operands[..insn.operand_count as usize].iter().any(|op| {
+        op.ty == zydis::OperandType::REGISTER
+            && op
+                .action
+                .intersects(zydis::OperandAction::WRITE | zydis::OperandAction::CONDWRITE)
+            && op.reg.get_largest_enclosing(mode) == reg
+    })
+}
+
+/// the constant value assigned to a register by the given instruction,
+/// like `lea rdx, [rip + __ImageBase]` or `mov edx, 0x401000`.
+fn get_register_value(va: VA, insn: &zydis::DecodedInstruction) -> Option<VA> {
+    let mut ops = visible_operands(insn);
+    let _dst = ops.next()?;
+    let src = ops.next()?;
+
+    match (insn.mnemonic, src.ty) {
+        (zydis::Mnemonic::LEA, zydis::OperandType::MEMORY)
+            if src.mem.base == zydis::Register::RIP && src.mem.index == zydis::Register::NONE =>
+        {
+            va_add_signed(va + insn.length as u64, src.mem.disp.displacement)
+        }
+        (zydis::Mnemonic::LEA, zydis::OperandType::MEMORY)
+            if src.mem.base == zydis::Register::NONE
+                && src.mem.index == zydis::Register::NONE
+                && src.mem.disp.displacement >= 0 =>
+        {
+            Some(src.mem.disp.displacement as u64)
+        }
+        (zydis::Mnemonic::MOV, zydis::OperandType::IMMEDIATE) => Some(src.imm.value),
+        _ => None,
+    }
+}
+
+/// the state of the search backwards from the `jmp`.
+struct Search {
+    mode: zydis::MachineMode,
+
+    /// the register that holds the jump target, until we find its load.
+    target: Option<zydis::Register>,
+    /// the register added to the loaded entry, like `rdx` in `add rcx, rdx`.
+    addend: Option<zydis::Register>,
+
+    /// the memory operand that loads the table entry,
+    /// like `dword ptr [rdx + rax*4 + table_rva]`.
+    load:       Option<zydis::ffi::MemoryInfo>,
+    entries:    Entries,
+    entry_size: u8,
+
+    /// the memory operand that loads the byte index, if any,
+    /// like `byte ptr [eax + index_table]`.
+    index_load: Option<zydis::ffi::MemoryInfo>,
+
+    /// the register that holds the index, until we find its bounds check.
+    index: Option<zydis::Register>,
+    /// the number of valid indices, from the bounds check.
+    count: Option<u64>,
+    /// the most recent conditional jump that we've stepped over,
+    /// which should be the one that acts on the bounds check.
+    cjmp:  Option<zydis::Mnemonic>,
+
+    /// the values of the registers used to address the tables.
+    values: HashMap<zydis::Register, Option<VA>>,
+}
+
+impl Search {
+    fn need_value(&mut self, reg: zydis::Register) {
+        if reg != zydis::Register::NONE {
+            self.values.entry(reg.get_largest_enclosing(self.mode)).or_insert(None);
+        }
+    }
+
+    fn value(&self, reg: zydis::Register) -> Option<VA> {
+        if reg == zydis::Register::NONE {
+            Some(0)
+        } else {
+            *self.values.get(&reg.get_largest_enclosing(self.mode))?
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.load.is_some() && self.count.is_some() && self.values.values().all(|v| v.is_some())
+    }
+
+    /// MSVC may hoist `lea rdx, [rip + __ImageBase]` into an earlier basic
+    /// block, where we won't find it by stepping backwards. so, if a table of
+    /// RVAs is addressed by a register whose value we haven't found,
+    /// assume that it's the image base. the targets are validated later.
+    fn assume_image_base(&mut self, module: &Module) {
+        let load = match &self.load {
+            Some(load) => load,
+            None => return,
+        };
+
+        let base = load.base.get_largest_enclosing(self.mode);
+        if !matches!(module.arch, Arch::X64)
+            || !matches!(self.entries, Entries::Unsigned(addend) if addend.get_largest_enclosing(self.mode) == base)
+        {
+            return;
+        }
+
+        if let Some(value) = self.values.get_mut(&base) {
+            if value.is_none() {
+                *value = Some(module.address_space.base_address);
+            }
+        }
+    }
+
+    /// handle the load of the table entry, like `mov ecx, [rdx + rax*4 +
+    /// table_rva]`. returns false if the load isn't recognized.
+    fn on_load(&mut self, mem: &zydis::ffi::MemoryInfo, entry_size: u8) -> bool {
+        if mem.index == zydis::Register::NONE || mem.scale != entry_size {
+            return false;
+        }
+
+        self.load = Some(mem.clone());
+        self.entry_size = entry_size;
+        self.index = Some(mem.index.get_largest_enclosing(self.mode));
+        self.need_value(mem.base);
+        true
+    }
+
+    /// step backwards over the given instruction.
+    /// returns false if the search can't continue.
+    fn step(&mut self, va: VA, insn: &zydis::DecodedInstruction) -> bool {
+        let mode = self.mode;
+
+        // first, find the load of the jump target, like `jmp rcx`.
+        if let Some(target) = self.target {
+            if !writes_register(mode, insn, target) {
+                return true;
+            }
+
+            let mut ops = visible_operands(insn);
+            let (dst, src) = match (ops.next(), ops.next()) {
+                (Some(dst), Some(src)) => (dst, src),
+                _ => return false,
+            };
+
+            match (insn.mnemonic, src.ty) {
+                // add rcx, rdx
+                (zydis::Mnemonic::ADD, zydis::OperandType::REGISTER) if self.addend.is_none() => {
+                    self.addend = Some(src.reg);
+                    self.need_value(src.reg);
+                    return true;
+                }
+                // mov ecx, dword ptr [rdx + rax*4 + table_rva]
+                // mov rax, qword ptr [rax*8 + table]
+                (zydis::Mnemonic::MOV, zydis::OperandType::MEMORY) => {
+                    self.entries = match (self.addend, dst.size) {
+                        (None, _) => Entries::Absolute,
+                        (Some(addend), 32) => Entries::Unsigned(addend),
+                        _ => return false,
+                    };
+                }
+                // movsxd rax, dword ptr [rcx + rax*4]
+                (zydis::Mnemonic::MOVSXD, zydis::OperandType::MEMORY) => match self.addend {
+                    Some(addend) => self.entries = Entries::Signed(addend),
+                    None => return false,
+                },
+                _ => return false,
+            }
+
+            self.target = None;
+            return self.on_load(&src.mem, (src.size / 8) as u8);
+        }
+
+        if matches!(
+            insn.mnemonic,
+            zydis::Mnemonic::CDQE | zydis::Mnemonic::CWDE | zydis::Mnemonic::CBW
+        ) {
+            // sign-extends the index in place.
+            return true;
+        }
+
+        // then, find the bounds check of the index, like `cmp eax, 0xC; ja default`.
+        if let Some(index) = self.index {
+            if insn.mnemonic == zydis::Mnemonic::JNBE || insn.mnemonic == zydis::Mnemonic::JNB {
+                self.cjmp = Some(insn.mnemonic);
+                return true;
+            }
+
+            let mut ops = visible_operands(insn);
+            if let (Some(dst), Some(src)) = (ops.next(), ops.next()) {
+                if insn.mnemonic == zydis::Mnemonic::CMP
+                    && dst.ty == zydis::OperandType::REGISTER
+                    && dst.reg.get_largest_enclosing(mode) == index
+                    && src.ty == zydis::OperandType::IMMEDIATE
+                {
+                    self.count = match self.cjmp {
+                        // cmp eax, 0xC; ja default
+                        Some(zydis::Mnemonic::JNBE) => src.imm.value.checked_add(1),
+                        // cmp eax, 0xD; jae default
+                        Some(zydis::Mnemonic::JNB) => Some(src.imm.value),
+                        _ => return false,
+                    };
+                    self.index = None;
+                    return true;
+                }
+
+                if get_written_register(mode, insn) == Some(index) {
+                    match (insn.mnemonic, src.ty) {
+                        // and eax, 0x7
+                        (zydis::Mnemonic::AND, zydis::OperandType::IMMEDIATE)
+                            if src.imm.value < MAX_ENTRIES && (src.imm.value + 1).is_power_of_two() =>
+                        {
+                            self.count = Some(src.imm.value + 1);
+                            self.index = None;
+                        }
+                        // movzx eax, byte ptr [rdx + rax + index_table_rva]
+                        (zydis::Mnemonic::MOVZX, zydis::OperandType::MEMORY)
+                            if src.size == 8 && self.index_load.is_none() =>
+                        {
+                            // the index may be encoded as the base, like `[eax + index_table]`.
+                            let (base, index) = if src.mem.index == zydis::Register::NONE {
+                                (zydis::Register::NONE, src.mem.base)
+                            } else {
+                                (src.mem.base, src.mem.index)
+                            };
+                            if index == zydis::Register::NONE || (src.mem.scale != 0 && src.mem.scale != 1) {
+                                return false;
+                            }
+
+                            let mut index_load = src.mem.clone();
+                            index_load.base = base;
+                            self.index_load = Some(index_load);
+                            self.need_value(base);
+                            self.index = Some(index.get_largest_enclosing(mode));
+                            self.cjmp = None;
+                        }
+                        // movsxd rax, ecx
+                        (
+                            zydis::Mnemonic::MOV | zydis::Mnemonic::MOVSXD | zydis::Mnemonic::MOVZX,
+                            zydis::OperandType::REGISTER,
+                        ) => {
+                            self.index = Some(src.reg.get_largest_enclosing(mode));
+                        }
+                        _ => return false,
+                    }
+                    return true;
+                }
+            }
+
+            if writes_register(mode, insn, index) {
+                return false;
+            }
+        }
+
+        // finally, find the values of the registers used to address the tables.
+        let pending: Vec<zydis::Register> = self
+            .values
+            .iter()
+            .filter(|(_, value)| value.is_none())
+            .map(|(&reg, _)| reg)
+            .collect();
+        for reg in pending {
+            if !writes_register(mode, insn, reg) {
+                continue;
+            }
+
+            if get_written_register(mode, insn) != Some(reg) {
+                return false;
+            }
+
+            match get_register_value(va, insn) {
+                Some(value) => {
+                    self.values.insert(reg, Some(value));
+                }
+                None => return false,
+            }
+        }
+
+        true
+    }
+}
+
+fn read_entry(module: &Module, va: VA, entry_size: u8) -> Option<u64> {
+    match entry_size {
+        4 => module.address_space.read_u32(va).ok().map(|v| v as u64),
+        8 => module.address_space.read_u64(va).ok(),
+        _ => None,
+    }
+}
+
+/// translate the search results into the table and its targets.
+fn read_jump_table(module: &Module, va: VA, search: &Search) -> Option<JumpTable> {
+    let load = search.load.as_ref()?;
+    let count = search.count?;
+    let entry_size = search.entry_size;
+
+    // the bound comes from the instruction stream, like `cmp eax, 0xFFFFFFFE`,
+    // so check it before reading anything.
+    if count == 0 || count > MAX_ENTRIES {
+        debug!("jump table: {:#x}: unexpected number of entries: {:#x}", va, count);
+        return None;
+    }
+
+    let index_table = match &search.index_load {
+        None => None,
+        Some(index_load) => {
+            let start = va_add_signed(search.value(index_load.base)?, index_load.disp.displacement)?;
+            Some(start..start.checked_add(count)?)
+        }
+    };
+
+    // with an index table, the bounds check applies to the index table,
+    // and the largest index bounds the jump table.
+    // since the indices are bytes, there are at most 0x100 entries.
+    let count = match &index_table {
+        None => count,
+        Some(index_table) => {
+            let indices = module
+                .address_space
+                .read_bytes(index_table.start, count as usize)
+                .ok()?;
+            *indices.iter().max()? as u64 + 1
+        }
+    };
+
+    // on x32, the displacement is an absolute address,
+    // which may appear negative when sign-extended.
+    let displacement = match module.arch {
+        Arch::X32 => load.disp.displacement as u32 as i64,
+        Arch::X64 => load.disp.displacement,
+    };
+    let start = va_add_signed(search.value(load.base)?, displacement)?;
+    let table = start..start.checked_add(count.checked_mul(entry_size as u64)?)?;
+
+    let mut targets = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let entry = read_entry(module, table.start + i * entry_size as u64, entry_size)?;
+        let target = match search.entries {
+            Entries::Absolute => Some(entry),
+            Entries::Unsigned(addend) => search.value(addend)?.checked_add(entry),
+            Entries::Signed(addend) => va_add_signed(search.value(addend)?, entry as u32 as i32 as i64),
+        }?;
+
+        if !module.probe_va(target, Permissions::X) {
+            debug!("jump table: {:#x}: invalid target: {:#x}", va, target);
+            return None;
+        }
+
+        targets.push(target);
+    }
+
+    Some(JumpTable {
+        address: va,
+        table,
+        entry_size,
+        index_table,
+        targets,
+    })
+}
+
+/// recover the jump table used by the given indirect `jmp`, if any.
+///
+/// `fallthrough_predecessors` maps from the address of an instruction
+/// to the instruction that falls through to it,
+/// so that we can step backwards from the `jmp`.
+pub fn find_jump_table(
+    module: &Module,
+    decoder: &zydis::Decoder,
+    fallthrough_predecessors: &BTreeMap<VA, VA>,
+    va: VA,
+    insn: &zydis::DecodedInstruction,
+) -> Option<JumpTable> {
+    let mode = get_machine_mode(module);
+    let op = visible_operands(insn).next()?;
+
+    let mut search = Search {
+        mode,
+        target: None,
+        addend: None,
+        load: None,
+        entries: Entries::Absolute,
+        entry_size: 0,
+        index_load: None,
+        index: None,
+        count: None,
+        cjmp: None,
+        values: Default::default(),
+    };
+
+    match op.ty {
+        // jmp dword ptr [eax*4 + table]
+        zydis::OperandType::MEMORY => {
+            if op.mem.base != zydis::Register::NONE || op.size as usize != module.arch.pointer_size() * 8 {
+                return None;
+            }
+
+            if !search.on_load(&op.mem, module.arch.pointer_size() as u8) {
+                return None;
+            }
+        }
+        // jmp rcx
+        zydis::OperandType::REGISTER => {
+            search.target = Some(op.reg.get_largest_enclosing(mode));
+        }
+        _ => return None,
+    }
+
+    let mut insn_buf = [0u8; 16];
+    let mut current = va;
+    for _ in 0..MAX_BACKTRACK {
+        if search.is_done() {
+            break;
+        }
+
+        current = match fallthrough_predecessors.get(&current) {
+            Some(&prev) => prev,
+            None => break,
+        };
+
+        if module.address_space.read_into(current, &mut insn_buf).is_err() {
+            break;
+        }
+
+        let prev = match decoder.decode(&insn_buf) {
+            Ok(Some(prev)) => prev,
+            _ => break,
+        };
+
+        if !search.step(current, &prev) {
+            debug!("jump table: {:#x}: unexpected instruction at {:#x}", va, current);
+            return None;
+        }
+    }
+
+    if !search.is_done() {
+        search.assume_image_base(module);
+    }
+
+    if !search.is_done() {
+        debug!("jump table: {:#x}: not recognized", va);
+        return None;
+    }
+
+    let table = read_jump_table(module, va, &search)?;
+    debug!(
+        "jump table: {:#x}: table: {:#x} entries: {} targets: {}",
+        va,
+        table.table.start,
+        table.targets.len(),
+        table.flows().len()
+    );
+
+    Some(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{analysis::cfg::*, rsrc::*, test::*};
+    use anyhow::Result;
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // .text:0000000180004A10  cmp     eax, 0Ch
+        // .text:0000000180004A13  ja      loc_180004002
+        // .text:0000000180004A19  lea     rdx, cs:180000000h
+        // .text:0000000180004A20  mov     ecx, ds:(off_180005BE0 -
+        // 180000000h)[rdx+rax*4] .text:0000000180004A27  add     rcx, rdx
+        // .text:0000000180004A2A  jmp     rcx
+        let cfg = build_cfg(&pe.module, 0x1_8000_4A10)?;
+        let table = &cfg.jump_tables[&0x1_8000_4A2A];
+        assert_eq!(table.table, 0x1_8000_5BE0..0x1_8000_5BE0 + 13 * 4);
+        assert_eq!(table.entry_size, 4);
+        assert_eq!(table.targets.len(), 13);
+        assert!(table.index_table.is_none());
+
+        let bb = cfg
+            .basic_blocks
+            .values()
+            .find(|bb| bb.address + bb.length == 0x1_8000_4A2C)
+            .unwrap();
+        assert_eq!(bb.successors.len(), table.flows().len());
+        for flow in bb.successors.iter() {
+            assert!(cfg.basic_blocks.contains_key(&flow.va()));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimikatz() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // .text:00401FCF  cmp     eax, 8
+        // .text:00401FD2  ja      short loc_402011
+        // .text:00401FD4  jmp     ds:off_402017[eax*4]
+        let cfg = build_cfg(&pe.module, 0x401FCF)?;
+        let table = &cfg.jump_tables[&0x401FD4];
+        assert_eq!(table.table, 0x402017..0x402017 + 9 * 4);
+        assert_eq!(table.targets.len(), 9);
+        assert_eq!(cfg.data_regions(), vec![0x402017..0x402017 + 9 * 4]);
+
+        // the short form `cmp eax, imm32` has an implicit eax operand.
+        //
+        // .text:00441E7E  cmp     eax, 0F5h
+        // .text:00441E83  ja      loc_44343A
+        // .text:00441E89  jmp     ds:off_44347B[eax*4]
+        let cfg = build_cfg(&pe.module, 0x441E7E)?;
+        let table = &cfg.jump_tables[&0x441E89];
+        assert_eq!(table.table, 0x44347B..0x44347B + 0xF6 * 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn byte_index_table() -> Result<()> {
+        // 0x0:  83 f8 03           cmp    eax, 0x3
+        // 0x3:  77 0f              ja     0x14
+        // 0x5:  0f b6 80 15 00 00 00  movzx eax, byte ptr [eax + 0x15]
+        // 0xC:  ff 24 85 19 00 00 00  jmp    dword ptr [eax*4 + 0x19]
+        // 0x13: 90                 nop
+        // 0x14: c3                 ret
+        // 0x15: 00 01 01 00        index table
+        // 0x19: 13 00 00 00        jump table: 0x13
+        // 0x1D: 14 00 00 00                    0x14
+        let module = load_shellcode32(
+            b"\x83\xF8\x03\x77\x0F\x0F\xB6\x80\x15\x00\x00\x00\xFF\x24\x85\x19\x00\x00\x00\x90\xC3\x00\x01\x01\x00\x13\x00\x00\x00\x14\x00\x00\x00",
+        );
+        let cfg = build_cfg(&module, 0x0)?;
+        let table = &cfg.jump_tables[&0xC];
+        assert_eq!(table.index_table, Some(0x15..0x19));
+        assert_eq!(table.table, 0x19..0x21);
+        assert_eq!(table.targets, vec![0x13, 0x14]);
+        assert!(cfg.basic_blocks.contains_key(&0x13));
+
+        Ok(())
+    }
+
+    #[test]
+    fn relative_to_table() -> Result<()> {
+        // 0x0:  83 f8 01                cmp    eax, 0x1
+        // 0x3:  77 11                   ja     0x16
+        // 0x5:  48 8d 0d 0b 00 00 00    lea    rcx, [rip + 0xB]  ; 0x17
+        // 0xC:  48 63 04 81             movsxd rax, dword ptr [rcx + rax*4]
+        // 0x10: 48 01 c8                add    rax, rcx
+        // 0x13: ff e0                   jmp    rax
+        // 0x15: 90                      nop
+        // 0x16: c3                      ret
+        // 0x17: fe ff ff ff             table: 0x15 - 0x17
+        // 0x1B: ff ff ff ff                    0x16 - 0x17
+        let module = load_shellcode64(
+            b"\x83\xF8\x01\x77\x11\x48\x8D\x0D\x0B\x00\x00\x00\x48\x63\x04\x81\x48\x01\xC8\xFF\xE0\x90\xC3\xFE\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+        );
+        let cfg = build_cfg(&module, 0x0)?;
+        let table = &cfg.jump_tables[&0x13];
+        assert_eq!(table.table, 0x17..0x1F);
+        assert_eq!(table.targets, vec![0x15, 0x16]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hoisted_image_base() -> Result<()> {
+        // the base register is loaded in a prior basic block,
+        // so its value is assumed to be the image base.
+        //
+        // 0x0:  48 8d 15 f9 ff ff ff    lea    rdx, [rip - 0x7]  ; 0x0
+        // 0x7:  eb 00                   jmp    0x9
+        // 0x9:  83 f8 01                cmp    eax, 0x1
+        // 0xC:  77 0d                   ja     0x1B
+        // 0xE:  8b 8c 82 1c 00 00 00    mov    ecx, dword ptr [rdx + rax*4 + 0x1C]
+        // 0x15: 48 03 ca                add    rcx, rdx
+        // 0x18: ff e1                   jmp    rcx
+        // 0x1A: 90                      nop
+        // 0x1B: c3                      ret
+        // 0x1C: 1a 00 00 00             table: 0x1A
+        // 0x20: 1b 00 00 00                    0x1B
+        let module = load_shellcode64(
+            b"\x48\x8D\x15\xF9\xFF\xFF\xFF\xEB\x00\x83\xF8\x01\x77\x0D\x8B\x8C\x82\x1C\x00\x00\x00\x48\x03\xCA\xFF\xE1\x90\xC3\x1A\x00\x00\x00\x1B\x00\x00\x00",
+        );
+        let cfg = build_cfg(&module, 0x0)?;
+        let table = &cfg.jump_tables[&0x18];
+        assert_eq!(table.table, 0x1C..0x24);
+        assert_eq!(table.targets, vec![0x1A, 0x1B]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn no_bounds_check() -> Result<()> {
+        // 0x0:  ff 24 85 07 00 00 00    jmp    dword ptr [eax*4 + 0x7]
+        // 0x7:  00 00 00 00
+        let module = load_shellcode32(b"\xFF\x24\x85\x07\x00\x00\x00\x00\x00\x00\x00");
+        let cfg = build_cfg(&module, 0x0)?;
+        assert!(cfg.jump_tables.is_empty());
+        assert_eq!(cfg.basic_blocks.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn huge_bound() -> Result<()> {
+        // the bound comes from the `cmp` immediate, as with `jae`,
+        // and must be rejected before the index table is read.
+        //
+        // 0x0:  83 f8 fe                cmp    eax, 0xFFFFFFFE
+        // 0x3:  73 0a                   jae    0xF
+        // 0x5:  0f b6 00                movzx  eax, byte ptr [eax]
+        // 0x8:  ff 24 85 10 00 00 00    jmp    dword ptr [eax*4 + 0x10]
+        // 0xF:  c3                      ret
+        let module = load_shellcode32(b"\x83\xF8\xFE\x73\x0A\x0F\xB6\x00\xFF\x24\x85\x10\x00\x00\x00\xC3");
+        let cfg = build_cfg(&module, 0x0)?;
+        assert!(cfg.jump_tables.is_empty());
+
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    ops::Range,
+};
 
 use anyhow::Result;
 use log::debug;
@@ -11,6 +14,13 @@ use crate::{
     util, VA,
 };
 
+pub mod functions;
+pub mod jump_tables;
+pub mod noret;
+
+use functions::FunctionBoundaries;
+use jump_tables::JumpTable;
+
 /// The type and destination of a control flow.
 #[derive(Debug, Clone, Copy)]
 pub enum Flow {
@@ -88,6 +98,27 @@ pub struct CFG {
     // alternative choice would be an FNV hash map,
     // because the keys are small.
     pub basic_blocks: BTreeMap<VA, BasicBlock>,
+
+    /// the jump tables recovered from indirect jumps,
+    /// indexed by the address of the `jmp` instruction.
+    pub jump_tables: BTreeMap<VA, JumpTable>,
+
+    /// the jumps to other functions, which aren't followed,
+    /// indexed by the address of the jump instruction.
+    /// only found when the function boundaries are provided,
+    /// see `build_cfg_with_boundaries`.
+    pub tail_calls: BTreeMap<VA, VA>,
+}
+
+impl CFG {
+    /// the regions of data found among the instructions, like jump tables,
+    /// which shouldn't be disassembled.
+    pub fn data_regions(&self) -> Vec<Range<VA>> {
+        self.jump_tables
+            .values()
+            .flat_map(|table| table.data_regions())
+            .collect()
+    }
 }
 
 /// Does the given instruction have a fallthrough flow?
@@ -125,8 +156,9 @@ pub fn does_insn_fallthrough(insn: &zydis::DecodedInstruction) -> bool {
                 }
             }
         }
-        // TODO: call may not fallthrough if function is noret.
-        // will need another pass to clean this up.
+        // a call may not fallthrough if the function is noret,
+        // but that depends on the target.
+        // see `noret::find_noret_functions` and `build_cfg_with_noret`.
         zydis::Mnemonic::CALL => true,
         _ => true,
     }
@@ -162,6 +194,30 @@ pub fn get_first_operand(insn: &zydis::DecodedInstruction) -> Option<&zydis::Dec
         .find(|op| op.visibility == zydis::OperandVisibility::EXPLICIT)
 }
 
+/// fetch the address referenced by a memory operand,
+/// when it doesn't depend on the value of a register:
+/// an absolute address, like `[0x401000]`,
+/// or a RIP-relative address, like `[rip + 0x10]`.
+pub fn get_memory_operand_ptr(va: VA, insn: &zydis::DecodedInstruction, op: &zydis::DecodedOperand) -> Option<VA> {
+    if op.mem.index != zydis::Register::NONE || !op.mem.disp.has_displacement {
+        return None;
+    }
+
+    if op.mem.base == zydis::Register::NONE {
+        if op.mem.disp.displacement < 0 {
+            return None;
+        }
+        Some(op.mem.disp.displacement as u64)
+    } else if op.mem.base == zydis::Register::RIP {
+        // only valid on x64.
+        // this works like a relative immediate,
+        // that is: ptr = va + displacement + instruction len
+        va_add_signed(va + insn.length as u64, op.mem.disp.displacement)
+    } else {
+        None
+    }
+}
+
 #[allow(clippy::if_same_then_else)]
 pub fn get_memory_operand_xref(
     module: &Module,
@@ -184,10 +240,10 @@ pub fn get_memory_operand_xref(
         //
         // see doctest: [test simple memory ptr operand]()
 
-        if op.mem.disp.displacement < 0 {
-            return Ok(None);
-        }
-        let ptr: VA = op.mem.disp.displacement as u64;
+        let ptr = match get_memory_operand_ptr(va, insn, op) {
+            None => return Ok(None),
+            Some(ptr) => ptr,
+        };
 
         let dst = match module.read_va_at_va(ptr) {
             Ok(dst) => dst,
@@ -210,7 +266,7 @@ pub fn get_memory_operand_xref(
         // it works like a relative immediate,
         // that is: dst = *(rva + displacement + instruction len)
 
-        let ptr = match va_add_signed(va + insn.length as u64, op.mem.disp.displacement as i64) {
+        let ptr = match get_memory_operand_ptr(va, insn, op) {
             None => return Ok(None),
             Some(ptr) => ptr,
         };
@@ -340,13 +396,13 @@ pub fn get_jmp_insn_flow(module: &Module, va: VA, insn: &zydis::DecodedInstructi
     let op = get_first_operand(insn).expect("JMP has no target");
 
     if op.ty == zydis::OperandType::MEMORY
-        && op.mem.scale == 0x4
+        && op.mem.index != zydis::Register::NONE
         && op.mem.base == zydis::Register::NONE
         && op.mem.disp.has_displacement
     {
         // this looks like a switch table, e.g. `JMP [0x1000+ecx*4]`
-        // it should probably be solved via emulation.
-        // see analysis/pe/pointers.rs for some experiments looking at pointer tables.
+        // which we can't resolve from this instruction alone.
+        // see `jump_tables::find_jump_table`.
         Ok(smallvec![])
     } else {
         match get_operand_xref(module, va, insn, op)? {
@@ -447,14 +503,35 @@ fn empty<'a, T>(mut i: Box<dyn Iterator<Item = T> + 'a>) -> bool {
     i.next().is_none()
 }
 
-fn read_insn_descriptors(module: &Module, va: VA) -> Result<BTreeMap<VA, InstructionDescriptor>> {
+#[derive(Default)]
+struct InstructionDescriptors {
+    insns:       BTreeMap<VA, InstructionDescriptor>,
+    jump_tables: BTreeMap<VA, JumpTable>,
+    tail_calls:  BTreeMap<VA, VA>,
+}
+
+fn read_insn_descriptors(
+    module: &Module,
+    function: VA,
+    noret: &BTreeSet<VA>,
+    boundaries: Option<&FunctionBoundaries>,
+) -> Result<InstructionDescriptors> {
     let decoder = dis::get_disassembler(module)?;
     let mut insn_buf = [0u8; 16];
 
     let mut queue: VecDeque<VA> = Default::default();
-    queue.push_back(va);
+    queue.push_back(function);
+
+    let mut descriptors: InstructionDescriptors = Default::default();
+    let InstructionDescriptors {
+        insns,
+        jump_tables,
+        tail_calls,
+    } = &mut descriptors;
 
-    let mut insns: BTreeMap<VA, InstructionDescriptor> = Default::default();
+    // so that we can step backwards from an indirect jump
+    // to find its jump table.
+    let mut fallthrough_predecessors: BTreeMap<VA, VA> = Default::default();
 
     loop {
         let va = match queue.pop_back() {
@@ -469,13 +546,46 @@ fn read_insn_descriptors(module: &Module, va: VA) -> Result<BTreeMap<VA, Instruc
         // TODO: optimize here by re-using buffers.
         if module.address_space.read_into(va, &mut insn_buf).is_ok() {
             if let Ok(Some(insn)) = decoder.decode(&insn_buf) {
-                let successors: Flows = get_insn_flow(module, va, &insn)?
+                let is_noret_call = insn.mnemonic == zydis::Mnemonic::CALL
+                    && !noret.is_empty()
+                    && noret::is_noret_call(module, va, &insn, noret);
+
+                let mut successors: Flows = get_insn_flow(module, va, &insn)?
                     // remove CALL instructions for cfg reconstruction.
                     .into_iter()
                     .filter(|succ| !matches!(succ, Flow::Call(_)))
+                    // and the fallthrough from calls that don't return.
+                    .filter(|succ| !(is_noret_call && matches!(succ, Flow::Fallthrough(_))))
                     .collect();
 
+                if let Some(boundaries) = boundaries {
+                    // jumps to other functions are tail calls,
+                    // so we don't include the other function's instructions.
+                    successors.retain(|succ| match succ {
+                        Flow::UnconditionalJump(target) | Flow::ConditionalJump(target)
+                            if boundaries.is_tail_call(function, *target) =>
+                        {
+                            debug!("cfg: {:#x}: tail call to {:#x}", va, target);
+                            tail_calls.insert(va, *target);
+                            false
+                        }
+                        _ => true,
+                    });
+                }
+
+                if insn.mnemonic == zydis::Mnemonic::JMP && successors.is_empty() {
+                    if let Some(table) =
+                        jump_tables::find_jump_table(module, &decoder, &fallthrough_predecessors, va, &insn)
+                    {
+                        successors.extend(table.flows());
+                        jump_tables.insert(va, table);
+                    }
+                }
+
                 for target in successors.iter() {
+                    if let Flow::Fallthrough(next) = target {
+                        fallthrough_predecessors.insert(*next, va);
+                    }
                     queue.push_back(target.va());
                 }
 
@@ -489,7 +599,7 @@ fn read_insn_descriptors(module: &Module, va: VA) -> Result<BTreeMap<VA, Instruc
         }
     }
 
-    Ok(insns)
+    Ok(descriptors)
 }
 
 /// compute successors for an instruction (specified by VA).
@@ -661,9 +771,43 @@ fn compute_basic_blocks(
 }
 
 pub fn build_cfg(module: &Module, va: VA) -> Result<CFG> {
+    build_cfg_with_noret(module, va, &Default::default())
+}
+
+/// like `build_cfg`, but calls to the given functions don't fall through.
+///
+/// the addresses may be of functions or of the pointers through which
+/// they're called, like import address table entries,
+/// such as those found by `noret::find_noret_functions`.
+pub fn build_cfg_with_noret(module: &Module, va: VA, noret: &BTreeSet<VA>) -> Result<CFG> {
+    build_cfg_inner(module, va, noret, None)
+}
+
+/// like `build_cfg_with_noret`, but jumps that leave the function,
+/// as determined by the given boundaries, are recorded as tail calls
+/// rather than followed.
+pub fn build_cfg_with_boundaries(
+    module: &Module,
+    va: VA,
+    noret: &BTreeSet<VA>,
+    boundaries: &FunctionBoundaries,
+) -> Result<CFG> {
+    build_cfg_inner(module, va, noret, Some(boundaries))
+}
+
+fn build_cfg_inner(
+    module: &Module,
+    va: VA,
+    noret: &BTreeSet<VA>,
+    boundaries: Option<&FunctionBoundaries>,
+) -> Result<CFG> {
     debug!("cfg: {:#x}", va);
 
-    let insns = read_insn_descriptors(module, va)?;
+    let InstructionDescriptors {
+        insns,
+        jump_tables,
+        tail_calls,
+    } = read_insn_descriptors(module, va, noret, boundaries)?;
     debug!("cfg: {:#x}: {} instructions", va, insns.len());
 
     let successors = compute_successors(&insns);
@@ -672,7 +816,11 @@ pub fn build_cfg(module: &Module, va: VA) -> Result<CFG> {
     let bbs = compute_basic_blocks(&insns, &predecessors, &successors);
     debug!("cfg: {:#x}: {} basic blocks", va, bbs.len());
 
-    Ok(CFG { basic_blocks: bbs })
+    Ok(CFG {
+        basic_blocks: bbs,
+        jump_tables,
+        tail_calls,
+    })
 }
 
 #[cfg(test)]
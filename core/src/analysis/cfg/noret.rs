@@ -0,0 +1,332 @@
+//! Find the functions that don't return to their caller,
+//! like `ExitProcess` or `abort`, so that `build_cfg` doesn't disassemble
+//! whatever follows a call to them.
+//!
+//! We seed the analysis with the imports known by name not to return,
+//! and the thunks that jump to them.
+//! Then, we spread to local functions for which every exit path ends in:
+//!
+//!   - a call or tail call to a no-return function,
+//!   - a breakpoint (`int3`), or
+//!   - a fast fail (`int 0x29`) or assertion (`int 0x2C`).
+//!
+//! A function with an exit we can't account for,
+//! like an indirect `jmp` or an instruction we can't decode,
+//! is assumed to return.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/cpp/cpp/noreturn
+//!   - https://hex-rays.com/blog/igors-tip-of-the-week-126-non-returning-functions/
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use anyhow::Result;
+use log::debug;
+use smallvec::{smallvec, SmallVec};
+
+use crate::{
+    analysis::{
+        cfg::{self, CFG},
+        dis,
+        pe::{Function, Import, ImportedSymbol},
+    },
+    aspace::AddressSpace,
+    module::Module,
+    RVA, VA,
+};
+
+/// the names of imported routines that don't return.
+///
+/// Mach-O symbols have a leading underscore, like `_exit`,
+/// which is stripped before matching, so `exit` and `_exit` match either.
+const NORET_IMPORTS: &[&str] = &[
+    // kernel32, ntdll
+    "ExitProcess",
+    "ExitThread",
+    "FatalAppExitA",
+    "FatalAppExitW",
+    "FatalExit",
+    "FreeLibraryAndExitThread",
+    "RaiseFailFastException",
+    "RtlExitUserProcess",
+    "RtlExitUserThread",
+    "RtlRaiseStatus",
+    // msvcrt, ucrt, vcruntime
+    "_amsg_exit",
+    "_exit",
+    "_Exit",
+    "_invalid_parameter_noinfo_noreturn",
+    "_invoke_watson",
+    "_CxxThrowException",
+    "abort",
+    "exit",
+    "quick_exit",
+    "longjmp",
+    "?terminate@@YAXXZ",
+    "__std_terminate",
+    // libc, libstdc++
+    "__assert_fail",
+    "__assert_rtn",
+    "__cxa_rethrow",
+    "__cxa_throw",
+    "__stack_chk_fail",
+    "_Unwind_Resume",
+    "err",
+    "errx",
+    "pthread_exit",
+];
+
+/// does the given import not return to its caller?
+pub fn is_noret_import(import: &Import) -> bool {
+    let name = match &import.symbol {
+        ImportedSymbol::Name(name) => name.as_str(),
+        ImportedSymbol::Ordinal(_) => return false,
+    };
+
+    NORET_IMPORTS.contains(&name) || matches!(name.strip_prefix('_'), Some(name) if NORET_IMPORTS.contains(&name))
+}
+
+/// find the addresses referenced by a `call` or `jmp`:
+/// the destination, if it can be resolved,
+/// and the pointer that's dereferenced, like an import address table entry.
+/// when the loader fills in the pointer, like for `call [ExitProcess]`,
+/// there's only the latter.
+pub fn get_branch_targets(module: &Module, va: VA, insn: &zydis::DecodedInstruction) -> SmallVec<[VA; 2]> {
+    let mut targets: SmallVec<[VA; 2]> = smallvec![];
+
+    let op = match cfg::get_first_operand(insn) {
+        Some(op) => op,
+        None => return targets,
+    };
+
+    match op.ty {
+        zydis::OperandType::IMMEDIATE if op.imm.is_relative => {
+            if let Ok(Some(dst)) = cfg::get_immediate_operand_xref(module, va, insn, op) {
+                targets.push(dst);
+            }
+        }
+        zydis::OperandType::MEMORY => {
+            if let Some(ptr) = cfg::get_memory_operand_ptr(va, insn, op) {
+                targets.push(ptr);
+                if let Ok(Some(dst)) = cfg::get_memory_operand_xref(module, va, insn, op) {
+                    targets.push(dst);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    targets
+}
+
+/// does the given `call` instruction invoke a no-return function?
+pub fn is_noret_call(module: &Module, va: VA, insn: &zydis::DecodedInstruction, noret: &BTreeSet<VA>) -> bool {
+    get_branch_targets(module, va, insn)
+        .iter()
+        .any(|target| noret.contains(target))
+}
+
+/// the addresses referenced by the calls and jumps found in the CFG,
+/// and whether the CFG has an exit path that may return to the caller.
+fn analyze_cfg(
+    module: &Module,
+    decoder: &zydis::Decoder,
+    cfg: &CFG,
+    noret: &BTreeSet<VA>,
+) -> Result<(BTreeSet<VA>, bool)> {
+    let mut references: BTreeSet<VA> = Default::default();
+    let mut returns = false;
+
+    for basic_block in cfg.basic_blocks.values() {
+        let buf = module
+            .address_space
+            .read_bytes(basic_block.address, basic_block.length as usize)?;
+
+        let mut last: Option<(VA, zydis::DecodedInstruction)> = None;
+        for (offset, insn) in dis::linear_disassemble(decoder, &buf) {
+            if let Ok(Some(insn)) = insn {
+                let va = basic_block.address + offset as RVA;
+                if matches!(insn.mnemonic, zydis::Mnemonic::CALL | zydis::Mnemonic::JMP) {
+                    references.extend(get_branch_targets(module, va, &insn));
+                }
+                last = Some((va, insn));
+            } else {
+                last = None;
+            }
+        }
+
+        if returns {
+            // keep collecting references,
+            // since they're needed if this function is analyzed again.
+            continue;
+        }
+
+        if !basic_block.successors.is_empty() {
+            // a flow to something we couldn't disassemble,
+            // like the end of a section.
+            if basic_block
+                .successors
+                .iter()
+                .any(|succ| !cfg.basic_blocks.contains_key(&succ.va()))
+            {
+                returns = true;
+            }
+            continue;
+        }
+
+        returns = match last {
+            // ret, or we couldn't figure out the exit.
+            None => true,
+            Some((va, insn)) => match insn.mnemonic {
+                zydis::Mnemonic::CALL => !is_noret_call(module, va, &insn, noret),
+                zydis::Mnemonic::JMP => !is_noret_call(module, va, &insn, noret),
+                zydis::Mnemonic::INT3 | zydis::Mnemonic::INT => cfg::does_insn_fallthrough(&insn),
+                _ => true,
+            },
+        };
+    }
+
+    Ok((references, returns))
+}
+
+/// find the functions that don't return to their caller.
+///
+/// the result contains the addresses of imports (that is, the import address
+/// table entries), thunks, and local functions,
+/// and is suitable for `build_cfg_with_noret`.
+pub fn find_noret_functions(module: &Module, functions: &[Function]) -> Result<BTreeSet<VA>> {
+    let decoder = dis::get_disassembler(module)?;
+    let mut noret: BTreeSet<VA> = Default::default();
+
+    for function in functions.iter() {
+        match function {
+            Function::Import(import) if is_noret_import(import) => {
+                debug!("noret: import: {:#x}: {}", import.address, import);
+                noret.insert(import.address);
+            }
+            Function::Thunk(thunk) if is_noret_import(&thunk.import) => {
+                debug!("noret: thunk: {:#x}: {}", thunk.address, thunk.import);
+                noret.insert(thunk.address);
+            }
+            _ => {}
+        }
+    }
+
+    let mut queue: VecDeque<VA> = Default::default();
+    let mut queued: BTreeSet<VA> = Default::default();
+    for function in functions.iter() {
+        if let Function::Local(va) = function {
+            if queued.insert(*va) {
+                queue.push_back(*va);
+            }
+        }
+    }
+
+    // map from an address to the functions that call or jump to it,
+    // which should be analyzed again when the address is found not to return.
+    let mut referrers: BTreeMap<VA, BTreeSet<VA>> = Default::default();
+
+    while let Some(function) = queue.pop_front() {
+        queued.remove(&function);
+
+        if noret.contains(&function) {
+            continue;
+        }
+
+        let cfg = match cfg::build_cfg_with_noret(module, function, &noret) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                debug!("noret: {:#x}: failed to build CFG: {}", function, e);
+                continue;
+            }
+        };
+
+        if cfg.basic_blocks.is_empty() {
+            continue;
+        }
+
+        let (references, returns) = analyze_cfg(module, &decoder, &cfg, &noret)?;
+        for reference in references.into_iter() {
+            referrers.entry(reference).or_default().insert(function);
+        }
+
+        if returns {
+            continue;
+        }
+
+        debug!("noret: function: {:#x}", function);
+        noret.insert(function);
+
+        if let Some(referrers) = referrers.get(&function) {
+            for &referrer in referrers.iter() {
+                if !noret.contains(&referrer) && queued.insert(referrer) {
+                    queue.push_back(referrer);
+                }
+            }
+        }
+    }
+
+    Ok(noret)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analysis::cfg::{build_cfg, build_cfg_with_noret, noret::*},
+        rsrc::*,
+        test::*,
+    };
+    use anyhow::Result;
+
+    #[test]
+    fn local() -> Result<()> {
+        // 0x0:  85 c0             test   eax, eax
+        // 0x2:  75 06             jnz    0xA
+        // 0x4:  e8 02 00 00 00    call   0xB
+        // 0x9:  40                inc    eax  ; junk
+        // 0xA:  c3                ret
+        // 0xB:  e8 01 00 00 00    call   0x11
+        // 0x10: cc                int3
+        // 0x11: cd 29             int    0x29
+        let module = load_shellcode32(b"\x85\xC0\x75\x06\xE8\x02\x00\x00\x00\x40\xC3\xE8\x01\x00\x00\x00\xCC\xCD\x29");
+        let functions = vec![Function::Local(0x0), Function::Local(0xB), Function::Local(0x11)];
+
+        // 0x0 may return, via the jnz.
+        let noret = find_noret_functions(&module, &functions)?;
+        assert_eq!(noret.iter().cloned().collect::<Vec<_>>(), vec![0xB, 0x11]);
+
+        // without the analysis, the call at 0x4 falls through to the junk.
+        let cfg = build_cfg(&module, 0x0)?;
+        assert_eq!(cfg.basic_blocks[&0x4].length, 6);
+
+        let cfg = build_cfg_with_noret(&module, 0x0, &noret)?;
+        assert_eq!(cfg.basic_blocks.len(), 3);
+        assert_eq!(cfg.basic_blocks[&0x4].length, 5);
+        assert!(cfg.basic_blocks[&0x4].successors.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimikatz() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        let functions = crate::analysis::pe::find_functions(&pe)?;
+        let noret = find_noret_functions(&pe.module, &functions)?;
+
+        // ExitProcess
+        assert!(noret.contains(&0x4733CC));
+        // mimikatz_end, which calls ExitProcess
+        assert!(noret.contains(&0x445796));
+        // wmain, which loops until mimikatz_end
+        assert!(noret.contains(&0x445620));
+
+        // .text:00445709  call    mimikatz_end
+        // .text:0044570E  int     3
+        let cfg = build_cfg(&pe.module, 0x445620)?;
+        assert_eq!(cfg.basic_blocks[&0x445709].length, 6);
+        let cfg = build_cfg_with_noret(&pe.module, 0x445620, &noret)?;
+        assert_eq!(cfg.basic_blocks[&0x445709].length, 5);
+
+        Ok(())
+    }
+}
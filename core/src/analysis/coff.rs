@@ -0,0 +1,158 @@
+//! Analysis passes for COFF objects, like those in `analysis::pe`.
+//!
+//! Imports are the slots for the undefined `__imp_` symbols,
+//! and functions are the symbols defined in executable sections.
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use log::debug;
+
+pub use crate::analysis::pe::{Function, Import, ImportedSymbol};
+use crate::{
+    loader::coff::{
+        archive::{ImportNameType, ImportObject},
+        Coff,
+    },
+    module::Permissions,
+    VA,
+};
+
+/// the library name used for imported symbols that aren't described by an
+/// import object, since the object doesn't otherwise bind a symbol to a
+/// library.
+pub const UNKNOWN_LIBRARY: &str = "*";
+
+/// the prefix of symbols that name an import address table entry,
+/// like `__imp_ExitProcess`.
+const IMPORT_PREFIX: &str = "__imp_";
+
+/// find the imports referenced by the object.
+///
+/// provide the import objects from an import library, like
+/// `Archive::imports`, to resolve the DLL that exports each symbol.
+pub fn get_imports(coff: &Coff, import_objects: &[ImportObject]) -> Result<BTreeMap<VA, Import>> {
+    let mut imports: BTreeMap<VA, Import> = Default::default();
+
+    for symbol in coff.symbols.iter().filter(|symbol| symbol.undefined) {
+        let (address, name) = match (symbol.address, symbol.name.strip_prefix(IMPORT_PREFIX)) {
+            (Some(address), Some(name)) => (address, name),
+            // not referenced, or defined by another object.
+            _ => continue,
+        };
+
+        let import_object = import_objects.iter().find(|import| import.name == name);
+        let dll = smol_str::SmolStr::new(
+            import_object
+                .map(|import| import.dll.as_str())
+                .unwrap_or(UNKNOWN_LIBRARY),
+        );
+        let symbol = match import_object {
+            Some(import) if import.name_type == ImportNameType::Ordinal => {
+                ImportedSymbol::Ordinal(import.ordinal_or_hint as u32)
+            }
+            _ => ImportedSymbol::Name(smol_str::SmolStr::new(name)),
+        };
+        debug!("imports: {:#x}: {}!{:?}", address, dll, name);
+
+        imports.insert(
+            address,
+            Import {
+                address,
+                dll,
+                symbol,
+                delay_loaded: false,
+                pinvoke: false,
+            },
+        );
+    }
+
+    Ok(imports)
+}
+
+/// find the functions defined by the object:
+/// those symbols in executable sections that are typed as functions or are
+/// visible to other objects.
+pub fn find_function_starts(coff: &Coff) -> Result<Vec<VA>> {
+    let mut function_starts: Vec<VA> = coff
+        .symbols
+        .iter()
+        .filter(|symbol| !symbol.section_definition && !symbol.undefined)
+        .filter(|symbol| symbol.function || symbol.external)
+        .filter_map(|symbol| symbol.address)
+        .filter(|&va| coff.module.probe_va(va, Permissions::X))
+        .collect();
+
+    function_starts.sort_unstable();
+    function_starts.dedup();
+
+    Ok(function_starts)
+}
+
+pub fn find_functions(coff: &Coff, import_objects: &[ImportObject]) -> Result<Vec<Function>> {
+    let imports = get_imports(coff, import_objects)?;
+    debug!("imports: found {} imports", imports.len());
+
+    let function_starts = find_function_starts(coff)?;
+    debug!("functions: found {} functions", function_starts.len());
+
+    let mut functions: Vec<Function> = Default::default();
+    functions.extend(function_starts.iter().map(|&f| Function::Local(f)));
+    functions.extend(imports.values().cloned().map(Function::Import));
+    functions.sort_unstable();
+
+    Ok(functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::COFF);
+        let coff = crate::loader::coff::Coff::from_bytes(&buf)?;
+
+        // main, add, helper
+        let starts = crate::analysis::coff::find_function_starts(&coff)?;
+        assert_eq!(vec![0x40_1000, 0x40_1017, 0x40_101B], starts);
+
+        let imports = crate::analysis::coff::get_imports(&coff, &[])?;
+        assert_eq!(1, imports.len());
+        assert_eq!("*!ExitProcess", format!("{}", imports.values().next().unwrap()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn lib() -> Result<()> {
+        let buf = get_buf(Rsrc::LIB);
+        let lib = crate::loader::coff::archive::Archive::from_bytes(&buf)?;
+        let import_objects = lib.imports();
+
+        let (_, coff) = lib.objects().next().unwrap();
+        let functions = crate::analysis::coff::find_functions(&coff, &import_objects)?;
+        assert_eq!(4, functions.len());
+
+        let imports = crate::analysis::coff::get_imports(&coff, &import_objects)?;
+        assert_eq!(
+            "kernel32.dll!ExitProcess",
+            format!("{}", imports.values().next().unwrap())
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "disassembler")]
+    #[test]
+    fn hello_cfg() -> Result<()> {
+        let buf = get_buf(Rsrc::COFF);
+        let coff = crate::loader::coff::Coff::from_bytes(&buf)?;
+
+        // main calls add and helper, which are reached via applied relocations.
+        let cfg = crate::analysis::cfg::build_cfg(&coff.module, 0x40_1000)?;
+        assert!(!cfg.basic_blocks.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,797 @@
+//! Propagate constants through the registers and stack slots of a function,
+//! so that we can resolve indirect calls and jumps, like:
+//!
+//! ```text
+//!     mov    eax, [__imp_CreateFileW]
+//!     call   eax
+//!
+//!     lea    rcx, [rip + callback]
+//!     mov    [rsp + 0x20], rcx
+//!     call   qword ptr [rsp + 0x20]
+//! ```
+//!
+//! and recover the arguments passed to calls, like `push offset aKernel32Dll`.
+//!
+//! This is a forward dataflow analysis over the CFG of a function.
+//! A register or stack slot has a known value at an instruction when every path
+//! to the instruction assigns it the same value: a constant, the contents of a
+//! global, or an address on the stack. Anything else is unknown.
+//!
+//! We make some assumptions that aren't always true:
+//!
+//!   - writes through pointers, other than to the stack, don't alias the stack,
+//!   - callees preserve the stack pointer, clobber the volatile registers, and
+//!     only modify the top slots of the caller's stack (arguments on x32, home
+//!     space on x64), and
+//!   - arguments are passed per the Windows calling conventions: on the stack
+//!     for x32, and in `rcx`, `rdx`, `r8`, and `r9` for x64.
+//!
+//! references:
+//!   - https://en.wikipedia.org/wiki/Constant_folding#Constant_propagation
+//!   - https://docs.microsoft.com/en-us/cpp/build/x64-calling-convention
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use anyhow::Result;
+use log::debug;
+use smallvec::SmallVec;
+
+use crate::{
+    analysis::{
+        cfg::{self, CFG},
+        dis,
+    },
+    arch::Arch,
+    aspace::AddressSpace,
+    module::{Module, Permissions},
+    RVA, VA,
+};
+
+/// the number of leading arguments recovered at each call.
+const ARGUMENT_COUNT: usize = 4;
+
+/// the minimum number of characters in a string argument.
+const MIN_STRING_LENGTH: usize = 4;
+
+/// the maximum number of characters read from a UTF-16 string argument.
+const MAX_STRING_LENGTH: usize = 0x400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Value {
+    /// a constant, like from `mov eax, 0x10` or `lea rcx, [rip + global]`.
+    Constant(u64),
+    /// the contents of the global at the given address,
+    /// which aren't known until runtime, like from `mov eax,
+    /// [__imp_CreateFileW]`.
+    Load(VA),
+    /// an address on the stack, as an offset from the stack pointer
+    /// at the start of the function, like from `lea eax, [ebp - 0x10]`.
+    Stack(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Argument {
+    pub value:  Value,
+    /// when the value points to an ASCII or UTF-16 string, its contents.
+    pub string: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    /// map from an indirect call or jump, like `call eax`,
+    /// to the code in the module that it reaches.
+    pub targets: BTreeMap<VA, VA>,
+
+    /// map from an indirect call or jump, like `call eax`,
+    /// to the global from which its destination was loaded,
+    /// like the import address table entry in `mov eax, [__imp_CreateFileW]`.
+    /// compare with the addresses of `analysis::pe::Import` to find the import.
+    pub pointers: BTreeMap<VA, VA>,
+
+    /// map from a call instruction to its leading arguments, in order,
+    /// with `None` for an argument that isn't known.
+    /// on x32, these are the top slots of the stack,
+    /// so they may include values that aren't arguments to the callee.
+    pub arguments: BTreeMap<VA, Vec<Option<Argument>>>,
+}
+
+impl Annotations {
+    /// merge the annotations of another function into these.
+    pub fn extend(&mut self, other: Annotations) {
+        self.targets.extend(other.targets);
+        self.pointers.extend(other.pointers);
+        self.arguments.extend(other.arguments);
+    }
+}
+
+/// the known values at a point in a function.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct State {
+    /// indexed by the largest enclosing register, like `rax`.
+    registers: HashMap<zydis::Register, Value>,
+    /// pointer-sized slots on the stack, indexed by offset, like
+    /// `Value::Stack`.
+    slots:     BTreeMap<i64, Value>,
+}
+
+impl State {
+    /// the values known in both states.
+    fn join(&self, other: &State) -> State {
+        State {
+            registers: self
+                .registers
+                .iter()
+                .filter(|(reg, value)| other.registers.get(reg) == Some(value))
+                .map(|(&reg, &value)| (reg, value))
+                .collect(),
+            slots:     self
+                .slots
+                .iter()
+                .filter(|(offset, value)| other.slots.get(offset) == Some(value))
+                .map(|(&offset, &value)| (offset, value))
+                .collect(),
+        }
+    }
+}
+
+/// the operands as they'd be listed by a disassembler, including implicit
+/// ones like `eax` in the short form of `mov eax, [0x401000]` (`A1 00 10 40
+/// 00`).
+fn visible_operands(insn: &zydis::DecodedInstruction) -> impl Iterator<Item = &zydis::DecodedOperand> {
+    insn.operands[..insn.operand_count as usize].iter().filter(|op| {
+        op.visibility == zydis::OperandVisibility::EXPLICIT || op.visibility == zydis::OperandVisibility::IMPLICIT
+    })
+}
+
+/// read the ASCII or UTF-16 string at the given address, if any.
+fn read_string(module: &Module, va: VA) -> Option<String> {
+    if !module.probe_va(va, Permissions::R) {
+        return None;
+    }
+
+    if let Ok(s) = module.address_space.read_ascii(va, MIN_STRING_LENGTH) {
+        return Some(s);
+    }
+
+    // like `L"kernel32.dll"`.
+    let mut chars: Vec<u16> = vec![];
+    for i in 0..MAX_STRING_LENGTH {
+        match module.address_space.read_u16(va + (i * 2) as RVA) {
+            Ok(c) if (0x20..0x7F).contains(&c) || c == 0x9 || c == 0xA || c == 0xD => chars.push(c),
+            _ => break,
+        }
+    }
+
+    if chars.len() < MIN_STRING_LENGTH {
+        return None;
+    }
+
+    String::from_utf16(&chars).ok()
+}
+
+struct Context<'a> {
+    module: &'a Module,
+    mode:   zydis::MachineMode,
+    /// the size of a pointer, in bytes.
+    psize:  i64,
+    /// `esp` or `rsp`.
+    sp:     zydis::Register,
+    /// `ebp` or `rbp`.
+    bp:     zydis::Register,
+}
+
+impl<'a> Context<'a> {
+    fn new(module: &'a Module) -> Context<'a> {
+        match module.arch {
+            Arch::X32 => Context {
+                module,
+                mode: zydis::MachineMode::LEGACY_32,
+                psize: 4,
+                sp: zydis::Register::ESP,
+                bp: zydis::Register::EBP,
+            },
+            Arch::X64 => Context {
+                module,
+                mode: zydis::MachineMode::LONG_64,
+                psize: 8,
+                sp: zydis::Register::RSP,
+                bp: zydis::Register::RBP,
+            },
+        }
+    }
+
+    /// truncate the given value to the size of a pointer.
+    fn mask(&self, value: u64) -> u64 {
+        match self.module.arch {
+            Arch::X32 => value & 0xFFFF_FFFF,
+            Arch::X64 => value,
+        }
+    }
+
+    fn read_register(&self, state: &State, reg: zydis::Register) -> Option<Value> {
+        if reg == zydis::Register::NONE {
+            return None;
+        }
+
+        let width = reg.get_width(self.mode) as i64;
+        let value = *state.registers.get(&reg.get_largest_enclosing(self.mode))?;
+
+        if width == self.psize * 8 {
+            Some(value)
+        } else if width == 32 {
+            // the low half of an x64 register, like `eax`.
+            match value {
+                Value::Constant(c) => Some(Value::Constant(c & 0xFFFF_FFFF)),
+                _ => None,
+            }
+        } else {
+            None
+        }
+    }
+
+    fn write_register(&self, state: &mut State, reg: zydis::Register, value: Option<Value>) {
+        let width = reg.get_width(self.mode) as i64;
+
+        let value = if width == self.psize * 8 {
+            value
+        } else if width == 32 {
+            // on x64, writes to a 32-bit register, like `eax`, are zero extended.
+            match value {
+                Some(Value::Constant(c)) => Some(Value::Constant(c & 0xFFFF_FFFF)),
+                _ => None,
+            }
+        } else {
+            // a partial write, like to `al`.
+            None
+        };
+
+        let reg = reg.get_largest_enclosing(self.mode);
+        match value {
+            Some(value) => state.registers.insert(reg, value),
+            None => state.registers.remove(&reg),
+        };
+    }
+
+    /// the address referenced by the given memory operand, if known,
+    /// like a global `[0x401000]` or a stack slot `[ebp - 0x10]`.
+    fn memory_address(
+        &self,
+        state: &State,
+        va: VA,
+        insn: &zydis::DecodedInstruction,
+        op: &zydis::DecodedOperand,
+    ) -> Option<Value> {
+        if matches!(op.mem.segment, zydis::Register::FS | zydis::Register::GS) {
+            // like the TEB/PEB.
+            return None;
+        }
+
+        if let Some(ptr) = cfg::get_memory_operand_ptr(va, insn, op) {
+            return Some(Value::Constant(ptr));
+        }
+
+        if op.mem.index != zydis::Register::NONE {
+            return None;
+        }
+
+        let disp = if op.mem.disp.has_displacement {
+            op.mem.disp.displacement
+        } else {
+            0
+        };
+
+        match self.read_register(state, op.mem.base)? {
+            Value::Stack(offset) => Some(Value::Stack(offset.wrapping_add(disp))),
+            Value::Constant(c) => Some(Value::Constant(self.mask(c.wrapping_add(disp as u64)))),
+            Value::Load(_) => None,
+        }
+    }
+
+    /// `size` is in bits.
+    fn read_memory(&self, state: &State, address: Option<Value>, size: u16) -> Option<Value> {
+        if size as i64 != self.psize * 8 {
+            return None;
+        }
+
+        match address? {
+            Value::Stack(offset) => state.slots.get(&offset).copied(),
+            Value::Constant(ptr) => Some(Value::Load(ptr)),
+            Value::Load(_) => None,
+        }
+    }
+
+    /// `size` is in bits.
+    fn write_memory(&self, state: &mut State, address: Option<Value>, size: u16, value: Option<Value>) {
+        // writes elsewhere aren't tracked.
+        let offset = match address {
+            Some(Value::Stack(offset)) => offset,
+            _ => return,
+        };
+
+        let size = std::cmp::max(1, size as i64 / 8);
+        let overlapping: SmallVec<[i64; 2]> = state
+            .slots
+            .range(offset - self.psize + 1..offset + size)
+            .map(|(&offset, _)| offset)
+            .collect();
+        for offset in overlapping.iter() {
+            state.slots.remove(offset);
+        }
+
+        if size == self.psize {
+            if let Some(value) = value {
+                state.slots.insert(offset, value);
+            }
+        }
+    }
+
+    fn read_operand(
+        &self,
+        state: &State,
+        va: VA,
+        insn: &zydis::DecodedInstruction,
+        op: &zydis::DecodedOperand,
+    ) -> Option<Value> {
+        match op.ty {
+            zydis::OperandType::REGISTER => self.read_register(state, op.reg),
+            zydis::OperandType::IMMEDIATE if !op.imm.is_relative => Some(Value::Constant(self.mask(op.imm.value))),
+            zydis::OperandType::MEMORY => {
+                let address = self.memory_address(state, va, insn, op);
+                self.read_memory(state, address, op.size)
+            }
+            _ => None,
+        }
+    }
+
+    fn write_operand(
+        &self,
+        state: &mut State,
+        va: VA,
+        insn: &zydis::DecodedInstruction,
+        op: &zydis::DecodedOperand,
+        value: Option<Value>,
+    ) {
+        match op.ty {
+            zydis::OperandType::REGISTER => self.write_register(state, op.reg, value),
+            zydis::OperandType::MEMORY => {
+                let address = self.memory_address(state, va, insn, op);
+                self.write_memory(state, address, op.size, value);
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&self, state: &mut State, value: Option<Value>) {
+        if let Some(Value::Stack(offset)) = self.read_register(state, self.sp) {
+            let offset = offset - self.psize;
+            self.write_register(state, self.sp, Some(Value::Stack(offset)));
+            self.write_memory(state, Some(Value::Stack(offset)), (self.psize * 8) as u16, value);
+        }
+    }
+
+    fn pop(&self, state: &mut State) -> Option<Value> {
+        if let Some(Value::Stack(offset)) = self.read_register(state, self.sp) {
+            self.write_register(state, self.sp, Some(Value::Stack(offset + self.psize)));
+            state.slots.get(&offset).copied()
+        } else {
+            None
+        }
+    }
+
+    fn call(&self, state: &mut State) {
+        let volatile: &[zydis::Register] = match self.module.arch {
+            Arch::X32 => &[zydis::Register::EAX, zydis::Register::ECX, zydis::Register::EDX],
+            Arch::X64 => &[
+                zydis::Register::RAX,
+                zydis::Register::RCX,
+                zydis::Register::RDX,
+                zydis::Register::R8,
+                zydis::Register::R9,
+                zydis::Register::R10,
+                zydis::Register::R11,
+            ],
+        };
+        for reg in volatile.iter() {
+            state.registers.remove(reg);
+        }
+
+        // on x32, the callee may pop its arguments,
+        // and on x64, the callee may spill its arguments to the home space.
+        if let Some(Value::Stack(offset)) = self.read_register(state, self.sp) {
+            for i in 0..ARGUMENT_COUNT as i64 {
+                state.slots.remove(&(offset + i * self.psize));
+            }
+        }
+    }
+
+    /// forget the registers and stack slots written by the given instruction.
+    fn clobber(&self, state: &mut State, va: VA, insn: &zydis::DecodedInstruction) {
+        let ops = &insn.operands[..insn.operand_count as usize];
+        let writes = ops
+            .iter()
+            .filter(|op| op.action.intersects(zydis::OperandAction::MASK_WRITE));
+
+        // compute the addresses before any of the registers change,
+        // like `edi` in `stosd`.
+        let addresses: SmallVec<[(Option<Value>, u16); 2]> = writes
+            .clone()
+            .filter(|op| op.ty == zydis::OperandType::MEMORY)
+            .map(|op| (self.memory_address(state, va, insn, op), op.size))
+            .collect();
+        for &(address, size) in addresses.iter() {
+            self.write_memory(state, address, size, None);
+        }
+
+        for op in writes.filter(|op| op.ty == zydis::OperandType::REGISTER) {
+            state.registers.remove(&op.reg.get_largest_enclosing(self.mode));
+        }
+    }
+
+    /// update the state with the effects of the given instruction.
+    fn step(&self, state: &mut State, va: VA, insn: &zydis::DecodedInstruction) {
+        let ops: SmallVec<[&zydis::DecodedOperand; 3]> = visible_operands(insn).collect();
+
+        match (insn.mnemonic, ops.as_slice()) {
+            (zydis::Mnemonic::MOV, [dst, src]) => {
+                let value = self.read_operand(state, va, insn, src);
+                self.write_operand(state, va, insn, dst, value);
+            }
+            (zydis::Mnemonic::LEA, [dst, src]) => {
+                let value = self.memory_address(state, va, insn, src);
+                self.write_register(state, dst.reg, value);
+            }
+            (zydis::Mnemonic::XOR | zydis::Mnemonic::SUB, [dst, src])
+                if dst.ty == zydis::OperandType::REGISTER
+                    && src.ty == zydis::OperandType::REGISTER
+                    && dst.reg == src.reg =>
+            {
+                // like `xor eax, eax`.
+                self.write_register(state, dst.reg, Some(Value::Constant(0)));
+            }
+            (zydis::Mnemonic::ADD | zydis::Mnemonic::SUB, [dst, src])
+                if dst.ty == zydis::OperandType::REGISTER && src.ty == zydis::OperandType::IMMEDIATE =>
+            {
+                // like `sub esp, 0x10`.
+                let delta = if insn.mnemonic == zydis::Mnemonic::ADD {
+                    src.imm.value as i64
+                } else {
+                    (src.imm.value as i64).wrapping_neg()
+                };
+
+                let value = match self.read_register(state, dst.reg) {
+                    Some(Value::Stack(offset)) => Some(Value::Stack(offset.wrapping_add(delta))),
+                    Some(Value::Constant(c)) => Some(Value::Constant(self.mask(c.wrapping_add(delta as u64)))),
+                    _ => None,
+                };
+                self.write_register(state, dst.reg, value);
+            }
+            (zydis::Mnemonic::PUSH, [src]) => {
+                let value = self.read_operand(state, va, insn, src);
+                self.push(state, value);
+            }
+            (zydis::Mnemonic::POP, [dst]) => {
+                let value = self.pop(state);
+                self.write_operand(state, va, insn, dst, value);
+            }
+            (zydis::Mnemonic::LEAVE, _) => {
+                // mov esp, ebp; pop ebp
+                let bp = self.read_register(state, self.bp);
+                self.write_register(state, self.sp, bp);
+                let value = self.pop(state);
+                self.write_register(state, self.bp, value);
+            }
+            (zydis::Mnemonic::CALL, _) => self.call(state),
+            _ => self.clobber(state, va, insn),
+        }
+    }
+
+    /// the value of the destination of the given indirect `call` or `jmp`.
+    fn branch_target(&self, state: &State, va: VA, insn: &zydis::DecodedInstruction) -> Option<Value> {
+        let op = cfg::get_first_operand(insn)?;
+
+        match op.ty {
+            zydis::OperandType::REGISTER => self.read_register(state, op.reg),
+            // `call [0x401000]` is resolved by `cfg::get_call_insn_flow`.
+            zydis::OperandType::MEMORY if cfg::get_memory_operand_ptr(va, insn, op).is_none() => {
+                let address = self.memory_address(state, va, insn, op);
+                self.read_memory(state, address, op.size)
+            }
+            _ => None,
+        }
+    }
+
+    fn arguments(&self, state: &State) -> Vec<Option<Argument>> {
+        let values: SmallVec<[Option<Value>; ARGUMENT_COUNT]> = match self.module.arch {
+            Arch::X32 => match self.read_register(state, self.sp) {
+                Some(Value::Stack(offset)) => (0..ARGUMENT_COUNT as i64)
+                    .map(|i| state.slots.get(&(offset + i * self.psize)).copied())
+                    .collect(),
+                _ => Default::default(),
+            },
+            Arch::X64 => [
+                zydis::Register::RCX,
+                zydis::Register::RDX,
+                zydis::Register::R8,
+                zydis::Register::R9,
+            ]
+            .iter()
+            .map(|reg| state.registers.get(reg).copied())
+            .collect(),
+        };
+
+        let mut arguments: Vec<Option<Argument>> = values
+            .into_iter()
+            .map(|value| {
+                value.map(|value| Argument {
+                    value,
+                    string: match value {
+                        Value::Constant(ptr) => read_string(self.module, ptr),
+                        _ => None,
+                    },
+                })
+            })
+            .collect();
+
+        while let Some(None) = arguments.last() {
+            arguments.pop();
+        }
+
+        arguments
+    }
+
+    /// record what's known at the given instruction, before it executes.
+    fn annotate(&self, state: &State, va: VA, insn: &zydis::DecodedInstruction, annotations: &mut Annotations) {
+        if !matches!(insn.mnemonic, zydis::Mnemonic::CALL | zydis::Mnemonic::JMP) {
+            return;
+        }
+
+        match self.branch_target(state, va, insn) {
+            Some(Value::Constant(dst)) if self.module.probe_va(dst, Permissions::X) => {
+                annotations.targets.insert(va, dst);
+            }
+            Some(Value::Load(ptr)) if self.module.probe_va(ptr, Permissions::RWX) => {
+                annotations.pointers.insert(va, ptr);
+                if let Ok(dst) = self.module.read_va_at_va(ptr) {
+                    if self.module.probe_va(dst, Permissions::X) {
+                        annotations.targets.insert(va, dst);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if insn.mnemonic == zydis::Mnemonic::CALL {
+            let arguments = self.arguments(state);
+            if !arguments.is_empty() {
+                annotations.arguments.insert(va, arguments);
+            }
+        }
+    }
+}
+
+/// find the targets of the indirect calls and jumps in the given function,
+/// and the arguments to its calls.
+pub fn analyze_function(module: &Module, function: VA, cfg: &CFG) -> Result<Annotations> {
+    let decoder = dis::get_disassembler(module)?;
+    let ctx = Context::new(module);
+    let mut annotations: Annotations = Default::default();
+
+    if !cfg.basic_blocks.contains_key(&function) {
+        return Ok(annotations);
+    }
+
+    let mut insns: BTreeMap<VA, Vec<(VA, zydis::DecodedInstruction)>> = Default::default();
+    for bb in cfg.basic_blocks.values() {
+        let buf = module.address_space.read_bytes(bb.address, bb.length as usize)?;
+        insns.insert(
+            bb.address,
+            dis::linear_disassemble(&decoder, &buf)
+                .filter_map(|(offset, insn)| match insn {
+                    Ok(Some(insn)) => Some((bb.address + offset as RVA, insn)),
+                    _ => None,
+                })
+                .collect(),
+        );
+    }
+
+    // the state at the start of each reachable basic block.
+    let mut inputs: BTreeMap<VA, State> = Default::default();
+    let mut entry: State = Default::default();
+    ctx.write_register(&mut entry, ctx.sp, Some(Value::Stack(0)));
+    inputs.insert(function, entry);
+
+    let mut queue: VecDeque<VA> = Default::default();
+    let mut queued: BTreeSet<VA> = Default::default();
+    queue.push_back(function);
+    queued.insert(function);
+
+    // joins only forget values, so this reaches a fixed point.
+    while let Some(address) = queue.pop_front() {
+        queued.remove(&address);
+
+        let mut state = inputs[&address].clone();
+        for (va, insn) in insns[&address].iter() {
+            ctx.step(&mut state, *va, insn);
+        }
+
+        for succ in cfg.basic_blocks[&address].successors.iter() {
+            let succ = succ.va();
+            if !cfg.basic_blocks.contains_key(&succ) {
+                continue;
+            }
+
+            let changed = match inputs.get(&succ) {
+                None => {
+                    inputs.insert(succ, state.clone());
+                    true
+                }
+                Some(existing) => {
+                    let joined = existing.join(&state);
+                    if joined != *existing {
+                        inputs.insert(succ, joined);
+                        true
+                    } else {
+                        false
+                    }
+                }
+            };
+
+            if changed && queued.insert(succ) {
+                queue.push_back(succ);
+            }
+        }
+    }
+
+    for (address, input) in inputs.iter() {
+        let mut state = input.clone();
+        for (va, insn) in insns[address].iter() {
+            ctx.annotate(&state, *va, insn, &mut annotations);
+            ctx.step(&mut state, *va, insn);
+        }
+    }
+
+    debug!(
+        "dataflow: {:#x}: resolved {} targets, {} pointers, {} calls with arguments",
+        function,
+        annotations.targets.len(),
+        annotations.pointers.len(),
+        annotations.arguments.len()
+    );
+
+    Ok(annotations)
+}
+
+/// find the targets of the indirect calls and jumps in the given functions,
+/// and the arguments to their calls,
+/// suitable for `build_call_graph_with_annotations` and
+/// `build_xrefs_with_annotations`.
+pub fn analyze_functions(module: &Module, cfgs: &BTreeMap<VA, CFG>) -> Result<Annotations> {
+    let mut annotations: Annotations = Default::default();
+
+    for (&function, cfg) in cfgs.iter() {
+        annotations.extend(analyze_function(module, function, cfg)?);
+    }
+
+    Ok(annotations)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analysis::{cfg::build_cfg, dataflow::*, pe},
+        rsrc::*,
+        test::*,
+    };
+    use anyhow::Result;
+
+    #[test]
+    fn shellcode32() -> Result<()> {
+        // 0x0:  a1 20 00 00 00       mov    eax, [0x20]
+        // 0x5:  ff d0                call   eax
+        // 0x7:  68 24 00 00 00       push   0x24
+        // 0xC:  b9 1c 00 00 00       mov    ecx, 0x1C
+        // 0x11: ff d1                call   ecx
+        // 0x13: 83 c4 04             add    esp, 4
+        // 0x16: c3                   ret
+        // 0x17: 90 90 90 90 90       nop
+        // 0x1C: c3 90 90 90          ret
+        // 0x20: 1c 00 00 00          dd     0x1C
+        // 0x24: 61 62 63 64 00       db     "abcd", 0
+        let module = load_shellcode32(
+            b"\xA1\x20\x00\x00\x00\xFF\xD0\x68\x24\x00\x00\x00\xB9\x1C\x00\x00\x00\xFF\xD1\x83\xC4\x04\xC3\x90\x90\x90\x90\x90\xC3\x90\x90\x90\x1C\x00\x00\x00\x61\x62\x63\x64\x00",
+        );
+        let cfg = build_cfg(&module, 0x0)?;
+        let annotations = analyze_function(&module, 0x0, &cfg)?;
+
+        // loaded from the global at 0x20.
+        assert_eq!(annotations.pointers[&0x5], 0x20);
+        assert_eq!(annotations.targets[&0x5], 0x1C);
+        assert_eq!(annotations.targets[&0x11], 0x1C);
+        assert!(!annotations.pointers.contains_key(&0x11));
+
+        assert!(!annotations.arguments.contains_key(&0x5));
+        assert_eq!(
+            annotations.arguments[&0x11],
+            vec![Some(Argument {
+                value:  Value::Constant(0x24),
+                string: Some("abcd".to_string()),
+            })]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn shellcode64() -> Result<()> {
+        // 0x0:  48 83 ec 28             sub    rsp, 0x28
+        // 0x4:  48 8d 0d 15 00 00 00    lea    rcx, [rip + 0x15]  ; 0x20
+        // 0xB:  48 89 4c 24 20          mov    [rsp + 0x20], rcx
+        // 0x10: 48 8d 15 0d 00 00 00    lea    rdx, [rip + 0xD]   ; 0x24
+        // 0x17: ff 54 24 20             call   qword ptr [rsp + 0x20]
+        // 0x1B: 48 83 c4 28             add    rsp, 0x28
+        // 0x1F: c3                      ret
+        // 0x20: c3 00 00 00             ret
+        // 0x24: 68 00 65 00 6c 00 ...   dw     L"hello", 0
+        let module = load_shellcode64(
+            b"\x48\x83\xEC\x28\x48\x8D\x0D\x15\x00\x00\x00\x48\x89\x4C\x24\x20\x48\x8D\x15\x0D\x00\x00\x00\xFF\x54\x24\x20\x48\x83\xC4\x28\xC3\xC3\x00\x00\x00\x68\x00\x65\x00\x6C\x00\x6C\x00\x6F\x00\x00\x00",
+        );
+        let cfg = build_cfg(&module, 0x0)?;
+        let annotations = analyze_function(&module, 0x0, &cfg)?;
+
+        assert_eq!(annotations.targets[&0x17], 0x20);
+        assert!(annotations.pointers.is_empty());
+        assert_eq!(
+            annotations.arguments[&0x17],
+            vec![
+                Some(Argument {
+                    value:  Value::Constant(0x20),
+                    string: None,
+                }),
+                Some(Argument {
+                    value:  Value::Constant(0x24),
+                    string: Some("hello".to_string()),
+                }),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimikatz() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // .text:004022FA  mov     edi, ds:LocalFree
+        // ...
+        // .text:00402319  call    edi
+        let function = 0x4022F2;
+        let cfg = build_cfg(&pe.module, function)?;
+        let annotations = analyze_function(&pe.module, function, &cfg)?;
+        assert_eq!(annotations.pointers[&0x402319], 0x473300);
+        let imports = pe::get_imports(&pe)?;
+        assert_eq!(imports[&0x473300].to_string(), "KERNEL32.dll!LocalFree");
+
+        // .text:004478CA  mov     [ebp+var_118], offset sub_4476E7
+        // ...
+        // .text:00447996  call    [ebp+var_118]
+        let function = 0x447817;
+        let cfg = build_cfg(&pe.module, function)?;
+        let annotations = analyze_function(&pe.module, function, &cfg)?;
+        assert_eq!(annotations.targets[&0x447996], 0x4476E7);
+
+        // .text:0040104B  push    eax
+        // .text:0040104C  push    offset aErrorKullMAcrI ; "ERROR kull_m_acr_init ;
+        // SCardConnect: 0x%08x\n" .text:00401051  call    kprintf
+        let function = 0x401000;
+        let cfg = build_cfg(&pe.module, function)?;
+        let annotations = analyze_function(&pe.module, function, &cfg)?;
+        let arguments = &annotations.arguments[&0x401051];
+        assert_eq!(arguments.len(), 1);
+        assert_eq!(
+            arguments[0].as_ref().unwrap().string.as_deref(),
+            Some("ERROR kull_m_acr_init ; SCardConnect: 0x%08x\n")
+        );
+
+        Ok(())
+    }
+}
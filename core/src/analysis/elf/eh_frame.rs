@@ -0,0 +1,38 @@
+//! Parse the call frame information (`.eh_frame`) to find function starts.
+//!
+//! Compilers emit unwind information for most functions, even in C, so that
+//! exceptions can propagate through them. This survives stripping.
+//! Some entries cover a region of stubs rather than a function, like `.plt`.
+use anyhow::Result;
+
+use crate::{
+    loader::elf::{eh_frame::read_eh_frame, ELF},
+    module::Permissions,
+    VA,
+};
+
+pub fn find_elf_eh_frame_functions(elf: &ELF) -> Result<Vec<VA>> {
+    Ok(read_eh_frame(elf)?
+        .into_iter()
+        .map(|fde| fde.range.start)
+        .filter(|&va| elf.module.probe_va(va, Permissions::X))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::HELLO);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        let mut fns = crate::analysis::elf::eh_frame::find_elf_eh_frame_functions(&elf)?;
+        fns.sort_unstable();
+        assert_eq!(vec![0x1020, 0x10A0, 0x10B0, 0x1199, 0x119D, 0x11DE], fns);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+//! Parse the ELF header for the entry point (if present).
+//!
+//! Executables should have an entry point, while shared objects often don't.
+use anyhow::Result;
+
+use crate::{loader::elf::ELF, module::Permissions, VA};
+
+pub fn find_elf_entrypoint(elf: &ELF) -> Result<Vec<VA>> {
+    match elf.entry_point() {
+        Some(entry_point) if elf.module.probe_va(entry_point, Permissions::X) => Ok(vec![entry_point]),
+        _ => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::HELLO);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        let fns = crate::analysis::elf::entrypoints::find_elf_entrypoint(&elf)?;
+        assert_eq!(vec![0x10B0], fns);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+//! Parse the dynamic symbol table to find the exported functions.
+//!
+//! ELF files may also export data, like `_IO_stdin_used`,
+//! and marker symbols without a type, like `_end`, which we ignore.
+use anyhow::Result;
+
+use crate::{
+    loader::elf::{symbols::SymbolKind, ELF},
+    module::Permissions,
+    VA,
+};
+
+pub fn find_elf_exports(elf: &ELF) -> Result<Vec<VA>> {
+    let exports: Vec<VA> = elf
+        .get_dynamic_symbols()?
+        .into_iter()
+        .filter(|sym| !sym.import && sym.kind == SymbolKind::Function)
+        .map(|sym| sym.address)
+        .filter(|&va| elf.module.probe_va(va, Permissions::X))
+        .collect();
+
+    Ok(exports)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::HELLO);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        // greeting, add, _start, main
+        let mut fns = crate::analysis::elf::exports::find_elf_exports(&elf)?;
+        fns.sort_unstable();
+        assert_eq!(vec![0x10B0, 0x1199, 0x119D, 0x11DE], fns);
+
+        Ok(())
+    }
+}
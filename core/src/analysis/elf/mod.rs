@@ -0,0 +1,212 @@
+//! Analysis passes for ELF files, like those in `analysis::pe`.
+//!
+//! Imports are the GOT slots referenced by code, and thunks are the PLT stubs
+//! that jump through them.
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use log::debug;
+
+pub use crate::analysis::pe::{Function, Import, ImportedSymbol, Thunk};
+#[cfg(feature = "disassembler")]
+use crate::analysis::{dis, pe};
+#[cfg(feature = "disassembler")]
+use crate::{arch::Arch, aspace::AddressSpace};
+use crate::{loader::elf::ELF, VA};
+#[cfg(feature = "disassembler")]
+use std::collections::HashSet;
+
+pub mod eh_frame;
+pub mod entrypoints;
+pub mod exports;
+
+/// the library name used for imported symbols that aren't versioned,
+/// since ELF files don't otherwise bind a symbol to a library.
+pub const UNKNOWN_LIBRARY: &str = "*";
+
+pub fn get_imports(elf: &ELF) -> Result<BTreeMap<VA, Import>> {
+    let mut imports: BTreeMap<VA, Import> = Default::default();
+
+    for reloc in elf.get_import_relocations()?.into_iter() {
+        if !reloc.symbol.import {
+            // like a GOT slot for data exported by this module.
+            continue;
+        }
+
+        let dll = smol_str::SmolStr::new(reloc.symbol.library.as_deref().unwrap_or(UNKNOWN_LIBRARY));
+        debug!("imports: {}!{}", dll, reloc.symbol.name);
+
+        imports.insert(
+            reloc.address,
+            Import {
+                address: reloc.address,
+                dll,
+                symbol: ImportedSymbol::Name(smol_str::SmolStr::new(&reloc.symbol.name)),
+                delay_loaded: false,
+                pinvoke: false,
+            },
+        );
+    }
+
+    Ok(imports)
+}
+
+/// sections that contain PLT stubs, each of which jumps through a GOT slot.
+/// `.plt.sec` is used when Intel CET is enabled, and `.plt.got` for functions
+/// that are only called through `GLOB_DAT` slots.
+#[cfg(feature = "disassembler")]
+const PLT_SECTIONS: &[&str] = &[".plt", ".plt.sec", ".plt.got"];
+
+#[cfg(feature = "disassembler")]
+pub fn find_thunks(elf: &ELF, imports: &BTreeMap<VA, Import>) -> Result<BTreeMap<VA, Thunk>> {
+    let mut thunks: BTreeMap<VA, Thunk> = Default::default();
+    let decoder = dis::get_disassembler(&elf.module)?;
+    let e = elf.elf()?;
+
+    let got = match elf.module.arch {
+        Arch::X32 => e
+            .dynamic
+            .as_ref()
+            .and_then(|dynamic| dynamic.info.pltgot)
+            .map(|got| got as VA),
+        Arch::X64 => None,
+    };
+
+    for shdr in e.section_headers.iter() {
+        match e.shdr_strtab.get_at(shdr.sh_name) {
+            Some(name) if PLT_SECTIONS.contains(&name) => {}
+            _ => continue,
+        }
+        if shdr.sh_addr == 0 || shdr.sh_size == 0 {
+            continue;
+        }
+        let range = match elf.section_range(shdr) {
+            Some(range) => range,
+            None => continue,
+        };
+
+        // stubs are fixed size, though the first entry of `.plt`, which invokes
+        // the lazy binding resolver, doesn't jump through an import slot.
+        let entry_size = if shdr.sh_entsize != 0 { shdr.sh_entsize } else { 0x10 };
+        let buf = elf
+            .module
+            .address_space
+            .read_bytes(range.start, (range.end - range.start) as usize)?;
+
+        for (offset, insn) in dis::linear_disassemble(&decoder, &buf) {
+            if let Ok(Some(insn)) = insn {
+                if insn.mnemonic != zydis::Mnemonic::JMP {
+                    continue;
+                }
+
+                let va = shdr.sh_addr + offset as u64;
+                let ptr = match pe::get_jmp_pointer(va, &insn, got) {
+                    Some(ptr) => ptr,
+                    None => continue,
+                };
+
+                if let Some(import) = imports.get(&ptr) {
+                    let thunk = Thunk {
+                        address: shdr.sh_addr + (offset as u64 / entry_size) * entry_size,
+                        import:  import.clone(),
+                    };
+                    debug!("thunk: {:#x} -> {}", thunk.address, thunk.import);
+                    thunks.insert(thunk.address, thunk);
+                }
+            }
+        }
+    }
+
+    Ok(thunks)
+}
+
+#[cfg(feature = "disassembler")]
+fn find_function_candidates(elf: &ELF) -> Result<HashSet<VA>> {
+    let mut function_starts: HashSet<VA> = Default::default();
+    function_starts.extend(crate::analysis::elf::entrypoints::find_elf_entrypoint(elf)?);
+    function_starts.extend(crate::analysis::elf::exports::find_elf_exports(elf)?);
+    function_starts.extend(crate::analysis::elf::eh_frame::find_elf_eh_frame_functions(elf)?);
+
+    // TODO: validate that the code looks ok
+
+    Ok(function_starts)
+}
+
+#[cfg(feature = "disassembler")]
+pub fn find_functions(elf: &ELF) -> Result<Vec<Function>> {
+    let imports = get_imports(elf)?;
+    debug!("imports: found {} imports", imports.len());
+
+    let thunks = find_thunks(elf, &imports)?;
+    let function_starts = find_function_candidates(elf)?;
+
+    Ok(pe::merge_functions(&function_starts, &thunks, &imports))
+}
+
+#[cfg(feature = "disassembler")]
+pub fn find_function_starts(elf: &ELF) -> Result<Vec<VA>> {
+    Ok(pe::get_function_starts(find_functions(elf)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::HELLO);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        let imports = crate::analysis::elf::get_imports(&elf)?;
+        assert_eq!(12, imports.len());
+        assert_eq!("libc.so.6!puts", format!("{}", imports[&0x4008]));
+        assert_eq!("libc.so.6!__cxa_finalize", format!("{}", imports[&0x3FE0]));
+        assert_eq!("*!__gmon_start__", format!("{}", imports[&0x3FD0]));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "disassembler")]
+    #[test]
+    fn hello_functions() -> Result<()> {
+        let buf = get_buf(Rsrc::HELLO);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        let imports = crate::analysis::elf::get_imports(&elf)?;
+        let thunks = crate::analysis::elf::find_thunks(&elf, &imports)?;
+        // seven in .plt, and __cxa_finalize in .plt.got
+        assert_eq!(8, thunks.len());
+        assert_eq!("libc.so.6!puts", format!("{}", thunks[&0x1040].import));
+        assert_eq!("libc.so.6!__cxa_finalize", format!("{}", thunks[&0x10A0].import));
+
+        let starts = crate::analysis::elf::find_function_starts(&elf)?;
+        assert!(starts.contains(&0x11DE));
+        assert!(!starts.contains(&0x10A0));
+
+        let cfg = crate::analysis::cfg::build_cfg(&elf.module, 0x11DE)?;
+        assert!(cfg.basic_blocks.len() > 1);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "disassembler")]
+    #[test]
+    fn hello_invalid_plt() -> Result<()> {
+        let mut buf = get_buf(Rsrc::HELLO);
+        // .plt sh_size, which is clamped to the containing segment.
+        buf[0x34E0..0x34E8].copy_from_slice(&0x7FFF_FFFF_FFFFu64.to_le_bytes());
+        // .plt.got sh_addr, which overflows with the size.
+        buf[0x3518..0x3520].copy_from_slice(&0xFFFF_FFFF_FFFF_FFFCu64.to_le_bytes());
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        let imports = crate::analysis::elf::get_imports(&elf)?;
+        let thunks = crate::analysis::elf::find_thunks(&elf, &imports)?;
+        // the enlarged .plt spans .plt.got, too.
+        assert_eq!(8, thunks.len());
+        assert_eq!("libc.so.6!puts", format!("{}", thunks[&0x1040].import));
+        assert_eq!("libc.so.6!__cxa_finalize", format!("{}", thunks[&0x10A0].import));
+
+        Ok(())
+    }
+}
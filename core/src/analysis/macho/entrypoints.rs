@@ -0,0 +1,30 @@
+//! Parse `LC_MAIN` (or `LC_UNIXTHREAD`) for the entry point (if present).
+//!
+//! Executables should have an entry point, while dynamic libraries don't.
+use anyhow::Result;
+
+use crate::{loader::macho::MachO, module::Permissions, VA};
+
+pub fn find_macho_entrypoint(macho: &MachO) -> Result<Vec<VA>> {
+    match macho.entry_point()? {
+        Some(entry_point) if macho.module.probe_va(entry_point, Permissions::X) => Ok(vec![entry_point]),
+        _ => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        let fns = crate::analysis::macho::entrypoints::find_macho_entrypoint(&macho)?;
+        assert_eq!(vec![0x1_0000_0500], fns);
+
+        Ok(())
+    }
+}
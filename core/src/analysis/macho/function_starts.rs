@@ -0,0 +1,33 @@
+//! Parse `LC_FUNCTION_STARTS` to find function starts.
+//!
+//! The linker records every function, including static ones,
+//! and this survives stripping.
+use anyhow::Result;
+
+use crate::{loader::macho::MachO, module::Permissions, VA};
+
+pub fn find_macho_function_starts(macho: &MachO) -> Result<Vec<VA>> {
+    Ok(macho
+        .get_function_starts()?
+        .into_iter()
+        .filter(|&va| macho.module.probe_va(va, Permissions::X))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        // _main, _add, and the static helper
+        let fns = crate::analysis::macho::function_starts::find_macho_function_starts(&macho)?;
+        assert_eq!(vec![0x1_0000_0500, 0x1_0000_0540, 0x1_0000_0544], fns);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+//! Analysis passes for Mach-O files, like those in `analysis::pe`.
+//!
+//! Imports are the pointer slots bound by dyld, and thunks are the symbol
+//! stubs that jump through them.
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result;
+use log::debug;
+
+pub use crate::analysis::pe::{Function, Import, ImportedSymbol, Thunk};
+use crate::{analysis::pe, aspace::AddressSpace, loader::macho::MachO, VA};
+
+pub mod entrypoints;
+pub mod function_starts;
+pub mod symbols;
+
+/// the short name of a library, from its install name, like:
+///
+///   - `/usr/lib/libSystem.B.dylib` -> `libSystem.B.dylib`
+///   - `/System/Library/Frameworks/Foundation.framework/Versions/C/Foundation`
+///     -> `Foundation`
+pub fn get_library_name(install_name: &str) -> &str {
+    install_name.rsplit('/').next().unwrap_or(install_name)
+}
+
+pub fn get_imports(macho: &MachO) -> Result<BTreeMap<VA, Import>> {
+    let mut imports: BTreeMap<VA, Import> = Default::default();
+
+    for import in macho.get_imports()?.into_iter() {
+        let dll = smol_str::SmolStr::new(get_library_name(&import.library));
+        debug!("imports: {}!{}", dll, import.name);
+
+        imports.insert(
+            import.address,
+            Import {
+                address: import.address,
+                dll,
+                symbol: ImportedSymbol::Name(smol_str::SmolStr::new(&import.name)),
+                delay_loaded: false,
+                pinvoke: false,
+            },
+        );
+    }
+
+    Ok(imports)
+}
+
+/// fetch the pointer dereferenced by the x86-64 symbol stub at the given
+/// address, which is always `jmp [rip+0x0]` (`FF 25 disp32`).
+fn get_stub_pointer(macho: &MachO, va: VA) -> Option<VA> {
+    let buf = macho.module.address_space.read_bytes(va, 6).ok()?;
+    if buf[0] != 0xFF || buf[1] != 0x25 {
+        return None;
+    }
+
+    let disp = i32::from_le_bytes([buf[2], buf[3], buf[4], buf[5]]);
+    Some((va + 6).wrapping_add(disp as i64 as u64))
+}
+
+/// find the symbol stubs, which don't need to be disassembled,
+/// since the indirect symbol table describes them.
+pub fn find_thunks(macho: &MachO, imports: &BTreeMap<VA, Import>) -> Result<BTreeMap<VA, Thunk>> {
+    let mut thunks: BTreeMap<VA, Thunk> = Default::default();
+
+    for stub in macho.get_stubs()?.into_iter() {
+        // prefer the pointer slot the stub actually jumps through,
+        // otherwise, match the import by name.
+        let import = get_stub_pointer(macho, stub.address)
+            .and_then(|ptr| imports.get(&ptr))
+            .or_else(|| {
+                imports
+                    .values()
+                    .find(|import| matches!(&import.symbol, ImportedSymbol::Name(name) if name == &stub.name))
+            });
+
+        if let Some(import) = import {
+            let thunk = Thunk {
+                address: stub.address,
+                import:  import.clone(),
+            };
+            debug!("thunk: {:#x} -> {}", thunk.address, thunk.import);
+            thunks.insert(thunk.address, thunk);
+        }
+    }
+
+    Ok(thunks)
+}
+
+fn find_function_candidates(macho: &MachO) -> Result<HashSet<VA>> {
+    let mut function_starts: HashSet<VA> = Default::default();
+    function_starts.extend(crate::analysis::macho::entrypoints::find_macho_entrypoint(macho)?);
+    function_starts.extend(crate::analysis::macho::symbols::find_macho_symbols(macho)?);
+    function_starts.extend(crate::analysis::macho::function_starts::find_macho_function_starts(
+        macho,
+    )?);
+
+    // TODO: validate that the code looks ok
+
+    Ok(function_starts)
+}
+
+pub fn find_functions(macho: &MachO) -> Result<Vec<Function>> {
+    let imports = get_imports(macho)?;
+    debug!("imports: found {} imports", imports.len());
+
+    let thunks = find_thunks(macho, &imports)?;
+    let function_starts = find_function_candidates(macho)?;
+
+    Ok(pe::merge_functions(&function_starts, &thunks, &imports))
+}
+
+pub fn find_function_starts(macho: &MachO) -> Result<Vec<VA>> {
+    Ok(pe::get_function_starts(find_functions(macho)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        let imports = crate::analysis::macho::get_imports(&macho)?;
+        assert_eq!(4, imports.len());
+        assert_eq!(
+            "libSystem.B.dylib!dyld_stub_binder",
+            format!("{}", imports[&0x1_0000_1000])
+        );
+        assert_eq!("libSystem.B.dylib!_exit", format!("{}", imports[&0x1_0000_1008]));
+        assert_eq!("libSystem.B.dylib!_puts", format!("{}", imports[&0x1_0000_1018]));
+
+        let thunks = crate::analysis::macho::find_thunks(&macho, &imports)?;
+        assert_eq!(2, thunks.len());
+        assert_eq!(0x1_0000_1010, thunks[&0x1_0000_054A].import.address);
+        assert_eq!(
+            "libSystem.B.dylib!_printf",
+            format!("{}", thunks[&0x1_0000_054A].import)
+        );
+        assert_eq!("libSystem.B.dylib!_puts", format!("{}", thunks[&0x1_0000_0550].import));
+
+        let mut starts = crate::analysis::macho::find_function_starts(&macho)?;
+        starts.sort_unstable();
+        assert_eq!(vec![0x1_0000_0500, 0x1_0000_0540, 0x1_0000_0544], starts);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "disassembler")]
+    #[test]
+    fn hello_cfg() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        let cfg = crate::analysis::cfg::build_cfg(&macho.module, 0x1_0000_0500)?;
+        assert!(!cfg.basic_blocks.is_empty());
+
+        Ok(())
+    }
+}
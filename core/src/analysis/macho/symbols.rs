@@ -0,0 +1,40 @@
+//! Parse the symbol table to find the defined functions.
+//!
+//! Unstripped images name their static functions, too,
+//! while stripped images retain just the exported ones.
+//! Symbols for data, and markers like `__mh_execute_header`,
+//! fall outside of the executable sections, so we ignore them.
+use anyhow::Result;
+
+use crate::{loader::macho::MachO, module::Permissions, VA};
+
+pub fn find_macho_symbols(macho: &MachO) -> Result<Vec<VA>> {
+    let symbols: Vec<VA> = macho
+        .get_symbols()?
+        .into_iter()
+        .filter(|sym| !sym.undefined)
+        .map(|sym| sym.address)
+        .filter(|&va| macho.module.probe_va(va, Permissions::X))
+        .collect();
+
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        // _main, _add
+        let mut fns = crate::analysis::macho::symbols::find_macho_symbols(&macho)?;
+        fns.sort_unstable();
+        assert_eq!(vec![0x1_0000_0500, 0x1_0000_0540], fns);
+
+        Ok(())
+    }
+}
@@ -1,9 +1,24 @@
+//! Analysis passes over a loaded module.
+//!
+//! The passes for each file format, like `analysis::elf`, produce the same
+//! `Import`, `Thunk` and `Function` descriptions as `analysis::pe`,
+//! so the results can be fed into `build_cfg` and `build_call_graph`
+//! unchanged.
 #[cfg(feature = "disassembler")]
 pub mod call_graph;
 #[cfg(feature = "disassembler")]
 pub mod cfg;
+pub mod coff;
+#[cfg(feature = "disassembler")]
+pub mod dataflow;
 #[cfg(feature = "disassembler")]
 pub mod dis;
+pub mod elf;
 #[cfg(feature = "flirt")]
 pub mod flirt;
+pub mod macho;
 pub mod pe;
+#[cfg(feature = "disassembler")]
+pub mod raw;
+#[cfg(feature = "disassembler")]
+pub mod xrefs;
@@ -30,16 +30,40 @@
 //! I've heard that Intel x86 is "self-synchronizing", though I don't have a
 //! reference. In any case, the effect is that linear disassembly should work
 //! well *most* of the time.
+//!
+//! Calls to no-return functions, like `ExitProcess`, are often followed by
+//! padding or data that disassembles to more calls. When the no-return
+//! functions are known, skip the instructions after such a call until
+//! the next address that's a known function start or branch target.
 
 // TODO: detect thunks (call to unconditional jmp).
 
+use std::collections::{BTreeSet, HashSet};
+
 use log::debug;
 
 use anyhow::Result;
 
-use crate::{analysis::dis, aspace::AddressSpace, loader::pe::PE, module::Permissions, util, VA};
+use crate::{
+    analysis::{cfg::noret, dis},
+    aspace::AddressSpace,
+    loader::pe::PE,
+    module::Permissions,
+    util, VA,
+};
 
 pub fn find_pe_call_targets(pe: &PE) -> Result<Vec<VA>> {
+    find_pe_call_targets_with_noret(pe, &Default::default(), &Default::default())
+}
+
+/// like `find_pe_call_targets`, but ignore the calls found after a call to
+/// one of the given no-return functions, up to the next function start or
+/// branch target.
+pub fn find_pe_call_targets_with_noret(
+    pe: &PE,
+    noret: &BTreeSet<VA>,
+    function_starts: &HashSet<VA>,
+) -> Result<Vec<VA>> {
     let mut ret = vec![];
     let decoder = dis::get_disassembler(&pe.module)?;
 
@@ -48,13 +72,46 @@ pub fn find_pe_call_targets(pe: &PE) -> Result<Vec<VA>> {
         let vstart: VA = section.virtual_range.start;
         let vsize = (section.virtual_range.end - section.virtual_range.start) as usize;
         let sec_buf = pe.module.address_space.read_bytes(vstart, vsize)?;
+
+        // the addresses that some instruction flows to,
+        // which may end a region of dead code.
+        let mut branch_targets: HashSet<VA> = Default::default();
+        if !noret.is_empty() {
+            for (insn_offset, insn) in dis::linear_disassemble(&decoder, &sec_buf) {
+                if let Ok(Some(insn)) = insn {
+                    if matches!(
+                        insn.meta.category,
+                        zydis::InstructionCategory::CALL
+                            | zydis::InstructionCategory::COND_BR
+                            | zydis::InstructionCategory::UNCOND_BR
+                    ) {
+                        let insn_va: VA = vstart + insn_offset as u64;
+                        branch_targets.extend(noret::get_branch_targets(&pe.module, insn_va, &insn));
+                    }
+                }
+            }
+        }
+
+        let mut is_dead = false;
         for (insn_offset, insn) in dis::linear_disassemble(&decoder, &sec_buf) {
             if let Ok(Some(insn)) = insn {
+                let insn_va: VA = vstart + insn_offset as u64;
+                if is_dead {
+                    if function_starts.contains(&insn_va) || branch_targets.contains(&insn_va) {
+                        is_dead = false;
+                    } else {
+                        continue;
+                    }
+                }
+
                 if insn.meta.category != zydis::InstructionCategory::CALL {
                     continue;
                 }
 
-                let insn_va: VA = vstart + insn_offset as u64;
+                if !noret.is_empty() && noret::is_noret_call(&pe.module, insn_va, &insn, noret) {
+                    is_dead = true;
+                }
+
                 let op0 = &insn.operands[0];
 
                 match op0.ty {
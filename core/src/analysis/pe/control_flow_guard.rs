@@ -14,155 +14,200 @@
 //! The Load Config Control Flow Guard metadata also references:
 //!   - function pointer to indirect call check routine (supported)
 //!   - function pointer to indirect call dispatch routine (supported)
-//!   - IAT entry table (not supported here)
-//!   - LongJump entry table (not supported here)
+//!   - function pointers to XFG check and dispatch routines (supported)
+//!   - return flow failure and stack pointer verification routines (supported)
+//!   - IAT entry table (not functions, see `find_pe_cfguard_iat_entries`)
+//!   - LongJump and EH continuation tables (not functions, see
+//!     `find_pe_cfguard_code_pointers`)
 //!
 //! references:
 //!   - https://docs.microsoft.com/en-us/windows/desktop/debug/pe-format#load-configuration-directory
 //!   - https://lucasg.github.io/2017/02/05/Control-Flow-Guard/
 
 use anyhow::Result;
-use byteorder::{ByteOrder, LittleEndian};
 use log::debug;
 
 use crate::{
-    arch::Arch,
-    aspace::AddressSpace,
-    loader::{pe, pe::PE},
+    loader::pe::{
+        load_config::{read_guard_table, read_load_config_directory, GuardFlags, LoadConfigDirectory, Table},
+        PE,
+    },
     module::Permissions,
     VA,
 };
 
-const IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_MASK: u32 = 0xF000_0000;
-const IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT: u32 = 28;
-const IMAGE_GUARD_CF_FUNCTION_TABLE_PRESENT: u32 = 0x400;
-
-pub fn find_pe_cfguard_functions(pe: &PE) -> Result<Vec<VA>> {
+/// read the entries of the given guard table, stopping at the first
+/// non-executable target.
+fn read_code_table(pe: &PE, table: &Option<Table>, guard_flags: GuardFlags, name: &str) -> Result<Vec<VA>> {
     let mut ret = vec![];
 
-    if let Ok(Some(load_config_directory)) = pe.get_data_directory(pe::IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG) {
-        debug!("load config directory: {:#x}", load_config_directory.address);
-
-        // offsets defined here:
-        // https://docs.microsoft.com/en-us/windows/desktop/debug/pe-format#load-configuration-directory
+    let table = match table {
+        Some(table) if table.address != 0 => table,
+        _ => {
+            debug!("{} table empty", name);
+            return Ok(ret);
+        }
+    };
+    debug!("{} table: {:#x} count: {:#x}", name, table.address, table.count);
+
+    for target in read_guard_table(pe, table, guard_flags)?.into_iter() {
+        if pe.module.probe_va(target, Permissions::X) {
+            ret.push(target);
+        } else {
+            debug!("unexpected non-executable {} target: {:#x}", name, target);
+            break;
+        }
+    }
 
-        // according to IDA, the first DWORD is `Size` not `Characteristics` (unused).
-        let size = pe.module.address_space.read_u32(load_config_directory.address)?;
+    Ok(ret)
+}
 
-        // max offset into the config directory that we'll read.
-        let max_config_directory_offset = match pe.module.arch {
-            // CFG flags
-            Arch::X32 => 0x58,
-            Arch::X64 => 0x94,
-        };
+/// dereference the given function pointer found in the load config directory,
+/// such as `GuardCFCheckFunctionPointer`.
+fn read_function_pointer(pe: &PE, fptr: Option<VA>, name: &str) -> Option<VA> {
+    // set to 0x0 when not used, as is often the case on 32-bit Windows DLLs.
+    let fptr = match fptr {
+        Some(fptr) if fptr != 0 => fptr,
+        _ => return None,
+    };
+    debug!("{} function pointer: {:#x}", name, fptr);
+
+    match pe.module.read_va_at_va(fptr) {
+        Ok(f) if pe.module.probe_va(f, Permissions::X) => {
+            debug!("{}: {:#x}", name, f);
+            Some(f)
+        }
+        _ => None,
+    }
+}
 
-        // in `d3d11sdklayers.dll` for example, the config size is 0x70,
-        // which is much too small to read the CFG table options.
-        if max_config_directory_offset > size {
+/// fetch the load config directory, if it has a CF Guard table.
+fn get_cfguard_load_config(pe: &PE) -> Result<Option<(LoadConfigDirectory, GuardFlags)>> {
+    let load_config = match read_load_config_directory(pe)? {
+        Some(load_config) => load_config,
+        None => return Ok(None),
+    };
+
+    // in `d3d11sdklayers.dll` for example, the config size is 0x70,
+    // which is much too small to read the CFG table options.
+    let guard_flags = match load_config.guard_flags {
+        Some(guard_flags) => guard_flags,
+        None => {
             debug!("no CF Guard table: load config directory too small");
-            return Ok(vec![]);
+            return Ok(None);
         }
+    };
+    debug!("CF guard flags: {:#x}", guard_flags.bits());
 
-        let load_config = pe.module.address_space.slice(load_config_directory.address)?;
+    if !guard_flags.contains(GuardFlags::CF_FUNCTION_TABLE_PRESENT) {
+        return Ok(None);
+    }
 
-        let cfg_flags: u32 = match pe.module.arch {
-            Arch::X32 => load_config.read_u32(0x58)?,
-            Arch::X64 => load_config.read_u32(0x90)?,
-        };
-        debug!("CF guard flags: {:#x}", cfg_flags);
-
-        if cfg_flags & IMAGE_GUARD_CF_FUNCTION_TABLE_PRESENT > 0 {
-            let stride = ((cfg_flags & IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_MASK)
-                >> IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT) as usize;
-            if stride > 8 {
-                // stride should really be 1, but we'll accept up to 8 for future compatibility.
-                debug!("unexpected CF guard stride: {:#x}", stride);
-                return Ok(vec![]);
-            }
+    if guard_flags.stride() > 8 {
+        // stride should really be 1, but we'll accept up to 8 for future compatibility.
+        debug!("unexpected CF guard stride: {:#x}", guard_flags.stride());
+        return Ok(None);
+    }
 
-            let cfg_table_va: VA = match pe.module.arch {
-                Arch::X32 => load_config.read_pointer(pe.module.arch, 0x50)?,
-                Arch::X64 => load_config.read_pointer(pe.module.arch, 0x80)?,
-            };
-            if cfg_table_va == 0 {
-                debug!("CF guard table empty");
-                return Ok(vec![]);
-            };
-            debug!("CF Guard table: {:#x}", cfg_table_va);
-
-            let cfg_table_count = match pe.module.arch {
-                Arch::X32 => load_config.read_u32(0x54)? as u64,
-                Arch::X64 => load_config.read_u64(0x88)? as u64,
-            };
-            debug!("CF Guard table count: {:#x}", cfg_table_count);
-
-            // read the table buffer once up front, then iterate slices over it with
-            // windows. this is at the expense of one allocation for the table.
-            // it be faster than doing pe.module.with_va().read_i32() on each offset, on
-            // large tables.
-            //
-            // 4 == sizeof(i32) RVA to function start, both x32 and x64
-            let cfg_table_entry_size: usize = 4 + stride;
-            let cfg_table = pe
-                .module
-                .address_space
-                .read_bytes(cfg_table_va, cfg_table_count as usize * cfg_table_entry_size)?;
-            for entry_buf in cfg_table.chunks_exact(cfg_table_entry_size) {
-                let target = pe.module.address_space.base_address + LittleEndian::read_i32(entry_buf) as u64;
-
-                if pe.module.probe_va(target, Permissions::X) {
-                    ret.push(target);
-                } else {
-                    debug!("unexpected non-executable CFG target: {:#x}", target);
-                    break;
-                }
-            }
+    Ok(Some((load_config, guard_flags)))
+}
 
-            // add function pointed to by GuardCFCheckFunctionPointer
-            let guard_check_icall_fptr: VA = match pe.module.arch {
-                Arch::X32 => load_config.read_pointer(pe.module.arch, 0x48)?,
-                Arch::X64 => load_config.read_pointer(pe.module.arch, 0x70)?,
-            };
-            if guard_check_icall_fptr != 0 {
-                debug!(
-                    "CF Guard check indirect call function pointer: {:#x}",
-                    guard_check_icall_fptr
-                );
-
-                if let Ok(guard_check_icall) = pe.module.read_va_at_va(guard_check_icall_fptr) {
-                    if pe.module.probe_va(guard_check_icall, Permissions::X) {
-                        debug!("CF Guard check icall: {:#x}", guard_check_icall);
-                        ret.push(guard_check_icall);
-                    }
-                }
-            }
+pub fn find_pe_cfguard_functions(pe: &PE) -> Result<Vec<VA>> {
+    let mut ret = vec![];
 
-            // add function pointed to by GuardCFDispatchFunctionPointer
-            //
-            // set to 0x0 when not used, as is often the case on 32-bit Windows DLLs.
-            let guard_dispatch_icall_fptr: VA = match pe.module.arch {
-                Arch::X32 => load_config.read_pointer(pe.module.arch, 0x4C)?,
-                Arch::X64 => load_config.read_pointer(pe.module.arch, 0x78)?,
-            };
-            if guard_dispatch_icall_fptr != 0 {
-                debug!(
-                    "CF Guard dispatch indirect call function pointer: {:#x}",
-                    guard_dispatch_icall_fptr
-                );
-
-                if let Ok(guard_dispatch_icall) = pe.module.read_va_at_va(guard_dispatch_icall_fptr) {
-                    if pe.module.probe_va(guard_dispatch_icall, Permissions::X) {
-                        debug!("CF Guard dispatch icall: {:#x}", guard_dispatch_icall);
-                        ret.push(guard_dispatch_icall);
-                    }
-                }
+    if let Some((load_config, guard_flags)) = get_cfguard_load_config(pe)? {
+        ret.extend(read_code_table(
+            pe,
+            &load_config.guard_cf_function_table,
+            guard_flags,
+            "CF Guard",
+        )?);
+
+        // this routine isn't referenced via a pointer, but directly.
+        if let Some(f) = load_config.guard_rf_failure_routine {
+            if f != 0 && pe.module.probe_va(f, Permissions::X) {
+                debug!("RF Guard failure routine: {:#x}", f);
+                ret.push(f);
             }
         }
+
+        ret.extend(
+            [
+                (load_config.guard_cf_check_function_pointer, "CF Guard check icall"),
+                (
+                    load_config.guard_cf_dispatch_function_pointer,
+                    "CF Guard dispatch icall",
+                ),
+                (load_config.guard_xfg_check_function_pointer, "XFG check icall"),
+                (load_config.guard_xfg_dispatch_function_pointer, "XFG dispatch icall"),
+                (
+                    load_config.guard_xfg_table_dispatch_function_pointer,
+                    "XFG table dispatch icall",
+                ),
+                (
+                    load_config.guard_rf_failure_routine_function_pointer,
+                    "RF Guard failure routine",
+                ),
+                (
+                    load_config.guard_rf_verify_stack_pointer_function_pointer,
+                    "RF Guard verify stack pointer",
+                ),
+                (load_config.guard_memcpy_function_pointer, "Guard memcpy"),
+            ]
+            .iter()
+            .filter_map(|&(fptr, name)| read_function_pointer(pe, fptr, name)),
+        );
     }
 
     Ok(ret)
 }
 
+/// find the valid targets of `longjmp` and exception handler continuations.
+/// these are code addresses, though not function starts:
+/// typically they're the instructions following a call.
+pub fn find_pe_cfguard_code_pointers(pe: &PE) -> Result<Vec<VA>> {
+    let mut ret = vec![];
+
+    if let Some((load_config, guard_flags)) = get_cfguard_load_config(pe)? {
+        if guard_flags.contains(GuardFlags::CF_LONGJUMP_TABLE_PRESENT) {
+            ret.extend(read_code_table(
+                pe,
+                &load_config.guard_long_jump_target_table,
+                guard_flags,
+                "CF Guard longjmp",
+            )?);
+        }
+
+        if guard_flags.contains(GuardFlags::EH_CONTINUATION_TABLE_PRESENT) {
+            ret.extend(read_code_table(
+                pe,
+                &load_config.guard_eh_continuation_table,
+                guard_flags,
+                "CF Guard EH continuation",
+            )?);
+        }
+    }
+
+    Ok(ret)
+}
+
+/// find the IAT entries whose address is taken, that is, imports that are
+/// used as function pointers rather than just called.
+/// these are pointers to data (the IAT), not code.
+pub fn find_pe_cfguard_iat_entries(pe: &PE) -> Result<Vec<VA>> {
+    if let Some((load_config, guard_flags)) = get_cfguard_load_config(pe)? {
+        if let Some(table) = load_config.guard_address_taken_iat_entry_table {
+            debug!(
+                "CF Guard address taken IAT entry table: {:#x} count: {:#x}",
+                table.address, table.count
+            );
+            return read_guard_table(pe, &table, guard_flags);
+        }
+    }
+
+    Ok(vec![])
+}
+
 #[cfg(test)]
 mod tests {
     use crate::rsrc::*;
@@ -176,6 +221,57 @@ mod tests {
         let fns = crate::analysis::pe::control_flow_guard::find_pe_cfguard_functions(&pe)?;
         assert_eq!(1502, fns.len());
 
+        // k32 declares a longjmp table, but its empty.
+        let ptrs = crate::analysis::pe::control_flow_guard::find_pe_cfguard_code_pointers(&pe)?;
+        assert_eq!(0, ptrs.len());
+
+        let iat_entries = crate::analysis::pe::control_flow_guard::find_pe_cfguard_iat_entries(&pe)?;
+        assert_eq!(2, iat_entries.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32_longjmp() -> Result<()> {
+        use crate::{
+            analysis::pe::Function,
+            loader::pe::{load_config::GuardFlags, IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG},
+        };
+        use byteorder::{ByteOrder, LittleEndian};
+
+        let mut buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // found by the call target heuristic, though not in the function table.
+        let target = Function::Local(0x1_8000_2930);
+        assert!(crate::analysis::pe::find_functions(&pe)?.contains(&target));
+
+        // move the last entry of the function table into a longjmp table,
+        // and point it at the target.
+        // the tables are found at 0x80 and 0xB0 of the x64 load config directory.
+        let directory = pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG)?.unwrap();
+        let offset = pe.module.file_offset(directory.address)?;
+        let table = LittleEndian::read_u64(&buf[offset + 0x80..]);
+        let count = LittleEndian::read_u64(&buf[offset + 0x88..]);
+        let flags = GuardFlags::from_bits_truncate(LittleEndian::read_u32(&buf[offset + 0x90..]));
+        let entry = table + (count - 1) * (4 + flags.stride() as u64);
+        LittleEndian::write_u64(&mut buf[offset + 0x88..], count - 1);
+        LittleEndian::write_u32(
+            &mut buf[offset + 0x90..],
+            (flags | GuardFlags::CF_LONGJUMP_TABLE_PRESENT).bits(),
+        );
+        LittleEndian::write_u64(&mut buf[offset + 0xB0..], entry);
+        LittleEndian::write_u64(&mut buf[offset + 0xB8..], 1);
+        let entry_offset = pe.module.file_offset(entry)?;
+        LittleEndian::write_u32(&mut buf[entry_offset..], 0x2930);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let ptrs = crate::analysis::pe::control_flow_guard::find_pe_cfguard_code_pointers(&pe)?;
+        assert_eq!(vec![0x1_8000_2930], ptrs);
+
+        // a longjmp target is not a function start.
+        assert!(!crate::analysis::pe::find_functions(&pe)?.contains(&target));
+
         Ok(())
     }
 
@@ -4,26 +4,25 @@
 //! PEs may export data, which we'll assume isn't in an executable section.
 use anyhow::Result;
 
-use crate::{loader::pe::PE, module::Permissions, VA};
+use crate::{
+    loader::pe::{
+        exports::{read_exports, ExportTarget},
+        PE,
+    },
+    VA,
+};
 
 pub fn find_pe_exports(pe: &PE) -> Result<Vec<VA>> {
-    let base_address = match pe.header.optional_header {
-        Some(opt) => opt.windows_fields.image_base,
-        _ => 0x40_0000,
-    };
-
-    let exports: Vec<VA> = pe
-        .pe()?
-        .exports
-        .iter()
+    let exports: Vec<VA> = read_exports(pe)?
+        .into_iter()
         // re-exports are simply strings that point to a `DLL.export_name` ASCII string.
         // therefore, they're not functions/code.
-        .filter(|&exp| exp.reexport.is_none())
-        .map(|exp| base_address + exp.rva as u64)
-        .filter(|&va| {
-            // PE may export data, so ensure the exports we track are executable
-            // (functions).
-            pe.module.probe_va(va, Permissions::X)
+        //
+        // PE may export data, so ensure the exports we track are executable
+        // (functions).
+        .filter_map(|exp| match exp.target {
+            ExportTarget::Code(va) => Some(va),
+            _ => None,
         })
         .collect();
 
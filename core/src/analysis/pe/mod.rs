@@ -8,13 +8,14 @@ use crate::analysis::{cfg, dis};
 use crate::{
     aspace::AddressSpace,
     loader::pe::{
-        imports,
+        clr, imports,
         imports::{read_best_thunk_data, IMAGE_THUNK_DATA},
         PE,
     },
     RVA, VA,
 };
 #[cfg(feature = "disassembler")]
+use std::collections::BTreeSet;
 use std::collections::HashSet;
 
 #[cfg(feature = "disassembler")]
@@ -26,6 +27,7 @@ pub mod patterns;
 pub mod pointers;
 pub mod runtime_functions;
 pub mod safeseh;
+pub mod tls_callbacks;
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum ImportedSymbol {
@@ -35,11 +37,18 @@ pub enum ImportedSymbol {
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Import {
-    /// the address of the First Thunk.
+    /// the address of the First Thunk,
+    /// or for delay-loaded imports, the Import Address Table entry.
     /// that is, the thing that will be referenced by code.
-    pub address: VA,
-    pub dll:     smol_str::SmolStr,
-    pub symbol:  ImportedSymbol,
+    pub address:      VA,
+    pub dll:          smol_str::SmolStr,
+    pub symbol:       ImportedSymbol,
+    /// the import is resolved on first call, via the delay-load helper,
+    /// rather than by the loader.
+    pub delay_loaded: bool,
+    /// the import is a .NET P/Invoke, which managed code references by token.
+    /// the address is that of the method's row in the metadata tables.
+    pub pinvoke:      bool,
 }
 
 impl std::fmt::Display for Import {
@@ -65,13 +74,82 @@ pub enum Function {
     Import(Import),
 }
 
+fn read_imported_symbol(pe: &PE, dll: &str, thunk: IMAGE_THUNK_DATA) -> Result<ImportedSymbol> {
+    match thunk {
+        IMAGE_THUNK_DATA::Function(name_rva) => {
+            // u16    hint
+            // asciiz name
+            let name = pe.module.address_space.relative.read_ascii(name_rva + 2, 1)?;
+            debug!("imports: {}!{}", dll, name);
+            Ok(ImportedSymbol::Name(smol_str::SmolStr::new(name)))
+        }
+        IMAGE_THUNK_DATA::Ordinal(ord) => {
+            debug!("imports: {}!#{}", dll, ord);
+            Ok(ImportedSymbol::Ordinal(ord))
+        }
+    }
+}
+
+/// parse the .NET metadata, if this is a .NET assembly.
+///
+/// a malformed CLR header shouldn't prevent the analysis of the native code,
+/// so errors are logged and treated like there's no metadata.
+fn read_clr_metadata(pe: &PE) -> Option<clr::Metadata> {
+    match pe.get_clr_metadata() {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            debug!("clr: failed to parse metadata: {}", e);
+            None
+        }
+    }
+}
+
 pub fn get_imports(pe: &PE) -> Result<BTreeMap<VA, Import>> {
+    read_imports(pe, read_clr_metadata(pe).as_ref())
+}
+
+/// read the imports described by the given delay-load descriptor.
+fn read_delay_imports(pe: &PE, descriptor: &imports::IMAGE_DELAYLOAD_DESCRIPTOR) -> Result<Vec<Import>> {
+    let base_address = pe.module.address_space.base_address;
+    let psize = pe.module.arch.pointer_size();
+    let mut ret = vec![];
+
+    let dll = smol_str::SmolStr::new(descriptor.read_name(pe)?);
+    debug!("imports: delay-loaded: {}", dll);
+
+    for i in 0.. {
+        // the Import Name Table (INT) describes the symbol,
+        // while code references the Import Address Table (IAT) entry.
+        // until the import is resolved, the IAT entry points to a stub that invokes
+        // the delay-load helper.
+        let int = base_address + descriptor.import_name_table + (i * psize) as RVA;
+        let iat = base_address + descriptor.import_address_table + (i * psize) as RVA;
+
+        let thunk = imports::read_delay_import_thunk_data(pe, descriptor, int)?;
+        if let IMAGE_THUNK_DATA::Function(0x0) = thunk {
+            break;
+        }
+
+        ret.push(Import {
+            address:      iat,
+            dll:          dll.clone(),
+            symbol:       read_imported_symbol(pe, &dll, thunk)?,
+            delay_loaded: true,
+            pinvoke:      false,
+        });
+    }
+
+    Ok(ret)
+}
+
+/// like `get_imports`, using the already parsed .NET metadata, if any,
+/// for the P/Invoke imports.
+fn read_imports(pe: &PE, metadata: Option<&clr::Metadata>) -> Result<BTreeMap<VA, Import>> {
     let mut imports: BTreeMap<VA, Import> = Default::default();
+    let base_address = pe.module.address_space.base_address;
+    let psize = pe.module.arch.pointer_size();
 
     if let Some(import_directory) = imports::get_import_directory(pe)? {
-        let base_address = pe.module.address_space.base_address;
-        let psize = pe.module.arch.pointer_size();
-
         for import_descriptor in imports::read_import_descriptors(pe, import_directory) {
             let dll = smol_str::SmolStr::new(pe.module.address_space.relative.read_ascii(import_descriptor.name, 1)?);
             debug!("imports: {}", dll);
@@ -84,19 +162,7 @@ pub fn get_imports(pe: &PE) -> Result<BTreeMap<VA, Import>> {
                     break;
                 }
 
-                let symbol = match read_best_thunk_data(pe, oft, ft)? {
-                    IMAGE_THUNK_DATA::Function(name_rva) => {
-                        // u16    hint
-                        // asciiz name
-                        let name = pe.module.address_space.relative.read_ascii(name_rva + 2, 1)?;
-                        debug!("imports: {}!{}", dll, name);
-                        ImportedSymbol::Name(smol_str::SmolStr::new(name))
-                    }
-                    IMAGE_THUNK_DATA::Ordinal(ord) => {
-                        debug!("imports: {}!#{}", dll, ord);
-                        ImportedSymbol::Ordinal(ord)
-                    }
-                };
+                let symbol = read_imported_symbol(pe, &dll, read_best_thunk_data(pe, oft, ft)?)?;
 
                 imports.insert(
                     ft,
@@ -104,15 +170,262 @@ pub fn get_imports(pe: &PE) -> Result<BTreeMap<VA, Import>> {
                         address: ft,
                         dll: dll.clone(),
                         symbol,
+                        delay_loaded: false,
+                        pinvoke: false,
                     },
                 );
             }
         }
     }
 
+    if let Some(delay_import_directory) = imports::get_delay_import_directory(pe)? {
+        for descriptor in imports::read_delay_import_descriptors(pe, delay_import_directory) {
+            // one bad descriptor shouldn't hide the other imports.
+            match read_delay_imports(pe, &descriptor) {
+                Ok(delay_imports) => imports.extend(delay_imports.into_iter().map(|import| (import.address, import))),
+                Err(e) => debug!("imports: delay-loaded: {:?}: failed to read: {}", descriptor, e),
+            }
+        }
+    }
+
+    if let Some(metadata) = metadata {
+        for impl_map in metadata.impl_maps.iter() {
+            let (member, scope) = match (impl_map.member_forwarded, impl_map.import_scope) {
+                (Some(member), Some(scope)) => (member, scope),
+                _ => continue,
+            };
+
+            let dll = match metadata.get_module_ref(scope) {
+                Some(module_ref) => smol_str::SmolStr::new(&module_ref.name),
+                None => continue,
+            };
+
+            let address = match metadata.get_row_address(member) {
+                Some(address) => address,
+                None => continue,
+            };
+
+            debug!("imports: P/Invoke: {}!{}", dll, impl_map.import_name);
+            imports.insert(
+                address,
+                Import {
+                    address,
+                    dll,
+                    symbol: ImportedSymbol::Name(smol_str::SmolStr::new(&impl_map.import_name)),
+                    delay_loaded: false,
+                    pinvoke: true,
+                },
+            );
+        }
+    }
+
     Ok(imports)
 }
 
+/// a module loaded into the process from which an image was dumped,
+/// used to resolve the addresses found in the image's Import Address Table.
+#[derive(Clone)]
+pub struct LoadedModule {
+    /// like `kernel32.dll`.
+    pub name:    smol_str::SmolStr,
+    /// the size of the mapped image, `SizeOfImage`.
+    pub size:    RVA,
+    /// the exported symbols, by RVA.
+    /// prefers names to ordinals.
+    pub exports: BTreeMap<RVA, ImportedSymbol>,
+}
+
+impl LoadedModule {
+    /// collect the exports of the given module,
+    /// which may be loaded at any base address, such as from the file on disk.
+    pub fn from_pe(name: &str, pe: &PE) -> Result<LoadedModule> {
+        let base_address = pe.module.address_space.base_address;
+        let size = match pe.header.optional_header {
+            Some(opt) => opt.windows_fields.size_of_image as RVA,
+            None => pe
+                .module
+                .sections
+                .iter()
+                .map(|section| section.virtual_range.end - base_address)
+                .max()
+                .unwrap_or(0),
+        };
+
+        let mut exports: BTreeMap<RVA, ImportedSymbol> = Default::default();
+        for export in crate::loader::pe::exports::read_exports(pe)?.into_iter() {
+            // forwarded exports resolve to the implementing module,
+            // so the IAT will never point here.
+            let va = match export.target {
+                crate::loader::pe::exports::ExportTarget::Code(va) => va,
+                crate::loader::pe::exports::ExportTarget::Data(va) => va,
+                crate::loader::pe::exports::ExportTarget::Forwarder(_) => continue,
+            };
+
+            let symbol = match export.name {
+                Some(name) => ImportedSymbol::Name(smol_str::SmolStr::new(name)),
+                None => ImportedSymbol::Ordinal(export.ordinal),
+            };
+
+            let existing = exports.entry(va - base_address).or_insert_with(|| symbol.clone());
+            if matches!(existing, ImportedSymbol::Ordinal(_)) {
+                *existing = symbol;
+            }
+        }
+
+        Ok(LoadedModule {
+            name: smol_str::SmolStr::new(name),
+            size,
+            exports,
+        })
+    }
+}
+
+/// find the slots of the Import Address Table:
+/// those covered by the IAT data directory,
+/// and those referenced by the import descriptors.
+fn find_iat_slots(pe: &PE) -> Result<Vec<VA>> {
+    let base_address = pe.module.address_space.base_address;
+    let psize = pe.module.arch.pointer_size();
+    let mut slots: Vec<VA> = Default::default();
+
+    if let Some(iat) = pe.get_data_directory(crate::loader::pe::IMAGE_DIRECTORY_ENTRY_IAT)? {
+        // the size comes from the headers, so the IAT must fit within its section.
+        let section = pe
+            .module
+            .sections
+            .iter()
+            .find(|section| section.virtual_range.contains(&iat.address));
+
+        match section {
+            Some(section) if iat.address != base_address => {
+                let end = std::cmp::min(iat.address.saturating_add(iat.size), section.virtual_range.end);
+                for i in 0..((end - iat.address) as usize / psize) {
+                    slots.push(iat.address + (i * psize) as RVA);
+                }
+            }
+            _ => debug!("imports: IAT: invalid directory: {:#x}", iat.address),
+        }
+    }
+
+    // the IAT entries whose address is taken, as recorded by CF Guard,
+    // which is useful when the IAT data directory has been destroyed.
+    slots.extend(crate::analysis::pe::control_flow_guard::find_pe_cfguard_iat_entries(
+        pe,
+    )?);
+
+    if let Some(import_directory) = imports::get_import_directory(pe)? {
+        for import_descriptor in imports::read_import_descriptors(pe, import_directory) {
+            for i in 0.. {
+                let ft = base_address + import_descriptor.first_thunk + (i * psize) as RVA;
+                match pe.module.read_va_at_va(ft) {
+                    Ok(0x0) | Err(_) => break,
+                    Ok(_) => slots.push(ft),
+                }
+            }
+        }
+    }
+
+    slots.sort_unstable();
+    slots.dedup();
+    Ok(slots)
+}
+
+/// like `get_imports`, but for an image dumped from memory, whose Import
+/// Address Table has already been filled in by the Windows loader.
+///
+/// each IAT entry holds the address of the imported symbol,
+/// so given the modules loaded into the process, keyed by base address,
+/// we can rebuild the import names, even when the import names are missing or
+/// destroyed, such as by a packer.
+/// entries that can't be resolved keep the names from the import directory.
+///
+/// note that a forwarded import, like `kernel32.dll!HeapAlloc`, is reported as
+/// its implementation, like `ntdll.dll!RtlAllocateHeap`.
+pub fn get_imports_from_iat(pe: &PE, modules: &BTreeMap<VA, LoadedModule>) -> Result<BTreeMap<VA, Import>> {
+    read_imports_from_iat(pe, modules, read_clr_metadata(pe).as_ref())
+}
+
+fn read_imports_from_iat(
+    pe: &PE,
+    modules: &BTreeMap<VA, LoadedModule>,
+    metadata: Option<&clr::Metadata>,
+) -> Result<BTreeMap<VA, Import>> {
+    let mut imports = match read_imports(pe, metadata) {
+        Ok(imports) => imports,
+        Err(e) => {
+            debug!("imports: failed to parse import directory: {}", e);
+            Default::default()
+        }
+    };
+
+    for slot in find_iat_slots(pe)?.into_iter() {
+        let target = match pe.module.read_va_at_va(slot) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+
+        let (base_address, module) = match modules.range(..=target).next_back() {
+            Some((&base_address, module)) if target < base_address + module.size => (base_address, module),
+            _ => continue,
+        };
+
+        let symbol = match module.exports.get(&(target - base_address)) {
+            Some(symbol) => symbol.clone(),
+            None => continue,
+        };
+
+        let import = Import {
+            address: slot,
+            dll: module.name.clone(),
+            symbol,
+            delay_loaded: imports.get(&slot).map(|import| import.delay_loaded).unwrap_or(false),
+            pinvoke: false,
+        };
+        debug!("imports: IAT: {:#x}: {}", slot, import);
+        imports.insert(slot, import);
+    }
+
+    Ok(imports)
+}
+
+/// find the pointer dereferenced by the given `JMP` instruction, like:
+///
+///   - `JMP [0x0]` (x32)
+///   - `JMP [rip+0x0]` (x64)
+///   - `JMP [ebx+0x0]` (x32 position independent code, given the value of
+///     `ebx`)
+#[cfg(feature = "disassembler")]
+pub(crate) fn get_jmp_pointer(va: VA, insn: &zydis::DecodedInstruction, ebx: Option<VA>) -> Option<VA> {
+    let op = cfg::get_first_operand(insn)?;
+
+    if !matches!(op.ty, zydis::OperandType::MEMORY) {
+        return None;
+    }
+
+    if op.mem.index != zydis::Register::NONE || op.mem.scale != 0 || !op.mem.disp.has_displacement {
+        return None;
+    }
+
+    if op.mem.base == zydis::Register::NONE {
+        // the operand is a deref of a memory address.
+        // for example: JMP [0x0]
+        // this means: read the ptr from 0x0, and then jump to it.
+        if op.mem.disp.displacement < 0 {
+            return None;
+        }
+        Some(op.mem.disp.displacement as u64)
+    } else if op.mem.base == zydis::Register::RIP {
+        // this is RIP-relative addressing.
+        // it works like a relative immediate,
+        // that is: dst = *(rva + displacement + instruction len)
+        cfg::va_add_signed(va + insn.length as u64, op.mem.disp.displacement)
+    } else if op.mem.base == zydis::Register::EBX {
+        cfg::va_add_signed(ebx?, op.mem.disp.displacement)
+    } else {
+        None
+    }
+}
+
 #[cfg(feature = "disassembler")]
 pub fn find_thunks(pe: &PE, imports: &BTreeMap<VA, Import>, functions: &HashSet<VA>) -> Result<BTreeMap<VA, Thunk>> {
     let mut thunks: BTreeMap<VA, Thunk> = Default::default();
@@ -125,57 +438,18 @@ pub fn find_thunks(pe: &PE, imports: &BTreeMap<VA, Import>, functions: &HashSet<
                     continue;
                 }
 
-                let op = cfg::get_first_operand(&insn).expect("JMP has no target");
-
-                if let zydis::OperandType::MEMORY = op.ty {
-                    // 32-bit
-                    if op.mem.base == zydis::Register::NONE
-                        && op.mem.index == zydis::Register::NONE
-                        && op.mem.scale == 0
-                        && op.mem.disp.has_displacement
-                    {
-                        // the operand is a deref of a memory address.
-                        // for example: JMP [0x0]
-                        // this means: read the ptr from 0x0, and then jump to it.
-
-                        if op.mem.disp.displacement < 0 {
-                            continue;
-                        }
-                        let ptr: VA = op.mem.disp.displacement as u64;
-
-                        if let Some(import) = imports.get(&ptr) {
-                            let thunk = Thunk {
-                                address: function,
-                                import:  import.clone(),
-                            };
-                            debug!("thunk: {:#x} -> {}", thunk.address, thunk.import);
-                            thunks.insert(thunk.address, thunk);
-                        }
-                    } else if op.mem.base == zydis::Register::RIP
-                            // only valid on x64
-                            && op.mem.index == zydis::Register::NONE
-                            && op.mem.scale == 0
-                            && op.mem.disp.has_displacement
-                    {
-                        // this is RIP-relative addressing.
-                        // it works like a relative immediate,
-                        // that is: dst = *(rva + displacement + instruction len)
-
-                        let ptr =
-                            match cfg::va_add_signed(function + insn.length as u64, op.mem.disp.displacement as i64) {
-                                None => continue,
-                                Some(ptr) => ptr,
-                            };
-
-                        if let Some(import) = imports.get(&ptr) {
-                            let thunk = Thunk {
-                                address: function,
-                                import:  import.clone(),
-                            };
-                            debug!("thunk: {:#x} -> {}", thunk.address, thunk.import);
-                            thunks.insert(thunk.address, thunk);
-                        }
-                    }
+                let ptr = match get_jmp_pointer(function, &insn, None) {
+                    Some(ptr) => ptr,
+                    None => continue,
+                };
+
+                if let Some(import) = imports.get(&ptr) {
+                    let thunk = Thunk {
+                        address: function,
+                        import:  import.clone(),
+                    };
+                    debug!("thunk: {:#x} -> {}", thunk.address, thunk.import);
+                    thunks.insert(thunk.address, thunk);
                 }
             }
         }
@@ -185,30 +459,62 @@ pub fn find_thunks(pe: &PE, imports: &BTreeMap<VA, Import>, functions: &HashSet<
 }
 
 #[cfg(feature = "disassembler")]
-pub fn find_functions(pe: &PE) -> Result<Vec<Function>> {
-    let imports = get_imports(pe)?;
-    debug!("imports: found {} imports", imports.len());
-
+fn find_function_candidates(pe: &PE, metadata: Option<&clr::Metadata>, noret: &BTreeSet<VA>) -> Result<HashSet<VA>> {
     let mut function_starts: HashSet<VA> = Default::default();
     function_starts.extend(crate::analysis::pe::entrypoints::find_pe_entrypoint(pe)?);
     function_starts.extend(crate::analysis::pe::exports::find_pe_exports(pe)?);
     function_starts.extend(crate::analysis::pe::safeseh::find_pe_safeseh_handlers(pe)?);
+    function_starts.extend(crate::analysis::pe::tls_callbacks::find_pe_tls_callbacks(pe)?);
     function_starts.extend(crate::analysis::pe::runtime_functions::find_pe_runtime_functions(pe)?);
     function_starts.extend(crate::analysis::pe::control_flow_guard::find_pe_cfguard_functions(pe)?);
-    function_starts.extend(crate::analysis::pe::call_targets::find_pe_call_targets(pe)?);
-    function_starts.extend(crate::analysis::pe::patterns::find_function_prologues(pe)?);
-    function_starts.extend(crate::analysis::pe::pointers::find_pe_nonrelocated_executable_pointers(
+
+    // longjmp and EH continuation targets are code, but not function starts,
+    // so don't let the heuristic passes below claim them.
+    let continuations: HashSet<VA> = crate::analysis::pe::control_flow_guard::find_pe_cfguard_code_pointers(pe)?
+        .into_iter()
+        .collect();
+    let mut heuristic_starts: HashSet<VA> = Default::default();
+    heuristic_starts.extend(crate::analysis::pe::patterns::find_function_prologues(pe)?);
+    heuristic_starts.extend(crate::analysis::pe::pointers::find_pe_nonrelocated_executable_pointers(
         pe,
     )?);
+    let known_starts: HashSet<VA> = function_starts.union(&heuristic_starts).cloned().collect();
+    heuristic_starts.extend(crate::analysis::pe::call_targets::find_pe_call_targets_with_noret(
+        pe,
+        noret,
+        &known_starts,
+    )?);
+    function_starts.extend(heuristic_starts.difference(&continuations));
+
+    // in .NET assemblies, the IL method bodies and metadata are found in the
+    // executable section alongside the native stubs, but they're not x86 code.
+    let managed_ranges = match clr::get_managed_ranges(pe, metadata) {
+        Ok(ranges) => ranges,
+        Err(e) => {
+            debug!("clr: failed to find managed ranges: {}", e);
+            Default::default()
+        }
+    };
+    function_starts.retain(|va| !managed_ranges.iter().any(|range| range.contains(va)));
 
     // TODO: validate that the code looks ok
 
-    let thunks = find_thunks(pe, &imports, &function_starts)?;
+    Ok(function_starts)
+}
+
+/// merge the function starts, thunks, and imports into one sorted list.
+/// thunks are reported as such, rather than as local functions.
+pub(crate) fn merge_functions(
+    function_starts: &HashSet<VA>,
+    thunks: &BTreeMap<VA, Thunk>,
+    imports: &BTreeMap<VA, Import>,
+) -> Vec<Function> {
     debug!("functions: found {} function candidates", function_starts.len());
     debug!("functions: found {} thunks", thunks.len());
 
-    let function_starts: Vec<_> = function_starts
-        .difference(&thunks.keys().cloned().collect())
+    let function_starts: Vec<VA> = function_starts
+        .iter()
+        .filter(|va| !thunks.contains_key(va))
         .cloned()
         .collect();
     debug!("functions: found {} functions", function_starts.len());
@@ -219,17 +525,357 @@ pub fn find_functions(pe: &PE) -> Result<Vec<Function>> {
     functions.extend(imports.values().cloned().map(Function::Import));
     functions.sort_unstable();
 
-    Ok(functions)
+    functions
 }
 
-#[cfg(feature = "disassembler")]
-pub fn find_function_starts(pe: &PE) -> Result<Vec<VA>> {
-    Ok(find_functions(pe)?
+/// the addresses of the local functions, ignoring thunks and imports.
+pub fn get_function_starts(functions: Vec<Function>) -> Vec<VA> {
+    functions
         .into_iter()
-        .filter(|f| matches!(f, Function::Local(_)))
-        .map(|f| match f {
-            Function::Local(va) => va,
-            _ => unreachable!(),
+        .filter_map(|f| match f {
+            Function::Local(va) => Some(va),
+            _ => None,
         })
-        .collect())
+        .collect()
+}
+
+#[cfg(feature = "disassembler")]
+fn collect_functions(pe: &PE, imports: &BTreeMap<VA, Import>, function_starts: HashSet<VA>) -> Result<Vec<Function>> {
+    let thunks = find_thunks(pe, imports, &function_starts)?;
+    Ok(merge_functions(&function_starts, &thunks, imports))
+}
+
+/// like `find_functions`, but also provide the addresses of the functions that
+/// don't return, which is suitable for `build_cfg_with_noret`.
+///
+/// once the no-return functions are known, the functions are found again,
+/// so that the calls found in the dead code after a no-return call
+/// don't introduce bogus function starts.
+/// this costs a second pass, plus a CFG for each function.
+#[cfg(feature = "disassembler")]
+pub fn find_functions_and_noret(pe: &PE) -> Result<(Vec<Function>, BTreeSet<VA>)> {
+    let metadata = read_clr_metadata(pe);
+    let imports = read_imports(pe, metadata.as_ref())?;
+    debug!("imports: found {} imports", imports.len());
+
+    let function_starts = find_function_candidates(pe, metadata.as_ref(), &Default::default())?;
+    let functions = collect_functions(pe, &imports, function_starts)?;
+
+    let noret = crate::analysis::cfg::noret::find_noret_functions(&pe.module, &functions)?;
+    debug!("functions: found {} no-return functions", noret.len());
+    if noret.is_empty() {
+        return Ok((functions, noret));
+    }
+
+    let function_starts = find_function_candidates(pe, metadata.as_ref(), &noret)?;
+    let functions = collect_functions(pe, &imports, function_starts)?;
+
+    Ok((functions, noret))
+}
+
+/// find the functions in a single pass,
+/// without accounting for the dead code after calls to no-return functions.
+/// see `find_functions_and_noret`.
+#[cfg(feature = "disassembler")]
+pub fn find_functions(pe: &PE) -> Result<Vec<Function>> {
+    let metadata = read_clr_metadata(pe);
+    let imports = read_imports(pe, metadata.as_ref())?;
+    debug!("imports: found {} imports", imports.len());
+
+    let function_starts = find_function_candidates(pe, metadata.as_ref(), &Default::default())?;
+
+    collect_functions(pe, &imports, function_starts)
+}
+
+/// like `find_functions`, but treat the procedures from the given PDB as
+/// authoritative: each procedure is a function, and heuristic candidates that
+/// fall within the body of a procedure are discarded.
+#[cfg(all(feature = "disassembler", feature = "pdb"))]
+pub fn find_functions_with_pdb(pe: &PE, symbols: &crate::loader::pe::pdb::Symbols) -> Result<Vec<Function>> {
+    let metadata = read_clr_metadata(pe);
+    let imports = read_imports(pe, metadata.as_ref())?;
+    debug!("imports: found {} imports", imports.len());
+
+    // procedures are sorted by start address.
+    let procedures = &symbols.procedures;
+    let is_within_procedure = |va: VA| -> bool {
+        // find the last procedure that starts at or before the address.
+        let index = match procedures.binary_search_by_key(&va, |procedure| procedure.range.start) {
+            // the address is a procedure start, which is fine.
+            Ok(_) => return false,
+            Err(0) => return false,
+            Err(index) => index - 1,
+        };
+        procedures[index].range.contains(&va)
+    };
+
+    let mut function_starts: HashSet<VA> = find_function_candidates(pe, metadata.as_ref(), &Default::default())?
+        .into_iter()
+        .filter(|&va| !is_within_procedure(va))
+        .collect();
+    function_starts.extend(procedures.iter().map(|procedure| procedure.range.start));
+
+    collect_functions(pe, &imports, function_starts)
+}
+
+/// like `find_functions`, but for an image dumped from memory,
+/// resolving imports via the Import Address Table. See `get_imports_from_iat`.
+#[cfg(feature = "disassembler")]
+pub fn find_functions_with_modules(pe: &PE, modules: &BTreeMap<VA, LoadedModule>) -> Result<Vec<Function>> {
+    let metadata = read_clr_metadata(pe);
+    let imports = read_imports_from_iat(pe, modules, metadata.as_ref())?;
+    debug!("imports: found {} imports", imports.len());
+
+    let function_starts = find_function_candidates(pe, metadata.as_ref(), &Default::default())?;
+
+    collect_functions(pe, &imports, function_starts)
+}
+
+#[cfg(feature = "disassembler")]
+pub fn find_function_starts(pe: &PE) -> Result<Vec<VA>> {
+    Ok(get_function_starts(find_functions(pe)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let imports = crate::analysis::pe::get_imports(&pe)?;
+        assert_eq!(1248, imports.len());
+        assert!(imports.values().all(|imp| !imp.delay_loaded));
+
+        Ok(())
+    }
+
+    #[test]
+    fn iat() -> Result<()> {
+        use crate::analysis::pe::{get_imports_from_iat, ImportedSymbol, LoadedModule};
+        use std::collections::BTreeMap;
+
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // pretend kernel32 imports from a copy of itself, loaded elsewhere.
+        let module = LoadedModule::from_pe("kernel32.dll", &pe)?;
+        let (&rva, _) = module
+            .exports
+            .iter()
+            .find(|(_, symbol)| matches!(symbol, ImportedSymbol::Name(name) if name == "CreateFileW"))
+            .unwrap();
+        let mut modules: BTreeMap<crate::VA, LoadedModule> = Default::default();
+        modules.insert(0x7FF8_0000_0000, module);
+
+        // the IAT entries haven't been resolved, so the names come from the import
+        // directory.
+        let imports = get_imports_from_iat(&pe, &modules)?;
+        assert_eq!(1248, imports.len());
+        let (&slot, import) = imports.iter().next().unwrap();
+        assert_ne!("kernel32.dll!CreateFileW", format!("{}", import));
+
+        // as though the Windows loader resolved the first IAT entry.
+        let mut buf = buf.clone();
+        let offset = pe.module.file_offset(slot)?;
+        buf[offset..offset + 8].copy_from_slice(&(0x7FF8_0000_0000 + rva).to_le_bytes());
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let imports = get_imports_from_iat(&pe, &modules)?;
+        assert_eq!(1248, imports.len());
+        assert_eq!("kernel32.dll!CreateFileW", format!("{}", imports[&slot]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn iat_mapped() -> Result<()> {
+        use crate::analysis::pe::{get_imports_from_iat, ImportedSymbol, LoadedModule};
+        use byteorder::{ByteOrder, LittleEndian};
+        use std::collections::BTreeMap;
+
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let module = LoadedModule::from_pe("kernel32.dll", &pe)?;
+        let (&rva, _) = module
+            .exports
+            .iter()
+            .find(|(_, symbol)| matches!(symbol, ImportedSymbol::Name(name) if name == "CreateFileW"))
+            .unwrap();
+        let mut modules: BTreeMap<crate::VA, LoadedModule> = Default::default();
+        modules.insert(0x7FF8_0000_0000, module);
+
+        // as though dumped from memory, after the Windows loader resolved the first
+        // IAT entry.
+        let slot = *crate::analysis::pe::get_imports(&pe)?.keys().next().unwrap();
+        let mut buf = crate::test::dump_pe(&pe);
+        let offset = (slot - 0x1_8000_0000) as usize;
+        buf[offset..offset + 8].copy_from_slice(&(0x7FF8_0000_0000 + rva).to_le_bytes());
+        let pe = crate::loader::pe::PE::from_memory(&buf)?;
+        assert_eq!(crate::loader::pe::Layout::Mapped, pe.layout);
+
+        let imports = get_imports_from_iat(&pe, &modules)?;
+        assert_eq!(1248, imports.len());
+        assert_eq!("kernel32.dll!CreateFileW", format!("{}", imports[&slot]));
+
+        // an IAT data directory whose size runs past its section.
+        let e_lfanew = LittleEndian::read_u32(&buf[0x3C..]) as usize;
+        let directory = e_lfanew + 4 + 20 + 112 + crate::loader::pe::IMAGE_DIRECTORY_ENTRY_IAT * 8;
+        LittleEndian::write_u32(&mut buf[directory + 4..], 0xFFFF_FFF0);
+        let pe = crate::loader::pe::PE::from_memory(&buf)?;
+
+        let imports = get_imports_from_iat(&pe, &modules)?;
+        assert_eq!(1248, imports.len());
+        assert_eq!("kernel32.dll!CreateFileW", format!("{}", imports[&slot]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn dotnet() -> Result<()> {
+        let buf = get_buf(Rsrc::DOTNET);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // just the runtime entry point, and no P/Invokes.
+        let imports = crate::analysis::pe::get_imports(&pe)?;
+        assert_eq!(1, imports.len());
+        assert_eq!(
+            "mscoree.dll!_CorExeMain",
+            format!("{}", imports.values().next().unwrap())
+        );
+        assert!(imports.values().all(|imp| !imp.pinvoke));
+
+        Ok(())
+    }
+
+    #[test]
+    fn junk_clr_header() -> Result<()> {
+        use byteorder::{ByteOrder, LittleEndian};
+
+        let mut buf = get_buf(Rsrc::K32);
+
+        // point the COM descriptor data directory at the DOS stub,
+        // and there, place a CLR header whose metadata isn't mapped.
+        let e_lfanew = LittleEndian::read_u32(&buf[0x3C..]) as usize;
+        let optional_header = e_lfanew + 4 + 20;
+        let directory = optional_header + 112 + 14 * 8;
+        LittleEndian::write_u32(&mut buf[directory..], 0x40);
+        LittleEndian::write_u32(&mut buf[directory + 4..], 0x48);
+        LittleEndian::write_u32(&mut buf[0x40..], 0x48);
+        LittleEndian::write_u32(&mut buf[0x48..], 0xFFFF_FF00);
+        LittleEndian::write_u32(&mut buf[0x4C..], 0x100);
+
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        assert!(pe.get_clr_metadata().is_err());
+
+        // the native code is still analyzed.
+        let imports = crate::analysis::pe::get_imports(&pe)?;
+        assert_eq!(1248, imports.len());
+        assert!(crate::analysis::pe::find_functions(&pe)?.len() > 1248);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let imports = crate::analysis::pe::get_imports(&pe)?;
+        assert_eq!(593, imports.len());
+        assert_eq!(21, imports.values().filter(|imp| imp.delay_loaded).count());
+
+        let imp = &imports[&0x4b96e0];
+        assert!(imp.delay_loaded);
+        assert_eq!("bcrypt.dll!BCryptOpenAlgorithmProvider", format!("{}", imp));
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi_invalid_delay_import() -> Result<()> {
+        use byteorder::{ByteOrder, LittleEndian};
+
+        let mut buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        let directory = pe
+            .get_data_directory(crate::loader::pe::IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT)?
+            .unwrap();
+        let descriptor = crate::loader::pe::imports::read_image_delayload_descriptor(&pe, directory.address)?;
+        let dll = descriptor.read_name(&pe)?;
+        let count = crate::analysis::pe::get_imports(&pe)?
+            .values()
+            .filter(|imp| imp.delay_loaded && imp.dll == dll.as_str())
+            .count();
+        assert!(count > 0);
+
+        // point the name of the first delay-load descriptor at unmapped memory.
+        let offset = pe.module.file_offset(directory.address)?;
+        LittleEndian::write_u32(&mut buf[offset + 0x4..], 0x7FFF_FFF0);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // only the imports of that descriptor are lost.
+        let imports = crate::analysis::pe::get_imports(&pe)?;
+        assert_eq!(593 - count, imports.len());
+        assert_eq!(21 - count, imports.values().filter(|imp| imp.delay_loaded).count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32_noret() -> Result<()> {
+        use crate::analysis::pe::Function;
+
+        let mut buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // 0x18001B190 ends with a call to a no-return function,
+        // followed by `int3` padding.
+        let (_, noret) = crate::analysis::pe::find_functions_and_noret(&pe)?;
+        assert!(noret.contains(&0x1_8001_B190));
+
+        // turn the padding at 0x18001B19A into a call to 0x18001B194,
+        // which is not a function start.
+        let offset = pe.module.file_offset(0x1_8001_B19A)?;
+        buf[offset..offset + 5].copy_from_slice(&[0xE8, 0xF5, 0xFF, 0xFF, 0xFF]);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // the call target heuristic alone finds it,
+        assert!(crate::analysis::pe::call_targets::find_pe_call_targets(&pe)?.contains(&0x1_8001_B194));
+        // but it's dead code, after the call to the no-return function.
+        let (functions, _) = crate::analysis::pe::find_functions_and_noret(&pe)?;
+        assert!(!functions.contains(&Function::Local(0x1_8001_B194)));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "pdb")]
+    #[test]
+    fn nop_pdb() -> Result<()> {
+        use crate::analysis::pe::Function;
+
+        let buf = get_buf(Rsrc::NOP);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        let mut symbols = crate::loader::pe::pdb::load_pdb_symbols(&pe, get_path(Rsrc::PDB))?;
+
+        let functions = crate::analysis::pe::find_functions_with_pdb(&pe, &symbols)?;
+        assert!(functions.contains(&Function::Local(0x401000)));
+        assert!(functions.contains(&Function::Local(0x40102B)));
+        assert!(functions.contains(&Function::Local(0x40105C)));
+        assert!(crate::analysis::pe::find_functions(&pe)? == functions);
+
+        // when `printf` claims the code that follows it, rather than `_amsg_exit`,
+        // the function there is discarded.
+        symbols.procedures.truncate(2);
+        symbols.procedures[1].range.end = 0x401081;
+        let functions = crate::analysis::pe::find_functions_with_pdb(&pe, &symbols)?;
+        assert!(functions.contains(&Function::Local(0x40102B)));
+        assert!(!functions.contains(&Function::Local(0x40105C)));
+
+        Ok(())
+    }
 }
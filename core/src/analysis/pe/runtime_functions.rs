@@ -10,6 +10,8 @@
 ///
 /// ref: https://docs.microsoft.com/en-us/cpp/build/exception-handling-x64
 /// ref: https://stackoverflow.com/questions/19808172/struct-runtime-function
+use std::{collections::BTreeMap, ops::Range};
+
 use anyhow::Result;
 use log::debug;
 use thiserror::Error;
@@ -150,7 +152,10 @@ fn read_unwind_info(pe: &PE, offset: VA) -> Result<UnwindInfo> {
     })
 }
 
-pub fn find_pe_runtime_functions(pe: &PE) -> Result<Vec<VA>> {
+/// read the RUNTIME_FUNCTION entries from the exception directory,
+/// along with the start address of the primary entry for each,
+/// which is the entry itself unless its UNWIND_INFO is chained.
+fn read_runtime_functions(pe: &PE) -> Result<Vec<(RuntimeFunction, VA)>> {
     let mut ret = vec![];
 
     if !matches!(pe.module.arch, Arch::X64) {
@@ -166,11 +171,13 @@ pub fn find_pe_runtime_functions(pe: &PE) -> Result<Vec<VA>> {
         {
             if let Some(runtime_function) = read_runtime_function(pe, va)? {
                 let mut unwind_info = read_unwind_info(pe, runtime_function.unwind_info_address)?;
+                let mut primary = runtime_function.function_start;
 
                 // if the UNWIND_INFO is chained,
                 // keep following it until it reaches the "primary entry".
                 while let UnwindInfoData::ChainedUnwindInfo(runtime_function) = unwind_info.data {
                     debug!("pdata: found chained UNWIND_INFO");
+                    primary = runtime_function.function_start;
                     unwind_info = read_unwind_info(pe, runtime_function.unwind_info_address)?;
                 }
 
@@ -178,10 +185,8 @@ pub fn find_pe_runtime_functions(pe: &PE) -> Result<Vec<VA>> {
                     return Err(RuntimeFunctionError::InvalidRuntimeFunction.into());
                 }
 
-                let function = runtime_function.function_start;
-
-                debug!("pdata: found RUNTIME_FUNCTION: {:#x}", function);
-                ret.push(function);
+                debug!("pdata: found RUNTIME_FUNCTION: {:#x}", runtime_function.function_start);
+                ret.push((runtime_function, primary));
             } else {
                 // just read an entry filled with zeros.
                 // assume this means we reached the end of the table.
@@ -193,6 +198,52 @@ pub fn find_pe_runtime_functions(pe: &PE) -> Result<Vec<VA>> {
     Ok(ret)
 }
 
+/// find the start addresses of the functions described by the primary
+/// RUNTIME_FUNCTION entries.
+/// the secondary entries, whose UNWIND_INFO is chained,
+/// describe chunks of these functions (see `find_pe_runtime_function_chunks`).
+pub fn find_pe_runtime_functions(pe: &PE) -> Result<Vec<VA>> {
+    Ok(read_runtime_functions(pe)?
+        .into_iter()
+        .filter(|(runtime_function, primary)| runtime_function.function_start == *primary)
+        .map(|(runtime_function, _)| runtime_function.function_start)
+        .collect())
+}
+
+/// find the address ranges covered by each function described by a
+/// RUNTIME_FUNCTION, indexed by the function start.
+///
+/// a function may have many ranges, or chunks,
+/// when the UNWIND_INFO for a secondary range is chained to the primary entry,
+/// such as for code that the compiler moved out of line.
+/// the first range is always that of the primary entry.
+pub fn find_pe_runtime_function_chunks(pe: &PE) -> Result<BTreeMap<VA, Vec<Range<VA>>>> {
+    let mut chunks: BTreeMap<VA, Vec<Range<VA>>> = Default::default();
+
+    let runtime_functions = read_runtime_functions(pe)?;
+
+    // primary entries first, so that they lead each list of ranges.
+    for (runtime_function, primary) in runtime_functions.iter() {
+        if runtime_function.function_start == *primary {
+            chunks
+                .entry(*primary)
+                .or_default()
+                .push(runtime_function.function_start..runtime_function.function_end);
+        }
+    }
+
+    for (runtime_function, primary) in runtime_functions.iter() {
+        if runtime_function.function_start != *primary {
+            chunks
+                .entry(*primary)
+                .or_default()
+                .push(runtime_function.function_start..runtime_function.function_end);
+        }
+    }
+
+    Ok(chunks)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::rsrc::*;
@@ -204,9 +255,12 @@ mod tests {
         let pe = crate::loader::pe::PE::from_bytes(&buf)?;
 
         let fns = crate::analysis::pe::runtime_functions::find_pe_runtime_functions(&pe)?;
-        assert_eq!(1800, fns.len());
+        // 1800 entries, less the secondary ones.
+        assert_eq!(1461, fns.len());
 
         assert_eq!(fns[0], 0x180001010);
+        // the out-of-line chunk of 0x180001068.
+        assert!(!fns.contains(&0x18002291E));
 
         Ok(())
     }
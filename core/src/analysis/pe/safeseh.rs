@@ -18,9 +18,10 @@ use anyhow::Result;
 use log::debug;
 
 use crate::{
-    arch::Arch,
-    aspace::AddressSpace,
-    loader::{pe, pe::PE},
+    loader::pe::{
+        load_config::{read_load_config_directory, read_se_handler_table},
+        PE,
+    },
     module::Permissions,
     VA,
 };
@@ -28,55 +29,31 @@ use crate::{
 pub fn find_pe_safeseh_handlers(pe: &PE) -> Result<Vec<VA>> {
     let mut ret = vec![];
 
-    if let Ok(Some(load_config_directory)) = pe.get_data_directory(pe::IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG) {
-        debug!("load config directory: {:#x}", load_config_directory.address);
+    let load_config = match read_load_config_directory(pe) {
+        Ok(Some(load_config)) => load_config,
+        _ => return Ok(ret),
+    };
 
-        // offsets defined here:
-        // https://docs.microsoft.com/en-us/windows/desktop/debug/pe-format#load-configuration-directory
-
-        // according to IDA, the first DWORD is `Size` not `Characteristics` (unused).
-        let size = pe.module.address_space.read_u32(load_config_directory.address)?;
-
-        // max offset into the config directory that we'll read.
-        let max_config_directory_offset = match pe.module.arch {
-            // CFG flags
-            Arch::X32 => 0x48,
-            Arch::X64 => 0x70,
-        };
-
-        if max_config_directory_offset > size {
+    let table = match load_config.se_handler_table {
+        Some(table) => table,
+        None => {
             debug!("no SafeSEH table: load config directory too small");
             return Ok(ret);
         }
-
-        let sehandler_table_va: VA = match pe.module.arch {
-            Arch::X32 => pe.module.read_va_at_va(load_config_directory.address + 0x40)?,
-            Arch::X64 => pe.module.read_va_at_va(load_config_directory.address + 0x60)?,
-        };
-        if sehandler_table_va == 0 {
-            debug!("SafeSEH table empty");
-            return Ok(ret);
-        };
-        debug!("SafeSEH table: {:#x}", sehandler_table_va);
-
-        let sehandler_table_count = match pe.module.arch {
-            Arch::X32 => pe.module.address_space.read_u32(load_config_directory.address + 0x44)? as u64,
-            Arch::X64 => pe.module.address_space.read_u64(load_config_directory.address + 0x68)? as u64,
-        };
-        debug!("SafeSEH table count: {:#x}", sehandler_table_count);
-
-        let mut offset = sehandler_table_va;
-        for _ in 0..sehandler_table_count {
-            let target = pe.module.read_rva_at_va(offset)?;
-            let target = target as u64 + pe.module.address_space.base_address;
-
-            if pe.module.probe_va(target, Permissions::X) {
-                ret.push(target);
-            } else {
-                debug!("unexpected non-executable SafeSEH target: {:#x}", target);
-                break;
-            }
-            offset += pe.module.arch.pointer_size() as u64
+    };
+
+    if table.address == 0 {
+        debug!("SafeSEH table empty");
+        return Ok(ret);
+    };
+    debug!("SafeSEH table: {:#x} count: {:#x}", table.address, table.count);
+
+    for target in read_se_handler_table(pe, &table)?.into_iter() {
+        if pe.module.probe_va(target, Permissions::X) {
+            ret.push(target);
+        } else {
+            debug!("unexpected non-executable SafeSEH target: {:#x}", target);
+            break;
         }
     }
 
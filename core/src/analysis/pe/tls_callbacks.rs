@@ -0,0 +1,131 @@
+//! Parse the PE TLS directory for callbacks that run before the entry point.
+//!
+//! The TLS directory references a NULL-terminated array of pointers (VAs) to
+//! callback functions. The loader invokes each on process and thread
+//! attach/detach, so they're a common place to hide code that runs before the
+//! entry point.
+//!
+//! Bail if the table reference, table, or any of its entries don't make sense.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#tls-callback-functions
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    loader::pe::{tls, PE},
+    module::Permissions,
+    VA,
+};
+
+pub fn find_pe_tls_callbacks(pe: &PE) -> Result<Vec<VA>> {
+    let mut ret = vec![];
+
+    if let Some(tls_directory) = tls::read_tls_directory(pe)? {
+        debug!("TLS callbacks: {:#x}", tls_directory.address_of_callbacks);
+
+        for callback in tls::read_tls_callbacks(pe, &tls_directory)?.into_iter() {
+            if pe.module.probe_va(callback, Permissions::X) {
+                ret.push(callback);
+            } else {
+                debug!("unexpected non-executable TLS callback: {:#x}", callback);
+                break;
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rsrc::*;
+    use anyhow::Result;
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let fns = crate::analysis::pe::tls_callbacks::find_pe_tls_callbacks(&pe)?;
+        assert_eq!(0, fns.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tiny() -> Result<()> {
+        let buf = get_buf(Rsrc::TINY);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let fns = crate::analysis::pe::tls_callbacks::find_pe_tls_callbacks(&pe)?;
+        assert_eq!(0, fns.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn nop() -> Result<()> {
+        let buf = get_buf(Rsrc::NOP);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let fns = crate::analysis::pe::tls_callbacks::find_pe_tls_callbacks(&pe)?;
+        assert_eq!(0, fns.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let fns = crate::analysis::pe::tls_callbacks::find_pe_tls_callbacks(&pe)?;
+        assert_eq!(0, fns.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi_synthetic() -> Result<()> {
+        use byteorder::{ByteOrder, LittleEndian};
+
+        // mimikatz doesn't have a TLS directory, so add one,
+        // placing the structures in the zero padding at the end of .data (file offset
+        // 0xB8180).
+        let mut buf = get_buf(Rsrc::MIMI);
+
+        // IMAGE_DIRECTORY_ENTRY_TLS
+        LittleEndian::write_u32(&mut buf[0x1D8..], 0xB9780);
+        LittleEndian::write_u32(&mut buf[0x1DC..], 0x18);
+
+        // IMAGE_TLS_DIRECTORY32 at 0x4B9780
+        let directory = 0xB8180;
+        LittleEndian::write_u32(&mut buf[directory..], 0x4B_97E0); // StartAddressOfRawData
+        LittleEndian::write_u32(&mut buf[directory + 0x4..], 0x4B_97F0); // EndAddressOfRawData
+        LittleEndian::write_u32(&mut buf[directory + 0x8..], 0x4B_97D8); // AddressOfIndex
+        LittleEndian::write_u32(&mut buf[directory + 0xC..], 0x4B_97C0); // AddressOfCallBacks
+
+        // callbacks at 0x4B97C0: two functions, then the TLS directory (not code),
+        // then the NULL terminator.
+        LittleEndian::write_u32(&mut buf[directory + 0x40..], 0x40_1000);
+        LittleEndian::write_u32(&mut buf[directory + 0x44..], 0x40_105D);
+        LittleEndian::write_u32(&mut buf[directory + 0x48..], 0x4B_9780);
+        LittleEndian::write_u32(&mut buf[directory + 0x4C..], 0x0);
+
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let tls_directory = crate::loader::pe::tls::read_tls_directory(&pe)?.unwrap();
+        assert_eq!(0x4B_97C0, tls_directory.address_of_callbacks);
+        assert_eq!(
+            vec![0x40_1000, 0x40_105D, 0x4B_9780],
+            crate::loader::pe::tls::read_tls_callbacks(&pe, &tls_directory)?
+        );
+
+        // stop at the first callback that isn't executable.
+        let fns = crate::analysis::pe::tls_callbacks::find_pe_tls_callbacks(&pe)?;
+        assert_eq!(vec![0x40_1000, 0x40_105D], fns);
+
+        Ok(())
+    }
+}
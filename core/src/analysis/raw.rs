@@ -0,0 +1,99 @@
+//! Analysis passes for raw bytes, like shellcode.
+//!
+//! There are no headers or symbols to describe the functions,
+//! so we start at the entry point and follow `call` instructions,
+//! rather than linearly disassembling regions that may well contain data.
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    analysis::{cfg, dis},
+    aspace::AddressSpace,
+    loader::raw::Raw,
+    module::Permissions,
+    RVA, VA,
+};
+
+/// find the functions reachable from the entry point via `call` instructions,
+/// including the entry point itself.
+pub fn find_function_starts(raw: &Raw) -> Result<Vec<VA>> {
+    let decoder = dis::get_disassembler(&raw.module)?;
+
+    let mut functions: BTreeSet<VA> = Default::default();
+    let mut queue: Vec<VA> = raw.entry_point.into_iter().collect();
+
+    while let Some(function) = queue.pop() {
+        if functions.contains(&function) {
+            continue;
+        }
+
+        let cfg = match cfg::build_cfg(&raw.module, function) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                debug!("raw: {:#x}: failed to build CFG: {}", function, e);
+                continue;
+            }
+        };
+        functions.insert(function);
+
+        for basic_block in cfg.basic_blocks.values() {
+            let buf = raw
+                .module
+                .address_space
+                .read_bytes(basic_block.address, basic_block.length as usize)?;
+
+            for (offset, insn) in dis::linear_disassemble(&decoder, &buf) {
+                if let Ok(Some(insn)) = insn {
+                    if !matches!(insn.mnemonic, zydis::enums::Mnemonic::CALL) {
+                        continue;
+                    }
+
+                    let va = basic_block.address + offset as RVA;
+                    for flow in cfg::get_call_insn_flow(&raw.module, va, &insn)?.iter() {
+                        if let cfg::Flow::Call(target) = *flow {
+                            if raw.module.probe_va(target, Permissions::X) {
+                                debug!("raw: {:#x}: call {:#x}", va, target);
+                                queue.push(target);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(functions.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{arch::Arch, loader::raw::Raw, module::Permissions};
+
+    #[test]
+    fn calls() -> Result<()> {
+        // 0x0: call 0x7
+        // 0x5: jmp  0x5
+        // 0x7: call 0xD
+        // 0xC: ret
+        // 0xD: ret
+        let buf = b"\xE8\x02\x00\x00\x00\xEB\xFE\xE8\x01\x00\x00\x00\xC3\xC3";
+        let raw = Raw::from_bytes(buf, Arch::X64, 0x1000, Permissions::RWX, Some(0x1000))?;
+
+        let functions = crate::analysis::raw::find_function_starts(&raw)?;
+        assert_eq!(vec![0x1000, 0x1007, 0x100D], functions);
+
+        Ok(())
+    }
+
+    #[test]
+    fn no_entry_point() -> Result<()> {
+        let raw = Raw::from_bytes(b"\xC3", Arch::X64, 0x1000, Permissions::RWX, None)?;
+        assert!(crate::analysis::raw::find_function_starts(&raw)?.is_empty());
+
+        Ok(())
+    }
+}
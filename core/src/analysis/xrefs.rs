@@ -0,0 +1,508 @@
+//! An index of the cross-references (xrefs) among the code and data of a
+//! module, so that we can answer questions like "who reads this global?",
+//! "who references this string?", or "which instruction loads this function
+//! pointer?".
+//!
+//! We find:
+//!
+//!   - code to code: calls, jumps (including tail calls and jump table
+//!     targets), and fallthroughs, from the CFGs of the given functions,
+//!   - code to data: reads and writes of memory operands with a fixed address,
+//!     and addresses taken via `lea` or an immediate operand, and
+//!   - data to code or data: pointer-sized values, in non-executable sections
+//!     and jump tables, that point into the module.
+//!
+//! Each xref is indexed by both its source and target,
+//! so queries work in either direction.
+//!
+//! references:
+//!   - https://hex-rays.com/products/ida/support/idadoc/1305.shtml
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
+
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    analysis::{
+        cfg::{self, Flow, CFG},
+        dataflow::Annotations,
+        dis,
+    },
+    arch::Arch,
+    aspace::AddressSpace,
+    module::{Module, Permissions},
+    RVA, VA,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum XrefKind {
+    // code to code.
+    /// like: `push ebp` to the subsequent instruction.
+    Fallthrough,
+    /// like: `call 0x401000`.
+    Call,
+    /// like: `jmp 0x401000`, including tail calls and jump table targets.
+    UnconditionalJump,
+    /// like: `jnz 0x401000`.
+    ConditionalJump,
+
+    // code to data.
+    /// like: `mov eax, [0x401000]`.
+    Read,
+    /// like: `mov [0x401000], eax`.
+    Write,
+    /// like: `lea eax, [0x401000]` or `push 0x401000`.
+    Address,
+
+    // data to code or data.
+    /// like: `dd 0x401000`.
+    Pointer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Xref {
+    /// the address of the instruction or pointer that references the target.
+    pub from: VA,
+    pub to:   VA,
+    pub kind: XrefKind,
+}
+
+#[derive(Default)]
+pub struct Xrefs {
+    /// map from target address to the xrefs to it, sorted and deduplicated.
+    to:   BTreeMap<VA, Vec<Xref>>,
+    /// map from source address to the xrefs from it, sorted and deduplicated.
+    from: BTreeMap<VA, Vec<Xref>>,
+}
+
+impl Xrefs {
+    pub fn new(xrefs: Vec<Xref>) -> Xrefs {
+        let mut index: Xrefs = Default::default();
+
+        for xref in xrefs.into_iter() {
+            index.to.entry(xref.to).or_default().push(xref);
+            index.from.entry(xref.from).or_default().push(xref);
+        }
+
+        for xrefs in index.to.values_mut().chain(index.from.values_mut()) {
+            xrefs.sort_unstable();
+            xrefs.dedup();
+        }
+
+        index
+    }
+
+    /// the xrefs to the given address.
+    pub fn to(&self, va: VA) -> &[Xref] {
+        self.to.get(&va).map(|xrefs| xrefs.as_slice()).unwrap_or_default()
+    }
+
+    /// the xrefs from the given address,
+    /// such as from an instruction or pointer.
+    pub fn from(&self, va: VA) -> &[Xref] {
+        self.from.get(&va).map(|xrefs| xrefs.as_slice()).unwrap_or_default()
+    }
+
+    /// the xrefs to any address in the given range,
+    /// such as the fields of a global structure, ordered by target.
+    pub fn to_range(&self, range: Range<VA>) -> impl Iterator<Item = &Xref> {
+        self.to.range(range).flat_map(|(_, xrefs)| xrefs.iter())
+    }
+
+    /// the xrefs from any address in the given range,
+    /// such as the instructions of a basic block, ordered by source.
+    pub fn from_range(&self, range: Range<VA>) -> impl Iterator<Item = &Xref> {
+        self.from.range(range).flat_map(|(_, xrefs)| xrefs.iter())
+    }
+
+    /// all the xrefs, ordered by source.
+    pub fn iter(&self) -> impl Iterator<Item = &Xref> {
+        self.from.values().flat_map(|xrefs| xrefs.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.from.values().map(|xrefs| xrefs.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.from.is_empty()
+    }
+}
+
+/// is the given address found within the module?
+fn is_valid_target(module: &Module, va: VA) -> bool {
+    module.probe_va(va, Permissions::RWX)
+}
+
+fn flow_kind(flow: &Flow) -> Option<XrefKind> {
+    match flow {
+        Flow::Fallthrough(_) => Some(XrefKind::Fallthrough),
+        Flow::Call(_) => Some(XrefKind::Call),
+        Flow::UnconditionalJump(_) => Some(XrefKind::UnconditionalJump),
+        Flow::ConditionalJump(_) => Some(XrefKind::ConditionalJump),
+        // this is just the subsequent instruction,
+        // which is also found by the fallthrough.
+        Flow::ConditionalMove(_) => None,
+    }
+}
+
+/// find the code to data xrefs from the operands of the given instruction.
+fn get_operand_xrefs(module: &Module, va: VA, insn: &zydis::DecodedInstruction, xrefs: &mut Vec<Xref>) {
+    for op in insn
+        .operands
+        .iter()
+        .filter(|op| op.visibility == zydis::OperandVisibility::EXPLICIT)
+    {
+        match op.ty {
+            zydis::OperandType::MEMORY => {
+                // like `mov eax, [0x401000]` or `mov eax, [rip + 0x10]`,
+                // or an array like `mov eax, [ecx*4 + 0x401000]`,
+                // for which we reference the start.
+                let ptr = if op.mem.base == zydis::Register::NONE
+                    && op.mem.index != zydis::Register::NONE
+                    && op.mem.disp.displacement > 0
+                {
+                    Some(op.mem.disp.displacement as VA)
+                } else {
+                    cfg::get_memory_operand_ptr(va, insn, op)
+                };
+
+                let ptr = match ptr {
+                    Some(ptr) if is_valid_target(module, ptr) => ptr,
+                    _ => continue,
+                };
+
+                if insn.mnemonic == zydis::Mnemonic::LEA {
+                    xrefs.push(Xref {
+                        from: va,
+                        to:   ptr,
+                        kind: XrefKind::Address,
+                    });
+                    continue;
+                }
+
+                if op.action.intersects(zydis::OperandAction::MASK_READ) {
+                    xrefs.push(Xref {
+                        from: va,
+                        to:   ptr,
+                        kind: XrefKind::Read,
+                    });
+                }
+
+                if op.action.intersects(zydis::OperandAction::MASK_WRITE) {
+                    xrefs.push(Xref {
+                        from: va,
+                        to:   ptr,
+                        kind: XrefKind::Write,
+                    });
+                }
+            }
+            zydis::OperandType::IMMEDIATE if !op.imm.is_relative => {
+                // like `push 0x401000` or `mov eax, 0x401000`.
+                let value = op.imm.value;
+                if is_valid_target(module, value) {
+                    xrefs.push(Xref {
+                        from: va,
+                        to:   value,
+                        kind: XrefKind::Address,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// find the xrefs from the instructions in the given CFGs.
+///
+/// an instruction found in more than one CFG is only analyzed once.
+fn get_code_xrefs(
+    module: &Module,
+    cfgs: &BTreeMap<VA, CFG>,
+    annotations: &Annotations,
+    xrefs: &mut Vec<Xref>,
+) -> Result<()> {
+    let decoder = dis::get_disassembler(module)?;
+    let mut seen: BTreeSet<VA> = Default::default();
+
+    for (&function, cfg) in cfgs.iter() {
+        debug!("xrefs: {:#x}", function);
+
+        for bb in cfg.basic_blocks.values() {
+            let buf = module.address_space.read_bytes(bb.address, bb.length as usize)?;
+
+            for (offset, insn) in dis::linear_disassemble(&decoder, &buf) {
+                let insn = match insn {
+                    Ok(Some(insn)) => insn,
+                    _ => continue,
+                };
+
+                let va = bb.address + offset as RVA;
+                if !seen.insert(va) {
+                    continue;
+                }
+
+                let next = va + insn.length as u64;
+                if next < bb.address + bb.length {
+                    xrefs.push(Xref {
+                        from: va,
+                        to:   next,
+                        kind: XrefKind::Fallthrough,
+                    });
+                } else {
+                    // the last instruction in the basic block,
+                    // whose flows, like those from a jump table or noret call,
+                    // are best described by the CFG.
+                    xrefs.extend(bb.successors.iter().filter_map(|succ| {
+                        flow_kind(succ).map(|kind| Xref {
+                            from: va,
+                            to: succ.va(),
+                            kind,
+                        })
+                    }));
+                }
+
+                if insn.mnemonic == zydis::Mnemonic::CALL {
+                    for flow in cfg::get_call_insn_flow(module, va, &insn)?.iter() {
+                        xrefs.push(Xref {
+                            from: va,
+                            to:   flow.va(),
+                            kind: XrefKind::Call,
+                        });
+                    }
+                }
+
+                // an indirect call or jump, like `call eax`, resolved by dataflow.
+                if let Some(&target) = annotations.targets.get(&va) {
+                    xrefs.push(Xref {
+                        from: va,
+                        to:   target,
+                        kind: if insn.mnemonic == zydis::Mnemonic::CALL {
+                            XrefKind::Call
+                        } else {
+                            XrefKind::UnconditionalJump
+                        },
+                    });
+                }
+
+                // a jump to another function, which isn't a CFG successor.
+                if let Some(&target) = cfg.tail_calls.get(&va) {
+                    xrefs.push(Xref {
+                        from: va,
+                        to:   target,
+                        kind: if insn.mnemonic == zydis::Mnemonic::JMP {
+                            XrefKind::UnconditionalJump
+                        } else {
+                            XrefKind::ConditionalJump
+                        },
+                    });
+                }
+
+                get_operand_xrefs(module, va, &insn, xrefs);
+            }
+        }
+
+        // the entries of jump tables point to the cases.
+        for table in cfg.jump_tables.values() {
+            for (i, &target) in table.targets.iter().enumerate() {
+                xrefs.push(Xref {
+                    from: table.table.start + (i * table.entry_size as usize) as RVA,
+                    to:   target,
+                    kind: XrefKind::Pointer,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// find the pointer-sized values in the non-executable sections that point
+/// into the module.
+///
+/// assumes pointers are aligned to their size.
+fn get_data_xrefs(module: &Module, xrefs: &mut Vec<Xref>) -> Result<()> {
+    let psize = module.arch.pointer_size();
+
+    for section in module.sections.iter() {
+        if section.permissions.intersects(Permissions::X) {
+            continue;
+        }
+
+        debug!(
+            "xrefs: scanning section {:#x}-{:#x}",
+            section.virtual_range.start, section.virtual_range.end
+        );
+
+        let start = section.virtual_range.start;
+        let size = (section.virtual_range.end - start) as usize;
+        let buf = module.address_space.read_bytes(start, size)?;
+
+        for (i, chunk) in buf.chunks_exact(psize).enumerate() {
+            let ptr = match module.arch {
+                Arch::X32 => u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as VA,
+                Arch::X64 => u64::from_le_bytes([
+                    chunk[0], chunk[1], chunk[2], chunk[3], chunk[4], chunk[5], chunk[6], chunk[7],
+                ]),
+            };
+
+            if ptr != 0 && is_valid_target(module, ptr) {
+                xrefs.push(Xref {
+                    from: start + (i * psize) as RVA,
+                    to:   ptr,
+                    kind: XrefKind::Pointer,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// index the xrefs among the code of the given CFGs and the module's data.
+///
+/// the CFGs are typically those of the functions found by a pass like
+/// `analysis::pe::find_function_starts`, perhaps built with the function
+/// boundaries, so that tail calls are found.
+pub fn build_xrefs(module: &Module, cfgs: &BTreeMap<VA, CFG>) -> Result<Xrefs> {
+    build_xrefs_with_annotations(module, cfgs, &Default::default())
+}
+
+/// like `build_xrefs`, but also use the targets of indirect calls and jumps,
+/// like `call eax`, from `dataflow::analyze_functions`.
+pub fn build_xrefs_with_annotations(
+    module: &Module,
+    cfgs: &BTreeMap<VA, CFG>,
+    annotations: &Annotations,
+) -> Result<Xrefs> {
+    let mut xrefs: Vec<Xref> = Default::default();
+
+    get_code_xrefs(module, cfgs, annotations, &mut xrefs)?;
+    get_data_xrefs(module, &mut xrefs)?;
+
+    debug!("xrefs: found {} xrefs", xrefs.len());
+    Ok(Xrefs::new(xrefs))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analysis::{
+            cfg::{build_cfg, CFG},
+            pe,
+            xrefs::*,
+        },
+        rsrc::*,
+        test::*,
+        VA,
+    };
+    use anyhow::Result;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn shellcode() -> Result<()> {
+        // 0x0:  a1 12 00 00 00       mov    eax, [0x12]
+        // 0x5:  a3 12 00 00 00       mov    [0x12], eax
+        // 0xA:  68 12 00 00 00       push   0x12
+        // 0xF:  74 00                jz     0x11
+        // 0x11: c3                   ret
+        // 0x12: 00 00 00 00          dd     0x0
+        let module = load_shellcode32(
+            b"\xA1\x12\x00\x00\x00\xA3\x12\x00\x00\x00\x68\x12\x00\x00\x00\x74\x00\xC3\x00\x00\x00\x00",
+        );
+        let mut cfgs: BTreeMap<VA, CFG> = Default::default();
+        cfgs.insert(0x0, build_cfg(&module, 0x0)?);
+        let xrefs = build_xrefs(&module, &cfgs)?;
+
+        let kinds: Vec<(VA, XrefKind)> = xrefs.to(0x12).iter().map(|xref| (xref.from, xref.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![(0x0, XrefKind::Read), (0x5, XrefKind::Write), (0xA, XrefKind::Address)]
+        );
+
+        assert_eq!(
+            xrefs.from(0xF),
+            &[
+                Xref {
+                    from: 0xF,
+                    to:   0x11,
+                    kind: XrefKind::Fallthrough,
+                },
+                Xref {
+                    from: 0xF,
+                    to:   0x11,
+                    kind: XrefKind::ConditionalJump,
+                },
+            ]
+        );
+        // the fallthroughs, and the jz.
+        assert_eq!(xrefs.to_range(0x0..0x12).count(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn annotations() -> Result<()> {
+        // 0x0:  b8 08 00 00 00       mov    eax, 0x8
+        // 0x5:  ff d0                call   eax
+        // 0x7:  c3                   ret
+        // 0x8:  c3                   ret
+        let module = load_shellcode32(b"\xB8\x08\x00\x00\x00\xFF\xD0\xC3\xC3");
+        let mut cfgs: BTreeMap<VA, CFG> = Default::default();
+        cfgs.insert(0x0, build_cfg(&module, 0x0)?);
+
+        let xrefs = build_xrefs(&module, &cfgs)?;
+        assert!(xrefs.from(0x5).iter().all(|xref| xref.kind != XrefKind::Call));
+
+        let annotations = crate::analysis::dataflow::analyze_functions(&module, &cfgs)?;
+        let xrefs = build_xrefs_with_annotations(&module, &cfgs, &annotations)?;
+        assert!(xrefs.from(0x5).contains(&Xref {
+            from: 0x5,
+            to:   0x8,
+            kind: XrefKind::Call,
+        }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let mut cfgs: BTreeMap<VA, CFG> = Default::default();
+        for &function in pe::find_function_starts(&pe)?.iter() {
+            if let Ok(cfg) = build_cfg(&pe.module, function) {
+                cfgs.insert(function, cfg);
+            }
+        }
+        let xrefs = build_xrefs(&pe.module, &cfgs)?;
+
+        // .text:0000000180060504  call    sub_180001068
+        assert!(xrefs.to(0x1_8000_1068).contains(&Xref {
+            from: 0x1_8006_0504,
+            to:   0x1_8000_1068,
+            kind: XrefKind::Call,
+        }));
+
+        // a call through the import address table, which reads the entry.
+        // .text:00000001800605CB  call    qword ptr [rip + 0x19497]
+        assert!(xrefs.from(0x1_8006_05CB).contains(&Xref {
+            from: 0x1_8006_05CB,
+            to:   0x1_8007_9A68,
+            kind: XrefKind::Read,
+        }));
+
+        // .text:0000000180060561  mov     rax, qword ptr [rip + 0x48900]
+        assert!(xrefs.to(0x1_800A_8E68).contains(&Xref {
+            from: 0x1_8006_0561,
+            to:   0x1_800A_8E68,
+            kind: XrefKind::Read,
+        }));
+
+        Ok(())
+    }
+}
@@ -1,3 +1,11 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ArchError {
+    #[error("unsupported architecture: {0}")]
+    UnsupportedArchitecture(String),
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum Arch {
     X32,
@@ -12,3 +20,24 @@ impl Arch {
         }
     }
 }
+
+/// parse the names used by the CLI tools and pylancelot.
+///
+/// ```
+/// use lancelot::arch::Arch;
+///
+/// assert!(matches!("x32".parse::<Arch>().unwrap(), Arch::X32));
+/// assert!(matches!("x64".parse::<Arch>().unwrap(), Arch::X64));
+/// assert!("arm64".parse::<Arch>().is_err());
+/// ```
+impl std::str::FromStr for Arch {
+    type Err = ArchError;
+
+    fn from_str(s: &str) -> Result<Arch, ArchError> {
+        match s {
+            "x32" => Ok(Arch::X32),
+            "x64" => Ok(Arch::X64),
+            _ => Err(ArchError::UnsupportedArchitecture(s.to_string())),
+        }
+    }
+}
@@ -1,19 +1,124 @@
 #![allow(clippy::nonstandard_macro_braces)] // clippy bug, see https://github.com/rust-lang/rust-clippy/issues/7434
 
+use std::{collections::BTreeMap, sync::Arc};
+
 use anyhow::Result;
 use byteorder::{ByteOrder, LittleEndian};
 use thiserror::Error;
 
 use crate::{
     arch::Arch,
-    pagemap::{PageMap, PageMapError},
-    RVA, VA,
+    pagemap::{PageMap, PageMapError, PAGE_SIZE},
+    util, RVA, VA,
 };
 
 #[derive(Debug, Error)]
 pub enum AddressSpaceError {
     #[error("String is too short")]
     StringTooShort,
+
+    #[error("region overlaps existing region: {0:#x}")]
+    RegionOverlaps(VA),
+
+    #[error("address space has no backing buffer")]
+    NoBackingBuffer,
+
+    #[error("invalid backed range: {0:#x}")]
+    InvalidBackedRange(RVA),
+
+    #[error("region extends past the end of the address space: {0:#x}")]
+    InvalidRegion(VA),
+}
+
+/// A shared, read-only buffer, like the contents of a memory-mapped file.
+///
+/// An address space can read file-backed ranges directly from this buffer,
+/// rather than copying them into its pages.
+/// It can wrap any owner of bytes, such as a `Vec<u8>` or a `memmap2::Mmap`,
+/// and is cheap to clone.
+///
+/// ```
+/// use lancelot::aspace::SharedBuffer;
+///
+/// let buf = SharedBuffer::new(vec![0x1, 0x2, 0x3]);
+/// assert_eq!(buf.len(), 3);
+/// assert_eq!(&buf[1..], &[0x2, 0x3]);
+/// ```
+#[derive(Clone)]
+pub struct SharedBuffer(Arc<dyn AsRef<[u8]> + Send + Sync>);
+
+impl SharedBuffer {
+    pub fn new<T: AsRef<[u8]> + Send + Sync + 'static>(buf: T) -> SharedBuffer {
+        SharedBuffer(Arc::new(buf))
+    }
+}
+
+impl std::ops::Deref for SharedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0.as_ref().as_ref()
+    }
+}
+
+impl AsRef<[u8]> for SharedBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl From<Vec<u8>> for SharedBuffer {
+    fn from(buf: Vec<u8>) -> SharedBuffer {
+        SharedBuffer::new(buf)
+    }
+}
+
+impl From<&[u8]> for SharedBuffer {
+    fn from(buf: &[u8]) -> SharedBuffer {
+        SharedBuffer::new(buf.to_vec())
+    }
+}
+
+impl std::fmt::Debug for SharedBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "SharedBuffer({:#x} bytes)", self.len())
+    }
+}
+
+/// A page whose contents are found in the backing buffer.
+/// Bytes beyond `length` are zero, like the tail of a section
+/// whose virtual size exceeds its raw size.
+#[derive(Clone, Copy)]
+struct BackedPage {
+    offset: usize,
+    length: usize,
+}
+
+/// The pages of an address space that are read from a shared buffer.
+#[derive(Clone)]
+struct BackedPages {
+    buf:   SharedBuffer,
+    pages: Vec<Option<BackedPage>>,
+}
+
+impl BackedPages {
+    fn get(&self, offset: RVA) -> Option<&BackedPage> {
+        self.pages
+            .get((offset as usize) / PAGE_SIZE)
+            .and_then(|page| page.as_ref())
+    }
+
+    /// read from the given page, which must contain the entire requested
+    /// range.
+    fn read_into(&self, page: &BackedPage, page_offset: usize, buf: &mut [u8]) {
+        buf.fill(0x0);
+
+        if page_offset < page.length {
+            let size = std::cmp::min(buf.len(), page.length - page_offset);
+            let start = page.offset + page_offset;
+            buf[..size].copy_from_slice(&self.buf[start..start + size]);
+        }
+    }
 }
 
 pub trait AddressSpace<T> {
@@ -69,6 +174,31 @@ pub trait AddressSpace<T> {
     fn read_ascii(&self, offset: T, minimum_length: usize) -> Result<String>;
 }
 
+/// Read a NULL-terminated, ASCII-encoded string at the given offset,
+/// fetching each byte with the given function, which returns None when the
+/// address isn't mapped.
+fn read_ascii_with<F: Fn(u64) -> Option<u8>>(offset: u64, minimum_length: usize, get: F) -> Result<String> {
+    const END_OF_ASCII: u8 = 0x7F;
+    const SPACE: u8 = 0x20;
+    const TAB: u8 = 0x9;
+    const NEWLINE: u8 = 0xA;
+    const LINEFEED: u8 = 0xD;
+
+    let buf: Vec<u8> = (offset..std::u64::MAX)
+        .map(get)
+        .take_while(|c| c.is_some())
+        .map(|c| c.unwrap())
+        .take_while(|&c| c != 0)
+        .take_while(|&c| c < END_OF_ASCII && (c >= SPACE || c == TAB || c == NEWLINE || c == LINEFEED))
+        .collect();
+
+    if buf.len() < minimum_length {
+        return Err(AddressSpaceError::StringTooShort.into());
+    }
+
+    Ok(String::from_utf8(buf)?)
+}
+
 /// An AddressSpace in which data is mapped at or near after a base address,
 /// and addressed using positive offsets from this base address.
 ///
@@ -78,9 +208,14 @@ pub trait AddressSpace<T> {
 /// Note that this implements `AddressSpace<RVA>` and not `AddressSpace<VA>`.
 /// Use `AbsoluteAddressSpace` when you're dealing with absolute addresses
 /// (`VA`).
+///
+/// Pages may be stored in the address space itself, or, when created with
+/// `with_backing`, read on demand from a shared buffer, like a memory-mapped
+/// file. Writes to such pages copy them into the address space first.
 #[derive(Clone)]
 pub struct RelativeAddressSpace {
     pub(crate) map: PageMap<u8>,
+    backing:        Option<BackedPages>,
 }
 
 impl RelativeAddressSpace {
@@ -93,43 +228,162 @@ impl RelativeAddressSpace {
 
     pub fn with_capacity(size: u64) -> RelativeAddressSpace {
         RelativeAddressSpace {
-            map: PageMap::with_capacity(size),
+            map:     PageMap::with_capacity(size),
+            backing: None,
+        }
+    }
+
+    /// Create an address space that can map ranges of the given buffer
+    /// without copying them. See `map_backed`.
+    pub fn with_backing(size: u64, buf: SharedBuffer) -> RelativeAddressSpace {
+        let page_count = (size as usize) / PAGE_SIZE + 1;
+        RelativeAddressSpace {
+            map:     PageMap::with_capacity(size),
+            backing: Some(BackedPages {
+                buf,
+                pages: vec![None; page_count],
+            }),
         }
     }
 
     pub fn from_buf(buf: &[u8]) -> RelativeAddressSpace {
         RelativeAddressSpace {
-            map: PageMap::from_items(buf),
+            map:     PageMap::from_items(buf),
+            backing: None,
+        }
+    }
+
+    /// Map `length` bytes of the backing buffer, found at `buf_offset`,
+    /// at the given page-aligned offset, padding with zeros to the end of
+    /// `size` bytes (rounded up to the next page).
+    /// The data is read from the backing buffer when needed, rather than
+    /// copied.
+    ///
+    /// Errors:
+    ///
+    ///   - AddressSpaceError::NoBackingBuffer - if not created via
+    ///     `with_backing`.
+    ///   - AddressSpaceError::InvalidBackedRange - if the offset isn't page
+    ///     aligned, or the range doesn't fit.
+    ///
+    /// ```
+    /// use lancelot::aspace::{AddressSpace, RelativeAddressSpace, SharedBuffer};
+    ///
+    /// let buf = SharedBuffer::new(vec![0x1, 0x2, 0x3]);
+    /// let mut aspace = RelativeAddressSpace::with_backing(0x3000, buf);
+    /// aspace.map_backed(0x1000, 0x1, 0x2, 0x1800).unwrap();
+    ///
+    /// assert_eq!(aspace.read_bytes(0x1000, 3).unwrap(), vec![0x2, 0x3, 0x0]);
+    /// assert_eq!(aspace.read_u8(0x2FFF).unwrap(), 0x0);
+    /// assert!(aspace.read_u8(0x0).is_err());
+    /// ```
+    pub fn map_backed(&mut self, offset: RVA, buf_offset: usize, length: usize, size: u64) -> Result<()> {
+        let backing = match &mut self.backing {
+            Some(backing) => backing,
+            None => return Err(AddressSpaceError::NoBackingBuffer.into()),
+        };
+
+        let size = std::cmp::max(size, length as u64);
+        let buf_end = buf_offset.saturating_add(length);
+        if (offset as usize) & (PAGE_SIZE - 1) != 0
+            || buf_end > backing.buf.len()
+            || (offset + size) as usize > backing.pages.len() * PAGE_SIZE
+        {
+            return Err(AddressSpaceError::InvalidBackedRange(offset).into());
+        }
+
+        let first_page = offset as usize / PAGE_SIZE;
+        let page_count = util::align(size, PAGE_SIZE as u64) as usize / PAGE_SIZE;
+        for i in 0..page_count {
+            let page_offset = i * PAGE_SIZE;
+            backing.pages[first_page + i] = Some(BackedPage {
+                offset: buf_offset + std::cmp::min(page_offset, length),
+                length: std::cmp::min(PAGE_SIZE, length.saturating_sub(page_offset)),
+            });
         }
+
+        Ok(())
+    }
+
+    /// Overwrite the data at the given offset, which must already be mapped.
+    ///
+    /// Pages read from a backing buffer are first copied into the address
+    /// space, so the buffer itself is never modified.
+    ///
+    /// Errors:
+    ///
+    ///   - PageMapError - if the address is not mapped.
+    pub fn write_into(&mut self, offset: RVA, buf: &[u8]) -> Result<()> {
+        if let Some(backing) = &self.backing {
+            if !buf.is_empty() {
+                let first_page = offset as usize / PAGE_SIZE;
+                let last_page = (offset as usize + buf.len() - 1) / PAGE_SIZE;
+                for page_index in first_page..=last_page {
+                    let page_address = (page_index * PAGE_SIZE) as RVA;
+                    if self.map.probe(page_address) {
+                        continue;
+                    }
+
+                    if let Some(page) = backing.get(page_address) {
+                        let mut page_buf = vec![0u8; PAGE_SIZE];
+                        backing.read_into(page, 0, &mut page_buf);
+                        self.map.write(page_address, &page_buf)?;
+                    }
+                }
+            }
+        }
+
+        self.map.update(offset, buf)
+    }
+
+    fn get(&self, offset: RVA) -> Option<u8> {
+        if let Some(v) = self.map.get(offset) {
+            return Some(v);
+        }
+
+        let backing = self.backing.as_ref()?;
+        let page = backing.get(offset)?;
+        let mut buf = [0u8; 1];
+        backing.read_into(page, offset as usize % PAGE_SIZE, &mut buf);
+        Some(buf[0])
     }
 }
 
 impl AddressSpace<RVA> for RelativeAddressSpace {
     fn read_into(&self, offset: RVA, buf: &mut [u8]) -> Result<()> {
-        self.map.slice_into(offset, buf)?;
+        let backing = match &self.backing {
+            Some(backing) => backing,
+            None => {
+                self.map.slice_into(offset, buf)?;
+                return Ok(());
+            }
+        };
+
+        // read page by page, preferring pages that have been written to.
+        let mut offset = offset;
+        let mut buf = buf;
+        while !buf.is_empty() {
+            let page_offset = offset as usize % PAGE_SIZE;
+            let size = std::cmp::min(buf.len(), PAGE_SIZE - page_offset);
+            let (head, tail) = buf.split_at_mut(size);
+
+            if self.map.probe(offset) {
+                self.map.slice_into(offset, head)?;
+            } else if let Some(page) = backing.get(offset) {
+                backing.read_into(page, page_offset, head);
+            } else {
+                return Err(PageMapError::NotMapped.into());
+            }
+
+            offset += size as RVA;
+            buf = tail;
+        }
+
         Ok(())
     }
 
     fn read_ascii(&self, offset: RVA, minimum_length: usize) -> Result<String> {
-        const END_OF_ASCII: u8 = 0x7F;
-        const SPACE: u8 = 0x20;
-        const TAB: u8 = 0x9;
-        const NEWLINE: u8 = 0xA;
-        const LINEFEED: u8 = 0xD;
-
-        let buf: Vec<u8> = (offset..std::u64::MAX)
-            .map(|offset| self.map.get(offset))
-            .take_while(|c| c.is_some())
-            .map(|c| c.unwrap())
-            .take_while(|&c| c != 0)
-            .take_while(|&c| c < END_OF_ASCII && (c >= SPACE || c == TAB || c == NEWLINE || c == LINEFEED))
-            .collect();
-
-        if buf.len() < minimum_length {
-            return Err(AddressSpaceError::StringTooShort.into());
-        }
-
-        Ok(String::from_utf8(buf)?)
+        read_ascii_with(offset, minimum_length, |offset| self.get(offset))
     }
 
     fn slice(&self, offset: RVA) -> Result<AddressSpaceSlice> {
@@ -166,8 +420,8 @@ impl AddressSpace<RVA> for &RelativeAddressSpace {
 ///
 /// Internally, this is a `RelativeAddressSpace` + a base address.
 /// So, its not a good fit for multiple modules that may be mapped different
-/// places. Probably want to implement `SparseAddressSpace` (collection of
-/// `AbsoluteAddressSpace`s)  for this.
+/// places. Use `SparseAddressSpace` (collection of `AbsoluteAddressSpace`s)
+/// for this.
 ///
 /// Note that this implements `AddressSpace<VA>` and not `AddressSpace<RVA>`.
 /// Use `RelativeAddressSpace` when you're dealing with relative addresses
@@ -224,6 +478,309 @@ impl AddressSpace<VA> for &AbsoluteAddressSpace {
     }
 }
 
+/// An AddressSpace made up of many regions mapped at unrelated addresses,
+/// such as the memory of a process captured in a minidump.
+///
+/// Unlike `AbsoluteAddressSpace`, the regions can be spread across the entire
+/// 64-bit address space, since each is backed by its own
+/// `AbsoluteAddressSpace`. Reads may span adjacent regions.
+///
+/// Note that this implements `AddressSpace<VA>` and not `AddressSpace<RVA>`.
+#[derive(Clone, Default)]
+pub struct SparseAddressSpace {
+    /// the regions, by base address, with their sizes.
+    regions: BTreeMap<VA, (RVA, AbsoluteAddressSpace)>,
+}
+
+impl SparseAddressSpace {
+    /// Map the given data at the given address.
+    ///
+    /// Errors:
+    ///
+    ///   - AddressSpaceError::RegionOverlaps - if the data overlaps an existing
+    ///     region.
+    ///   - AddressSpaceError::InvalidRegion - if the data extends past the end
+    ///     of the address space.
+    ///
+    /// ```
+    /// use lancelot::aspace::{AddressSpace, SparseAddressSpace};
+    ///
+    /// let mut aspace: SparseAddressSpace = Default::default();
+    /// aspace.map(0x7FF0_0000_1000, &[0x1, 0x2]).unwrap();
+    /// aspace.map(0x7FF0_0000_1002, &[0x3]).unwrap();
+    /// assert_eq!(aspace.read_bytes(0x7FF0_0000_1000, 3).unwrap(), vec![0x1, 0x2, 0x3]);
+    ///
+    /// assert!(aspace.read_u8(0x7FF0_0000_1003).is_err());
+    /// assert!(aspace.map(0x7FF0_0000_1001, &[0x0]).is_err());
+    /// assert!(aspace.map(0xFFFF_FFFF_FFFF_FFFF, &[0x0, 0x1]).is_err());
+    /// ```
+    pub fn map(&mut self, address: VA, buf: &[u8]) -> Result<()> {
+        let size = buf.len() as RVA;
+        if size == 0 {
+            return Ok(());
+        }
+
+        let end = match address.checked_add(size) {
+            Some(end) => end,
+            None => return Err(AddressSpaceError::InvalidRegion(address).into()),
+        };
+
+        if let Some((&start, &(region_size, _))) = self.regions.range(..end).next_back() {
+            if start + region_size > address {
+                return Err(AddressSpaceError::RegionOverlaps(start).into());
+            }
+        }
+
+        let region = RelativeAddressSpace::from_buf(buf).into_absolute(address)?;
+        self.regions.insert(address, (size, region));
+        Ok(())
+    }
+
+    /// The ranges of the mapped regions, ordered by address.
+    pub fn regions(&self) -> impl Iterator<Item = std::ops::Range<VA>> + '_ {
+        self.regions.iter().map(|(&start, &(size, _))| start..start + size)
+    }
+
+    /// Is the given address mapped?
+    pub fn probe(&self, offset: VA) -> bool {
+        self.get_region(offset).is_some()
+    }
+
+    fn get_region(&self, offset: VA) -> Option<(VA, &AbsoluteAddressSpace)> {
+        match self.regions.range(..=offset).next_back() {
+            Some((&start, (size, region))) if offset - start < *size => Some((start + size, region)),
+            _ => None,
+        }
+    }
+}
+
+impl AddressSpace<VA> for SparseAddressSpace {
+    fn read_into(&self, offset: VA, buf: &mut [u8]) -> Result<()> {
+        let mut offset = offset;
+        let mut buf = buf;
+
+        while !buf.is_empty() {
+            let (end, region) = match self.get_region(offset) {
+                Some(region) => region,
+                None => return Err(PageMapError::NotMapped.into()),
+            };
+
+            let size = std::cmp::min(buf.len() as u64, end - offset) as usize;
+            let (head, tail) = buf.split_at_mut(size);
+            region.read_into(offset, head)?;
+
+            offset += size as u64;
+            buf = tail;
+        }
+
+        Ok(())
+    }
+
+    fn read_ascii(&self, offset: VA, minimum_length: usize) -> Result<String> {
+        match self.get_region(offset) {
+            Some((_, region)) => region.read_ascii(offset, minimum_length),
+            None => Err(PageMapError::NotMapped.into()),
+        }
+    }
+
+    fn slice(&self, offset: RVA) -> Result<AddressSpaceSlice> {
+        Ok(AddressSpaceSlice {
+            base_address: offset,
+            inner:        Box::new(self),
+        })
+    }
+}
+
+impl AddressSpace<VA> for &SparseAddressSpace {
+    fn read_into(&self, offset: VA, buf: &mut [u8]) -> Result<()> {
+        (*self).read_into(offset, buf)
+    }
+
+    fn read_ascii(&self, offset: VA, minimum_length: usize) -> Result<String> {
+        (*self).read_ascii(offset, minimum_length)
+    }
+
+    fn slice(&self, offset: RVA) -> Result<AddressSpaceSlice> {
+        (*self).slice(offset)
+    }
+}
+
+/// A modification to the bytes of an address space. See
+/// `OverlayAddressSpace`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patch {
+    pub address:  VA,
+    /// the bytes found at the address when the patch was made.
+    pub original: Vec<u8>,
+    /// the bytes written by the patch.
+    pub patched:  Vec<u8>,
+}
+
+/// An AddressSpace that records patches over another address space,
+/// which itself is never modified.
+///
+/// Reads are served from the patched bytes first, then the inner address
+/// space. The patches are kept as a list that can be inspected and undone.
+/// Use `to_module` to analyze the patched bytes, such as with `build_cfg`,
+/// and `loader::pe::PE::export_patched` to write them to a new PE file.
+///
+/// Note that this implements `AddressSpace<VA>` and not `AddressSpace<RVA>`.
+///
+/// ```
+/// use lancelot::aspace::{AddressSpace, OverlayAddressSpace, SparseAddressSpace};
+///
+/// let mut inner: SparseAddressSpace = Default::default();
+/// inner.map(0x1000, &[0x1, 0x2, 0x3, 0x4]).unwrap();
+///
+/// let mut aspace = OverlayAddressSpace::new(&inner);
+/// aspace.patch(0x1001, &[0x90, 0x90]).unwrap();
+/// assert_eq!(aspace.read_bytes(0x1000, 4).unwrap(), vec![0x1, 0x90, 0x90, 0x4]);
+/// assert_eq!(inner.read_bytes(0x1000, 4).unwrap(), vec![0x1, 0x2, 0x3, 0x4]);
+///
+/// // patches may only cover mapped data.
+/// assert!(aspace.patch(0x1003, &[0x90, 0x90]).is_err());
+///
+/// assert_eq!(aspace.patches()[0].original, vec![0x2, 0x3]);
+/// aspace.undo();
+/// assert_eq!(aspace.read_bytes(0x1000, 4).unwrap(), vec![0x1, 0x2, 0x3, 0x4]);
+/// ```
+#[derive(Clone)]
+pub struct OverlayAddressSpace<A: AddressSpace<VA>> {
+    inner:   A,
+    /// in the order they were made.
+    patches: Vec<Patch>,
+    /// the current patched bytes, by address.
+    overlay: BTreeMap<VA, u8>,
+}
+
+impl<A: AddressSpace<VA>> OverlayAddressSpace<A> {
+    pub fn new(inner: A) -> OverlayAddressSpace<A> {
+        OverlayAddressSpace {
+            inner,
+            patches: vec![],
+            overlay: Default::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// The patches, in the order they were made.
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+
+    /// Write the given bytes at the given address, which must be mapped.
+    /// Later patches take precedence over earlier ones that they overlap.
+    pub fn patch(&mut self, address: VA, buf: &[u8]) -> Result<()> {
+        let original = self.read_bytes(address, buf.len())?;
+
+        for (i, &b) in buf.iter().enumerate() {
+            self.overlay.insert(address + i as u64, b);
+        }
+
+        self.patches.push(Patch {
+            address,
+            original,
+            patched: buf.to_vec(),
+        });
+
+        Ok(())
+    }
+
+    /// Remove the most recent patch, if any.
+    pub fn undo(&mut self) -> Option<Patch> {
+        let patch = self.patches.pop();
+        self.rebuild_overlay();
+        patch
+    }
+
+    /// Remove the patch at the given index into `patches`, if any,
+    /// leaving the others in place.
+    pub fn revert(&mut self, index: usize) -> Option<Patch> {
+        if index >= self.patches.len() {
+            return None;
+        }
+
+        let patch = self.patches.remove(index);
+        self.rebuild_overlay();
+        Some(patch)
+    }
+
+    fn rebuild_overlay(&mut self) {
+        self.overlay.clear();
+        for patch in self.patches.iter() {
+            for (i, &b) in patch.patched.iter().enumerate() {
+                self.overlay.insert(patch.address + i as u64, b);
+            }
+        }
+    }
+
+    /// Create a copy of the given module with the patches applied,
+    /// so that analysis passes like `build_cfg` see the patched bytes.
+    /// The given module isn't modified.
+    pub fn to_module(&self, module: &crate::module::Module) -> Result<crate::module::Module> {
+        let mut patched = module.clone();
+        let base_address = patched.address_space.base_address;
+
+        for patch in self.patches.iter() {
+            if patch.address < base_address {
+                return Err(PageMapError::NotMapped.into());
+            }
+
+            patched
+                .address_space
+                .relative
+                .write_into(patch.address - base_address, &patch.patched)?;
+        }
+
+        Ok(patched)
+    }
+}
+
+impl<A: AddressSpace<VA>> AddressSpace<VA> for OverlayAddressSpace<A> {
+    fn read_into(&self, offset: VA, buf: &mut [u8]) -> Result<()> {
+        self.inner.read_into(offset, buf)?;
+
+        if !buf.is_empty() {
+            for (&address, &b) in self.overlay.range(offset..offset + buf.len() as u64) {
+                buf[(address - offset) as usize] = b;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_ascii(&self, offset: VA, minimum_length: usize) -> Result<String> {
+        read_ascii_with(offset, minimum_length, |offset| match self.overlay.get(&offset) {
+            Some(&b) => Some(b),
+            None => self.inner.read_u8(offset).ok(),
+        })
+    }
+
+    fn slice(&self, offset: RVA) -> Result<AddressSpaceSlice> {
+        Ok(AddressSpaceSlice {
+            base_address: offset,
+            inner:        Box::new(self),
+        })
+    }
+}
+
+impl<A: AddressSpace<VA>> AddressSpace<VA> for &OverlayAddressSpace<A> {
+    fn read_into(&self, offset: VA, buf: &mut [u8]) -> Result<()> {
+        (*self).read_into(offset, buf)
+    }
+
+    fn read_ascii(&self, offset: VA, minimum_length: usize) -> Result<String> {
+        (*self).read_ascii(offset, minimum_length)
+    }
+
+    fn slice(&self, offset: RVA) -> Result<AddressSpaceSlice> {
+        (*self).slice(offset)
+    }
+}
+
 pub struct AddressSpaceSlice<'a> {
     /// offset from the start of the underlying aspace that this slice begins
     base_address: RVA,
@@ -73,6 +73,58 @@ pub fn link_imports(emu: &mut Emulator, pe: &PE) -> Result<BTreeMap<VA, String>>
         }
     }
 
+    // delay-loaded imports work similarly, except that until resolved,
+    // the Import Address Table (IAT) entry points to a stub that invokes the
+    // delay-load helper. so we point the IAT entry to the Import Name Table
+    // (INT) entry, skipping the helper, and resolve the API when the INT entry
+    // is fetched.
+    if let Some(delay_import_directory) = get_delay_import_directory(pe)? {
+        for descriptor in read_delay_import_descriptors(pe, delay_import_directory) {
+            let dll = match descriptor.read_name(pe) {
+                Ok(dll) => dll.to_lowercase(),
+                Err(e) => {
+                    debug!("emu: plat: win: {:?}: failed to read name: {}", descriptor, e);
+                    continue;
+                }
+            };
+            let name_table = base_address + descriptor.import_name_table;
+            let address_table = base_address + descriptor.import_address_table;
+
+            for i in 0.. {
+                let name_addr = name_table + (i * psize) as RVA;
+                let address_addr = address_table + (i * psize) as RVA;
+
+                let name = match read_delay_import_thunk_data(pe, &descriptor, name_addr) {
+                    Err(_) | Ok(IMAGE_THUNK_DATA::Function(0x0)) => break,
+                    Ok(IMAGE_THUNK_DATA::Ordinal(n)) => format!("#{}", n),
+                    Ok(IMAGE_THUNK_DATA::Function(rva)) => {
+                        match read_image_import_by_name(pe, pe.module.address_space.base_address + rva) {
+                            Ok(import) => import.name,
+                            Err(e) => {
+                                debug!("emu: plat: win: {:#x}: failed to read import name: {}", name_addr, e);
+                                break;
+                            }
+                        }
+                    }
+                };
+                debug!(
+                    "emu: plat: win: link delay-loaded import {:#x} -> {}!{} ",
+                    name_addr, dll, name
+                );
+                imports.insert(name_addr, format!("{}!{}", dll, name));
+
+                match pe.module.arch {
+                    Arch::X32 => {
+                        emu.mem.poke_u32(address_addr, name_addr as u32)?;
+                    }
+                    Arch::X64 => {
+                        emu.mem.poke_u64(address_addr, name_addr)?;
+                    }
+                }
+            }
+        }
+    }
+
     Ok(imports)
 }
 
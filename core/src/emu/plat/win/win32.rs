@@ -291,4 +291,25 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn delay_imports() -> Result<()> {
+        let pe = crate::loader::pe::PE::from_bytes(&get_buf(Rsrc::MIMI))?;
+
+        let mut emu: Win32Emulator = Default::default();
+        emu.load_pe(&pe)?;
+
+        // we've mapped the delay-load IAT entry to point to the INT entry,
+        // rather than the stub that invokes the delay-load helper.
+        //
+        //     IAT         INT
+        //     0x4b96e0 -> 0x4b241c (name: BCryptOpenAlgorithmProvider)
+        assert_eq!(emu.mem().read_u32(0x4b96e0)?, 0x4b241c);
+        assert_eq!(
+            emu.resolve_address(0x4b241c).unwrap(),
+            "bcrypt.dll!BCryptOpenAlgorithmProvider"
+        );
+
+        Ok(())
+    }
 }
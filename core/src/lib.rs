@@ -14,6 +14,7 @@ pub mod emu;
 pub mod loader;
 pub mod module;
 pub mod pagemap;
+pub mod search;
 pub mod util;
 
 #[cfg(any(test, doctest, feature = "test"))]
@@ -0,0 +1,358 @@
+//! Parse static libraries (`.lib`), which are `ar` archives of COFF objects.
+//!
+//! Import libraries are also `.lib` files, though most of their members are
+//! "short import" objects that describe a single symbol exported by a DLL,
+//! rather than COFF objects with code.
+//!
+//! We parse the archive ourselves, since MSVC terminates long member names
+//! with a NUL rather than the `/\n` used by GNU.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#archive-library-file-format
+//!   - https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#import-library-format
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::{
+    arch::Arch,
+    loader::coff::{Coff, CoffError, IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_I386},
+};
+
+const ARCHIVE_MAGIC: &[u8] = b"!<arch>\n";
+const SIZEOF_MEMBER_HEADER: usize = 60;
+const SIZEOF_IMPORT_HEADER: usize = 20;
+
+/// A member of an archive, such as `hello.obj`.
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub name:   String,
+    /// the offset of the member's data within the archive, after its header.
+    pub offset: usize,
+    pub size:   usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportType {
+    Code,
+    Data,
+    Const,
+}
+
+/// how to derive the name exported by the DLL from the symbol name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportNameType {
+    /// import by ordinal, found in `ordinal_or_hint`.
+    Ordinal,
+    /// the symbol name is the export name.
+    Name,
+    /// the symbol name without its leading `?`, `@`, or `_`.
+    NameNoPrefix,
+    /// the symbol name without its leading `?`, `@`, or `_`,
+    /// and truncated at the first `@`.
+    NameUndecorate,
+    /// the export name follows the DLL name.
+    NameExportAs,
+}
+
+/// A short import object, as found in import libraries,
+/// that describes a symbol exported by a DLL.
+#[derive(Clone, Debug)]
+pub struct ImportObject {
+    pub arch:            Arch,
+    /// like `kernel32.dll`.
+    pub dll:             String,
+    /// like `ExitProcess`.
+    pub name:            String,
+    pub ordinal_or_hint: u16,
+    pub typ:             ImportType,
+    pub name_type:       ImportNameType,
+}
+
+pub enum ArchiveMember {
+    Object(Box<Coff>),
+    Import(ImportObject),
+}
+
+/// A parsed static or import library.
+/// The `buf` field contains the raw data.
+pub struct Archive {
+    pub buf:     Vec<u8>,
+    /// the members, in the order they're found in the archive,
+    /// excluding the linker members and long names table.
+    pub members: Vec<Member>,
+}
+
+impl Archive {
+    pub fn from_bytes(buf: &[u8]) -> Result<Archive> {
+        load_archive(buf)
+    }
+
+    pub fn get_member_data(&self, member: &Member) -> &[u8] {
+        &self.buf[member.offset..member.offset + member.size]
+    }
+
+    /// parse and load the given member,
+    /// either as a COFF object or as an import object.
+    pub fn load_member(&self, member: &Member) -> Result<ArchiveMember> {
+        let buf = self.get_member_data(member);
+
+        if ImportObject::is_import_object(buf) {
+            Ok(ArchiveMember::Import(ImportObject::from_bytes(buf)?))
+        } else {
+            Ok(ArchiveMember::Object(Box::new(Coff::from_bytes(buf)?)))
+        }
+    }
+
+    /// load each of the COFF objects in the archive,
+    /// skipping import objects and members that fail to load.
+    pub fn objects<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a Member, Coff)> + 'a> {
+        Box::new(
+            self.members
+                .iter()
+                .filter_map(move |member| match self.load_member(member) {
+                    Ok(ArchiveMember::Object(coff)) => Some((member, *coff)),
+                    Ok(ArchiveMember::Import(_)) => None,
+                    Err(e) => {
+                        debug!("archive: {}: failed to load: {}", member.name, e);
+                        None
+                    }
+                }),
+        )
+    }
+
+    /// parse each of the import objects in the archive.
+    pub fn imports(&self) -> Vec<ImportObject> {
+        self.members
+            .iter()
+            .map(|member| self.get_member_data(member))
+            .filter(|buf| ImportObject::is_import_object(buf))
+            .filter_map(|buf| ImportObject::from_bytes(buf).ok())
+            .collect()
+    }
+}
+
+fn read_cstr(buf: &[u8]) -> Option<String> {
+    let end = buf.iter().position(|&b| b == 0x0)?;
+    Some(String::from_utf8_lossy(&buf[..end]).to_string())
+}
+
+impl ImportObject {
+    /// does the given member data start with the short import signature?
+    pub fn is_import_object(buf: &[u8]) -> bool {
+        buf.len() >= SIZEOF_IMPORT_HEADER
+            && LittleEndian::read_u16(buf) == 0x0
+            && LittleEndian::read_u16(&buf[2..]) == 0xFFFF
+            // version 0 is a short import, while /bigobj objects use version 2.
+            && LittleEndian::read_u16(&buf[4..]) == 0x0
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<ImportObject> {
+        if !ImportObject::is_import_object(buf) {
+            return Err(CoffError::FormatNotSupported("not an import object".to_string()).into());
+        }
+
+        let arch = match LittleEndian::read_u16(&buf[6..]) {
+            IMAGE_FILE_MACHINE_I386 => Arch::X32,
+            IMAGE_FILE_MACHINE_AMD64 => Arch::X64,
+            machine => return Err(CoffError::FormatNotSupported(format!("machine: {:#x}", machine)).into()),
+        };
+        let size_of_data = LittleEndian::read_u32(&buf[12..]) as usize;
+        let ordinal_or_hint = LittleEndian::read_u16(&buf[16..]);
+        let flags = LittleEndian::read_u16(&buf[18..]);
+
+        let typ = match flags & 0b11 {
+            0 => ImportType::Code,
+            1 => ImportType::Data,
+            2 => ImportType::Const,
+            typ => return Err(CoffError::MalformedCoffFile(format!("import type: {}", typ)).into()),
+        };
+        let name_type = match (flags >> 2) & 0b111 {
+            0 => ImportNameType::Ordinal,
+            1 => ImportNameType::Name,
+            2 => ImportNameType::NameNoPrefix,
+            3 => ImportNameType::NameUndecorate,
+            4 => ImportNameType::NameExportAs,
+            name_type => return Err(CoffError::MalformedCoffFile(format!("import name type: {}", name_type)).into()),
+        };
+
+        let data = match buf.get(SIZEOF_IMPORT_HEADER..SIZEOF_IMPORT_HEADER + size_of_data) {
+            Some(data) => data,
+            None => return Err(CoffError::MalformedCoffFile("import data out of bounds".to_string()).into()),
+        };
+        // the symbol name, then the DLL name, each NUL-terminated.
+        let name_end = data
+            .iter()
+            .position(|&b| b == 0x0)
+            .ok_or_else(|| CoffError::MalformedCoffFile("import name".to_string()))?;
+        let name = String::from_utf8_lossy(&data[..name_end]).to_string();
+        let dll = read_cstr(&data[name_end + 1..])
+            .ok_or_else(|| CoffError::MalformedCoffFile("import dll name".to_string()))?;
+
+        Ok(ImportObject {
+            arch,
+            dll,
+            name,
+            ordinal_or_hint,
+            typ,
+            name_type,
+        })
+    }
+}
+
+fn parse_decimal(buf: &[u8]) -> Option<usize> {
+    std::str::from_utf8(buf).ok()?.trim_end().parse().ok()
+}
+
+/// resolve a `/123` reference into the long names table.
+fn get_long_name(longnames: &[u8], offset: usize) -> Option<String> {
+    let buf = longnames.get(offset..)?;
+    let end = buf.iter().position(|&b| b == 0x0 || b == b'\n').unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..end]).trim_end_matches('/').to_string())
+}
+
+fn load_archive(buf: &[u8]) -> Result<Archive> {
+    if !buf.starts_with(ARCHIVE_MAGIC) {
+        return Err(CoffError::FormatNotSupported("not an archive".to_string()).into());
+    }
+
+    let mut members = vec![];
+    let mut longnames: &[u8] = &[];
+    let mut offset = ARCHIVE_MAGIC.len();
+
+    while offset + SIZEOF_MEMBER_HEADER <= buf.len() {
+        let header = &buf[offset..offset + SIZEOF_MEMBER_HEADER];
+        if &header[58..60] != b"`\n" {
+            return Err(CoffError::MalformedCoffFile(format!("invalid member header at {:#x}", offset)).into());
+        }
+
+        let raw_name = &header[..16];
+        let size = parse_decimal(&header[48..58])
+            .ok_or_else(|| CoffError::MalformedCoffFile(format!("invalid member size at {:#x}", offset)))?;
+
+        let mut data_offset = offset + SIZEOF_MEMBER_HEADER;
+        let mut data_size = size;
+        if data_offset + size > buf.len() {
+            return Err(CoffError::MalformedCoffFile(format!("member at {:#x} out of bounds", offset)).into());
+        }
+
+        let name = String::from_utf8_lossy(raw_name).trim_end().to_string();
+        let name = if name == "/" || name == "/<ECSYMBOLS>/" || name == "__.SYMDEF" {
+            // linker members, which index the symbols.
+            None
+        } else if name == "//" {
+            longnames = &buf[data_offset..data_offset + size];
+            None
+        } else if let Some(len) = name.strip_prefix("#1/") {
+            // BSD: the name immediately follows the header.
+            let len = parse_decimal(len.as_bytes())
+                .filter(|&len| len <= size)
+                .ok_or_else(|| CoffError::MalformedCoffFile(format!("invalid member name at {:#x}", offset)))?;
+            let name = read_cstr(&buf[data_offset..data_offset + len])
+                .unwrap_or_else(|| String::from_utf8_lossy(&buf[data_offset..data_offset + len]).to_string());
+            data_offset += len;
+            data_size -= len;
+            Some(name)
+        } else if let Some(index) = name.strip_prefix('/') {
+            let index = parse_decimal(index.as_bytes())
+                .ok_or_else(|| CoffError::MalformedCoffFile(format!("invalid member name at {:#x}", offset)))?;
+            Some(
+                get_long_name(longnames, index)
+                    .ok_or_else(|| CoffError::MalformedCoffFile(format!("invalid long name at {:#x}", offset)))?,
+            )
+        } else {
+            Some(name.trim_end_matches('/').to_string())
+        };
+
+        if let Some(name) = name {
+            debug!("archive: member: {} at {:#x} size {:#x}", name, data_offset, data_size);
+            members.push(Member {
+                name,
+                offset: data_offset,
+                size: data_size,
+            });
+        }
+
+        // members are aligned to two bytes.
+        offset += SIZEOF_MEMBER_HEADER + size + (size & 1);
+    }
+
+    Ok(Archive {
+        buf: buf.to_vec(),
+        members,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{arch::Arch, loader::coff::archive::*, rsrc::*};
+    use anyhow::Result;
+
+    #[test]
+    fn members() -> Result<()> {
+        let buf = get_buf(Rsrc::LIB);
+        let lib = Archive::from_bytes(&buf)?;
+
+        let names: Vec<_> = lib.members.iter().map(|member| member.name.as_str()).collect();
+        assert_eq!(
+            vec![
+                "hello.obj",
+                "kernel32.dll",
+                "kernel32.dll",
+                "kernel32.dll",
+                "kernel32.dll",
+                "kernel32.dll"
+            ],
+            names
+        );
+
+        // the object is the same as the standalone fixture.
+        assert_eq!(get_buf(Rsrc::COFF), lib.get_member_data(&lib.members[0]));
+
+        let objects: Vec<_> = lib.objects().collect();
+        // hello.obj, and the import descriptor, null descriptor and null thunk objects.
+        assert_eq!(4, objects.len());
+        assert!(objects[0].1.symbols.iter().any(|symbol| symbol.name == "main"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn imports() -> Result<()> {
+        let buf = get_buf(Rsrc::LIB);
+        let lib = Archive::from_bytes(&buf)?;
+
+        let imports = lib.imports();
+        assert_eq!(2, imports.len());
+
+        assert!(matches!(imports[0].arch, Arch::X64));
+        assert_eq!("kernel32.dll", imports[0].dll);
+        assert_eq!("ExitProcess", imports[0].name);
+        assert_eq!(ImportType::Code, imports[0].typ);
+        assert_eq!(ImportNameType::Name, imports[0].name_type);
+
+        assert_eq!("GetLastError", imports[1].name);
+        assert_eq!(ImportNameType::Ordinal, imports[1].name_type);
+        assert_eq!(12, imports[1].ordinal_or_hint);
+
+        let member = lib.members.last().unwrap();
+        assert!(matches!(lib.load_member(member)?, ArchiveMember::Import(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<()> {
+        assert!(Archive::from_bytes(b"").is_err());
+        assert!(Archive::from_bytes(b"MZ").is_err());
+
+        // truncated member.
+        let buf = get_buf(Rsrc::LIB);
+        assert!(Archive::from_bytes(&buf[..0x200]).is_err());
+
+        // an empty archive has no members.
+        assert!(Archive::from_bytes(b"!<arch>\n")?.members.is_empty());
+
+        Ok(())
+    }
+}
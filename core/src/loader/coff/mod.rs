@@ -0,0 +1,571 @@
+//! Load COFF objects (`.obj`), like those produced by MSVC and found in static
+//! libraries. See `archive` for `.lib` files.
+//!
+//! An object has sections, a symbol table, and relocations, but no preferred
+//! layout in memory, since that's decided by the linker.
+//! So, we map each section at its own page, starting at `BASE_ADDRESS`,
+//! and apply the relocations, such as calls between functions in the same
+//! object.
+//! Symbols defined elsewhere, like imports, have no address of their own,
+//! so each gets a pointer-sized slot in a synthetic `.externs` section,
+//! and references to it are fixed up to point there.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#coff-file-header-object-and-image
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+use thiserror::Error;
+
+pub mod archive;
+
+use crate::{
+    arch::Arch,
+    aspace::RelativeAddressSpace,
+    module::{Module, Permissions, Section},
+    util, RVA, VA,
+};
+
+#[derive(Error, Debug)]
+pub enum CoffError {
+    #[error("format not supported: {0}")]
+    FormatNotSupported(String),
+
+    #[error("malformed COFF file: {0}")]
+    MalformedCoffFile(String),
+}
+
+/// the name of the synthetic section that contains a slot for each
+/// referenced undefined symbol.
+pub const EXTERNS_SECTION_NAME: &str = ".externs";
+
+/// the address at which the sections are mapped.
+/// the first page is left empty, so that no section is found at the base
+/// address.
+pub const BASE_ADDRESS: VA = 0x40_0000;
+
+/// the largest uninitialized data section that's mapped,
+/// since its size comes from the header rather than the file data.
+const MAX_UNINITIALIZED_DATA_SIZE: u64 = 0x1000_0000;
+
+pub(crate) const IMAGE_FILE_MACHINE_I386: u16 = 0x14C;
+pub(crate) const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+
+const IMAGE_SCN_CNT_UNINITIALIZED_DATA: u32 = 0x0000_0080;
+/// The section contains comments or other information, like `.drectve`.
+const IMAGE_SCN_LNK_INFO: u32 = 0x0000_0200;
+/// The section will not become part of the image.
+const IMAGE_SCN_LNK_REMOVE: u32 = 0x0000_0800;
+/// The section can be discarded as needed, like `.debug$S`.
+const IMAGE_SCN_MEM_DISCARDABLE: u32 = 0x0200_0000;
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const IMAGE_SCN_MEM_READ: u32 = 0x4000_0000;
+const IMAGE_SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+const IMAGE_SYM_CLASS_EXTERNAL: u8 = 2;
+const IMAGE_SYM_CLASS_STATIC: u8 = 3;
+const IMAGE_SYM_CLASS_FILE: u8 = 103;
+const IMAGE_SYM_CLASS_WEAK_EXTERNAL: u8 = 105;
+const IMAGE_SYM_DTYPE_FUNCTION: u16 = 0x20;
+
+// x86 relocations.
+pub const IMAGE_REL_I386_DIR32: u16 = 0x0006;
+pub const IMAGE_REL_I386_DIR32NB: u16 = 0x0007;
+pub const IMAGE_REL_I386_SECTION: u16 = 0x000A;
+pub const IMAGE_REL_I386_SECREL: u16 = 0x000B;
+pub const IMAGE_REL_I386_TOKEN: u16 = 0x000C;
+pub const IMAGE_REL_I386_SECREL7: u16 = 0x000D;
+pub const IMAGE_REL_I386_REL32: u16 = 0x0014;
+
+// x86-64 relocations.
+pub const IMAGE_REL_AMD64_ADDR64: u16 = 0x0001;
+pub const IMAGE_REL_AMD64_ADDR32: u16 = 0x0002;
+pub const IMAGE_REL_AMD64_ADDR32NB: u16 = 0x0003;
+pub const IMAGE_REL_AMD64_REL32: u16 = 0x0004;
+pub const IMAGE_REL_AMD64_REL32_5: u16 = 0x0009;
+pub const IMAGE_REL_AMD64_SECTION: u16 = 0x000A;
+pub const IMAGE_REL_AMD64_SECREL: u16 = 0x000B;
+pub const IMAGE_REL_AMD64_SECREL7: u16 = 0x000C;
+pub const IMAGE_REL_AMD64_TOKEN: u16 = 0x000D;
+pub const IMAGE_REL_AMD64_SREL32: u16 = 0x000E;
+pub const IMAGE_REL_AMD64_SSPAN32: u16 = 0x0010;
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    /// the index into the symbol table, as referenced by relocations.
+    pub index:              usize,
+    pub name:               String,
+    /// the address of the symbol in the loaded module,
+    /// if its defined in a mapped section, or is a referenced undefined symbol.
+    pub address:            Option<VA>,
+    /// one-based index into the section table.
+    /// zero for undefined symbols, and negative for absolute and debugging
+    /// symbols.
+    pub section_number:     i16,
+    pub value:              u32,
+    /// like `IMAGE_SYM_CLASS_EXTERNAL`.
+    pub storage_class:      u8,
+    /// the symbol type says its a function.
+    pub function:           bool,
+    /// the symbol is visible outside of this object.
+    pub external:           bool,
+    /// the symbol names a section, like `.text`, rather than something within
+    /// it.
+    pub section_definition: bool,
+    /// the symbol is defined in another object or library.
+    /// when referenced, its address is a slot in the `EXTERNS_SECTION_NAME`
+    /// section.
+    pub undefined:          bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Relocation {
+    /// the address of the bytes to be fixed up.
+    pub address: VA,
+    /// the index of the target symbol. see `Coff::get_symbol`.
+    pub symbol:  usize,
+    /// like `IMAGE_REL_AMD64_REL32`.
+    pub typ:     u16,
+    /// the number of bytes fixed up.
+    pub size:    usize,
+    /// the relocation was applied to the loaded module.
+    /// false for relocation types we don't know how to apply,
+    /// like `IMAGE_REL_AMD64_SECREL`.
+    pub applied: bool,
+}
+
+/// A parsed and loaded COFF object.
+/// The `buf` field contains the raw data.
+/// The `module` field contains an address space with the sections mapped.
+pub struct Coff {
+    pub buf:         Vec<u8>,
+    pub module:      Module,
+    /// ordered by index.
+    pub symbols:     Vec<Symbol>,
+    /// ordered by address.
+    pub relocations: Vec<Relocation>,
+}
+
+impl Coff {
+    pub fn from_bytes(buf: &[u8]) -> Result<Coff> {
+        load_coff(buf)
+    }
+
+    pub fn executable_sections<'b>(&'b self) -> Box<dyn Iterator<Item = &'b Section> + 'b> {
+        Box::new(
+            self.module
+                .sections
+                .iter()
+                .filter(|section| section.permissions.intersects(Permissions::X)),
+        )
+    }
+
+    /// fetch the symbol at the given index in the symbol table.
+    pub fn get_symbol(&self, index: usize) -> Option<&Symbol> {
+        self.symbols
+            .binary_search_by_key(&index, |symbol| symbol.index)
+            .ok()
+            .map(|i| &self.symbols[i])
+    }
+}
+
+fn get_relocation_size(arch: Arch, typ: u16) -> usize {
+    match (arch, typ) {
+        (Arch::X32, IMAGE_REL_I386_SECTION) => 2,
+        (Arch::X32, IMAGE_REL_I386_SECREL7) => 1,
+        (Arch::X32, IMAGE_REL_I386_DIR32)
+        | (Arch::X32, IMAGE_REL_I386_DIR32NB)
+        | (Arch::X32, IMAGE_REL_I386_SECREL)
+        | (Arch::X32, IMAGE_REL_I386_TOKEN)
+        | (Arch::X32, IMAGE_REL_I386_REL32) => 4,
+        (Arch::X64, IMAGE_REL_AMD64_ADDR64) => 8,
+        (Arch::X64, IMAGE_REL_AMD64_SECTION) => 2,
+        (Arch::X64, IMAGE_REL_AMD64_SECREL7) => 1,
+        (Arch::X64, IMAGE_REL_AMD64_ADDR32..=IMAGE_REL_AMD64_REL32_5)
+        | (Arch::X64, IMAGE_REL_AMD64_SECREL)
+        | (Arch::X64, IMAGE_REL_AMD64_TOKEN)
+        | (Arch::X64, IMAGE_REL_AMD64_SREL32)
+        | (Arch::X64, IMAGE_REL_AMD64_SSPAN32) => 4,
+        // like IMAGE_REL_*_ABSOLUTE, which is ignored.
+        _ => 0,
+    }
+}
+
+/// fix up the bytes for the given relocation, in place,
+/// returning false if we don't know how to apply it.
+///
+/// COFF relocations don't have an explicit addend,
+/// rather, its whatever is already found at the fixup.
+fn apply_relocation(arch: Arch, typ: u16, address: VA, target: VA, buf: &mut [u8]) -> bool {
+    match (arch, typ) {
+        (Arch::X32, IMAGE_REL_I386_DIR32) | (Arch::X64, IMAGE_REL_AMD64_ADDR32) => {
+            let addend = LittleEndian::read_u32(buf);
+            LittleEndian::write_u32(buf, (target as u32).wrapping_add(addend));
+        }
+        (Arch::X32, IMAGE_REL_I386_DIR32NB) | (Arch::X64, IMAGE_REL_AMD64_ADDR32NB) => {
+            let addend = LittleEndian::read_u32(buf);
+            LittleEndian::write_u32(buf, ((target - BASE_ADDRESS) as u32).wrapping_add(addend));
+        }
+        (Arch::X64, IMAGE_REL_AMD64_ADDR64) => {
+            let addend = LittleEndian::read_u64(buf);
+            LittleEndian::write_u64(buf, target.wrapping_add(addend));
+        }
+        (Arch::X32, IMAGE_REL_I386_REL32) | (Arch::X64, IMAGE_REL_AMD64_REL32..=IMAGE_REL_AMD64_REL32_5) => {
+            // relative to the end of the fixup,
+            // plus, for REL32_1 through REL32_5, the bytes of the immediate that follows.
+            let extra = match arch {
+                Arch::X32 => 0,
+                Arch::X64 => (typ - IMAGE_REL_AMD64_REL32) as u64,
+            };
+            let addend = LittleEndian::read_u32(buf);
+            let delta = target.wrapping_sub(address + 4 + extra) as u32;
+            LittleEndian::write_u32(buf, delta.wrapping_add(addend));
+        }
+        _ => return false,
+    }
+
+    true
+}
+
+fn get_section_name(section: &goblin::pe::section_table::SectionTable) -> String {
+    match &section.real_name {
+        Some(name) => name.clone(),
+        None => String::from_utf8_lossy(&section.name[..])
+            .trim_end_matches('\u{0}')
+            .to_string(),
+    }
+}
+
+fn get_section_permissions(characteristics: u32) -> Permissions {
+    let mut perms = Permissions::empty();
+    if characteristics & IMAGE_SCN_MEM_READ > 0 {
+        perms.insert(Permissions::R);
+    }
+    if characteristics & IMAGE_SCN_MEM_WRITE > 0 {
+        perms.insert(Permissions::W);
+    }
+    if characteristics & IMAGE_SCN_MEM_EXECUTE > 0 {
+        perms.insert(Permissions::X);
+    }
+    perms
+}
+
+fn read_symbols(
+    buf: &[u8],
+    header: &goblin::pe::header::CoffHeader,
+    section_addresses: &[Option<VA>],
+) -> Result<Vec<Symbol>> {
+    let mut symbols = vec![];
+    if header.pointer_to_symbol_table == 0 || header.number_of_symbol_table == 0 {
+        return Ok(symbols);
+    }
+
+    let table = header.symbols(buf)?;
+    let strings = header.strings(buf)?;
+
+    for (index, _, symbol) in table.iter() {
+        if symbol.storage_class == IMAGE_SYM_CLASS_FILE {
+            continue;
+        }
+
+        let name = symbol.name(&strings)?.to_string();
+
+        let address = if symbol.section_number > 0 {
+            section_addresses
+                .get(symbol.section_number as usize - 1)
+                .copied()
+                .flatten()
+                .map(|section_address| section_address + symbol.value as u64)
+        } else {
+            None
+        };
+
+        debug!("coff: symbol: {}: {:?}", name, address);
+        symbols.push(Symbol {
+            index,
+            name,
+            address,
+            section_number: symbol.section_number,
+            value: symbol.value,
+            storage_class: symbol.storage_class,
+            function: symbol.typ & 0xF0 == IMAGE_SYM_DTYPE_FUNCTION,
+            external: symbol.storage_class == IMAGE_SYM_CLASS_EXTERNAL
+                || symbol.storage_class == IMAGE_SYM_CLASS_WEAK_EXTERNAL,
+            section_definition: symbol.storage_class == IMAGE_SYM_CLASS_STATIC
+                && symbol.number_of_aux_symbols > 0
+                && symbol.value == 0,
+            undefined: symbol.section_number == 0
+                && (symbol.storage_class == IMAGE_SYM_CLASS_EXTERNAL
+                    || symbol.storage_class == IMAGE_SYM_CLASS_WEAK_EXTERNAL),
+        });
+    }
+
+    Ok(symbols)
+}
+
+fn load_coff(buf: &[u8]) -> Result<Coff> {
+    if buf.len() >= 4 && LittleEndian::read_u16(buf) == 0x0 && LittleEndian::read_u16(&buf[2..]) == 0xFFFF {
+        // short import objects and /bigobj objects start with this signature.
+        // import objects are handled by `archive::ImportObject`.
+        return Err(CoffError::FormatNotSupported("import object or bigobj".to_string()).into());
+    }
+
+    let mut offset = 0;
+    let header = goblin::pe::header::CoffHeader::parse(buf, &mut offset)?;
+
+    let arch = match header.machine {
+        IMAGE_FILE_MACHINE_I386 => Arch::X32,
+        IMAGE_FILE_MACHINE_AMD64 => Arch::X64,
+        machine => return Err(CoffError::FormatNotSupported(format!("machine: {:#x}", machine)).into()),
+    };
+    debug!("coff: arch: {:?}", arch);
+
+    offset += header.size_of_optional_header as usize;
+    let section_table = header.sections(buf, &mut offset)?;
+
+    // lay out the sections one after another, each on its own page.
+    let mut sections = vec![];
+    let mut section_addresses: Vec<Option<VA>> = vec![];
+    let mut datas: Vec<Vec<u8>> = vec![];
+    let mut address = BASE_ADDRESS + 0x1000;
+    for section in section_table.iter() {
+        let name = get_section_name(section);
+
+        if section.characteristics & (IMAGE_SCN_LNK_INFO | IMAGE_SCN_LNK_REMOVE | IMAGE_SCN_MEM_DISCARDABLE) > 0 {
+            debug!("coff: section: {}: not mapped", name);
+            section_addresses.push(None);
+            continue;
+        }
+
+        let size = section.size_of_raw_data as u64;
+        let (physical_range, data) = if section.characteristics & IMAGE_SCN_CNT_UNINITIALIZED_DATA > 0 {
+            if size > MAX_UNINITIALIZED_DATA_SIZE {
+                return Err(CoffError::MalformedCoffFile(format!("section {} too large", name)).into());
+            }
+            (0..0, vec![0u8; size as usize])
+        } else {
+            let start = section.pointer_to_raw_data as u64;
+            match buf.get(start as usize..(start + size) as usize) {
+                Some(data) => (start..start + size, data.to_vec()),
+                None => {
+                    return Err(CoffError::MalformedCoffFile(format!("section {} out of bounds", name)).into());
+                }
+            }
+        };
+
+        debug!("coff: section: {} at {:#x}", name, address);
+        sections.push(Section {
+            physical_range,
+            virtual_range: address..address + size,
+            permissions: get_section_permissions(section.characteristics),
+            name,
+        });
+        section_addresses.push(Some(address));
+        datas.push(data);
+
+        address = util::align(address + size + 1, 0x1000);
+    }
+
+    let mut symbols = read_symbols(buf, &header, &section_addresses)?;
+
+    // give each referenced undefined symbol, like `__imp_ExitProcess`, a
+    // pointer-sized slot in a synthetic section, so that references to it
+    // resolve to a unique address, like imports do in a linked image.
+    let mut referenced: BTreeSet<usize> = Default::default();
+    for section in section_table.iter() {
+        referenced.extend(section.relocations(buf)?.map(|reloc| reloc.symbol_table_index as usize));
+    }
+    let pointer_size = arch.pointer_size() as u64;
+    let mut externs = 0u64;
+    for symbol in symbols.iter_mut() {
+        if symbol.undefined && referenced.contains(&symbol.index) {
+            symbol.address = Some(address + externs * pointer_size);
+            externs += 1;
+        }
+    }
+    if externs > 0 {
+        let size = externs * pointer_size;
+        debug!("coff: section: {} at {:#x}", EXTERNS_SECTION_NAME, address);
+        sections.push(Section {
+            physical_range: 0..0,
+            virtual_range:  address..address + size,
+            permissions:    Permissions::R,
+            name:           EXTERNS_SECTION_NAME.to_string(),
+        });
+        datas.push(vec![0u8; size as usize]);
+        address = util::align(address + size + 1, 0x1000);
+    }
+
+    let mut relocations = vec![];
+    let mut data_index = 0;
+    for (section, section_address) in section_table.iter().zip(section_addresses.iter()) {
+        let section_address = match section_address {
+            Some(section_address) => *section_address,
+            None => continue,
+        };
+        let data = &mut datas[data_index];
+        data_index += 1;
+
+        for reloc in section.relocations(buf)? {
+            let offset = reloc.virtual_address.wrapping_sub(section.virtual_address) as usize;
+            let size = get_relocation_size(arch, reloc.typ);
+            let address = section_address + offset as RVA;
+
+            let target = symbols
+                .binary_search_by_key(&(reloc.symbol_table_index as usize), |symbol| symbol.index)
+                .ok()
+                .and_then(|i| symbols[i].address);
+
+            let applied = match (target, data.get_mut(offset..offset + size)) {
+                (Some(target), Some(fixup)) => apply_relocation(arch, reloc.typ, address, target, fixup),
+                _ => false,
+            };
+
+            debug!(
+                "coff: relocation: {:#x} type {:#x} applied: {}",
+                address, reloc.typ, applied
+            );
+            relocations.push(Relocation {
+                address,
+                symbol: reloc.symbol_table_index as usize,
+                typ: reloc.typ,
+                size,
+                applied,
+            });
+        }
+    }
+    relocations.sort_by_key(|reloc| reloc.address);
+
+    // writezx may pad out an extra page.
+    let capacity = address - BASE_ADDRESS + 0x1000;
+    let mut address_space = RelativeAddressSpace::with_capacity(capacity);
+    for (section, data) in sections.iter().zip(datas.iter()) {
+        address_space
+            .map
+            .writezx(section.virtual_range.start - BASE_ADDRESS, data)?;
+        debug!(
+            "coff: address space: mapped {:#x} - {:#x} {:?}",
+            section.virtual_range.start, section.virtual_range.end, section.permissions
+        );
+    }
+
+    let module = Module {
+        arch,
+        sections,
+        address_space: address_space.into_absolute(BASE_ADDRESS)?,
+    };
+
+    debug!("coff: loaded");
+    Ok(Coff {
+        buf: buf.to_vec(),
+        module,
+        symbols,
+        relocations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{aspace::AddressSpace, loader::coff::*, module::Permissions, rsrc::*};
+    use anyhow::Result;
+
+    #[test]
+    fn base_address() -> Result<()> {
+        let buf = get_buf(Rsrc::COFF);
+        let coff = Coff::from_bytes(&buf)?;
+
+        assert!(matches!(coff.module.arch, Arch::X64));
+        assert_eq!(BASE_ADDRESS, coff.module.address_space.base_address);
+
+        // .text, .data, .bss, .externs
+        let names: Vec<_> = coff
+            .module
+            .sections
+            .iter()
+            .map(|section| section.name.as_str())
+            .collect();
+        assert_eq!(vec![".text", ".data", ".bss", EXTERNS_SECTION_NAME], names);
+        assert_eq!(1, coff.executable_sections().count());
+
+        // sub rsp, 0x28
+        assert_eq!(0x48, coff.module.address_space.read_u8(0x40_1000)?);
+        assert!(coff.module.probe_va(0x40_1000, Permissions::X));
+        assert_eq!(
+            b"hello world".to_vec(),
+            coff.module.address_space.read_bytes(0x40_2000, 11)?
+        );
+        // .bss is mapped, but not backed by the file.
+        assert_eq!(0x0, coff.module.address_space.read_u8(0x40_30FF)?);
+        assert!(coff.module.probe_va(0x40_30FF, Permissions::W));
+
+        Ok(())
+    }
+
+    #[test]
+    fn symbols() -> Result<()> {
+        let buf = get_buf(Rsrc::COFF);
+        let coff = Coff::from_bytes(&buf)?;
+
+        let get = |name: &str| coff.symbols.iter().find(|symbol| symbol.name == name).unwrap();
+
+        assert_eq!(Some(0x40_1000), get("main").address);
+        assert!(get("main").function);
+        assert!(get("main").external);
+        assert_eq!(Some(0x40_101B), get("helper").address);
+        assert!(!get("helper").external);
+        assert!(get(".text").section_definition);
+        assert_eq!(Some(0x40_200C), get("counter").address);
+        assert_eq!(Some(0x40_3000), get("buffer").address);
+
+        assert!(get("__imp_ExitProcess").undefined);
+        assert_eq!(Some(0x40_4000), get("__imp_ExitProcess").address);
+
+        let main = get("main");
+        assert_eq!(main.name, coff.get_symbol(main.index).unwrap().name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn relocations() -> Result<()> {
+        let buf = get_buf(Rsrc::COFF);
+        let coff = Coff::from_bytes(&buf)?;
+
+        assert_eq!(5, coff.relocations.len());
+        assert!(coff.relocations.iter().all(|reloc| reloc.applied));
+
+        // call add
+        let call = coff.relocations[0].address;
+        assert_eq!(0x40_1005, call);
+        let disp = coff.module.address_space.read_u32(call)? as i32;
+        assert_eq!(0x40_1017, (call + 4).wrapping_add(disp as i64 as u64));
+
+        // call [__imp_ExitProcess]
+        let disp = coff.module.address_space.read_u32(0x40_1012)? as i32;
+        assert_eq!(0x40_4000, (0x40_1016u64).wrapping_add(disp as i64 as u64));
+
+        // counter: dq main
+        assert_eq!(0x40_1000, coff.module.address_space.read_u64(0x40_200C)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<()> {
+        assert!(Coff::from_bytes(b"").is_err());
+        assert!(Coff::from_bytes(&[0u8; 0x40]).is_err());
+
+        // truncated in the middle of the .text section.
+        let buf = get_buf(Rsrc::COFF);
+        assert!(Coff::from_bytes(&buf[..0xA0]).is_err());
+
+        // .bss SizeOfRawData, which isn't backed by the file.
+        let mut buf = get_buf(Rsrc::COFF);
+        buf[0x74..0x78].copy_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+        assert!(Coff::from_bytes(&buf).is_err());
+
+        Ok(())
+    }
+}
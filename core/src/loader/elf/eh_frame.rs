@@ -0,0 +1,378 @@
+//! Parse the call frame information (`.eh_frame`) used for exception handling
+//! and stack unwinding.
+//!
+//! The section is a sequence of records, each either a Common Information
+//! Entry (CIE) or a Frame Description Entry (FDE). Each FDE describes the
+//! unwind rules for a range of code, typically a function, and references a
+//! CIE that describes, among other things, how the FDE encodes addresses:
+//!
+//! ```text
+//! CIE                                 FDE
+//!   length       u32                    length          u32
+//!   CIE id       u32 (0)                CIE pointer     u32 (relative)
+//!   version      u8                     PC begin        encoded
+//!   augmentation asciiz, like "zR"      PC range        encoded
+//!   code align   uleb128                ...
+//!   data align   sleb128
+//!   return reg   uleb128
+//!   aug. data    (when "z")
+//!   ...
+//! ```
+//!
+//! We only parse enough to recover the code ranges, which make good function
+//! start candidates, even in stripped files.
+//! When the section headers are missing, the `.eh_frame_hdr` segment
+//! (`PT_GNU_EH_FRAME`) points to the `.eh_frame` data.
+//!
+//! references:
+//!   - https://refspecs.linuxfoundation.org/LSB_5.0.0/LSB-Core-generic/LSB-Core-generic/ehframechpt.html
+//!   - https://refspecs.linuxfoundation.org/LSB_5.0.0/LSB-Core-generic/LSB-Core-generic/dwarfext.html
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::{aspace::AddressSpace, loader::elf::ELF, util, VA};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameDescriptionEntry {
+    /// the code described by this entry.
+    pub range: std::ops::Range<VA>,
+}
+
+const DW_EH_PE_ABSPTR: u8 = 0x00;
+const DW_EH_PE_ULEB128: u8 = 0x01;
+const DW_EH_PE_UDATA2: u8 = 0x02;
+const DW_EH_PE_UDATA4: u8 = 0x03;
+const DW_EH_PE_UDATA8: u8 = 0x04;
+const DW_EH_PE_SLEB128: u8 = 0x09;
+const DW_EH_PE_SDATA2: u8 = 0x0A;
+const DW_EH_PE_SDATA4: u8 = 0x0B;
+const DW_EH_PE_SDATA8: u8 = 0x0C;
+
+const DW_EH_PE_PCREL: u8 = 0x10;
+const DW_EH_PE_DATAREL: u8 = 0x30;
+
+const DW_EH_PE_OMIT: u8 = 0xFF;
+
+fn malformed(reason: &str) -> anyhow::Error {
+    crate::loader::elf::ELFError::MalformedELFFile(format!("eh_frame: {}", reason)).into()
+}
+
+fn read_bytes<'a>(buf: &'a [u8], offset: &mut usize, size: usize) -> Result<&'a [u8]> {
+    let bytes = buf
+        .get(*offset..*offset + size)
+        .ok_or_else(|| malformed("buffer too small"))?;
+    *offset += size;
+    Ok(bytes)
+}
+
+fn read_u8(buf: &[u8], offset: &mut usize) -> Result<u8> {
+    Ok(read_bytes(buf, offset, 1)?[0])
+}
+
+fn read_uleb128(buf: &[u8], offset: &mut usize) -> Result<u64> {
+    util::read_uleb128(buf, offset).ok_or_else(|| malformed("buffer too small"))
+}
+
+fn read_sleb128(buf: &[u8], offset: &mut usize) -> Result<i64> {
+    util::read_sleb128(buf, offset).ok_or_else(|| malformed("buffer too small"))
+}
+
+/// read a pointer with the given `DW_EH_PE_*` encoding.
+/// `address` is the virtual address of `buf`, used for PC-relative pointers,
+/// and `data_address` is used for data-relative pointers.
+fn read_encoded_pointer(
+    buf: &[u8],
+    address: VA,
+    data_address: Option<VA>,
+    offset: &mut usize,
+    encoding: u8,
+    psize: usize,
+) -> Result<u64> {
+    let field_address = address + *offset as u64;
+
+    let value = match encoding & 0x0F {
+        DW_EH_PE_ABSPTR => match psize {
+            4 => LittleEndian::read_u32(read_bytes(buf, offset, 4)?) as u64,
+            _ => LittleEndian::read_u64(read_bytes(buf, offset, 8)?),
+        },
+        DW_EH_PE_ULEB128 => read_uleb128(buf, offset)?,
+        DW_EH_PE_UDATA2 => LittleEndian::read_u16(read_bytes(buf, offset, 2)?) as u64,
+        DW_EH_PE_UDATA4 => LittleEndian::read_u32(read_bytes(buf, offset, 4)?) as u64,
+        DW_EH_PE_UDATA8 => LittleEndian::read_u64(read_bytes(buf, offset, 8)?),
+        DW_EH_PE_SLEB128 => read_sleb128(buf, offset)? as u64,
+        DW_EH_PE_SDATA2 => LittleEndian::read_i16(read_bytes(buf, offset, 2)?) as i64 as u64,
+        DW_EH_PE_SDATA4 => LittleEndian::read_i32(read_bytes(buf, offset, 4)?) as i64 as u64,
+        DW_EH_PE_SDATA8 => LittleEndian::read_i64(read_bytes(buf, offset, 8)?) as u64,
+        _ => return Err(malformed("unsupported pointer format")),
+    };
+
+    let value = match encoding & 0x70 {
+        0x00 => value,
+        DW_EH_PE_PCREL => field_address.wrapping_add(value),
+        DW_EH_PE_DATAREL => match data_address {
+            Some(data_address) => data_address.wrapping_add(value),
+            None => return Err(malformed("unsupported pointer application")),
+        },
+        _ => return Err(malformed("unsupported pointer application")),
+    };
+
+    if psize == 4 {
+        Ok(value & 0xFFFF_FFFF)
+    } else {
+        Ok(value)
+    }
+}
+
+/// parse the CIE at the given offset (just after the CIE id),
+/// returning the encoding of the FDE pointers.
+fn parse_cie(buf: &[u8], address: VA, mut offset: usize, psize: usize) -> Result<u8> {
+    let version = read_u8(buf, &mut offset)?;
+
+    let augmentation_start = offset;
+    let augmentation_len = buf[offset..]
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| malformed("unterminated augmentation"))?;
+    let augmentation = &buf[augmentation_start..augmentation_start + augmentation_len];
+    offset += augmentation_len + 1;
+
+    if augmentation.starts_with(b"eh") {
+        // GCC 2.x: the address of the exception table.
+        read_bytes(buf, &mut offset, psize)?;
+    }
+
+    // code alignment factor
+    read_uleb128(buf, &mut offset)?;
+    // data alignment factor
+    read_sleb128(buf, &mut offset)?;
+    // return address register
+    if version == 1 {
+        read_u8(buf, &mut offset)?;
+    } else {
+        read_uleb128(buf, &mut offset)?;
+    }
+
+    let mut fde_encoding = DW_EH_PE_ABSPTR;
+    if let Some(augmentation) = augmentation.strip_prefix(b"z") {
+        // augmentation data length
+        read_uleb128(buf, &mut offset)?;
+
+        for c in augmentation.iter() {
+            match c {
+                b'R' => fde_encoding = read_u8(buf, &mut offset)?,
+                b'L' => {
+                    // LSDA encoding
+                    read_u8(buf, &mut offset)?;
+                }
+                b'P' => {
+                    // personality routine
+                    let encoding = read_u8(buf, &mut offset)?;
+                    read_encoded_pointer(buf, address, None, &mut offset, encoding & 0x7F, psize)?;
+                }
+                b'S' | b'B' => {}
+                // the remaining augmentation data is unknown,
+                // but isn't needed since we've found the FDE encoding, if any.
+                _ => break,
+            }
+        }
+    }
+
+    Ok(fde_encoding)
+}
+
+/// parse the FDEs from the given `.eh_frame` data,
+/// which is found at the given virtual address.
+pub fn parse_eh_frame(buf: &[u8], address: VA, psize: usize) -> Result<Vec<FrameDescriptionEntry>> {
+    let mut fdes = vec![];
+    // from CIE offset to FDE pointer encoding.
+    let mut cies: BTreeMap<usize, u8> = Default::default();
+
+    let mut offset = 0usize;
+    while offset + 4 <= buf.len() {
+        let record_offset = offset;
+
+        let mut length = LittleEndian::read_u32(read_bytes(buf, &mut offset, 4)?) as u64;
+        if length == 0 {
+            // terminator
+            break;
+        } else if length == 0xFFFF_FFFF {
+            length = LittleEndian::read_u64(read_bytes(buf, &mut offset, 8)?);
+        }
+
+        let record_end = (offset as u64)
+            .checked_add(length)
+            .filter(|&end| end <= buf.len() as u64)
+            .ok_or_else(|| malformed("record out of bounds"))? as usize;
+
+        let id_offset = offset;
+        let id = LittleEndian::read_u32(read_bytes(buf, &mut offset, 4)?) as usize;
+
+        if id == 0 {
+            cies.insert(record_offset, parse_cie(buf, address, offset, psize)?);
+        } else {
+            // the CIE pointer is relative to the pointer field.
+            let cie_offset = id_offset
+                .checked_sub(id)
+                .ok_or_else(|| malformed("CIE pointer out of bounds"))?;
+
+            let encoding = match cies.get(&cie_offset) {
+                Some(&encoding) => encoding,
+                None => {
+                    // CIEs typically precede their FDEs, but aren't required to.
+                    let mut cie_id_offset = cie_offset + 4;
+                    if buf.get(cie_offset..cie_offset + 4) == Some(&[0xFF, 0xFF, 0xFF, 0xFF]) {
+                        cie_id_offset += 8;
+                    }
+                    let encoding = parse_cie(buf, address, cie_id_offset + 4, psize)?;
+                    cies.insert(cie_offset, encoding);
+                    encoding
+                }
+            };
+
+            if encoding != DW_EH_PE_OMIT {
+                let start = read_encoded_pointer(buf, address, None, &mut offset, encoding, psize)?;
+                // the range is a size, so only the format applies.
+                let size = read_encoded_pointer(buf, address, None, &mut offset, encoding & 0x0F, psize)?;
+
+                debug!("elf: eh_frame: FDE: {:#x} - {:#x}", start, start.wrapping_add(size));
+                fdes.push(FrameDescriptionEntry {
+                    range: start..start.wrapping_add(size),
+                });
+            }
+        }
+
+        offset = record_end;
+    }
+
+    Ok(fdes)
+}
+
+/// find the address and size of the `.eh_frame` data.
+/// prefer the section header, though fall back to the `.eh_frame_hdr` segment,
+/// in which case the size is the remainder of the containing segment.
+fn get_eh_frame_range(elf: &ELF, e: &goblin::elf::Elf) -> Result<Option<std::ops::Range<VA>>> {
+    for shdr in e.section_headers.iter() {
+        if e.shdr_strtab.get_at(shdr.sh_name) == Some(".eh_frame") && shdr.sh_addr != 0 {
+            return Ok(elf.section_range(shdr));
+        }
+    }
+
+    let phdr = match e
+        .program_headers
+        .iter()
+        .find(|phdr| phdr.p_type == goblin::elf::program_header::PT_GNU_EH_FRAME)
+    {
+        Some(phdr) => phdr,
+        None => return Ok(None),
+    };
+
+    // u8     version
+    // u8     eh_frame_ptr_enc
+    // u8     fde_count_enc
+    // u8     table_enc
+    // enc    eh_frame_ptr
+    let hdr_address = phdr.p_vaddr;
+    let hdr = elf.module.address_space.read_bytes(hdr_address, 0x4 + 0x8)?;
+    if hdr[0] != 1 {
+        return Err(malformed("unsupported eh_frame_hdr version"));
+    }
+
+    let mut offset = 4;
+    let eh_frame_address = read_encoded_pointer(
+        &hdr,
+        hdr_address,
+        Some(hdr_address),
+        &mut offset,
+        hdr[1],
+        elf.module.arch.pointer_size(),
+    )?;
+
+    match elf
+        .module
+        .sections
+        .iter()
+        .find(|section| section.virtual_range.contains(&eh_frame_address))
+    {
+        Some(section) => Ok(Some(eh_frame_address..section.virtual_range.end)),
+        None => Ok(None),
+    }
+}
+
+/// read the FDEs from the `.eh_frame` data of the given ELF.
+pub fn read_eh_frame(elf: &ELF) -> Result<Vec<FrameDescriptionEntry>> {
+    let e = elf.elf()?;
+
+    let range = match get_eh_frame_range(elf, &e)? {
+        Some(range) => range,
+        None => return Ok(vec![]),
+    };
+    debug!("elf: eh_frame: {:#x} - {:#x}", range.start, range.end);
+
+    let buf = elf
+        .module
+        .address_space
+        .read_bytes(range.start, (range.end - range.start) as usize)?;
+
+    parse_eh_frame(&buf, range.start, elf.module.arch.pointer_size())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::elf::eh_frame::*, rsrc::*};
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::HELLO);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        let fdes = read_eh_frame(&elf)?;
+        assert_eq!(6, fdes.len());
+        // _start
+        assert_eq!(0x10B0..0x10D2, fdes[0].range);
+        // .plt
+        assert_eq!(0x1020..0x10A0, fdes[1].range);
+        // main
+        assert_eq!(0x11DE..0x1248, fdes[5].range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hello_invalid_section_size() -> Result<()> {
+        // .eh_frame sh_size, which is clamped to the containing segment.
+        let mut buf = get_buf(Rsrc::HELLO);
+        buf[0x3660..0x3668].copy_from_slice(&0x7FFF_FFFF_FFFFu64.to_le_bytes());
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+        assert_eq!(6, read_eh_frame(&elf)?.len());
+
+        // .eh_frame sh_size, which overflows the address.
+        let mut buf = get_buf(Rsrc::HELLO);
+        buf[0x3660..0x3668].copy_from_slice(&u64::MAX.to_le_bytes());
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+        assert_eq!(0, read_eh_frame(&elf)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn hello_without_section_headers() -> Result<()> {
+        let mut buf = get_buf(Rsrc::HELLO);
+        // e_shoff, e_shnum, e_shstrndx
+        buf[0x28..0x30].copy_from_slice(&[0x0; 8]);
+        buf[0x3C..0x40].copy_from_slice(&[0x0; 4]);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+        assert_eq!(0, elf.elf()?.section_headers.len());
+
+        // found via .eh_frame_hdr instead.
+        let fdes = read_eh_frame(&elf)?;
+        assert_eq!(6, fdes.len());
+        assert_eq!(0x11DE..0x1248, fdes[5].range);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+#![allow(clippy::nonstandard_macro_braces)] // clippy bug, see https://github.com/rust-lang/rust-clippy/issues/7434
+
+use anyhow::Result;
+use log::debug;
+use thiserror::Error;
+
+pub mod eh_frame;
+pub mod reloc;
+pub mod symbols;
+
+use crate::{
+    arch::Arch,
+    aspace::RelativeAddressSpace,
+    module::{Module, Permissions, Section},
+    util, VA,
+};
+
+#[derive(Error, Debug)]
+pub enum ELFError {
+    #[error("format not supported: {0}")]
+    FormatNotSupported(String),
+
+    #[error("malformed ELF file: {0}")]
+    MalformedELFFile(String),
+}
+
+const PAGE_SIZE: u64 = 0x1000;
+
+/// the largest span of memory covered by the segments,
+/// like the 32-bit `SizeOfImage` of a PE file.
+const MAX_IMAGE_SIZE: u64 = 0xFFFF_F000;
+
+/// A parsed and loaded ELF file.
+/// The `buf` field contains the raw data.
+/// The `module` field contains an address space as the ELF would be loaded.
+///
+/// Segments are mapped at their virtual addresses, so position independent
+/// files (executables and shared objects) are loaded at base address 0x0.
+pub struct ELF {
+    pub buf:    Vec<u8>,
+    pub module: Module,
+    pub header: goblin::elf::header::Header,
+}
+
+impl ELF {
+    pub fn from_bytes(buf: &[u8]) -> Result<ELF> {
+        load_elf(buf)
+    }
+
+    /// the address of the entry point, if present.
+    /// shared objects typically don't have one.
+    pub fn entry_point(&self) -> Option<VA> {
+        match self.header.e_entry {
+            0 => None,
+            entry => Some(entry),
+        }
+    }
+
+    /// Parse the dynamic symbol table. See `symbols::read_dynamic_symbols`.
+    pub fn get_dynamic_symbols(&self) -> Result<Vec<symbols::Symbol>> {
+        symbols::read_dynamic_symbols(self)
+    }
+
+    /// Parse the PLT and GOT relocations. See `reloc::read_import_relocations`.
+    pub fn get_import_relocations(&self) -> Result<Vec<reloc::Relocation>> {
+        reloc::read_import_relocations(self)
+    }
+
+    pub fn executable_sections<'b>(&'b self) -> Box<dyn Iterator<Item = &'b Section> + 'b> {
+        Box::new(
+            self.module
+                .sections
+                .iter()
+                .filter(|section| section.permissions.intersects(Permissions::X)),
+        )
+    }
+
+    pub fn elf(&self) -> Result<goblin::elf::Elf<'_>> {
+        get_elf(&self.buf)
+    }
+
+    /// the addresses of the given section,
+    /// clamped to the segment that contains its start,
+    /// since the size comes from the header and may be bogus.
+    /// None when the section isn't loaded.
+    pub fn section_range(&self, shdr: &goblin::elf::SectionHeader) -> Option<std::ops::Range<VA>> {
+        let end = shdr.sh_addr.checked_add(shdr.sh_size)?;
+        let segment = self
+            .module
+            .sections
+            .iter()
+            .find(|section| section.virtual_range.contains(&shdr.sh_addr))?;
+
+        Some(shdr.sh_addr..std::cmp::min(end, segment.virtual_range.end))
+    }
+}
+
+fn get_elf(buf: &[u8]) -> Result<goblin::elf::Elf<'_>> {
+    let elf = goblin::elf::Elf::parse(buf)?;
+
+    if !elf.little_endian {
+        return Err(ELFError::FormatNotSupported("big endian".to_string()).into());
+    }
+
+    match elf.header.e_type {
+        goblin::elf::header::ET_EXEC | goblin::elf::header::ET_DYN => {}
+        goblin::elf::header::ET_REL => {
+            return Err(ELFError::FormatNotSupported("relocatable object file".to_string()).into());
+        }
+        e_type => {
+            return Err(ELFError::FormatNotSupported(format!("file type: {:#x}", e_type)).into());
+        }
+    }
+
+    Ok(elf)
+}
+
+/// Segment is executable.
+const PF_X: u32 = 0x1;
+
+/// Segment is writable.
+const PF_W: u32 = 0x2;
+
+/// Segment is readable.
+const PF_R: u32 = 0x4;
+
+fn load_elf_segment(index: usize, phdr: &goblin::elf::ProgramHeader) -> Result<Section> {
+    let mut perms = Permissions::empty();
+    if phdr.p_flags & PF_R > 0 {
+        perms.insert(Permissions::R);
+    }
+    if phdr.p_flags & PF_W > 0 {
+        perms.insert(Permissions::W);
+    }
+    if phdr.p_flags & PF_X > 0 {
+        perms.insert(Permissions::X);
+    }
+
+    // segments don't have names, so use the convention of other tools.
+    let name = format!("LOAD{}", index);
+    debug!("elf: segment: {} at {:#x}", name, phdr.p_vaddr);
+
+    let (pend, vend) = match (
+        phdr.p_offset.checked_add(phdr.p_filesz),
+        phdr.p_vaddr.checked_add(phdr.p_memsz),
+    ) {
+        (Some(pend), Some(vend)) => (pend, vend),
+        _ => return Err(ELFError::MalformedELFFile(format!("segment {} out of bounds", name)).into()),
+    };
+
+    Ok(Section {
+        physical_range: std::ops::Range {
+            start: phdr.p_offset,
+            end:   pend,
+        },
+        virtual_range: std::ops::Range {
+            start: phdr.p_vaddr,
+            end:   vend,
+        },
+        permissions: perms,
+        name,
+    })
+}
+
+// references:
+//   - https://refspecs.linuxfoundation.org/elf/gabi4+/ch5.pheader.html
+//   - https://refspecs.linuxfoundation.org/elf/elf.pdf
+fn load_elf(buf: &[u8]) -> Result<ELF> {
+    let elf = get_elf(buf)?;
+
+    let arch = match elf.header.e_machine {
+        goblin::elf::header::EM_386 => Arch::X32,
+        goblin::elf::header::EM_X86_64 => Arch::X64,
+        e_machine => {
+            return Err(ELFError::FormatNotSupported(format!(
+                "machine: {}",
+                goblin::elf::header::machine_to_str(e_machine)
+            ))
+            .into())
+        }
+    };
+    debug!("elf: arch: {:?}", arch);
+
+    let sections: Vec<Section> = elf
+        .program_headers
+        .iter()
+        .filter(|phdr| phdr.p_type == goblin::elf::program_header::PT_LOAD)
+        .enumerate()
+        .map(|(i, phdr)| load_elf_segment(i, phdr))
+        .collect::<Result<_>>()?;
+
+    if sections.is_empty() {
+        return Err(ELFError::MalformedELFFile("no loadable segments".to_string()).into());
+    }
+
+    for section in sections.iter() {
+        if section.physical_range.end > buf.len() as u64 {
+            return Err(ELFError::MalformedELFFile(format!("segment {} out of bounds", section.name)).into());
+        }
+    }
+
+    // segments are not necessarily page aligned,
+    // though they are congruent to their file offset modulo the page size.
+    let min_address = sections.iter().map(|sec| sec.virtual_range.start).min().unwrap();
+    let base_address = min_address - (min_address % PAGE_SIZE);
+    debug!("elf: base address: {:#x}", base_address);
+
+    // the address space is dense, so segments that are far apart,
+    // like at the start and end of memory, would require a huge allocation.
+    let max_address = sections.iter().map(|sec| sec.virtual_range.end).max().unwrap();
+    if max_address - base_address > MAX_IMAGE_SIZE {
+        return Err(ELFError::MalformedELFFile("segments too far apart".to_string()).into());
+    }
+    let max_page_address = util::align(max_address - base_address, PAGE_SIZE);
+    debug!("elf: address space: capacity: {:#x}", max_page_address);
+
+    let mut address_space = RelativeAddressSpace::with_capacity(max_page_address);
+
+    for section in sections.iter() {
+        // the section range contains VAs,
+        // while we're writing to the RelativeAddressSpace.
+        // so shift down by `base_address`.
+        let rstart = section.virtual_range.start - base_address;
+        let rend = section.virtual_range.end - base_address;
+
+        // adjacent segments may share a page, such as the end of .text and the start
+        // of .data, so only map the pages that aren't already present.
+        let mut page = rstart - (rstart % PAGE_SIZE);
+        while page < rend {
+            if !address_space.map.probe(page) {
+                address_space.map.map_empty(page, PAGE_SIZE as usize)?;
+            }
+            page += PAGE_SIZE;
+        }
+
+        // when the file size is smaller than the memory size, such as for .bss,
+        // the remainder is zero-filled.
+        let vsize = section.virtual_range.end - section.virtual_range.start;
+        let psize = std::cmp::min(section.physical_range.end - section.physical_range.start, vsize);
+        let pstart = section.physical_range.start as usize;
+        address_space.write_into(rstart, &buf[pstart..pstart + psize as usize])?;
+
+        debug!(
+            "elf: address space: mapped {:#x} - {:#x} {:?}",
+            section.virtual_range.start, section.virtual_range.end, section.permissions
+        );
+    }
+
+    let module = Module {
+        arch,
+        sections,
+        address_space: address_space.into_absolute(base_address)?,
+    };
+
+    debug!("elf: loaded");
+    Ok(ELF {
+        buf: buf.to_vec(),
+        module,
+        header: elf.header,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{aspace::AddressSpace, module::Permissions, rsrc::*};
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::HELLO);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        assert!(matches!(elf.module.arch, crate::arch::Arch::X64));
+        assert_eq!(0x0, elf.module.address_space.base_address);
+        assert_eq!(Some(0x10B0), elf.entry_point());
+
+        assert_eq!(4, elf.module.sections.len());
+        assert_eq!("LOAD1", elf.module.sections[1].name);
+        assert_eq!(Permissions::RX, elf.module.sections[1].permissions);
+        assert_eq!(0x1000..0x1251, elf.module.sections[1].virtual_range);
+        assert_eq!(1, elf.executable_sections().count());
+
+        // .data and .bss, mapped to the end of the page.
+        let data = &elf.module.sections[3];
+        assert_eq!(Permissions::RW, data.permissions);
+        assert_eq!(0x3DD0..0x4050, data.virtual_range);
+        assert_eq!(0x2DD0, elf.module.file_offset(0x3DD0)?);
+
+        // ELF header
+        assert_eq!(b"\x7FELF", &elf.module.address_space.read_bytes(0x0, 4)?[..]);
+        // _start: `xor ebp, ebp`
+        assert_eq!(&[0x31, 0xED], &elf.module.address_space.read_bytes(0x10B0, 2)?[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_segments() -> Result<()> {
+        use byteorder::{ByteOrder, LittleEndian};
+
+        // patch the last PT_LOAD segment, at the given field offset.
+        let patch = |field: usize, value: u64| -> Vec<u8> {
+            let mut buf = get_buf(Rsrc::HELLO);
+            let phoff = LittleEndian::read_u64(&buf[0x20..]) as usize;
+            let phentsize = LittleEndian::read_u16(&buf[0x36..]) as usize;
+            let phnum = LittleEndian::read_u16(&buf[0x38..]) as usize;
+            let phdr = (0..phnum)
+                .map(|i| phoff + i * phentsize)
+                .rfind(|&phdr| LittleEndian::read_u32(&buf[phdr..]) == goblin::elf::program_header::PT_LOAD)
+                .unwrap();
+            LittleEndian::write_u64(&mut buf[phdr + field..], value);
+            buf
+        };
+
+        // p_vaddr, far from the other segments.
+        assert!(crate::loader::elf::ELF::from_bytes(&patch(0x10, 0x7FFF_FFFF_FDD0)).is_err());
+        assert!(crate::loader::elf::ELF::from_bytes(&patch(0x10, 0xFFFF_FFFF_FFFF_FDD0)).is_err());
+        // p_filesz
+        assert!(crate::loader::elf::ELF::from_bytes(&patch(0x20, u64::MAX)).is_err());
+        // p_memsz
+        assert!(crate::loader::elf::ELF::from_bytes(&patch(0x28, u64::MAX)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        assert!(crate::loader::elf::ELF::from_bytes(&buf).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+//! Parse the relocations that bind imported symbols into the GOT.
+//!
+//! Code references an imported symbol via its slot in the Global Offset Table
+//! (GOT), which the dynamic loader fills in with the resolved address:
+//!
+//!   - `R_*_JUMP_SLOT` relocations, from `.rela.plt`/`.rel.plt`, describe the
+//!     GOT slots used by the Procedure Linkage Table (PLT) stubs. With lazy
+//!     binding, they initially point back into the PLT.
+//!   - `R_*_GLOB_DAT` relocations, from `.rela.dyn`/`.rel.dyn`, describe the
+//!     GOT slots referenced directly, like `call [rip+puts@GOTPCREL]`, and by
+//!     `.plt.got` stubs.
+//!
+//! Conveniently, these relocation types have the same values on i386 and
+//! x86-64.
+//!
+//! references:
+//!   - https://refspecs.linuxfoundation.org/elf/gabi4+/ch5.dynamic.html
+//!   - https://www.airs.com/blog/archives/41
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    loader::elf::{
+        symbols::{read_dynamic_symbols, Symbol},
+        ELF,
+    },
+    VA,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelocationKind {
+    JumpSlot,
+    GlobalData,
+}
+
+#[derive(Clone, Debug)]
+pub struct Relocation {
+    /// the address of the GOT slot.
+    pub address: VA,
+    pub kind:    RelocationKind,
+    pub symbol:  Symbol,
+}
+
+fn get_relocation_kind(r_type: u32) -> Option<RelocationKind> {
+    // R_386_JMP_SLOT == R_X86_64_JUMP_SLOT
+    // R_386_GLOB_DAT == R_X86_64_GLOB_DAT
+    match r_type {
+        goblin::elf::reloc::R_X86_64_JUMP_SLOT => Some(RelocationKind::JumpSlot),
+        goblin::elf::reloc::R_X86_64_GLOB_DAT => Some(RelocationKind::GlobalData),
+        _ => None,
+    }
+}
+
+/// read the PLT and GOT relocations that reference dynamic symbols,
+/// ordered by address.
+pub fn read_import_relocations(elf: &ELF) -> Result<Vec<Relocation>> {
+    let e = elf.elf()?;
+    let symbols = read_dynamic_symbols(elf)?;
+
+    let mut relocations = vec![];
+    for reloc in e.pltrelocs.iter().chain(e.dynrelas.iter()).chain(e.dynrels.iter()) {
+        let kind = match get_relocation_kind(reloc.r_type) {
+            Some(kind) => kind,
+            None => continue,
+        };
+
+        // symbol zero is the null symbol, which isn't in `symbols`.
+        let symbol = match reloc.r_sym.checked_sub(1).and_then(|i| symbols.get(i)) {
+            Some(symbol) => symbol.clone(),
+            None => continue,
+        };
+
+        debug!("elf: relocation: {:#x}: {:?} {}", reloc.r_offset, kind, symbol.name);
+        relocations.push(Relocation {
+            address: reloc.r_offset,
+            kind,
+            symbol,
+        });
+    }
+
+    relocations.sort_unstable_by_key(|reloc| reloc.address);
+
+    Ok(relocations)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::elf::reloc::*, rsrc::*};
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::HELLO);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        let relocs = read_import_relocations(&elf)?;
+        assert_eq!(12, relocs.len());
+        assert_eq!(
+            7,
+            relocs
+                .iter()
+                .filter(|reloc| reloc.kind == RelocationKind::JumpSlot)
+                .count()
+        );
+
+        let puts = relocs.iter().find(|reloc| reloc.symbol.name == "puts").unwrap();
+        assert_eq!(0x4008, puts.address);
+        assert_eq!(RelocationKind::JumpSlot, puts.kind);
+
+        let cxa_finalize = relocs
+            .iter()
+            .find(|reloc| reloc.symbol.name == "__cxa_finalize")
+            .unwrap();
+        assert_eq!(0x3FE0, cxa_finalize.address);
+        assert_eq!(RelocationKind::GlobalData, cxa_finalize.kind);
+
+        Ok(())
+    }
+}
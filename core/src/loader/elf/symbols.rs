@@ -0,0 +1,161 @@
+//! Parse the dynamic symbol table (`.dynsym`).
+//!
+//! The dynamic symbols describe both the functions and data that the module
+//! exports, and those that it imports from other modules.
+//! Imported symbols are undefined (section index `SHN_UNDEF`), and are bound
+//! to a library by name only at runtime. When the module uses symbol
+//! versioning, then the version requirements (`.gnu.version_r`) name the
+//! library that provides each version, like `puts@GLIBC_2.2.5` from
+//! `libc.so.6`.
+//!
+//! references:
+//!   - https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.symtab.html
+//!   - https://refspecs.linuxbase.org/LSB_5.0.0/LSB-Core-generic/LSB-Core-generic/symversion.html
+use anyhow::Result;
+use log::debug;
+
+use crate::{loader::elf::ELF, VA};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Object,
+    Other,
+}
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    /// the index of the symbol in the dynamic symbol table,
+    /// which is how relocations reference it.
+    pub index:   usize,
+    pub name:    String,
+    /// zero for imported symbols.
+    pub address: VA,
+    pub size:    u64,
+    pub kind:    SymbolKind,
+    /// the symbol is undefined in this module, and resolved at runtime.
+    pub import:  bool,
+    /// the symbol may be overridden by a global symbol of the same name.
+    pub weak:    bool,
+    /// like `GLIBC_2.2.5`.
+    pub version: Option<String>,
+    /// the library that provides the version of an imported symbol, like
+    /// `libc.so.6`.
+    pub library: Option<String>,
+}
+
+/// an indirect function, whose address is computed at runtime by calling the
+/// symbol, a resolver function.
+const STT_GNU_IFUNC: u8 = 10;
+
+/// fetch the (version, library) of the given version index,
+/// as described by the version requirements.
+fn get_required_version(elf: &goblin::elf::Elf, index: u16) -> Option<(String, String)> {
+    let verneed = elf.verneed.as_ref()?;
+
+    for need in verneed.iter() {
+        for aux in need.iter() {
+            if aux.vna_other == index {
+                let version = elf.dynstrtab.get_at(aux.vna_name)?;
+                let library = elf.dynstrtab.get_at(need.vn_file)?;
+                return Some((version.to_string(), library.to_string()));
+            }
+        }
+    }
+
+    None
+}
+
+/// read the symbols from the dynamic symbol table.
+/// the first, null, symbol is skipped.
+pub fn read_dynamic_symbols(elf: &ELF) -> Result<Vec<Symbol>> {
+    let e = elf.elf()?;
+    let mut symbols = vec![];
+
+    for (index, sym) in e.dynsyms.iter().enumerate().skip(1) {
+        let name = e.dynstrtab.get_at(sym.st_name).unwrap_or("").to_string();
+
+        let kind = match sym.st_type() {
+            goblin::elf::sym::STT_FUNC | STT_GNU_IFUNC => SymbolKind::Function,
+            goblin::elf::sym::STT_OBJECT => SymbolKind::Object,
+            _ => SymbolKind::Other,
+        };
+
+        let (version, library) = match e.versym.as_ref().and_then(|versym| versym.get_at(index)) {
+            Some(versym) if !versym.is_local() && !versym.is_global() => {
+                match get_required_version(&e, versym.version()) {
+                    Some((version, library)) => (Some(version), Some(library)),
+                    // a version defined by this module.
+                    None => (None, None),
+                }
+            }
+            _ => (None, None),
+        };
+
+        let import = sym.st_shndx == goblin::elf::section_header::SHN_UNDEF as usize;
+        debug!(
+            "elf: dynamic symbol: {}{} at {:#x}",
+            name,
+            version.as_ref().map(|v| format!("@{}", v)).unwrap_or_default(),
+            sym.st_value
+        );
+
+        symbols.push(Symbol {
+            index,
+            name,
+            address: if import { 0 } else { sym.st_value },
+            size: sym.st_size,
+            kind,
+            import,
+            weak: sym.st_bind() == goblin::elf::sym::STB_WEAK,
+            version,
+            library,
+        });
+    }
+
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::elf::symbols::*, rsrc::*};
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::HELLO);
+        let elf = crate::loader::elf::ELF::from_bytes(&buf)?;
+
+        let symbols = read_dynamic_symbols(&elf)?;
+        assert_eq!(22, symbols.len());
+        assert_eq!(12, symbols.iter().filter(|sym| sym.import).count());
+
+        let puts = symbols.iter().find(|sym| sym.name == "puts").unwrap();
+        assert_eq!(4, puts.index);
+        assert!(puts.import);
+        assert_eq!(SymbolKind::Function, puts.kind);
+        assert_eq!(Some("GLIBC_2.2.5"), puts.version.as_deref());
+        assert_eq!(Some("libc.so.6"), puts.library.as_deref());
+
+        let start_main = symbols.iter().find(|sym| sym.name == "__libc_start_main").unwrap();
+        assert_eq!(Some("GLIBC_2.34"), start_main.version.as_deref());
+
+        // unversioned, weak import.
+        let gmon = symbols.iter().find(|sym| sym.name == "__gmon_start__").unwrap();
+        assert!(gmon.import);
+        assert!(gmon.weak);
+        assert_eq!(None, gmon.library);
+
+        let main = symbols.iter().find(|sym| sym.name == "main").unwrap();
+        assert!(!main.import);
+        assert_eq!(0x11DE, main.address);
+        assert_eq!(0x6A, main.size);
+        assert_eq!(SymbolKind::Function, main.kind);
+
+        let stdin_used = symbols.iter().find(|sym| sym.name == "_IO_stdin_used").unwrap();
+        assert_eq!(SymbolKind::Object, stdin_used.kind);
+
+        Ok(())
+    }
+}
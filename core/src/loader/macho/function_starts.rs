@@ -0,0 +1,108 @@
+//! Parse the function starts table (`LC_FUNCTION_STARTS`).
+//!
+//! The linker emits the start address of every function in the image,
+//! including static functions that aren't in the symbol table,
+//! so this is a great source of function candidates, even for stripped images.
+//!
+//! The table is a sequence of ULEB128-encoded deltas, the first relative to the
+//! start of `__TEXT` and each subsequent one relative to the previous function,
+//! terminated by a zero delta.
+//!
+//! references:
+//!   - https://opensource.apple.com/source/ld64/ld64-609/src/ld/LinkEdit.hpp
+//!     (`FunctionStartsAtom`)
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    loader::macho::{MachO, MachOError},
+    util, VA,
+};
+
+/// read the function start addresses, in ascending order.
+pub fn read_function_starts(macho: &MachO) -> Result<Vec<VA>> {
+    let m = macho.macho()?;
+
+    let cmd = match m.load_commands.iter().find_map(|lc| match &lc.command {
+        goblin::mach::load_command::CommandVariant::FunctionStarts(cmd) => Some(cmd),
+        _ => None,
+    }) {
+        Some(cmd) => cmd,
+        None => return Ok(vec![]),
+    };
+
+    let text = match m.segments.iter().find(|segment| segment.name().ok() == Some("__TEXT")) {
+        Some(text) => text,
+        None => return Err(MachOError::MalformedMachOFile("no __TEXT segment".to_string()).into()),
+    };
+
+    let buf = match macho
+        .buf
+        .get(cmd.dataoff as usize..cmd.dataoff as usize + cmd.datasize as usize)
+    {
+        Some(buf) => buf,
+        None => return Err(MachOError::MalformedMachOFile("function starts out of bounds".to_string()).into()),
+    };
+
+    let mut starts = vec![];
+    let mut address = text.vmaddr;
+    let mut offset = 0;
+    while offset < buf.len() {
+        let delta = match util::read_uleb128(buf, &mut offset) {
+            Some(0) => break,
+            Some(delta) => delta,
+            None => return Err(MachOError::MalformedMachOFile("invalid function start".to_string()).into()),
+        };
+
+        // the starts are ascending, so any that follow an overflow are bogus, too.
+        address = match address.checked_add(delta) {
+            Some(address) => address,
+            None => {
+                debug!("macho: function start: overflow");
+                break;
+            }
+        };
+        debug!("macho: function start: {:#x}", address);
+        starts.push(address);
+    }
+
+    Ok(starts)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::macho::function_starts::*, rsrc::*};
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        // _main, _add, and a static helper that's not in the symbol table.
+        assert_eq!(
+            vec![0x1_0000_0500, 0x1_0000_0540, 0x1_0000_0544],
+            read_function_starts(&macho)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn overflow() -> Result<()> {
+        use byteorder::{ByteOrder, LittleEndian};
+
+        // in the thin image, the table is at 0x2048, referenced by the command at
+        // 0x478. the second delta overflows, so it's dropped.
+        let mut buf = get_buf(Rsrc::MACHO)[0x1000..0x3120].to_vec();
+        LittleEndian::write_u32(&mut buf[0x478 + 0xC..], 0xD);
+        buf[0x2048..0x2048 + 0xD].copy_from_slice(&[
+            0x80, 0x0A, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00,
+        ]);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+        assert_eq!(vec![0x1_0000_0500], read_function_starts(&macho)?);
+
+        Ok(())
+    }
+}
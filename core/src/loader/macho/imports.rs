@@ -0,0 +1,247 @@
+//! Parse the imported symbols and the stubs that call them.
+//!
+//! Code references an imported symbol via a pointer slot that dyld fills in
+//! with the resolved address, as described by the binding info of
+//! `LC_DYLD_INFO`:
+//!
+//!   - non-lazy pointers, in `__DATA,__got`, are bound when the image is
+//!     loaded, and referenced directly, like `call [rip+_exit@GOTPCREL]`.
+//!   - lazy pointers, in `__DATA,__la_symbol_ptr`, are bound on first use. They
+//!     initially point into `__TEXT,__stub_helper`, which invokes
+//!     `dyld_stub_binder`.
+//!
+//! Calls to imported functions go through the symbol stubs in
+//! `__TEXT,__stubs`, each of which is a `jmp [rip+lazy pointer]`.
+//! The stubs are fixed size, and the indirect symbol table names the symbol
+//! for each one, so we don't need to disassemble them.
+//!
+//! references:
+//!   - https://opensource.apple.com/source/xnu/xnu-7195.81.3/EXTERNAL_HEADERS/mach-o/loader.h
+//!   - https://www.mikeash.com/pyblog/friday-qa-2012-11-09-dyld-dynamic-linking-on-os-x.html
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::{
+    loader::macho::{symbols::get_library, MachO, MachOError, SECTION_TYPE},
+    VA,
+};
+
+#[derive(Clone, Debug)]
+pub struct Import {
+    /// the address of the pointer slot.
+    pub address: VA,
+    /// the install name of the library, like `/usr/lib/libSystem.B.dylib`.
+    pub library: String,
+    /// like `_printf`.
+    pub name:    String,
+    /// bound on first use, via the stub helper.
+    pub lazy:    bool,
+    /// the symbol may be missing at runtime, in which case the slot is zero.
+    pub weak:    bool,
+}
+
+/// read the imported symbols from the (lazy) binding info,
+/// ordered by the address of their pointer slot.
+pub fn read_imports(macho: &MachO) -> Result<Vec<Import>> {
+    let m = macho.macho()?;
+
+    let mut imports = vec![];
+    for import in m.imports()?.into_iter() {
+        debug!("macho: import: {:#x}: {}!{}", import.address, import.dylib, import.name);
+        imports.push(Import {
+            address: import.address,
+            library: import.dylib.to_string(),
+            name:    import.name.to_string(),
+            lazy:    import.is_lazy,
+            weak:    import.is_weak,
+        });
+    }
+
+    imports.sort_unstable_by_key(|import| import.address);
+
+    Ok(imports)
+}
+
+#[derive(Clone, Debug)]
+pub struct Stub {
+    /// the address of the stub.
+    pub address: VA,
+    /// like `_printf`.
+    pub name:    String,
+    /// the install name of the library that provides the symbol.
+    pub library: Option<String>,
+}
+
+const S_SYMBOL_STUBS: u32 = 0x8;
+
+// entries of the indirect symbol table that don't reference a symbol.
+const INDIRECT_SYMBOL_LOCAL: u32 = 0x8000_0000;
+const INDIRECT_SYMBOL_ABS: u32 = 0x4000_0000;
+
+const SEGMENT_COMMAND_64_SIZE: usize = 72;
+const SECTION_64_SIZE: usize = 80;
+
+/// the fields of a `section_64` that describe the stubs it contains,
+/// which goblin doesn't expose.
+struct StubSection {
+    addr:      VA,
+    size:      u64,
+    /// the index of the first entry of the indirect symbol table.
+    reserved1: u32,
+    /// the size of each stub.
+    reserved2: u32,
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Result<u32> {
+    match buf.get(offset..offset + 4) {
+        Some(b) => Ok(LittleEndian::read_u32(b)),
+        None => Err(MachOError::MalformedMachOFile("buffer too small".to_string()).into()),
+    }
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> Result<u64> {
+    match buf.get(offset..offset + 8) {
+        Some(b) => Ok(LittleEndian::read_u64(b)),
+        None => Err(MachOError::MalformedMachOFile("buffer too small".to_string()).into()),
+    }
+}
+
+/// find the sections of type `S_SYMBOL_STUBS` by parsing the raw section
+/// headers that follow each `LC_SEGMENT_64` command.
+fn get_stub_sections(macho: &MachO, m: &goblin::mach::MachO) -> Result<Vec<StubSection>> {
+    let mut sections = vec![];
+
+    for lc in m.load_commands.iter() {
+        let segment = match &lc.command {
+            goblin::mach::load_command::CommandVariant::Segment64(segment) => segment,
+            _ => continue,
+        };
+
+        for i in 0..segment.nsects as usize {
+            let offset = lc.offset + SEGMENT_COMMAND_64_SIZE + i * SECTION_64_SIZE;
+            let flags = read_u32(&macho.buf, offset + 64)?;
+            if flags & SECTION_TYPE != S_SYMBOL_STUBS {
+                continue;
+            }
+
+            sections.push(StubSection {
+                addr:      read_u64(&macho.buf, offset + 32)?,
+                size:      read_u64(&macho.buf, offset + 40)?,
+                reserved1: read_u32(&macho.buf, offset + 68)?,
+                reserved2: read_u32(&macho.buf, offset + 72)?,
+            });
+        }
+    }
+
+    Ok(sections)
+}
+
+/// read the symbol stubs and the symbols they jump to,
+/// ordered by address.
+pub fn read_stubs(macho: &MachO) -> Result<Vec<Stub>> {
+    let m = macho.macho()?;
+
+    let dysymtab = m.load_commands.iter().find_map(|lc| match &lc.command {
+        goblin::mach::load_command::CommandVariant::Dysymtab(dysymtab) => Some(dysymtab),
+        _ => None,
+    });
+    let (dysymtab, symbols) = match (dysymtab, m.symbols.as_ref()) {
+        (Some(dysymtab), Some(symbols)) => (dysymtab, symbols),
+        _ => return Ok(vec![]),
+    };
+
+    let mut stubs = vec![];
+    for section in get_stub_sections(macho, &m)?.into_iter() {
+        if section.reserved2 == 0 {
+            return Err(MachOError::MalformedMachOFile("invalid stub size".to_string()).into());
+        }
+
+        for i in 0..(section.size / section.reserved2 as u64) {
+            let index = section.reserved1 as u64 + i;
+            if index >= dysymtab.nindirectsyms as u64 {
+                return Err(MachOError::MalformedMachOFile("indirect symbol out of bounds".to_string()).into());
+            }
+
+            let symbol_index = read_u32(&macho.buf, dysymtab.indirectsymoff as usize + index as usize * 4)?;
+            if symbol_index & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) != 0 {
+                continue;
+            }
+
+            let address = match i
+                .checked_mul(section.reserved2 as u64)
+                .and_then(|offset| section.addr.checked_add(offset))
+            {
+                Some(address) => address,
+                None => {
+                    debug!("macho: stub: out of bounds");
+                    continue;
+                }
+            };
+
+            let (name, nlist) = symbols.get(symbol_index as usize)?;
+            let library = get_library(&m, &nlist).map(|library| library.to_string());
+
+            let stub = Stub {
+                address,
+                name: name.to_string(),
+                library,
+            };
+            debug!("macho: stub: {:#x}: {}", stub.address, stub.name);
+            stubs.push(stub);
+        }
+    }
+
+    stubs.sort_unstable_by_key(|stub| stub.address);
+
+    Ok(stubs)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::macho::imports::*, rsrc::*};
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        let imports = read_imports(&macho)?;
+        assert_eq!(4, imports.len());
+        assert_eq!(0x1_0000_1000, imports[0].address);
+        assert_eq!("dyld_stub_binder", imports[0].name);
+        assert_eq!("/usr/lib/libSystem.B.dylib", imports[0].library);
+        assert!(!imports[0].lazy);
+        assert_eq!(0x1_0000_1018, imports[3].address);
+        assert_eq!("_puts", imports[3].name);
+        assert!(imports[3].lazy);
+
+        let stubs = read_stubs(&macho)?;
+        assert_eq!(2, stubs.len());
+        assert_eq!(0x1_0000_054A, stubs[0].address);
+        assert_eq!("_printf", stubs[0].name);
+        assert_eq!(0x1_0000_0550, stubs[1].address);
+        assert_eq!("_puts", stubs[1].name);
+        assert_eq!(Some("/usr/lib/libSystem.B.dylib"), stubs[1].library.as_deref());
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_stubs() -> Result<()> {
+        // move __stubs to the end of the address space,
+        // so that only the first stub fits.
+        let mut buf = get_buf(Rsrc::MACHO);
+        buf[0x1120..0x1128].copy_from_slice(&0xFFFF_FFFF_FFFF_FFFCu64.to_le_bytes());
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        let stubs = read_stubs(&macho)?;
+        assert_eq!(1, stubs.len());
+        assert_eq!(0xFFFF_FFFF_FFFF_FFFC, stubs[0].address);
+        assert_eq!("_printf", stubs[0].name);
+
+        Ok(())
+    }
+}
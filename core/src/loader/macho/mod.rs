@@ -0,0 +1,380 @@
+#![allow(clippy::nonstandard_macro_braces)] // clippy bug, see https://github.com/rust-lang/rust-clippy/issues/7434
+
+use anyhow::Result;
+use log::debug;
+use thiserror::Error;
+
+pub mod function_starts;
+pub mod imports;
+pub mod symbols;
+
+use crate::{
+    arch::Arch,
+    aspace::RelativeAddressSpace,
+    module::{Module, Permissions, Section},
+    util, VA,
+};
+
+#[derive(Error, Debug)]
+pub enum MachOError {
+    #[error("format not supported: {0}")]
+    FormatNotSupported(String),
+
+    #[error("malformed Mach-O file: {0}")]
+    MalformedMachOFile(String),
+}
+
+const PAGE_SIZE: u64 = 0x1000;
+
+/// the largest span of memory covered by the segments,
+/// like the 32-bit `SizeOfImage` of a PE file.
+const MAX_IMAGE_SIZE: u64 = 0xFFFF_F000;
+
+/// A parsed and loaded Mach-O file.
+/// The `buf` field contains the raw data of the image,
+/// which, for universal (fat) binaries, is the x86-64 slice.
+/// The `module` field contains an address space as the image would be loaded.
+pub struct MachO {
+    pub buf:    Vec<u8>,
+    pub module: Module,
+    pub header: goblin::mach::header::Header,
+}
+
+impl MachO {
+    pub fn from_bytes(buf: &[u8]) -> Result<MachO> {
+        load_macho(buf)
+    }
+
+    /// the address of the entry point, from `LC_MAIN` or `LC_UNIXTHREAD`.
+    /// dynamic libraries don't have one.
+    pub fn entry_point(&self) -> Result<Option<VA>> {
+        match self.macho()?.entry {
+            0 => Ok(None),
+            entry => Ok(Some(entry)),
+        }
+    }
+
+    /// Parse the symbol table. See `symbols::read_symbols`.
+    pub fn get_symbols(&self) -> Result<Vec<symbols::Symbol>> {
+        symbols::read_symbols(self)
+    }
+
+    /// Parse the binding info for imported symbols. See
+    /// `imports::read_imports`.
+    pub fn get_imports(&self) -> Result<Vec<imports::Import>> {
+        imports::read_imports(self)
+    }
+
+    /// Parse the symbol stubs. See `imports::read_stubs`.
+    pub fn get_stubs(&self) -> Result<Vec<imports::Stub>> {
+        imports::read_stubs(self)
+    }
+
+    /// Parse `LC_FUNCTION_STARTS`. See
+    /// `function_starts::read_function_starts`.
+    pub fn get_function_starts(&self) -> Result<Vec<VA>> {
+        function_starts::read_function_starts(self)
+    }
+
+    pub fn executable_sections<'b>(&'b self) -> Box<dyn Iterator<Item = &'b Section> + 'b> {
+        Box::new(
+            self.module
+                .sections
+                .iter()
+                .filter(|section| section.permissions.intersects(Permissions::X)),
+        )
+    }
+
+    pub fn macho(&self) -> Result<goblin::mach::MachO<'_>> {
+        Ok(goblin::mach::MachO::parse(&self.buf, 0)?)
+    }
+}
+
+/// select the x86-64 image from the given file,
+/// which is either a single image, or a universal (fat) binary.
+fn get_x64_slice(buf: &[u8]) -> Result<&[u8]> {
+    match goblin::mach::Mach::parse(buf)? {
+        goblin::mach::Mach::Binary(_) => Ok(buf),
+        goblin::mach::Mach::Fat(fat) => match fat.find_cputype(goblin::mach::cputype::CPU_TYPE_X86_64)? {
+            Some(arch) => {
+                debug!("macho: fat: x86-64 slice at {:#x}", arch.offset);
+                (arch.offset as usize)
+                    .checked_add(arch.size as usize)
+                    .and_then(|end| buf.get(arch.offset as usize..end))
+                    .ok_or_else(|| MachOError::MalformedMachOFile("fat slice out of bounds".to_string()).into())
+            }
+            None => Err(MachOError::FormatNotSupported("no x86-64 slice".to_string()).into()),
+        },
+    }
+}
+
+fn get_macho(buf: &[u8]) -> Result<goblin::mach::MachO<'_>> {
+    let macho = goblin::mach::MachO::parse(buf, 0)?;
+
+    if macho.header.cputype() != goblin::mach::cputype::CPU_TYPE_X86_64 {
+        return Err(MachOError::FormatNotSupported(format!(
+            "CPU type: {}",
+            goblin::mach::cputype::get_arch_name_from_types(macho.header.cputype(), macho.header.cpusubtype())
+                .unwrap_or("unknown")
+        ))
+        .into());
+    }
+
+    if macho.is_object_file() {
+        return Err(MachOError::FormatNotSupported("object file".to_string()).into());
+    }
+
+    Ok(macho)
+}
+
+const VM_PROT_READ: u32 = 0x1;
+const VM_PROT_WRITE: u32 = 0x2;
+const VM_PROT_EXECUTE: u32 = 0x4;
+
+fn get_permissions(initprot: u32) -> Permissions {
+    let mut perms = Permissions::empty();
+    if initprot & VM_PROT_READ > 0 {
+        perms.insert(Permissions::R);
+    }
+    if initprot & VM_PROT_WRITE > 0 {
+        perms.insert(Permissions::W);
+    }
+    if initprot & VM_PROT_EXECUTE > 0 {
+        perms.insert(Permissions::X);
+    }
+    perms
+}
+
+pub(crate) const SECTION_TYPE: u32 = 0x0000_00FF;
+const S_ZEROFILL: u32 = 0x1;
+const S_GB_ZEROFILL: u32 = 0xC;
+const S_THREAD_LOCAL_ZEROFILL: u32 = 0x12;
+
+/// sections inherit the protections of their segment.
+/// section names are qualified by the segment, like `__TEXT,__text`.
+fn load_macho_sections(segment: &goblin::mach::segment::Segment) -> Result<Vec<Section>> {
+    let permissions = get_permissions(segment.initprot);
+
+    let mut sections = vec![];
+    for (section, _) in segment.sections()?.into_iter() {
+        let name = format!("{},{}", section.segname()?, section.name()?);
+
+        let physical_range = match section.flags & SECTION_TYPE {
+            // not backed by the file.
+            S_ZEROFILL | S_GB_ZEROFILL | S_THREAD_LOCAL_ZEROFILL => 0..0,
+            _ => match (section.offset as u64).checked_add(section.size) {
+                Some(end) => section.offset as u64..end,
+                None => {
+                    debug!("macho: section: {}: out of bounds", name);
+                    continue;
+                }
+            },
+        };
+
+        let virtual_range = match section.addr.checked_add(section.size) {
+            Some(end) => section.addr..end,
+            None => {
+                debug!("macho: section: {}: out of bounds", name);
+                continue;
+            }
+        };
+
+        debug!("macho: section: {} at {:#x}", name, section.addr);
+        sections.push(Section {
+            physical_range,
+            virtual_range,
+            permissions,
+            name,
+        });
+    }
+
+    Ok(sections)
+}
+
+// references:
+//   - https://github.com/aidansteele/osx-abi-macho-file-format-reference
+//   - https://opensource.apple.com/source/xnu/xnu-7195.81.3/EXTERNAL_HEADERS/mach-o/loader.h
+fn load_macho(buf: &[u8]) -> Result<MachO> {
+    let buf = get_x64_slice(buf)?;
+    let macho = get_macho(buf)?;
+
+    let arch = Arch::X64;
+    debug!("macho: arch: {:?}", arch);
+
+    // segments without any access, like __PAGEZERO, are guard regions
+    // that reserve address space, so we don't map them.
+    let segments: Vec<_> = macho
+        .segments
+        .iter()
+        .filter(|segment| segment.initprot != 0 && segment.vmsize != 0)
+        .filter(|segment| {
+            if segment.fileoff.checked_add(segment.filesize).is_none()
+                || segment.vmaddr.checked_add(segment.vmsize).is_none()
+            {
+                debug!("macho: segment {}: out of bounds", segment.name().unwrap_or("?"));
+                false
+            } else {
+                true
+            }
+        })
+        .collect();
+
+    if segments.is_empty() {
+        return Err(MachOError::MalformedMachOFile("no segments".to_string()).into());
+    }
+
+    for segment in segments.iter() {
+        if segment.fileoff + segment.filesize > buf.len() as u64 {
+            return Err(MachOError::MalformedMachOFile(format!("segment {} out of bounds", segment.name()?)).into());
+        }
+    }
+
+    let min_address = segments.iter().map(|segment| segment.vmaddr).min().unwrap();
+    let base_address = min_address - (min_address % PAGE_SIZE);
+    debug!("macho: base address: {:#x}", base_address);
+
+    let max_address = segments
+        .iter()
+        .map(|segment| segment.vmaddr + segment.vmsize)
+        .max()
+        .unwrap();
+    // the address space is dense, so segments that are far apart
+    // would require a huge allocation.
+    if max_address - base_address > MAX_IMAGE_SIZE {
+        return Err(MachOError::MalformedMachOFile("segments too far apart".to_string()).into());
+    }
+    let max_page_address = util::align(max_address - base_address, PAGE_SIZE);
+    debug!("macho: address space: capacity: {:#x}", max_page_address);
+
+    let mut address_space = RelativeAddressSpace::with_capacity(max_page_address);
+    let mut sections = vec![];
+
+    for segment in segments.iter() {
+        let rstart = segment.vmaddr - base_address;
+        let rend = rstart + segment.vmsize;
+
+        let mut page = rstart - (rstart % PAGE_SIZE);
+        while page < rend {
+            if !address_space.map.probe(page) {
+                address_space.map.map_empty(page, PAGE_SIZE as usize)?;
+            }
+            page += PAGE_SIZE;
+        }
+
+        // when the file size is smaller than the memory size, such as for __bss,
+        // the remainder is zero-filled.
+        let psize = std::cmp::min(segment.filesize, segment.vmsize);
+        let pstart = segment.fileoff as usize;
+        address_space.write_into(rstart, &buf[pstart..pstart + psize as usize])?;
+
+        debug!(
+            "macho: address space: mapped {} {:#x} - {:#x} {:?}",
+            segment.name()?,
+            segment.vmaddr,
+            segment.vmaddr + segment.vmsize,
+            get_permissions(segment.initprot)
+        );
+
+        sections.extend(load_macho_sections(segment)?);
+    }
+
+    let module = Module {
+        arch,
+        sections,
+        address_space: address_space.into_absolute(base_address)?,
+    };
+
+    debug!("macho: loaded");
+    Ok(MachO {
+        buf: buf.to_vec(),
+        module,
+        header: macho.header,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{aspace::AddressSpace, module::Permissions, rsrc::*};
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        // the x86-64 slice, from the universal binary.
+        assert_eq!(0x2120, macho.buf.len());
+        assert_eq!(0x1_0000_0000, macho.module.address_space.base_address);
+        assert_eq!(Some(0x1_0000_0500), macho.entry_point()?);
+
+        assert_eq!(7, macho.module.sections.len());
+        let text = &macho.module.sections[0];
+        assert_eq!("__TEXT,__text", text.name);
+        assert_eq!(Permissions::RX, text.permissions);
+        assert_eq!(0x1_0000_0500..0x1_0000_0549, text.virtual_range);
+        assert_eq!(0x500, macho.module.file_offset(0x1_0000_0500)?);
+
+        let got = &macho.module.sections[4];
+        assert_eq!("__DATA,__got", got.name);
+        assert_eq!(Permissions::RW, got.permissions);
+
+        // _main: `push rbp`
+        assert_eq!(0x55, macho.module.address_space.read_u8(0x1_0000_0500)?);
+        // mach header, within __TEXT
+        assert_eq!(0xFEEDFACF, macho.module.address_space.read_u32(0x1_0000_0000)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn thin() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf[0x1000..0x3120])?;
+        assert_eq!(Some(0x1_0000_0500), macho.entry_point()?);
+
+        // the arm64 slice isn't supported.
+        assert!(crate::loader::macho::MachO::from_bytes(&buf[0x4000..]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<()> {
+        use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+        // a fat slice whose end overflows.
+        let mut buf = get_buf(Rsrc::MACHO);
+        BigEndian::write_u32(&mut buf[0x8 + 0x8..], 0xFFFF_F000);
+        assert!(crate::loader::macho::MachO::from_bytes(&buf).is_err());
+
+        // the thin image, with its segment commands at 0x68 (__TEXT) and 0x1F0
+        // (__DATA).
+        let buf = get_buf(Rsrc::MACHO)[0x1000..0x3120].to_vec();
+
+        // __DATA, far from __TEXT.
+        let mut far = buf.clone();
+        LittleEndian::write_u64(&mut far[0x1F0 + 0x18..], 0x7FFF_0000_1000);
+        assert!(crate::loader::macho::MachO::from_bytes(&far).is_err());
+
+        // __DATA, whose end overflows, is skipped.
+        let mut huge = buf;
+        LittleEndian::write_u64(&mut huge[0x1F0 + 0x20..], u64::MAX);
+        let macho = crate::loader::macho::MachO::from_bytes(&huge)?;
+        assert!(macho
+            .module
+            .sections
+            .iter()
+            .all(|section| !section.name.starts_with("__DATA")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        assert!(crate::loader::macho::MachO::from_bytes(&buf).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+//! Parse the symbol table (`LC_SYMTAB`).
+//!
+//! The symbol table describes the functions and data defined by the module,
+//! as well as those that it imports from other modules.
+//! Imported symbols are undefined (type `N_UNDF`), and, with the two-level
+//! namespace used by modern images, the high byte of `n_desc` names the
+//! library that provides each symbol, as an index into the `LC_LOAD_DYLIB`
+//! commands.
+//!
+//! The symbol table can be stripped down to just the exported and imported
+//! symbols, so don't rely on it to find all functions.
+//!
+//! references:
+//!   - https://opensource.apple.com/source/xnu/xnu-7195.81.3/EXTERNAL_HEADERS/mach-o/nlist.h
+use anyhow::Result;
+use log::debug;
+
+use crate::{loader::macho::MachO, VA};
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    /// like `_main`, with the leading underscore added by the compiler.
+    pub name:      String,
+    /// zero for imported symbols.
+    pub address:   VA,
+    /// the symbol is visible outside of this module.
+    pub external:  bool,
+    /// the symbol is not defined in this module, and resolved at runtime.
+    pub undefined: bool,
+    /// a weak reference, or weak definition.
+    pub weak:      bool,
+    /// the install name of the library that provides an imported symbol, like
+    /// `/usr/lib/libSystem.B.dylib`.
+    pub library:   Option<String>,
+}
+
+// special library ordinals that don't reference an `LC_LOAD_DYLIB`.
+const SELF_LIBRARY_ORDINAL: usize = 0x0;
+const DYNAMIC_LOOKUP_ORDINAL: usize = 0xFE;
+const EXECUTABLE_ORDINAL: usize = 0xFF;
+
+/// fetch the library that provides the given undefined symbol.
+pub(crate) fn get_library<'a>(
+    macho: &goblin::mach::MachO<'a>,
+    nlist: &goblin::mach::symbols::Nlist,
+) -> Option<&'a str> {
+    // GET_LIBRARY_ORDINAL(n_desc)
+    match ((nlist.n_desc >> 8) & 0xFF) as usize {
+        SELF_LIBRARY_ORDINAL | DYNAMIC_LOOKUP_ORDINAL | EXECUTABLE_ORDINAL => None,
+        // goblin places the module itself at index zero,
+        // so the ordinals, which start at one, index directly into `libs`.
+        ordinal => macho.libs.get(ordinal).copied(),
+    }
+}
+
+/// read the symbols from the symbol table, in table order.
+/// debugging entries (stabs) are skipped.
+pub fn read_symbols(macho: &MachO) -> Result<Vec<Symbol>> {
+    let m = macho.macho()?;
+    let mut symbols = vec![];
+
+    for symbol in m.symbols() {
+        let (name, nlist) = symbol?;
+
+        if nlist.is_stab() {
+            continue;
+        }
+
+        let undefined = nlist.is_undefined();
+        let library = if undefined {
+            get_library(&m, &nlist).map(|library| library.to_string())
+        } else {
+            None
+        };
+
+        let symbol = Symbol {
+            name: name.to_string(),
+            address: nlist.n_value,
+            external: nlist.is_global(),
+            undefined,
+            weak: nlist.is_weak(),
+            library,
+        };
+        debug!("macho: symbol: {:#x}: {}", symbol.address, symbol.name);
+        symbols.push(symbol);
+    }
+
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::macho::symbols::*, rsrc::*};
+
+    #[test]
+    fn hello() -> Result<()> {
+        let buf = get_buf(Rsrc::MACHO);
+        let macho = crate::loader::macho::MachO::from_bytes(&buf)?;
+
+        let symbols = read_symbols(&macho)?;
+        assert_eq!(7, symbols.len());
+
+        let main = symbols.iter().find(|symbol| symbol.name == "_main").unwrap();
+        assert_eq!(0x1_0000_0500, main.address);
+        assert!(main.external);
+        assert!(!main.undefined);
+        assert_eq!(None, main.library);
+
+        let puts = symbols.iter().find(|symbol| symbol.name == "_puts").unwrap();
+        assert_eq!(0x0, puts.address);
+        assert!(puts.undefined);
+        assert_eq!(Some("/usr/lib/libSystem.B.dylib"), puts.library.as_deref());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+//! Parse the thread contexts (`CONTEXT`) captured in a minidump.
+//!
+//! The layout of the structure depends on the architecture of the process,
+//! so the x86 and AMD64 variants are normalized into a single `ThreadContext`
+//! with 64-bit registers.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/api/winnt/ns-winnt-context
+//!   - https://github.com/rust-minidump/rust-minidump/blob/master/minidump-common/src/format.rs
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{arch::Arch, loader::minidump::MinidumpError};
+
+const SIZEOF_CONTEXT_X86: usize = 0x2CC;
+const SIZEOF_CONTEXT_AMD64: usize = 0x4D0;
+
+/// the general purpose registers of a thread, as captured in the dump.
+/// for x86 processes, only the low 32 bits are used, and `r8`-`r15` are zero.
+#[derive(Clone, Debug, Default)]
+pub struct ThreadContext {
+    pub rax:    u64,
+    pub rbx:    u64,
+    pub rcx:    u64,
+    pub rdx:    u64,
+    pub rsp:    u64,
+    pub rbp:    u64,
+    pub rsi:    u64,
+    pub rdi:    u64,
+    pub r8:     u64,
+    pub r9:     u64,
+    pub r10:    u64,
+    pub r11:    u64,
+    pub r12:    u64,
+    pub r13:    u64,
+    pub r14:    u64,
+    pub r15:    u64,
+    pub rflags: u64,
+    pub rip:    u64,
+    pub es:     u16,
+    pub cs:     u16,
+    pub ss:     u16,
+    pub ds:     u16,
+    pub fs:     u16,
+    pub gs:     u16,
+}
+
+fn read_context_x86(buf: &[u8]) -> ThreadContext {
+    let r32 = |offset: usize| LittleEndian::read_u32(&buf[offset..]) as u64;
+    let r16 = |offset: usize| LittleEndian::read_u32(&buf[offset..]) as u16;
+
+    ThreadContext {
+        gs: r16(0x8C),
+        fs: r16(0x90),
+        es: r16(0x94),
+        ds: r16(0x98),
+        rdi: r32(0x9C),
+        rsi: r32(0xA0),
+        rbx: r32(0xA4),
+        rdx: r32(0xA8),
+        rcx: r32(0xAC),
+        rax: r32(0xB0),
+        rbp: r32(0xB4),
+        rip: r32(0xB8),
+        cs: r16(0xBC),
+        rflags: r32(0xC0),
+        rsp: r32(0xC4),
+        ss: r16(0xC8),
+        ..Default::default()
+    }
+}
+
+fn read_context_amd64(buf: &[u8]) -> ThreadContext {
+    let r64 = |offset: usize| LittleEndian::read_u64(&buf[offset..]);
+    let r16 = |offset: usize| LittleEndian::read_u16(&buf[offset..]);
+
+    ThreadContext {
+        cs:     r16(0x38),
+        ds:     r16(0x3A),
+        es:     r16(0x3C),
+        fs:     r16(0x3E),
+        gs:     r16(0x40),
+        ss:     r16(0x42),
+        rflags: LittleEndian::read_u32(&buf[0x44..]) as u64,
+        rax:    r64(0x78),
+        rcx:    r64(0x80),
+        rdx:    r64(0x88),
+        rbx:    r64(0x90),
+        rsp:    r64(0x98),
+        rbp:    r64(0xA0),
+        rsi:    r64(0xA8),
+        rdi:    r64(0xB0),
+        r8:     r64(0xB8),
+        r9:     r64(0xC0),
+        r10:    r64(0xC8),
+        r11:    r64(0xD0),
+        r12:    r64(0xD8),
+        r13:    r64(0xE0),
+        r14:    r64(0xE8),
+        r15:    r64(0xF0),
+        rip:    r64(0xF8),
+    }
+}
+
+/// parse the `CONTEXT` structure for the given architecture.
+pub fn read_context(arch: Arch, buf: &[u8]) -> Result<ThreadContext> {
+    let size = match arch {
+        Arch::X32 => SIZEOF_CONTEXT_X86,
+        Arch::X64 => SIZEOF_CONTEXT_AMD64,
+    };
+
+    if buf.len() < size {
+        return Err(MinidumpError::MalformedMinidump("thread context too small".to_string()).into());
+    }
+
+    match arch {
+        Arch::X32 => Ok(read_context_x86(buf)),
+        Arch::X64 => Ok(read_context_amd64(buf)),
+    }
+}
+
+#[cfg(feature = "emulator")]
+impl ThreadContext {
+    /// the registers with which to seed an emulator,
+    /// to resume execution from the captured state.
+    pub fn to_registers(&self) -> crate::emu::reg::Registers {
+        crate::emu::reg::Registers {
+            rax: self.rax,
+            rbx: self.rbx,
+            rcx: self.rcx,
+            rdx: self.rdx,
+            rsp: self.rsp,
+            rbp: self.rbp,
+            rsi: self.rsi,
+            rdi: self.rdi,
+            r8: self.r8,
+            r9: self.r9,
+            r10: self.r10,
+            r11: self.r11,
+            r12: self.r12,
+            r13: self.r13,
+            r14: self.r14,
+            r15: self.r15,
+            rflags: self.rflags,
+            rip: self.rip,
+            es: self.es,
+            cs: self.cs,
+            ss: self.ss,
+            ds: self.ds,
+            fs: self.fs,
+            gs: self.gs,
+            ..Default::default()
+        }
+    }
+}
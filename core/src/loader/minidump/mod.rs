@@ -0,0 +1,897 @@
+//! Load Windows minidumps (`.dmp`), as written by `MiniDumpWriteDump`,
+//! such as crash dumps and process dumps collected during incident response.
+//!
+//! A minidump is a collection of streams that describe a process:
+//! its loaded modules, threads, and some or all of its memory.
+//! We map every captured region of memory into a single `SparseAddressSpace`,
+//! and provide a `Module` view of each loaded image, so that the existing
+//! analysis passes can be applied to the images as they were in memory.
+//!
+//! Memory that wasn't captured, such as in a partial dump, is not mapped
+//! in the address space, and is zero in the module views.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/api/minidumpapiset/
+//!   - https://github.com/rust-minidump/rust-minidump/blob/master/minidump-common/src/format.rs
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+use thiserror::Error;
+
+pub mod context;
+
+use crate::{
+    arch::Arch,
+    aspace::{AddressSpace, RelativeAddressSpace, SparseAddressSpace},
+    loader::pe::PE,
+    module::{Module, Permissions, Section},
+    RVA, VA,
+};
+
+#[derive(Error, Debug)]
+pub enum MinidumpError {
+    #[error("format not supported: {0}")]
+    FormatNotSupported(String),
+
+    #[error("malformed minidump: {0}")]
+    MalformedMinidump(String),
+
+    #[error("thread not found: {0:#x}")]
+    ThreadNotFound(u32),
+}
+
+/// `MDMP`
+const MINIDUMP_SIGNATURE: u32 = 0x504D_444D;
+
+// ref: https://docs.microsoft.com/en-us/windows/win32/api/minidumpapiset/ne-minidumpapiset-minidump_stream_type
+const THREAD_LIST_STREAM: u32 = 3;
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const SYSTEM_INFO_STREAM: u32 = 7;
+const MEMORY64_LIST_STREAM: u32 = 9;
+const MEMORY_INFO_LIST_STREAM: u32 = 16;
+
+const PROCESSOR_ARCHITECTURE_INTEL: u16 = 0;
+const PROCESSOR_ARCHITECTURE_AMD64: u16 = 9;
+
+const SIZEOF_MINIDUMP_HEADER: usize = 0x20;
+const SIZEOF_MINIDUMP_DIRECTORY: usize = 0xC;
+const SIZEOF_MINIDUMP_THREAD: usize = 0x30;
+const SIZEOF_MINIDUMP_MODULE: usize = 0x6C;
+const SIZEOF_MINIDUMP_MEMORY_DESCRIPTOR: usize = 0x10;
+const SIZEOF_MINIDUMP_MEMORY_DESCRIPTOR64: usize = 0x10;
+
+// ref: https://docs.microsoft.com/en-us/windows/win32/memory/memory-protection-constants
+const PAGE_NOACCESS: u32 = 0x01;
+const PAGE_READONLY: u32 = 0x02;
+const PAGE_READWRITE: u32 = 0x04;
+const PAGE_WRITECOPY: u32 = 0x08;
+const PAGE_EXECUTE: u32 = 0x10;
+const PAGE_EXECUTE_READ: u32 = 0x20;
+const PAGE_EXECUTE_READWRITE: u32 = 0x40;
+const PAGE_EXECUTE_WRITECOPY: u32 = 0x80;
+
+const MEM_COMMIT: u32 = 0x1000;
+
+#[derive(Clone, Debug)]
+pub struct SystemInfo {
+    /// like `PROCESSOR_ARCHITECTURE_AMD64`.
+    pub processor_architecture: u16,
+    pub major_version:          u32,
+    pub minor_version:          u32,
+    pub build_number:           u32,
+}
+
+/// a region of process memory captured in the dump.
+#[derive(Clone, Debug)]
+pub struct MemoryRegion {
+    pub address:     VA,
+    pub size:        RVA,
+    /// from the memory info list, if present,
+    /// otherwise, RWX, since the protection isn't known.
+    pub permissions: Permissions,
+    /// where the data is found in the dump file.
+    pub file_offset: u64,
+}
+
+/// an image loaded into the process, like the main executable or a DLL.
+#[derive(Clone)]
+pub struct LoadedImage {
+    /// the full path, like `C:\Windows\System32\kernel32.dll`.
+    pub name:         String,
+    pub base_address: VA,
+    pub size:         RVA,
+    pub checksum:     u32,
+    pub timestamp:    u32,
+    /// the image, as captured in memory, at its actual base address.
+    pub module:       Module,
+}
+
+#[derive(Clone, Debug)]
+pub struct Thread {
+    pub id:      u32,
+    /// the address of the Thread Environment Block.
+    pub teb:     VA,
+    pub stack:   std::ops::Range<VA>,
+    pub context: Option<context::ThreadContext>,
+}
+
+/// A parsed minidump.
+/// The `buf` field contains the raw data.
+/// The `address_space` field contains every captured region of memory.
+pub struct Minidump {
+    pub buf:           Vec<u8>,
+    pub arch:          Arch,
+    pub system_info:   SystemInfo,
+    /// the captured regions, ordered by address.
+    pub regions:       Vec<MemoryRegion>,
+    pub address_space: SparseAddressSpace,
+    pub images:        Vec<LoadedImage>,
+    pub threads:       Vec<Thread>,
+}
+
+impl Minidump {
+    pub fn from_bytes(buf: &[u8]) -> Result<Minidump> {
+        load_minidump(buf)
+    }
+
+    /// find the loaded image that contains the given address.
+    pub fn get_image(&self, va: VA) -> Option<&LoadedImage> {
+        self.images
+            .iter()
+            .find(|image| image.base_address <= va && va - image.base_address < image.size)
+    }
+
+    /// find the thread with the given ID.
+    pub fn get_thread(&self, id: u32) -> Option<&Thread> {
+        self.threads.iter().find(|thread| thread.id == id)
+    }
+
+    /// create an emulator with every captured region mapped,
+    /// and the registers set from the given thread's context,
+    /// so that emulation can resume from the captured state.
+    #[cfg(feature = "emulator")]
+    pub fn emulator(&self, thread_id: u32) -> Result<crate::emu::Emulator> {
+        use crate::emu::mmu::PAGE_SIZE;
+
+        let thread = match self.get_thread(thread_id) {
+            Some(thread) => thread,
+            None => return Err(MinidumpError::ThreadNotFound(thread_id).into()),
+        };
+
+        let mut emu = crate::emu::Emulator::with_arch(self.arch);
+
+        // regions aren't necessarily page aligned, such as thread stacks,
+        // so map the pages that contain each region, first come first served.
+        let mut mapped: std::collections::HashSet<VA> = Default::default();
+        for region in self.regions.iter() {
+            let start = region.address & !(PAGE_SIZE as u64 - 1);
+            let end = crate::util::align(region.address + region.size, PAGE_SIZE as u64);
+
+            for page in (start..end).step_by(PAGE_SIZE) {
+                if mapped.insert(page) {
+                    emu.mem.mmap(page, PAGE_SIZE as u64, region.permissions)?;
+                }
+            }
+
+            let buf = self.address_space.read_bytes(region.address, region.size as usize)?;
+            let mut offset = 0usize;
+            while offset < buf.len() {
+                let va = region.address + offset as u64;
+                // write up to the end of the page.
+                let size = std::cmp::min(buf.len() - offset, PAGE_SIZE - (va as usize % PAGE_SIZE));
+                emu.mem.poke(va, &buf[offset..offset + size])?;
+                offset += size;
+            }
+        }
+
+        if let Some(context) = &thread.context {
+            emu.reg = context.to_registers();
+        }
+
+        // user-mode code finds the TEB via the fs (x32) or gs (x64) segment.
+        match self.arch {
+            Arch::X32 => emu.set_fsbase(thread.teb),
+            Arch::X64 => emu.set_gsbase(thread.teb),
+        }
+
+        Ok(emu)
+    }
+}
+
+fn malformed(msg: &str) -> anyhow::Error {
+    MinidumpError::MalformedMinidump(msg.to_string()).into()
+}
+
+fn get_bytes(buf: &[u8], offset: u64, size: u64) -> Result<&[u8]> {
+    offset
+        .checked_add(size)
+        .and_then(|end| buf.get(offset as usize..end as usize))
+        .ok_or_else(|| malformed("data out of bounds"))
+}
+
+fn read_u32(buf: &[u8], offset: u64) -> Result<u32> {
+    Ok(LittleEndian::read_u32(get_bytes(buf, offset, 4)?))
+}
+
+fn read_u64(buf: &[u8], offset: u64) -> Result<u64> {
+    Ok(LittleEndian::read_u64(get_bytes(buf, offset, 8)?))
+}
+
+/// read a `MINIDUMP_STRING`: a u32 byte length, followed by UTF-16LE data.
+fn read_string(buf: &[u8], offset: u64) -> Result<String> {
+    let size = read_u32(buf, offset)?;
+    let data = get_bytes(buf, offset + 4, size as u64)?;
+    let chars: Vec<u16> = data.chunks_exact(2).map(LittleEndian::read_u16).collect();
+    Ok(String::from_utf16_lossy(&chars))
+}
+
+/// read the stream directory into a map from stream type to (offset, size).
+/// if there are multiple streams of a type, the first one wins.
+fn read_streams(buf: &[u8]) -> Result<std::collections::BTreeMap<u32, (u64, u64)>> {
+    let header = get_bytes(buf, 0, SIZEOF_MINIDUMP_HEADER as u64)?;
+    if LittleEndian::read_u32(header) != MINIDUMP_SIGNATURE {
+        return Err(MinidumpError::FormatNotSupported("not a minidump".to_string()).into());
+    }
+
+    let count = LittleEndian::read_u32(&header[0x8..]) as u64;
+    let directory = LittleEndian::read_u32(&header[0xC..]) as u64;
+
+    let mut streams: std::collections::BTreeMap<u32, (u64, u64)> = Default::default();
+    for i in 0..count {
+        let entry = get_bytes(
+            buf,
+            directory + i * SIZEOF_MINIDUMP_DIRECTORY as u64,
+            SIZEOF_MINIDUMP_DIRECTORY as u64,
+        )?;
+        let stream_type = LittleEndian::read_u32(entry);
+        let size = LittleEndian::read_u32(&entry[0x4..]) as u64;
+        let offset = LittleEndian::read_u32(&entry[0x8..]) as u64;
+
+        debug!(
+            "minidump: stream: type {} at {:#x} size {:#x}",
+            stream_type, offset, size
+        );
+        streams.entry(stream_type).or_insert((offset, size));
+    }
+
+    Ok(streams)
+}
+
+fn read_system_info(buf: &[u8], offset: u64) -> Result<SystemInfo> {
+    let data = get_bytes(buf, offset, 0x18)?;
+
+    Ok(SystemInfo {
+        processor_architecture: LittleEndian::read_u16(data),
+        major_version:          LittleEndian::read_u32(&data[0x8..]),
+        minor_version:          LittleEndian::read_u32(&data[0xC..]),
+        build_number:           LittleEndian::read_u32(&data[0x10..]),
+    })
+}
+
+/// read the captured regions from `MINIDUMP_MEMORY_LIST`, as (address, size,
+/// file offset).
+fn read_memory_list(buf: &[u8], offset: u64) -> Result<Vec<(VA, RVA, u64)>> {
+    let count = read_u32(buf, offset)? as u64;
+    let mut regions = vec![];
+
+    for i in 0..count {
+        let descriptor = get_bytes(
+            buf,
+            offset + 4 + i * SIZEOF_MINIDUMP_MEMORY_DESCRIPTOR as u64,
+            SIZEOF_MINIDUMP_MEMORY_DESCRIPTOR as u64,
+        )?;
+        regions.push((
+            LittleEndian::read_u64(descriptor),
+            LittleEndian::read_u32(&descriptor[0x8..]) as RVA,
+            LittleEndian::read_u32(&descriptor[0xC..]) as u64,
+        ));
+    }
+
+    Ok(regions)
+}
+
+/// read the captured regions from `MINIDUMP_MEMORY64_LIST`, as (address, size,
+/// file offset). the data of the regions is stored contiguously, in order.
+fn read_memory64_list(buf: &[u8], offset: u64) -> Result<Vec<(VA, RVA, u64)>> {
+    let count = read_u64(buf, offset)?;
+    let mut data_offset = read_u64(buf, offset + 0x8)?;
+    let mut regions = vec![];
+
+    for i in 0..count {
+        let descriptor = get_bytes(
+            buf,
+            offset + 0x10 + i * SIZEOF_MINIDUMP_MEMORY_DESCRIPTOR64 as u64,
+            SIZEOF_MINIDUMP_MEMORY_DESCRIPTOR64 as u64,
+        )?;
+        let address = LittleEndian::read_u64(descriptor);
+        let size = LittleEndian::read_u64(&descriptor[0x8..]);
+
+        regions.push((address, size, data_offset));
+        // the data of later regions is then past the end of the file.
+        data_offset = data_offset.saturating_add(size);
+    }
+
+    Ok(regions)
+}
+
+fn get_permissions(protect: u32) -> Permissions {
+    // ignore modifiers like PAGE_GUARD.
+    match protect & 0xFF {
+        PAGE_NOACCESS => Permissions::empty(),
+        PAGE_READONLY => Permissions::R,
+        PAGE_READWRITE | PAGE_WRITECOPY => Permissions::RW,
+        PAGE_EXECUTE => Permissions::X,
+        PAGE_EXECUTE_READ => Permissions::RX,
+        PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY => Permissions::RWX,
+        _ => Permissions::RWX,
+    }
+}
+
+/// read the protection of each committed region from
+/// `MINIDUMP_MEMORY_INFO_LIST`, as (address, size, permissions).
+fn read_memory_info_list(buf: &[u8], offset: u64) -> Result<Vec<(VA, RVA, Permissions)>> {
+    let header_size = read_u32(buf, offset)? as u64;
+    let entry_size = read_u32(buf, offset + 0x4)? as u64;
+    let count = read_u64(buf, offset + 0x8)?;
+    if entry_size < 0x30 {
+        return Err(malformed("memory info entry too small"));
+    }
+
+    let mut infos = vec![];
+    for i in 0..count {
+        let entry = get_bytes(buf, offset + header_size + i * entry_size, entry_size)?;
+        let address = LittleEndian::read_u64(entry);
+        let size = LittleEndian::read_u64(&entry[0x18..]);
+        let state = LittleEndian::read_u32(&entry[0x20..]);
+        let protect = LittleEndian::read_u32(&entry[0x24..]);
+
+        if state != MEM_COMMIT {
+            continue;
+        }
+
+        infos.push((address, size, get_permissions(protect)));
+    }
+
+    Ok(infos)
+}
+
+fn read_threads(buf: &[u8], arch: Arch, offset: u64) -> Result<Vec<Thread>> {
+    let count = read_u32(buf, offset)? as u64;
+    let mut threads = vec![];
+
+    for i in 0..count {
+        let entry = get_bytes(
+            buf,
+            offset + 4 + i * SIZEOF_MINIDUMP_THREAD as u64,
+            SIZEOF_MINIDUMP_THREAD as u64,
+        )?;
+        let id = LittleEndian::read_u32(entry);
+        let teb = LittleEndian::read_u64(&entry[0x10..]);
+        let stack_start = LittleEndian::read_u64(&entry[0x18..]);
+        let stack_size = LittleEndian::read_u32(&entry[0x20..]) as u64;
+        let context_size = LittleEndian::read_u32(&entry[0x28..]) as u64;
+        let context_offset = LittleEndian::read_u32(&entry[0x2C..]) as u64;
+
+        let context =
+            match get_bytes(buf, context_offset, context_size).and_then(|data| context::read_context(arch, data)) {
+                Ok(context) => Some(context),
+                Err(e) => {
+                    debug!("minidump: thread {:#x}: failed to read context: {}", id, e);
+                    None
+                }
+            };
+
+        debug!("minidump: thread: {:#x} teb: {:#x}", id, teb);
+        threads.push(Thread {
+            id,
+            teb,
+            stack: stack_start..stack_start.saturating_add(stack_size),
+            context,
+        });
+    }
+
+    Ok(threads)
+}
+
+/// read the bytes of the given range from the address space,
+/// with zeros where memory wasn't captured.
+fn read_bytes_zx(address_space: &SparseAddressSpace, address: VA, size: RVA) -> Vec<u8> {
+    let mut buf = vec![0u8; size as usize];
+
+    for region in address_space.regions() {
+        let start = std::cmp::max(region.start, address);
+        let end = std::cmp::min(region.end, address.saturating_add(size));
+        if start >= end {
+            continue;
+        }
+
+        let dest = &mut buf[(start - address) as usize..(end - address) as usize];
+        // the range is known to be mapped.
+        address_space.read_into(start, dest).unwrap();
+    }
+
+    buf
+}
+
+/// create a view of the loaded image at the given address.
+/// if the PE headers were captured, the sections are described by the headers.
+/// otherwise, by the captured regions.
+fn load_image(
+    arch: Arch,
+    address_space: &SparseAddressSpace,
+    regions: &[MemoryRegion],
+    base_address: VA,
+    size: RVA,
+) -> Result<Module> {
+    let buf = read_bytes_zx(address_space, base_address, size);
+
+    match PE::from_memory(&buf) {
+        Ok(pe) => {
+            // the image may be loaded at an address other than its preferred base,
+            // but its relocations have already been applied, so just shift the
+            // sections.
+            let preferred_base_address = pe.module.address_space.base_address;
+            let mut module = pe.module;
+            let rebase = |va: VA| -> Result<VA> {
+                (va - preferred_base_address)
+                    .checked_add(base_address)
+                    .ok_or_else(|| malformed("section extends past the end of memory"))
+            };
+            for section in module.sections.iter_mut() {
+                section.virtual_range = rebase(section.virtual_range.start)?..rebase(section.virtual_range.end)?;
+            }
+            module.address_space.base_address = base_address;
+            Ok(module)
+        }
+        Err(e) => {
+            debug!("minidump: image {:#x}: failed to parse PE: {}", base_address, e);
+
+            let sections = regions
+                .iter()
+                .filter(|region| base_address <= region.address && region.address - base_address < size)
+                .map(|region| Section {
+                    physical_range: (region.address - base_address)..(region.address - base_address + region.size),
+                    virtual_range:  region.address..region.address + region.size,
+                    permissions:    region.permissions,
+                    name:           format!("{:#x}", region.address),
+                })
+                .collect();
+
+            Ok(Module {
+                arch,
+                sections,
+                address_space: RelativeAddressSpace::from_buf(&buf).into_absolute(base_address)?,
+            })
+        }
+    }
+}
+
+fn read_images(
+    buf: &[u8],
+    arch: Arch,
+    address_space: &SparseAddressSpace,
+    regions: &[MemoryRegion],
+    offset: u64,
+) -> Result<Vec<LoadedImage>> {
+    let count = read_u32(buf, offset)? as u64;
+    let mut images = vec![];
+
+    for i in 0..count {
+        let entry = get_bytes(
+            buf,
+            offset + 4 + i * SIZEOF_MINIDUMP_MODULE as u64,
+            SIZEOF_MINIDUMP_MODULE as u64,
+        )?;
+        let base_address = LittleEndian::read_u64(entry);
+        let size = LittleEndian::read_u32(&entry[0x8..]) as RVA;
+        let checksum = LittleEndian::read_u32(&entry[0xC..]);
+        let timestamp = LittleEndian::read_u32(&entry[0x10..]);
+        let name = read_string(buf, LittleEndian::read_u32(&entry[0x14..]) as u64)?;
+
+        debug!("minidump: image: {:#x}: {}", base_address, name);
+        if base_address.checked_add(size).is_none() {
+            debug!("minidump: image {:#x}: extends past the end of memory", base_address);
+            continue;
+        }
+
+        let module = match load_image(arch, address_space, regions, base_address, size) {
+            Ok(module) => module,
+            Err(e) => {
+                debug!("minidump: image {:#x}: failed to load: {}", base_address, e);
+                continue;
+            }
+        };
+
+        images.push(LoadedImage {
+            module,
+            name,
+            base_address,
+            size,
+            checksum,
+            timestamp,
+        });
+    }
+
+    Ok(images)
+}
+
+fn load_minidump(buf: &[u8]) -> Result<Minidump> {
+    let streams = read_streams(buf)?;
+
+    let system_info = match streams.get(&SYSTEM_INFO_STREAM) {
+        Some(&(offset, _)) => read_system_info(buf, offset)?,
+        None => return Err(malformed("no system info stream")),
+    };
+
+    let arch = match system_info.processor_architecture {
+        PROCESSOR_ARCHITECTURE_INTEL => Arch::X32,
+        PROCESSOR_ARCHITECTURE_AMD64 => Arch::X64,
+        arch => {
+            return Err(MinidumpError::FormatNotSupported(format!("processor architecture: {}", arch)).into());
+        }
+    };
+    debug!("minidump: arch: {:?}", arch);
+
+    // full dumps use the 64-bit memory list, and others the 32-bit list.
+    let mut descriptors = vec![];
+    if let Some(&(offset, _)) = streams.get(&MEMORY64_LIST_STREAM) {
+        descriptors.extend(read_memory64_list(buf, offset)?);
+    }
+    if let Some(&(offset, _)) = streams.get(&MEMORY_LIST_STREAM) {
+        descriptors.extend(read_memory_list(buf, offset)?);
+    }
+    descriptors.sort_unstable();
+
+    let infos = match streams.get(&MEMORY_INFO_LIST_STREAM) {
+        Some(&(offset, _)) => read_memory_info_list(buf, offset)?,
+        None => vec![],
+    };
+
+    let mut address_space: SparseAddressSpace = Default::default();
+    let mut regions = vec![];
+    for (address, size, file_offset) in descriptors.into_iter() {
+        // a truncated dump may be missing the end of the data, so keep what we have.
+        let start = std::cmp::min(file_offset as usize, buf.len());
+        let end = std::cmp::min(file_offset.saturating_add(size) as usize, buf.len());
+        let data = &buf[start..end];
+        if (data.len() as u64) < size {
+            debug!("minidump: region {:#x}: truncated", address);
+        }
+
+        if let Err(e) = address_space.map(address, data) {
+            // like a thread stack that's also captured in the memory list.
+            debug!("minidump: region {:#x}: failed to map: {}", address, e);
+            continue;
+        }
+
+        let permissions = infos
+            .iter()
+            .find(|&&(start, size, _)| start <= address && address - start < size)
+            .map(|&(_, _, permissions)| permissions)
+            .unwrap_or(Permissions::RWX);
+
+        debug!(
+            "minidump: region: {:#x} - {:#x} {:?}",
+            address,
+            address + data.len() as u64,
+            permissions
+        );
+        regions.push(MemoryRegion {
+            address,
+            size: data.len() as RVA,
+            permissions,
+            file_offset,
+        });
+    }
+
+    let images = match streams.get(&MODULE_LIST_STREAM) {
+        Some(&(offset, _)) => read_images(buf, arch, &address_space, &regions, offset)?,
+        None => vec![],
+    };
+
+    let threads = match streams.get(&THREAD_LIST_STREAM) {
+        Some(&(offset, _)) => read_threads(buf, arch, offset)?,
+        None => vec![],
+    };
+
+    debug!("minidump: loaded");
+    Ok(Minidump {
+        buf: buf.to_vec(),
+        arch,
+        system_info,
+        regions,
+        address_space,
+        images,
+        threads,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::{arch::Arch, aspace::AddressSpace, loader::minidump::*, rsrc::*, test::dump_pe};
+
+    const K32_BASE: VA = 0x7FF8_0000_0000;
+    const STACK_BASE: VA = 0x70_0000_0000;
+    const TEB: VA = 0x70_0001_0000;
+
+    fn push_u16(buf: &mut Vec<u8>, v: u16) {
+        buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn push_u32(buf: &mut Vec<u8>, v: u32) {
+        buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn push_u64(buf: &mut Vec<u8>, v: u64) {
+        buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    /// build a full minidump of an x64 process with kernel32 loaded
+    /// (at an address other than its preferred base), and one thread.
+    fn build_minidump() -> Vec<u8> {
+        let pe = crate::loader::pe::PE::from_bytes(&get_buf(Rsrc::K32)).unwrap();
+        let image = dump_pe(&pe);
+        let size_of_image = image.len() as u64;
+        let stack = vec![0x41u8; 0x1000];
+
+        // header, then directory of 6 streams, then the streams.
+        let mut buf = vec![];
+        push_u32(&mut buf, MINIDUMP_SIGNATURE);
+        push_u32(&mut buf, 0xA793);
+        push_u32(&mut buf, 6);
+        push_u32(&mut buf, 0x20);
+        buf.resize(0x20, 0);
+        buf.resize(0x20 + 6 * 0xC, 0);
+
+        let mut streams: Vec<(u32, usize, usize)> = vec![];
+
+        // SystemInfo
+        let start = buf.len();
+        push_u16(&mut buf, 9); // AMD64
+        buf.resize(start + 0x8, 0);
+        push_u32(&mut buf, 10);
+        push_u32(&mut buf, 0);
+        push_u32(&mut buf, 19041);
+        buf.resize(start + 0x38, 0);
+        streams.push((SYSTEM_INFO_STREAM, start, buf.len() - start));
+
+        // the module name, as a MINIDUMP_STRING.
+        let name_offset = buf.len();
+        let name: Vec<u16> = "C:\\Windows\\System32\\kernel32.dll".encode_utf16().collect();
+        push_u32(&mut buf, name.len() as u32 * 2);
+        for c in name.iter() {
+            push_u16(&mut buf, *c);
+        }
+        push_u16(&mut buf, 0);
+
+        // ModuleList
+        let start = buf.len();
+        push_u32(&mut buf, 1);
+        push_u64(&mut buf, K32_BASE);
+        push_u32(&mut buf, size_of_image as u32);
+        push_u32(&mut buf, 0x1234);
+        push_u32(&mut buf, 0x5678);
+        push_u32(&mut buf, name_offset as u32);
+        buf.resize(start + 4 + SIZEOF_MINIDUMP_MODULE, 0);
+        streams.push((MODULE_LIST_STREAM, start, buf.len() - start));
+
+        // the thread context.
+        let context_offset = buf.len();
+        let mut context = vec![0u8; 0x4D0];
+        LittleEndian::write_u16(&mut context[0x38..], 0x33);
+        LittleEndian::write_u32(&mut context[0x44..], 0x246);
+        LittleEndian::write_u64(&mut context[0x78..], 0x1);
+        LittleEndian::write_u64(&mut context[0x98..], STACK_BASE + 0x800);
+        LittleEndian::write_u64(&mut context[0xF0..], 0xF);
+        LittleEndian::write_u64(&mut context[0xF8..], K32_BASE + 0x1000);
+        buf.extend_from_slice(&context);
+
+        // ThreadList
+        let start = buf.len();
+        push_u32(&mut buf, 1);
+        push_u32(&mut buf, 0x1F2C);
+        push_u32(&mut buf, 0);
+        push_u32(&mut buf, 0);
+        push_u32(&mut buf, 0);
+        push_u64(&mut buf, TEB);
+        push_u64(&mut buf, STACK_BASE);
+        push_u32(&mut buf, stack.len() as u32);
+        push_u32(&mut buf, 0);
+        push_u32(&mut buf, context.len() as u32);
+        push_u32(&mut buf, context_offset as u32);
+        streams.push((THREAD_LIST_STREAM, start, buf.len() - start));
+
+        // MemoryInfoList
+        let start = buf.len();
+        push_u32(&mut buf, 0x10);
+        push_u32(&mut buf, 0x30);
+        push_u64(&mut buf, 2);
+        for &(address, size, protect) in [
+            (STACK_BASE, stack.len() as u64, PAGE_READWRITE),
+            (K32_BASE, size_of_image, PAGE_EXECUTE_READ),
+        ]
+        .iter()
+        {
+            push_u64(&mut buf, address);
+            push_u64(&mut buf, address);
+            push_u32(&mut buf, protect);
+            push_u32(&mut buf, 0);
+            push_u64(&mut buf, size);
+            push_u32(&mut buf, MEM_COMMIT);
+            push_u32(&mut buf, protect);
+            push_u32(&mut buf, 0x2_0000);
+            push_u32(&mut buf, 0);
+        }
+        streams.push((MEMORY_INFO_LIST_STREAM, start, buf.len() - start));
+
+        // a stream we don't parse.
+        streams.push((0xFFFF, 0, 0));
+
+        // Memory64List, with the data at the end of the file.
+        let start = buf.len();
+        push_u64(&mut buf, 2);
+        push_u64(&mut buf, (start + 0x10 + 2 * 0x10) as u64);
+        push_u64(&mut buf, STACK_BASE);
+        push_u64(&mut buf, stack.len() as u64);
+        push_u64(&mut buf, K32_BASE);
+        push_u64(&mut buf, size_of_image);
+        let size = buf.len() - start;
+        buf.extend_from_slice(&stack);
+        buf.extend_from_slice(&image);
+        streams.push((MEMORY64_LIST_STREAM, start, size));
+
+        for (i, &(stream_type, offset, size)) in streams.iter().enumerate() {
+            let entry = &mut buf[0x20 + i * 0xC..];
+            LittleEndian::write_u32(entry, stream_type);
+            LittleEndian::write_u32(&mut entry[0x4..], size as u32);
+            LittleEndian::write_u32(&mut entry[0x8..], offset as u32);
+        }
+
+        buf
+    }
+
+    #[test]
+    fn full() -> Result<()> {
+        let dmp = Minidump::from_bytes(&build_minidump())?;
+
+        assert!(matches!(dmp.arch, Arch::X64));
+        assert_eq!(19041, dmp.system_info.build_number);
+
+        assert_eq!(2, dmp.regions.len());
+        assert_eq!(STACK_BASE, dmp.regions[0].address);
+        assert_eq!(Permissions::RW, dmp.regions[0].permissions);
+        assert_eq!(Permissions::RX, dmp.regions[1].permissions);
+
+        // one address space, with every region.
+        assert_eq!(0x41, dmp.address_space.read_u8(STACK_BASE + 0xFFF)?);
+        assert!(dmp.address_space.read_u8(STACK_BASE + 0x1000).is_err());
+        assert_eq!(0x4D, dmp.address_space.read_u8(K32_BASE)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn images() -> Result<()> {
+        let dmp = Minidump::from_bytes(&build_minidump())?;
+        let pe = crate::loader::pe::PE::from_bytes(&get_buf(Rsrc::K32))?;
+
+        assert_eq!(1, dmp.images.len());
+        let image = dmp.get_image(K32_BASE + 0x1000).unwrap();
+        assert_eq!("C:\\Windows\\System32\\kernel32.dll", image.name);
+        assert_eq!(0x1234, image.checksum);
+        assert_eq!(0x5678, image.timestamp);
+
+        // the module view is at the actual base address, with the sections from the
+        // PE header.
+        let module = &image.module;
+        assert_eq!(K32_BASE, module.address_space.base_address);
+        assert_eq!(pe.module.sections.len(), module.sections.len());
+        let text = module.sections.iter().find(|section| section.name == ".text").unwrap();
+        assert_eq!(K32_BASE + 0x1000, text.virtual_range.start);
+        assert!(module.probe_va(K32_BASE + 0x1000, Permissions::X));
+        assert_eq!(
+            pe.module.address_space.read_u64(0x1_8000_1000)?,
+            module.address_space.read_u64(K32_BASE + 0x1000)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn threads() -> Result<()> {
+        let dmp = Minidump::from_bytes(&build_minidump())?;
+
+        assert_eq!(1, dmp.threads.len());
+        let thread = dmp.get_thread(0x1F2C).unwrap();
+        assert_eq!(TEB, thread.teb);
+        assert_eq!(STACK_BASE..STACK_BASE + 0x1000, thread.stack);
+
+        let context = thread.context.as_ref().unwrap();
+        assert_eq!(0x1, context.rax);
+        assert_eq!(0xF, context.r15);
+        assert_eq!(STACK_BASE + 0x800, context.rsp);
+        assert_eq!(K32_BASE + 0x1000, context.rip);
+        assert_eq!(0x246, context.rflags);
+        assert_eq!(0x33, context.cs);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "emulator")]
+    #[test]
+    fn emulator() -> Result<()> {
+        let dmp = Minidump::from_bytes(&build_minidump())?;
+        let emu = dmp.emulator(0x1F2C)?;
+
+        assert_eq!(K32_BASE + 0x1000, emu.reg.rip);
+        assert_eq!(STACK_BASE + 0x800, emu.reg.rsp);
+        assert_eq!(TEB, emu.gsbase());
+        assert_eq!(0x41, emu.mem.read_u8(STACK_BASE + 0x800)?);
+        assert_eq!(0x4D, emu.mem.read_u8(K32_BASE)?);
+
+        assert!(dmp.emulator(0x0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn partial() -> Result<()> {
+        // drop the end of the image data.
+        let mut buf = build_minidump();
+        buf.truncate(buf.len() - 0x1000);
+        let dmp = Minidump::from_bytes(&buf)?;
+
+        let image = &dmp.images[0];
+        let last = image.module.sections.last().unwrap();
+        assert!(dmp.address_space.read_u8(last.virtual_range.end - 1).is_err());
+        assert_eq!(0x0, image.module.address_space.read_u8(last.virtual_range.end - 1)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn overflow() -> Result<()> {
+        let stream = |buf: &[u8], i: usize| LittleEndian::read_u32(&buf[0x20 + i * 0xC + 0x8..]) as usize;
+
+        // a region, stack, and image that run past the end of memory.
+        let mut buf = build_minidump();
+        let memory = stream(&buf, 5);
+        LittleEndian::write_u64(&mut buf[memory + 0x10..], 0xFFFF_FFFF_FFFF_F800);
+        let threads = stream(&buf, 2);
+        LittleEndian::write_u64(&mut buf[threads + 4 + 0x18..], 0xFFFF_FFFF_FFFF_FFF0);
+        let modules = stream(&buf, 1);
+        LittleEndian::write_u64(&mut buf[modules + 4..], 0xFFFF_FFFF_FFFF_0000);
+        let dmp = Minidump::from_bytes(&buf)?;
+
+        assert_eq!(1, dmp.regions.len());
+        assert_eq!(K32_BASE, dmp.regions[0].address);
+        assert_eq!(u64::MAX, dmp.threads[0].stack.end);
+        assert!(dmp.images.is_empty());
+
+        // a region whose size runs past the end of the file.
+        let mut buf = build_minidump();
+        let memory = stream(&buf, 5);
+        LittleEndian::write_u64(&mut buf[memory + 0x18..], u64::MAX);
+        let dmp = Minidump::from_bytes(&buf)?;
+        assert_eq!(0x41, dmp.address_space.read_u8(STACK_BASE)?);
+        assert!(dmp.address_space.read_u8(K32_BASE).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<()> {
+        assert!(Minidump::from_bytes(b"").is_err());
+        assert!(Minidump::from_bytes(&get_buf(Rsrc::K32)).is_err());
+
+        let mut buf = build_minidump();
+        buf.truncate(0x40);
+        assert!(Minidump::from_bytes(&buf).is_err());
+
+        Ok(())
+    }
+}
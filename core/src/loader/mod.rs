@@ -1 +1,6 @@
+pub mod coff;
+pub mod elf;
+pub mod macho;
+pub mod minidump;
 pub mod pe;
+pub mod raw;
@@ -0,0 +1,674 @@
+//! Parse the Authenticode signature found in the PE certificate table.
+//!
+//! Unlike the other data directories, the certificate table
+//! (`IMAGE_DIRECTORY_ENTRY_SECURITY`) is referenced by file offset, not RVA,
+//! and is not mapped into memory. So, we work with the raw file data here.
+//!
+//! The table is a list of `WIN_CERTIFICATE` entries, each aligned to 8 bytes.
+//! An Authenticode signature is a PKCS#7 `SignedData` structure whose content
+//! (`SpcIndirectDataContent`) holds the hash of the image. The hash covers the
+//! whole file, except the checksum, the certificate table directory entry, and
+//! the certificate table itself. So, we can recompute it to tell if the file
+//! was modified after it was signed.
+//!
+//! We don't validate the signature or the certificate chain,
+//! only parse out the interesting fields.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#the-attribute-certificate-table-image-only
+//!   - http://download.microsoft.com/download/9/c/5/9c5b2167-8017-4bae-9fde-d599bac8184a/Authenticode_PE.docx
+//!   - https://datatracker.ietf.org/doc/html/rfc2315
+use anyhow::Result;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use log::debug;
+use thiserror::Error;
+
+use crate::loader::pe::PE;
+
+#[derive(Error, Debug)]
+pub enum AuthenticodeError {
+    #[error("malformed signature: {0}")]
+    MalformedSignature(&'static str),
+
+    #[error("unsupported digest algorithm: {0}")]
+    UnsupportedDigestAlgorithm(String),
+}
+
+pub const WIN_CERT_REVISION_1_0: u16 = 0x0100;
+pub const WIN_CERT_REVISION_2_0: u16 = 0x0200;
+
+pub const WIN_CERT_TYPE_X509: u16 = 0x0001;
+pub const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+pub const WIN_CERT_TYPE_RESERVED_1: u16 = 0x0003;
+pub const WIN_CERT_TYPE_TS_STACK_SIGNED: u16 = 0x0004;
+
+const SIZEOF_WIN_CERTIFICATE_HEADER: usize = 0x8;
+
+/// offset of `CheckSum` within the optional header, both PE32 and PE32+.
+const OFFSET_OPTIONAL_HEADER_CHECKSUM: usize = 0x40;
+
+#[derive(Clone, Debug)]
+pub struct WinCertificate {
+    /// the file offset of the entry.
+    pub offset:           usize,
+    pub revision:         u16,
+    /// WIN_CERT_TYPE_*
+    pub certificate_type: u16,
+    /// the certificate, such as a PKCS#7 SignedData structure,
+    /// not including the `WIN_CERTIFICATE` header.
+    pub data:             Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DigestAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    fn from_oid(oid: &str) -> Result<DigestAlgorithm> {
+        match oid {
+            "1.2.840.113549.2.5" => Ok(DigestAlgorithm::Md5),
+            "1.3.14.3.2.26" => Ok(DigestAlgorithm::Sha1),
+            "2.16.840.1.101.3.4.2.1" => Ok(DigestAlgorithm::Sha256),
+            "2.16.840.1.101.3.4.2.2" => Ok(DigestAlgorithm::Sha384),
+            "2.16.840.1.101.3.4.2.3" => Ok(DigestAlgorithm::Sha512),
+            _ => Err(AuthenticodeError::UnsupportedDigestAlgorithm(oid.to_string()).into()),
+        }
+    }
+}
+
+impl std::fmt::Display for DigestAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DigestAlgorithm::Md5 => write!(f, "MD5"),
+            DigestAlgorithm::Sha1 => write!(f, "SHA1"),
+            DigestAlgorithm::Sha256 => write!(f, "SHA256"),
+            DigestAlgorithm::Sha384 => write!(f, "SHA384"),
+            DigestAlgorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+/// An X.509 certificate embedded in the signature.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Certificate {
+    /// big endian, as found in the certificate.
+    pub serial_number: Vec<u8>,
+    /// like: `C=US, O=Microsoft Corporation, CN=Microsoft Windows`
+    pub issuer:        String,
+    pub subject:       String,
+}
+
+/// The entity that signed the content, identified by the issuer and serial
+/// number of its certificate.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Signer {
+    pub issuer:           String,
+    pub serial_number:    Vec<u8>,
+    pub digest_algorithm: DigestAlgorithm,
+}
+
+#[derive(Clone, Debug)]
+pub struct SignedData {
+    /// the algorithm used to compute the image hash.
+    pub digest_algorithm: DigestAlgorithm,
+    /// the image hash, as signed.
+    pub digest:           Vec<u8>,
+    pub certificates:     Vec<Certificate>,
+    pub signers:          Vec<Signer>,
+}
+
+impl SignedData {
+    /// find the certificate of the given signer, if embedded.
+    pub fn get_signer_certificate(&self, signer: &Signer) -> Option<&Certificate> {
+        self.certificates
+            .iter()
+            .find(|cert| cert.issuer == signer.issuer && cert.serial_number == signer.serial_number)
+    }
+}
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_UTF8_STRING: u8 = 0x0C;
+const TAG_PRINTABLE_STRING: u8 = 0x13;
+const TAG_T61_STRING: u8 = 0x14;
+const TAG_IA5_STRING: u8 = 0x16;
+const TAG_BMP_STRING: u8 = 0x1E;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CONTEXT_0: u8 = 0xA0;
+const TAG_CONTEXT_1: u8 = 0xA1;
+
+const OID_SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+const OID_SPC_INDIRECT_DATA: &str = "1.3.6.1.4.1.311.2.1.4";
+
+/// A DER encoded value.
+#[derive(Clone, Copy)]
+struct Tlv<'a> {
+    tag:     u8,
+    content: &'a [u8],
+}
+
+/// a minimal DER reader that supports the subset of ASN.1 found in
+/// Authenticode signatures: single byte tags and definite lengths.
+struct Der<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    fn new(buf: &'a [u8]) -> Der<'a> {
+        Der { buf }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        self.buf.first().cloned()
+    }
+
+    fn read(&mut self) -> Result<Tlv<'a>> {
+        if self.buf.len() < 2 {
+            return Err(AuthenticodeError::MalformedSignature("truncated value").into());
+        }
+
+        let tag = self.buf[0];
+        let (length, header_length) = match self.buf[1] {
+            length if length < 0x80 => (length as usize, 2),
+            0x80 => return Err(AuthenticodeError::MalformedSignature("indefinite length").into()),
+            length => {
+                let count = (length & 0x7F) as usize;
+                if count > 4 || self.buf.len() < 2 + count {
+                    return Err(AuthenticodeError::MalformedSignature("invalid length").into());
+                }
+                let length = self.buf[2..2 + count]
+                    .iter()
+                    .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+                (length, 2 + count)
+            }
+        };
+
+        if self.buf.len() < header_length + length {
+            return Err(AuthenticodeError::MalformedSignature("truncated value").into());
+        }
+
+        let content = &self.buf[header_length..header_length + length];
+        self.buf = &self.buf[header_length + length..];
+
+        Ok(Tlv { tag, content })
+    }
+
+    /// read the next value, which must have the given tag.
+    fn expect(&mut self, tag: u8) -> Result<Tlv<'a>> {
+        let tlv = self.read()?;
+        if tlv.tag != tag {
+            debug!("authenticode: expected tag {:#x}, found {:#x}", tag, tlv.tag);
+            return Err(AuthenticodeError::MalformedSignature("unexpected tag").into());
+        }
+        Ok(tlv)
+    }
+
+    /// read the next value, which must have the given tag,
+    /// and return a reader over its content.
+    fn enter(&mut self, tag: u8) -> Result<Der<'a>> {
+        Ok(Der::new(self.expect(tag)?.content))
+    }
+
+    fn read_oid(&mut self) -> Result<String> {
+        Ok(format_oid(self.expect(TAG_OID)?.content))
+    }
+
+    /// read an `AlgorithmIdentifier`, ignoring its parameters.
+    fn read_digest_algorithm(&mut self) -> Result<DigestAlgorithm> {
+        let mut algorithm = self.enter(TAG_SEQUENCE)?;
+        DigestAlgorithm::from_oid(&algorithm.read_oid()?)
+    }
+}
+
+/// format an OID like `1.2.840.113549.1.7.2`.
+fn format_oid(buf: &[u8]) -> String {
+    let mut components: Vec<u64> = vec![];
+    let mut value = 0u64;
+    for &b in buf.iter() {
+        value = (value << 7) | (b & 0x7F) as u64;
+        if b & 0x80 == 0 {
+            if components.is_empty() {
+                // the first byte encodes the first two components.
+                let first = std::cmp::min(value / 40, 2);
+                components.push(first);
+                components.push(value - first * 40);
+            } else {
+                components.push(value);
+            }
+            value = 0;
+        }
+    }
+
+    components.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(".")
+}
+
+fn format_string(tlv: &Tlv) -> String {
+    match tlv.tag {
+        TAG_UTF8_STRING | TAG_PRINTABLE_STRING | TAG_IA5_STRING => String::from_utf8_lossy(tlv.content).into_owned(),
+        // treat as latin-1
+        TAG_T61_STRING => tlv.content.iter().map(|&c| c as char).collect(),
+        TAG_BMP_STRING => {
+            let chars: Vec<u16> = tlv.content.chunks_exact(2).map(BigEndian::read_u16).collect();
+            String::from_utf16_lossy(&chars)
+        }
+        _ => hex_encode(tlv.content),
+    }
+}
+
+fn hex_encode(buf: &[u8]) -> String {
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn get_attribute_name(oid: &str) -> Option<&'static str> {
+    match oid {
+        "2.5.4.3" => Some("CN"),
+        "2.5.4.5" => Some("serialNumber"),
+        "2.5.4.6" => Some("C"),
+        "2.5.4.7" => Some("L"),
+        "2.5.4.8" => Some("ST"),
+        "2.5.4.9" => Some("street"),
+        "2.5.4.10" => Some("O"),
+        "2.5.4.11" => Some("OU"),
+        "1.2.840.113549.1.9.1" => Some("emailAddress"),
+        _ => None,
+    }
+}
+
+/// format an X.501 `Name` like `C=US, O=Microsoft Corporation, CN=...`.
+fn format_name(name: &[u8]) -> Result<String> {
+    let mut parts: Vec<String> = vec![];
+
+    let mut rdns = Der::new(name);
+    while !rdns.is_empty() {
+        let mut rdn = rdns.enter(TAG_SET)?;
+        while !rdn.is_empty() {
+            let mut attribute = rdn.enter(TAG_SEQUENCE)?;
+            let oid = attribute.read_oid()?;
+            let value = format_string(&attribute.read()?);
+
+            match get_attribute_name(&oid) {
+                Some(name) => parts.push(format!("{}={}", name, value)),
+                None => parts.push(format!("{}={}", oid, value)),
+            }
+        }
+    }
+
+    Ok(parts.join(", "))
+}
+
+// ```
+// Certificate ::= SEQUENCE {
+//   tbsCertificate ::= SEQUENCE {
+//     version         [0] EXPLICIT Version DEFAULT v1,
+//     serialNumber    INTEGER,
+//     signature       AlgorithmIdentifier,
+//     issuer          Name,
+//     validity        Validity,
+//     subject         Name,
+//     ...
+//   },
+//   ...
+// }
+// ```
+fn parse_certificate(buf: &[u8]) -> Result<Certificate> {
+    let mut certificate = Der::new(buf);
+    let mut tbs = certificate.enter(TAG_SEQUENCE)?;
+
+    if tbs.peek_tag() == Some(TAG_CONTEXT_0) {
+        tbs.read()?;
+    }
+    let serial_number = tbs.expect(TAG_INTEGER)?.content.to_vec();
+    tbs.expect(TAG_SEQUENCE)?;
+    let issuer = format_name(tbs.expect(TAG_SEQUENCE)?.content)?;
+    tbs.expect(TAG_SEQUENCE)?;
+    let subject = format_name(tbs.expect(TAG_SEQUENCE)?.content)?;
+
+    Ok(Certificate {
+        serial_number,
+        issuer,
+        subject,
+    })
+}
+
+// ```
+// SignerInfo ::= SEQUENCE {
+//   version                   INTEGER,
+//   issuerAndSerialNumber     SEQUENCE {
+//     issuer                    Name,
+//     serialNumber              INTEGER,
+//   },
+//   digestAlgorithm           AlgorithmIdentifier,
+//   ...
+// }
+// ```
+fn parse_signer(buf: &[u8]) -> Result<Signer> {
+    let mut signer = Der::new(buf);
+    signer.expect(TAG_INTEGER)?;
+
+    let mut issuer_and_serial_number = signer.enter(TAG_SEQUENCE)?;
+    let issuer = format_name(issuer_and_serial_number.expect(TAG_SEQUENCE)?.content)?;
+    let serial_number = issuer_and_serial_number.expect(TAG_INTEGER)?.content.to_vec();
+
+    let digest_algorithm = signer.read_digest_algorithm()?;
+
+    Ok(Signer {
+        issuer,
+        serial_number,
+        digest_algorithm,
+    })
+}
+
+// ```
+// ContentInfo ::= SEQUENCE {
+//   contentType               OID (signedData),
+//   content [0] EXPLICIT SignedData ::= SEQUENCE {
+//     version                 INTEGER,
+//     digestAlgorithms        SET OF AlgorithmIdentifier,
+//     contentInfo             SEQUENCE {
+//       contentType             OID (SPC_INDIRECT_DATA),
+//       content [0] EXPLICIT SpcIndirectDataContent ::= SEQUENCE {
+//         data                    SpcAttributeTypeAndOptionalValue,
+//         messageDigest           DigestInfo ::= SEQUENCE {
+//           digestAlgorithm         AlgorithmIdentifier,
+//           digest                  OCTETSTRING,
+//         }
+//       }
+//     },
+//     certificates            [0] IMPLICIT SET OF Certificate OPTIONAL,
+//     crls                    [1] IMPLICIT SET OF CRL OPTIONAL,
+//     signerInfos             SET OF SignerInfo,
+//   }
+// }
+// ```
+/// parse a PKCS#7 SignedData structure that contains an Authenticode
+/// signature.
+pub fn parse_signed_data(buf: &[u8]) -> Result<SignedData> {
+    let mut content_info = Der::new(buf).enter(TAG_SEQUENCE)?;
+    if content_info.read_oid()? != OID_SIGNED_DATA {
+        return Err(AuthenticodeError::MalformedSignature("not SignedData").into());
+    }
+
+    let mut signed_data = content_info.enter(TAG_CONTEXT_0)?.enter(TAG_SEQUENCE)?;
+    signed_data.expect(TAG_INTEGER)?;
+    signed_data.expect(TAG_SET)?;
+
+    let mut spc_content_info = signed_data.enter(TAG_SEQUENCE)?;
+    if spc_content_info.read_oid()? != OID_SPC_INDIRECT_DATA {
+        return Err(AuthenticodeError::MalformedSignature("not SpcIndirectDataContent").into());
+    }
+    let mut spc_indirect_data = spc_content_info.enter(TAG_CONTEXT_0)?.enter(TAG_SEQUENCE)?;
+    spc_indirect_data.expect(TAG_SEQUENCE)?;
+    let mut digest_info = spc_indirect_data.enter(TAG_SEQUENCE)?;
+    let digest_algorithm = digest_info.read_digest_algorithm()?;
+    let digest = digest_info.expect(TAG_OCTET_STRING)?.content.to_vec();
+
+    let mut certificates = vec![];
+    if signed_data.peek_tag() == Some(TAG_CONTEXT_0) {
+        let mut certs = signed_data.enter(TAG_CONTEXT_0)?;
+        while !certs.is_empty() {
+            let cert = certs.read()?;
+            if cert.tag != TAG_SEQUENCE {
+                // such as an attribute certificate.
+                continue;
+            }
+            certificates.push(parse_certificate(cert.content)?);
+        }
+    }
+
+    if signed_data.peek_tag() == Some(TAG_CONTEXT_1) {
+        signed_data.read()?;
+    }
+
+    let mut signers = vec![];
+    let mut signer_infos = signed_data.enter(TAG_SET)?;
+    while !signer_infos.is_empty() {
+        signers.push(parse_signer(signer_infos.expect(TAG_SEQUENCE)?.content)?);
+    }
+
+    Ok(SignedData {
+        digest_algorithm,
+        digest,
+        certificates,
+        signers,
+    })
+}
+
+/// fetch the file offset and size of the certificate table, if present.
+pub fn get_certificate_table(pe: &PE) -> Option<(usize, usize)> {
+    let opt = pe.header.optional_header?;
+    let table = opt.data_directories.get_certificate_table().as_ref()?;
+    if table.virtual_address == 0 || table.size == 0 {
+        return None;
+    }
+
+    Some((table.virtual_address as usize, table.size as usize))
+}
+
+/// walk the `WIN_CERTIFICATE` entries in the certificate table.
+pub fn read_certificates(pe: &PE) -> Result<Vec<WinCertificate>> {
+    let mut certificates = vec![];
+
+    let (start, size) = match get_certificate_table(pe) {
+        Some(table) => table,
+        None => return Ok(certificates),
+    };
+    debug!("authenticode: certificate table: {:#x}", start);
+
+    let end = std::cmp::min(start + size, pe.buf.len());
+    let mut offset = start;
+    while offset + SIZEOF_WIN_CERTIFICATE_HEADER <= end {
+        let length = LittleEndian::read_u32(&pe.buf[offset..]) as usize;
+        if length < SIZEOF_WIN_CERTIFICATE_HEADER || offset + length > end {
+            debug!("authenticode: invalid certificate length: {:#x}", length);
+            break;
+        }
+
+        certificates.push(WinCertificate {
+            offset,
+            revision: LittleEndian::read_u16(&pe.buf[offset + 0x4..]),
+            certificate_type: LittleEndian::read_u16(&pe.buf[offset + 0x6..]),
+            data: pe.buf[offset + SIZEOF_WIN_CERTIFICATE_HEADER..offset + length].to_vec(),
+        });
+
+        // entries are 8-byte aligned.
+        offset += crate::util::align(length as u64, 8) as usize;
+    }
+
+    Ok(certificates)
+}
+
+/// parse the Authenticode signatures found in the certificate table.
+pub fn read_signatures(pe: &PE) -> Result<Vec<SignedData>> {
+    read_certificates(pe)?
+        .iter()
+        .filter(|cert| cert.certificate_type == WIN_CERT_TYPE_PKCS_SIGNED_DATA)
+        .map(|cert| parse_signed_data(&cert.data))
+        .collect()
+}
+
+fn digest<D: sha2::Digest>(regions: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = D::new();
+    for region in regions.iter() {
+        hasher.update(region);
+    }
+    hasher.finalize().to_vec()
+}
+
+/// compute the Authenticode hash of the image, which covers the file data
+/// except the checksum, the certificate table data directory entry, and the
+/// certificate table.
+pub fn compute_authenticode_hash(pe: &PE, algorithm: DigestAlgorithm) -> Result<Vec<u8>> {
+    let buf = &pe.buf[..];
+
+    let optional_header = pe.header.dos_header.pe_pointer as usize + 0x4 + goblin::pe::header::SIZEOF_COFF_HEADER;
+    let checksum = optional_header + OFFSET_OPTIONAL_HEADER_CHECKSUM;
+    let certificate_table_entry = optional_header
+        + match pe.module.arch {
+            crate::arch::Arch::X32 => 0x60,
+            crate::arch::Arch::X64 => 0x70,
+        }
+        + super::IMAGE_DIRECTORY_ENTRY_SECURITY * 0x8;
+
+    if certificate_table_entry + 0x8 > buf.len() {
+        return Err(super::PEError::MalformedPEFile("header too small".to_string()).into());
+    }
+
+    // the certificate table follows the headers, and is found within the file.
+    let (table_start, table_end) = match get_certificate_table(pe) {
+        Some((start, size)) => match start.checked_add(size) {
+            Some(end) if start >= certificate_table_entry + 0x8 && end <= buf.len() => (start, end),
+            _ => {
+                return Err(super::PEError::MalformedPEFile("invalid certificate table".to_string()).into());
+            }
+        },
+        None => (buf.len(), buf.len()),
+    };
+
+    let regions = [
+        &buf[..checksum],
+        &buf[checksum + 0x4..certificate_table_entry],
+        &buf[certificate_table_entry + 0x8..table_start],
+        &buf[table_end..],
+    ];
+
+    Ok(match algorithm {
+        DigestAlgorithm::Md5 => digest::<md5::Md5>(&regions),
+        DigestAlgorithm::Sha1 => digest::<sha1::Sha1>(&regions),
+        DigestAlgorithm::Sha256 => digest::<sha2::Sha256>(&regions),
+        DigestAlgorithm::Sha384 => digest::<sha2::Sha384>(&regions),
+        DigestAlgorithm::Sha512 => digest::<sha2::Sha512>(&regions),
+    })
+}
+
+/// recompute the image hash and compare it with the hash found in the
+/// signature. if they differ, the file was modified after it was signed.
+///
+/// note: this doesn't verify that the signature or certificates are valid.
+pub fn is_authenticode_hash_valid(pe: &PE, signature: &SignedData) -> Result<bool> {
+    Ok(compute_authenticode_hash(pe, signature.digest_algorithm)? == signature.digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::pe::authenticode::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let certificates = read_certificates(&pe)?;
+        assert_eq!(1, certificates.len());
+        assert_eq!(0xAC400, certificates[0].offset);
+        assert_eq!(WIN_CERT_REVISION_2_0, certificates[0].revision);
+        assert_eq!(WIN_CERT_TYPE_PKCS_SIGNED_DATA, certificates[0].certificate_type);
+
+        let signatures = read_signatures(&pe)?;
+        assert_eq!(1, signatures.len());
+        let signature = &signatures[0];
+        assert_eq!(DigestAlgorithm::Sha256, signature.digest_algorithm);
+        assert_eq!(
+            "3f2176cf0cc0815ccdc92b36fe2dd2a2c839e51f72561e92a0c799d151a1a778",
+            hex_encode(&signature.digest)
+        );
+
+        assert_eq!(1, signature.signers.len());
+        let signer = &signature.signers[0];
+        let cert = signature.get_signer_certificate(signer).unwrap();
+        assert_eq!(
+            "C=US, ST=Washington, L=Redmond, O=Microsoft Corporation, CN=Microsoft Windows",
+            cert.subject
+        );
+        assert_eq!(
+            "C=US, ST=Washington, L=Redmond, O=Microsoft Corporation, CN=Microsoft Windows Production PCA 2011",
+            cert.issuer
+        );
+        assert_eq!(
+            "330000016268d12e79c420780f000000000162",
+            hex_encode(&cert.serial_number)
+        );
+
+        assert!(is_authenticode_hash_valid(&pe, signature)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32_tampered() -> Result<()> {
+        let mut buf = get_buf(Rsrc::K32);
+        // patch a byte in the .text section
+        buf[0x1000] ^= 0xFF;
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let signatures = read_signatures(&pe)?;
+        assert!(!is_authenticode_hash_valid(&pe, &signatures[0])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32_invalid_table() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let e_lfanew = LittleEndian::read_u32(&buf[0x3C..]) as usize;
+        let directory = e_lfanew + 4 + 20 + 0x70 + crate::loader::pe::IMAGE_DIRECTORY_ENTRY_SECURITY * 0x8;
+
+        // the certificate table points within the headers.
+        let mut patched = buf.clone();
+        LittleEndian::write_u32(&mut patched[directory..], 0x10);
+        let pe = crate::loader::pe::PE::from_bytes(&patched)?;
+        assert!(compute_authenticode_hash(&pe, DigestAlgorithm::Sha256).is_err());
+
+        // the certificate table extends past the end of the file.
+        let mut patched = buf.clone();
+        LittleEndian::write_u32(&mut patched[directory + 0x4..], 0xFFFF_FFF0);
+        let pe = crate::loader::pe::PE::from_bytes(&patched)?;
+        assert!(compute_authenticode_hash(&pe, DigestAlgorithm::Sha256).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tiny() -> Result<()> {
+        let buf = get_buf(Rsrc::TINY);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert_eq!(0, read_certificates(&pe)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let signatures = read_signatures(&pe)?;
+        assert_eq!(1, signatures.len());
+        let signature = &signatures[0];
+        assert_eq!(DigestAlgorithm::Sha1, signature.digest_algorithm);
+        assert_eq!(
+            "08330728485872feb9b3fae23afe5da1e2a1cc74",
+            hex_encode(&signature.digest)
+        );
+        assert!(is_authenticode_hash_valid(&pe, signature)?);
+
+        let signer = &signature.signers[0];
+        let cert = signature.get_signer_certificate(signer).unwrap();
+        assert!(cert.subject.contains("CN=Open Source Developer, Benjamin Delpy"));
+
+        Ok(())
+    }
+}
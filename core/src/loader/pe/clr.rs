@@ -0,0 +1,1256 @@
+//! Parse the .NET CLR header and metadata.
+//!
+//! Managed assemblies are PE files with a CLR header
+//! (`IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR`) that references the metadata.
+//! The metadata root lists a handful of streams:
+//!
+//!   - `#~` (or `#-`): the metadata tables, like TypeDef and MethodDef,
+//!   - `#Strings`: NULL-terminated UTF-8 identifiers,
+//!   - `#US`: user strings, that is, string literals, in UTF-16,
+//!   - `#Blob`: signatures and other binary data,
+//!   - `#GUID`: GUIDs, such as the module version ID.
+//!
+//! Each table has a fixed schema, but the width of its columns depends on the
+//! sizes of the heaps and the row counts of the referenced tables.
+//! So, we compute the layout of every table, even though we only parse the
+//! core tables: TypeDef, MethodDef, MemberRef, ModuleRef, and ImplMap.
+//!
+//! The IL method bodies and the metadata live in the `.text` section,
+//! alongside the little native code found in an assembly (typically, a stub
+//! that jumps to `mscoree!_CorExeMain`). So, the native analysis passes use
+//! `get_managed_ranges` to avoid treating IL as x86 code.
+//!
+//! references:
+//!   - https://www.ecma-international.org/publications-and-standards/standards/ecma-335/
+//!     Partition II, sections 22-25.
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+use thiserror::Error;
+
+use crate::{
+    aspace::AddressSpace,
+    loader::pe::{debug::Guid, DataDirectory, IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR, PE},
+    RVA, VA,
+};
+
+#[derive(Error, Debug)]
+pub enum ClrError {
+    #[error("malformed metadata: {0}")]
+    MalformedMetadata(&'static str),
+}
+
+pub const COMIMAGE_FLAGS_ILONLY: u32 = 0x0000_0001;
+pub const COMIMAGE_FLAGS_32BITREQUIRED: u32 = 0x0000_0002;
+pub const COMIMAGE_FLAGS_IL_LIBRARY: u32 = 0x0000_0004;
+pub const COMIMAGE_FLAGS_STRONGNAMESIGNED: u32 = 0x0000_0008;
+pub const COMIMAGE_FLAGS_NATIVE_ENTRYPOINT: u32 = 0x0000_0010;
+pub const COMIMAGE_FLAGS_TRACKDEBUGDATA: u32 = 0x0001_0000;
+pub const COMIMAGE_FLAGS_32BITPREFERRED: u32 = 0x0002_0000;
+
+/// `MethodAttributes.PinvokeImpl`: the method is implemented by native code,
+/// as described by an ImplMap row.
+pub const METHOD_ATTRIBUTE_PINVOKE_IMPL: u16 = 0x2000;
+
+/// `MethodImplAttributes.CodeTypeMask`
+pub const METHOD_IMPL_CODE_TYPE_MASK: u16 = 0x0003;
+pub const METHOD_IMPL_CODE_TYPE_IL: u16 = 0x0000;
+pub const METHOD_IMPL_CODE_TYPE_NATIVE: u16 = 0x0001;
+
+/// `BSJB`
+const METADATA_SIGNATURE: u32 = 0x424A_5342;
+
+const SIZEOF_IMAGE_COR20_HEADER: usize = 0x48;
+
+/// `IMAGE_COR20_HEADER`
+#[derive(Clone, Debug)]
+pub struct Cor20Header {
+    pub major_runtime_version: u16,
+    pub minor_runtime_version: u16,
+    pub metadata:              DataDirectory,
+    /// COMIMAGE_FLAGS_*
+    pub flags:                 u32,
+    /// a MethodDef (or File) token,
+    /// or if `COMIMAGE_FLAGS_NATIVE_ENTRYPOINT` is set, an RVA.
+    pub entry_point:           u32,
+    pub resources:             Option<DataDirectory>,
+    pub strong_name_signature: Option<DataDirectory>,
+    pub vtable_fixups:         Option<DataDirectory>,
+}
+
+impl Cor20Header {
+    pub fn is_il_only(&self) -> bool {
+        self.flags & COMIMAGE_FLAGS_ILONLY > 0
+    }
+}
+
+fn read_directory(buf: &[u8], base_address: VA) -> Option<DataDirectory> {
+    let rva = LittleEndian::read_u32(buf);
+    let size = LittleEndian::read_u32(&buf[0x4..]);
+    if rva == 0 || size == 0 {
+        None
+    } else {
+        Some(DataDirectory {
+            address: base_address + rva as RVA,
+            size:    size as RVA,
+        })
+    }
+}
+
+/// read the CLR header, if this is a .NET assembly.
+pub fn read_cor20_header(pe: &PE) -> Result<Option<Cor20Header>> {
+    let directory = match pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR)? {
+        Some(directory) if directory.size > 0 => directory,
+        _ => return Ok(None),
+    };
+
+    let base_address = pe.module.address_space.base_address;
+    let buf = pe
+        .module
+        .address_space
+        .read_bytes(directory.address, SIZEOF_IMAGE_COR20_HEADER)?;
+
+    let metadata = match read_directory(&buf[0x8..], base_address) {
+        Some(metadata) => metadata,
+        None => return Err(ClrError::MalformedMetadata("no metadata directory").into()),
+    };
+
+    Ok(Some(Cor20Header {
+        major_runtime_version: LittleEndian::read_u16(&buf[0x4..]),
+        minor_runtime_version: LittleEndian::read_u16(&buf[0x6..]),
+        metadata,
+        flags: LittleEndian::read_u32(&buf[0x10..]),
+        entry_point: LittleEndian::read_u32(&buf[0x14..]),
+        resources: read_directory(&buf[0x18..], base_address),
+        strong_name_signature: read_directory(&buf[0x20..], base_address),
+        vtable_fixups: read_directory(&buf[0x30..], base_address),
+    }))
+}
+
+/// The metadata tables, in the order they're stored in the `#~` stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MetadataTable {
+    Module               = 0x00,
+    TypeRef              = 0x01,
+    TypeDef              = 0x02,
+    FieldPtr             = 0x03,
+    Field                = 0x04,
+    MethodPtr            = 0x05,
+    MethodDef            = 0x06,
+    ParamPtr             = 0x07,
+    Param                = 0x08,
+    InterfaceImpl        = 0x09,
+    MemberRef            = 0x0A,
+    Constant             = 0x0B,
+    CustomAttribute      = 0x0C,
+    FieldMarshal         = 0x0D,
+    DeclSecurity         = 0x0E,
+    ClassLayout          = 0x0F,
+    FieldLayout          = 0x10,
+    StandAloneSig        = 0x11,
+    EventMap             = 0x12,
+    EventPtr             = 0x13,
+    Event                = 0x14,
+    PropertyMap          = 0x15,
+    PropertyPtr          = 0x16,
+    Property             = 0x17,
+    MethodSemantics      = 0x18,
+    MethodImpl           = 0x19,
+    ModuleRef            = 0x1A,
+    TypeSpec             = 0x1B,
+    ImplMap              = 0x1C,
+    FieldRVA             = 0x1D,
+    EncLog               = 0x1E,
+    EncMap               = 0x1F,
+    Assembly             = 0x20,
+    AssemblyProcessor    = 0x21,
+    AssemblyOS           = 0x22,
+    AssemblyRef          = 0x23,
+    AssemblyRefProcessor = 0x24,
+    AssemblyRefOS        = 0x25,
+    File                 = 0x26,
+    ExportedType         = 0x27,
+    ManifestResource     = 0x28,
+    NestedClass          = 0x29,
+    GenericParam         = 0x2A,
+    MethodSpec           = 0x2B,
+    GenericParamConstraint = 0x2C,
+}
+
+const METADATA_TABLES: [MetadataTable; 0x2D] = [
+    MetadataTable::Module,
+    MetadataTable::TypeRef,
+    MetadataTable::TypeDef,
+    MetadataTable::FieldPtr,
+    MetadataTable::Field,
+    MetadataTable::MethodPtr,
+    MetadataTable::MethodDef,
+    MetadataTable::ParamPtr,
+    MetadataTable::Param,
+    MetadataTable::InterfaceImpl,
+    MetadataTable::MemberRef,
+    MetadataTable::Constant,
+    MetadataTable::CustomAttribute,
+    MetadataTable::FieldMarshal,
+    MetadataTable::DeclSecurity,
+    MetadataTable::ClassLayout,
+    MetadataTable::FieldLayout,
+    MetadataTable::StandAloneSig,
+    MetadataTable::EventMap,
+    MetadataTable::EventPtr,
+    MetadataTable::Event,
+    MetadataTable::PropertyMap,
+    MetadataTable::PropertyPtr,
+    MetadataTable::Property,
+    MetadataTable::MethodSemantics,
+    MetadataTable::MethodImpl,
+    MetadataTable::ModuleRef,
+    MetadataTable::TypeSpec,
+    MetadataTable::ImplMap,
+    MetadataTable::FieldRVA,
+    MetadataTable::EncLog,
+    MetadataTable::EncMap,
+    MetadataTable::Assembly,
+    MetadataTable::AssemblyProcessor,
+    MetadataTable::AssemblyOS,
+    MetadataTable::AssemblyRef,
+    MetadataTable::AssemblyRefProcessor,
+    MetadataTable::AssemblyRefOS,
+    MetadataTable::File,
+    MetadataTable::ExportedType,
+    MetadataTable::ManifestResource,
+    MetadataTable::NestedClass,
+    MetadataTable::GenericParam,
+    MetadataTable::MethodSpec,
+    MetadataTable::GenericParamConstraint,
+];
+
+impl MetadataTable {
+    pub fn from_u8(v: u8) -> Option<MetadataTable> {
+        METADATA_TABLES.get(v as usize).cloned()
+    }
+}
+
+/// A metadata token, like `0x06000001`:
+/// the table in the high byte, and the 1-based row in the low three bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Token(pub u32);
+
+impl Token {
+    pub fn new(table: MetadataTable, row: u32) -> Token {
+        Token(((table as u32) << 24) | (row & 0x00FF_FFFF))
+    }
+
+    pub fn table(&self) -> Option<MetadataTable> {
+        MetadataTable::from_u8((self.0 >> 24) as u8)
+    }
+
+    pub fn row(&self) -> u32 {
+        self.0 & 0x00FF_FFFF
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#010x}", self.0)
+    }
+}
+
+/// An index that may reference one of a few tables,
+/// with the table encoded in the low bits.
+#[derive(Clone, Copy, Debug)]
+enum CodedIndex {
+    TypeDefOrRef,
+    HasConstant,
+    HasCustomAttribute,
+    HasFieldMarshal,
+    HasDeclSecurity,
+    MemberRefParent,
+    HasSemantics,
+    MethodDefOrRef,
+    MemberForwarded,
+    Implementation,
+    CustomAttributeType,
+    ResolutionScope,
+    TypeOrMethodDef,
+}
+
+impl CodedIndex {
+    fn tag_bits(&self) -> u32 {
+        match self {
+            CodedIndex::TypeDefOrRef => 2,
+            CodedIndex::HasConstant => 2,
+            CodedIndex::HasCustomAttribute => 5,
+            CodedIndex::HasFieldMarshal => 1,
+            CodedIndex::HasDeclSecurity => 2,
+            CodedIndex::MemberRefParent => 3,
+            CodedIndex::HasSemantics => 1,
+            CodedIndex::MethodDefOrRef => 1,
+            CodedIndex::MemberForwarded => 1,
+            CodedIndex::Implementation => 2,
+            CodedIndex::CustomAttributeType => 3,
+            CodedIndex::ResolutionScope => 2,
+            CodedIndex::TypeOrMethodDef => 1,
+        }
+    }
+
+    /// the tables referenced by each tag. some tags are unused.
+    fn tables(&self) -> &'static [Option<MetadataTable>] {
+        use MetadataTable::*;
+        match self {
+            CodedIndex::TypeDefOrRef => &[Some(TypeDef), Some(TypeRef), Some(TypeSpec)],
+            CodedIndex::HasConstant => &[Some(Field), Some(Param), Some(Property)],
+            CodedIndex::HasCustomAttribute => &[
+                Some(MethodDef),
+                Some(Field),
+                Some(TypeRef),
+                Some(TypeDef),
+                Some(Param),
+                Some(InterfaceImpl),
+                Some(MemberRef),
+                Some(Module),
+                Some(DeclSecurity),
+                Some(Property),
+                Some(Event),
+                Some(StandAloneSig),
+                Some(ModuleRef),
+                Some(TypeSpec),
+                Some(Assembly),
+                Some(AssemblyRef),
+                Some(File),
+                Some(ExportedType),
+                Some(ManifestResource),
+                Some(GenericParam),
+                Some(GenericParamConstraint),
+                Some(MethodSpec),
+            ],
+            CodedIndex::HasFieldMarshal => &[Some(Field), Some(Param)],
+            CodedIndex::HasDeclSecurity => &[Some(TypeDef), Some(MethodDef), Some(Assembly)],
+            CodedIndex::MemberRefParent => &[
+                Some(TypeDef),
+                Some(TypeRef),
+                Some(ModuleRef),
+                Some(MethodDef),
+                Some(TypeSpec),
+            ],
+            CodedIndex::HasSemantics => &[Some(Event), Some(Property)],
+            CodedIndex::MethodDefOrRef => &[Some(MethodDef), Some(MemberRef)],
+            CodedIndex::MemberForwarded => &[Some(Field), Some(MethodDef)],
+            CodedIndex::Implementation => &[Some(File), Some(AssemblyRef), Some(ExportedType)],
+            CodedIndex::CustomAttributeType => &[None, None, Some(MethodDef), Some(MemberRef), None],
+            CodedIndex::ResolutionScope => &[Some(Module), Some(ModuleRef), Some(AssemblyRef), Some(TypeRef)],
+            CodedIndex::TypeOrMethodDef => &[Some(TypeDef), Some(MethodDef)],
+        }
+    }
+
+    /// decode the value into a token, or `None` if its null or invalid.
+    fn decode(&self, value: u32) -> Option<Token> {
+        let tag = value & ((1 << self.tag_bits()) - 1);
+        let row = value >> self.tag_bits();
+        if row == 0 {
+            return None;
+        }
+
+        let table = (*self.tables().get(tag as usize)?)?;
+        Some(Token::new(table, row))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Column {
+    U16,
+    U32,
+    String,
+    Guid,
+    Blob,
+    Index(MetadataTable),
+    Coded(CodedIndex),
+}
+
+/// the columns of each table, from ECMA-335 Partition II, section 22.
+fn get_table_schema(table: MetadataTable) -> &'static [Column] {
+    use CodedIndex::*;
+    use Column::*;
+    use MetadataTable as T;
+
+    match table {
+        T::Module => &[U16, String, Guid, Guid, Guid],
+        T::TypeRef => &[Coded(ResolutionScope), String, String],
+        T::TypeDef => &[
+            U32,
+            String,
+            String,
+            Coded(TypeDefOrRef),
+            Index(T::Field),
+            Index(T::MethodDef),
+        ],
+        T::FieldPtr => &[Index(T::Field)],
+        T::Field => &[U16, String, Blob],
+        T::MethodPtr => &[Index(T::MethodDef)],
+        T::MethodDef => &[U32, U16, U16, String, Blob, Index(T::Param)],
+        T::ParamPtr => &[Index(T::Param)],
+        T::Param => &[U16, U16, String],
+        T::InterfaceImpl => &[Index(T::TypeDef), Coded(TypeDefOrRef)],
+        T::MemberRef => &[Coded(MemberRefParent), String, Blob],
+        // the type is a single byte followed by a padding byte.
+        T::Constant => &[U16, Coded(HasConstant), Blob],
+        T::CustomAttribute => &[Coded(HasCustomAttribute), Coded(CustomAttributeType), Blob],
+        T::FieldMarshal => &[Coded(HasFieldMarshal), Blob],
+        T::DeclSecurity => &[U16, Coded(HasDeclSecurity), Blob],
+        T::ClassLayout => &[U16, U32, Index(T::TypeDef)],
+        T::FieldLayout => &[U32, Index(T::Field)],
+        T::StandAloneSig => &[Blob],
+        T::EventMap => &[Index(T::TypeDef), Index(T::Event)],
+        T::EventPtr => &[Index(T::Event)],
+        T::Event => &[U16, String, Coded(TypeDefOrRef)],
+        T::PropertyMap => &[Index(T::TypeDef), Index(T::Property)],
+        T::PropertyPtr => &[Index(T::Property)],
+        T::Property => &[U16, String, Blob],
+        T::MethodSemantics => &[U16, Index(T::MethodDef), Coded(HasSemantics)],
+        T::MethodImpl => &[Index(T::TypeDef), Coded(MethodDefOrRef), Coded(MethodDefOrRef)],
+        T::ModuleRef => &[String],
+        T::TypeSpec => &[Blob],
+        T::ImplMap => &[U16, Coded(MemberForwarded), String, Index(T::ModuleRef)],
+        T::FieldRVA => &[U32, Index(T::Field)],
+        T::EncLog => &[U32, U32],
+        T::EncMap => &[U32],
+        T::Assembly => &[U32, U16, U16, U16, U16, U32, Blob, String, String],
+        T::AssemblyProcessor => &[U32],
+        T::AssemblyOS => &[U32, U32, U32],
+        T::AssemblyRef => &[U16, U16, U16, U16, U32, Blob, String, String, Blob],
+        T::AssemblyRefProcessor => &[U32, Index(T::AssemblyRef)],
+        T::AssemblyRefOS => &[U32, U32, U32, Index(T::AssemblyRef)],
+        T::File => &[U32, String, Blob],
+        T::ExportedType => &[U32, U32, String, String, Coded(Implementation)],
+        T::ManifestResource => &[U32, U32, String, Coded(Implementation)],
+        T::NestedClass => &[Index(T::TypeDef), Index(T::TypeDef)],
+        T::GenericParam => &[U16, U16, Coded(TypeOrMethodDef), String],
+        T::MethodSpec => &[Coded(MethodDefOrRef), Blob],
+        T::GenericParamConstraint => &[Index(T::GenericParam), Coded(TypeDefOrRef)],
+    }
+}
+
+const HEAP_SIZE_STRING: u8 = 0x01;
+const HEAP_SIZE_GUID: u8 = 0x02;
+const HEAP_SIZE_BLOB: u8 = 0x04;
+/// an undocumented flag that indicates four extra bytes follow the row counts.
+const HEAP_SIZE_EXTRA_DATA: u8 = 0x40;
+
+/// The layout of the `#~` stream: the row counts, row sizes,
+/// and offsets of each table.
+struct Tables {
+    buf:         Vec<u8>,
+    string_size: usize,
+    guid_size:   usize,
+    blob_size:   usize,
+    rows:        [u32; 0x40],
+    row_sizes:   [usize; 0x40],
+    offsets:     [usize; 0x40],
+}
+
+impl Tables {
+    fn parse(buf: Vec<u8>) -> Result<Tables> {
+        if buf.len() < 0x18 {
+            return Err(ClrError::MalformedMetadata("tables stream too small").into());
+        }
+
+        let heap_sizes = buf[0x6];
+        let valid = LittleEndian::read_u64(&buf[0x8..]);
+
+        let mut tables = Tables {
+            buf:         vec![],
+            string_size: if heap_sizes & HEAP_SIZE_STRING > 0 { 4 } else { 2 },
+            guid_size:   if heap_sizes & HEAP_SIZE_GUID > 0 { 4 } else { 2 },
+            blob_size:   if heap_sizes & HEAP_SIZE_BLOB > 0 { 4 } else { 2 },
+            rows:        [0; 0x40],
+            row_sizes:   [0; 0x40],
+            offsets:     [0; 0x40],
+        };
+
+        let mut offset = 0x18;
+        for i in 0..0x40 {
+            if valid & (1 << i) > 0 {
+                if offset + 4 > buf.len() {
+                    return Err(ClrError::MalformedMetadata("row counts out of bounds").into());
+                }
+                tables.rows[i] = LittleEndian::read_u32(&buf[offset..]);
+                offset += 4;
+            }
+        }
+
+        if heap_sizes & HEAP_SIZE_EXTRA_DATA > 0 {
+            offset += 4;
+        }
+
+        // row sizes depend on all the row counts, so compute them in a second pass.
+        for i in 0..0x40 {
+            if tables.rows[i] == 0 {
+                continue;
+            }
+
+            let table = match MetadataTable::from_u8(i as u8) {
+                Some(table) => table,
+                None => {
+                    // we don't know the size of this table, so can't find any that follow.
+                    // fortunately, the interesting tables come first.
+                    debug!("clr: unknown metadata table: {:#x}", i);
+                    break;
+                }
+            };
+
+            tables.row_sizes[i] = get_table_schema(table)
+                .iter()
+                .map(|&column| tables.column_size(column))
+                .sum();
+            tables.offsets[i] = offset;
+            offset += tables.row_sizes[i] * tables.rows[i] as usize;
+        }
+
+        tables.buf = buf;
+        Ok(tables)
+    }
+
+    fn index_size(&self, table: MetadataTable) -> usize {
+        if self.rows[table as usize] < 0x1_0000 {
+            2
+        } else {
+            4
+        }
+    }
+
+    fn column_size(&self, column: Column) -> usize {
+        match column {
+            Column::U16 => 2,
+            Column::U32 => 4,
+            Column::String => self.string_size,
+            Column::Guid => self.guid_size,
+            Column::Blob => self.blob_size,
+            Column::Index(table) => self.index_size(table),
+            Column::Coded(coded) => {
+                let max_rows = coded
+                    .tables()
+                    .iter()
+                    .flatten()
+                    .map(|&table| self.rows[table as usize])
+                    .max()
+                    .unwrap_or(0);
+                if max_rows < (1 << (16 - coded.tag_bits())) {
+                    2
+                } else {
+                    4
+                }
+            }
+        }
+    }
+
+    fn row_count(&self, table: MetadataTable) -> u32 {
+        self.rows[table as usize]
+    }
+
+    /// the offset of the given row within the tables stream.
+    /// rows are 1-based.
+    fn row_offset(&self, table: MetadataTable, row: u32) -> Option<usize> {
+        if row == 0 || row > self.row_count(table) || self.row_sizes[table as usize] == 0 {
+            return None;
+        }
+        Some(self.offsets[table as usize] + (row - 1) as usize * self.row_sizes[table as usize])
+    }
+
+    /// read the column values of the given row.
+    /// rows are 1-based.
+    fn read_row(&self, table: MetadataTable, row: u32) -> Result<Vec<u32>> {
+        let mut offset = match self.row_offset(table, row) {
+            Some(offset) => offset,
+            None => return Err(ClrError::MalformedMetadata("invalid row").into()),
+        };
+
+        if offset + self.row_sizes[table as usize] > self.buf.len() {
+            return Err(ClrError::MalformedMetadata("row out of bounds").into());
+        }
+
+        let mut values = vec![];
+        for &column in get_table_schema(table).iter() {
+            let value = match self.column_size(column) {
+                2 => LittleEndian::read_u16(&self.buf[offset..]) as u32,
+                _ => LittleEndian::read_u32(&self.buf[offset..]),
+            };
+            offset += self.column_size(column);
+
+            values.push(match column {
+                Column::Coded(coded) => coded.decode(value).map(|token| token.0).unwrap_or(0),
+                _ => value,
+            });
+        }
+
+        Ok(values)
+    }
+
+    fn read_rows(&self, table: MetadataTable) -> Result<Vec<(Token, Vec<u32>)>> {
+        (1..=self.row_count(table))
+            .map(|row| Ok((Token::new(table, row), self.read_row(table, row)?)))
+            .collect()
+    }
+}
+
+/// read a compressed unsigned integer, as used for blob lengths.
+/// returns the value and the number of bytes consumed.
+fn read_compressed_u32(buf: &[u8]) -> Option<(u32, usize)> {
+    let b0 = *buf.first()? as u32;
+    if b0 & 0x80 == 0 {
+        Some((b0, 1))
+    } else if b0 & 0xC0 == 0x80 {
+        Some((((b0 & 0x3F) << 8) | *buf.get(1)? as u32, 2))
+    } else if b0 & 0xE0 == 0xC0 {
+        let buf = buf.get(..4)?;
+        Some((LittleEndian::read_u32(&[buf[3], buf[2], buf[1], buf[0] & 0x1F]), 4))
+    } else {
+        None
+    }
+}
+
+fn token_or_none(value: u32) -> Option<Token> {
+    if value == 0 {
+        None
+    } else {
+        Some(Token(value))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamHeader {
+    /// like `#~` or `#Strings`.
+    pub name:   String,
+    /// relative to the metadata root.
+    pub offset: u32,
+    pub size:   u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct TypeDef {
+    pub token:       Token,
+    /// TypeAttributes
+    pub flags:       u32,
+    pub name:        String,
+    pub namespace:   String,
+    /// the base type: a TypeDef, TypeRef, or TypeSpec.
+    pub extends:     Option<Token>,
+    /// the first row of the Field table owned by this type.
+    pub field_list:  u32,
+    /// the first row of the MethodDef table owned by this type.
+    pub method_list: u32,
+}
+
+impl TypeDef {
+    /// like `System.Object`.
+    pub fn full_name(&self) -> String {
+        if self.namespace.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}.{}", self.namespace, self.name)
+        }
+    }
+}
+
+/// The location of an IL method body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MethodBody {
+    /// the method header, IL code, and any extra data sections,
+    /// like exception handling clauses.
+    pub range: std::ops::Range<VA>,
+    /// the IL code.
+    pub code:  std::ops::Range<VA>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MethodDef {
+    pub token:      Token,
+    pub rva:        RVA,
+    /// MethodImplAttributes
+    pub impl_flags: u16,
+    /// MethodAttributes
+    pub flags:      u16,
+    pub name:       String,
+    pub signature:  Vec<u8>,
+    /// the first row of the Param table owned by this method.
+    pub param_list: u32,
+    /// the IL method body, if the method has one.
+    pub body:       Option<MethodBody>,
+}
+
+impl MethodDef {
+    pub fn is_pinvoke(&self) -> bool {
+        self.flags & METHOD_ATTRIBUTE_PINVOKE_IMPL > 0
+    }
+
+    /// the method is implemented by native code at its RVA, rather than IL.
+    pub fn is_native(&self) -> bool {
+        self.impl_flags & METHOD_IMPL_CODE_TYPE_MASK == METHOD_IMPL_CODE_TYPE_NATIVE
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MemberRef {
+    pub token:     Token,
+    /// a TypeDef, TypeRef, ModuleRef, MethodDef, or TypeSpec.
+    pub parent:    Option<Token>,
+    pub name:      String,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ModuleRef {
+    pub token: Token,
+    /// like `kernel32.dll`.
+    pub name:  String,
+}
+
+/// A P/Invoke: a managed method (or field) implemented by a native export.
+#[derive(Clone, Debug)]
+pub struct ImplMap {
+    pub token:            Token,
+    /// PInvokeAttributes
+    pub flags:            u16,
+    /// the MethodDef (or Field) that is forwarded.
+    pub member_forwarded: Option<Token>,
+    /// the name of the export, like `MessageBoxW`.
+    pub import_name:      String,
+    /// the ModuleRef, like `user32.dll`.
+    pub import_scope:     Option<Token>,
+}
+
+/// The parsed metadata of a .NET assembly.
+pub struct Metadata {
+    /// the address of the metadata root.
+    pub address:     VA,
+    /// the runtime version, like `v4.0.30319`.
+    pub version:     String,
+    pub streams:     Vec<StreamHeader>,
+    strings:         Vec<u8>,
+    user_strings:    Vec<u8>,
+    blobs:           Vec<u8>,
+    guids:           Vec<u8>,
+    tables:          Tables,
+    /// the address of the tables stream.
+    tables_address:  VA,
+    pub type_defs:   Vec<TypeDef>,
+    pub method_defs: Vec<MethodDef>,
+    pub member_refs: Vec<MemberRef>,
+    pub module_refs: Vec<ModuleRef>,
+    pub impl_maps:   Vec<ImplMap>,
+}
+
+impl Metadata {
+    /// fetch the identifier at the given index into the `#Strings` heap.
+    pub fn get_string(&self, index: u32) -> Result<String> {
+        let buf = match self.strings.get(index as usize..) {
+            Some(buf) => buf,
+            None => return Err(ClrError::MalformedMetadata("string index out of bounds").into()),
+        };
+
+        let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        Ok(String::from_utf8_lossy(&buf[..end]).into_owned())
+    }
+
+    /// fetch the data at the given index into the `#Blob` heap.
+    pub fn get_blob(&self, index: u32) -> Result<&[u8]> {
+        read_blob(&self.blobs, index as usize)
+    }
+
+    /// fetch the GUID at the given index into the `#GUID` heap.
+    /// indices are 1-based.
+    pub fn get_guid(&self, index: u32) -> Option<Guid> {
+        if index == 0 {
+            return None;
+        }
+        let offset = (index as usize - 1) * 0x10;
+        self.guids.get(offset..offset + 0x10).map(Guid::from_bytes)
+    }
+
+    /// fetch the string literal at the given index into the `#US` heap,
+    /// as referenced by the `ldstr` instruction.
+    pub fn get_user_string(&self, index: u32) -> Result<String> {
+        let buf = read_blob(&self.user_strings, index as usize)?;
+        // the string is followed by a byte that indicates if any character
+        // needs special handling.
+        let chars: Vec<u16> = buf[..buf.len() & !1]
+            .chunks_exact(2)
+            .map(LittleEndian::read_u16)
+            .collect();
+        Ok(String::from_utf16_lossy(&chars))
+    }
+
+    /// collect all the string literals from the `#US` heap,
+    /// as pairs of (index, string).
+    pub fn get_user_strings(&self) -> Result<Vec<(u32, String)>> {
+        let mut strings = vec![];
+
+        // the first entry is always the empty blob.
+        let mut offset = 1;
+        while offset < self.user_strings.len() {
+            let (length, size) = match read_compressed_u32(&self.user_strings[offset..]) {
+                Some(length) => length,
+                None => break,
+            };
+
+            if length > 0 {
+                strings.push((offset as u32, self.get_user_string(offset as u32)?));
+            }
+
+            offset += size + length as usize;
+        }
+
+        Ok(strings)
+    }
+
+    /// the number of rows in the given table.
+    pub fn get_row_count(&self, table: MetadataTable) -> u32 {
+        self.tables.row_count(table)
+    }
+
+    /// the address of the row referenced by the given token.
+    pub fn get_row_address(&self, token: Token) -> Option<VA> {
+        let offset = self.tables.row_offset(token.table()?, token.row())?;
+        Some(self.tables_address + offset as RVA)
+    }
+
+    pub fn get_module_ref(&self, token: Token) -> Option<&ModuleRef> {
+        self.module_refs.iter().find(|module_ref| module_ref.token == token)
+    }
+
+    pub fn get_method_def(&self, token: Token) -> Option<&MethodDef> {
+        self.method_defs.iter().find(|method_def| method_def.token == token)
+    }
+
+    /// find the type that owns the given method.
+    pub fn get_method_type(&self, method_def: &MethodDef) -> Option<&TypeDef> {
+        // each type owns a run of methods, from its method list
+        // up to the method list of the next type.
+        let row = method_def.token.row();
+        self.type_defs
+            .iter()
+            .rev()
+            .find(|type_def| type_def.method_list != 0 && type_def.method_list <= row)
+    }
+}
+
+fn read_blob(heap: &[u8], index: usize) -> Result<&[u8]> {
+    let buf = match heap.get(index..) {
+        Some(buf) => buf,
+        None => return Err(ClrError::MalformedMetadata("blob index out of bounds").into()),
+    };
+
+    let (length, size) = match read_compressed_u32(buf) {
+        Some(length) => length,
+        None => return Err(ClrError::MalformedMetadata("invalid blob length").into()),
+    };
+
+    match buf.get(size..size + length as usize) {
+        Some(blob) => Ok(blob),
+        None => Err(ClrError::MalformedMetadata("blob out of bounds").into()),
+    }
+}
+
+const CORILMETHOD_TINY_FORMAT: u8 = 0x2;
+const CORILMETHOD_FAT_FORMAT: u8 = 0x3;
+const CORILMETHOD_MORE_SECTS: u16 = 0x8;
+const CORILMETHOD_SECT_FAT_FORMAT: u8 = 0x40;
+const CORILMETHOD_SECT_MORE_SECTS: u8 = 0x80;
+
+/// find the extent of the IL method body at the given address.
+fn read_method_body(pe: &PE, address: VA) -> Result<MethodBody> {
+    let aspace = &pe.module.address_space;
+
+    let header = aspace.read_u8(address)?;
+    match header & 0x3 {
+        CORILMETHOD_TINY_FORMAT => {
+            let code_size = (header >> 2) as u64;
+            Ok(MethodBody {
+                range: address..address + 1 + code_size,
+                code:  address + 1..address + 1 + code_size,
+            })
+        }
+        CORILMETHOD_FAT_FORMAT => {
+            let flags = aspace.read_u16(address)?;
+            let header_size = 4 * (flags >> 12) as u64;
+            let code_size = aspace.read_u32(address + 0x4)? as u64;
+
+            let code = address + header_size..address + header_size + code_size;
+            let mut end = code.end;
+
+            // extra data sections, like exception handling clauses, follow the code.
+            if flags & CORILMETHOD_MORE_SECTS > 0 {
+                loop {
+                    let section = crate::util::align(end, 4);
+                    let kind = aspace.read_u8(section)?;
+                    let size = if kind & CORILMETHOD_SECT_FAT_FORMAT > 0 {
+                        (aspace.read_u32(section)? >> 8) as u64
+                    } else {
+                        aspace.read_u8(section + 1)? as u64
+                    };
+
+                    if size == 0 {
+                        break;
+                    }
+                    end = section + size;
+
+                    if kind & CORILMETHOD_SECT_MORE_SECTS == 0 {
+                        break;
+                    }
+                }
+            }
+
+            Ok(MethodBody {
+                range: address..end,
+                code,
+            })
+        }
+        _ => Err(ClrError::MalformedMetadata("invalid method header").into()),
+    }
+}
+
+fn read_metadata_root(buf: &[u8]) -> Result<(String, Vec<StreamHeader>)> {
+    if buf.len() < 0x10 || LittleEndian::read_u32(buf) != METADATA_SIGNATURE {
+        return Err(ClrError::MalformedMetadata("invalid metadata signature").into());
+    }
+
+    let version_length = LittleEndian::read_u32(&buf[0xC..]) as usize;
+    let version = match buf.get(0x10..0x10 + version_length) {
+        Some(version) => {
+            let end = version.iter().position(|&b| b == 0).unwrap_or(version.len());
+            String::from_utf8_lossy(&version[..end]).into_owned()
+        }
+        None => return Err(ClrError::MalformedMetadata("version out of bounds").into()),
+    };
+
+    // u16 flags, u16 stream count
+    let mut offset = 0x10 + version_length;
+    if offset + 4 > buf.len() {
+        return Err(ClrError::MalformedMetadata("stream headers out of bounds").into());
+    }
+    let stream_count = LittleEndian::read_u16(&buf[offset + 2..]);
+    offset += 4;
+
+    let mut streams = vec![];
+    for _ in 0..stream_count {
+        if offset + 8 > buf.len() {
+            return Err(ClrError::MalformedMetadata("stream header out of bounds").into());
+        }
+
+        let name_buf = &buf[offset + 8..];
+        let name_length = match name_buf.iter().position(|&b| b == 0) {
+            Some(name_length) => name_length,
+            None => return Err(ClrError::MalformedMetadata("invalid stream name").into()),
+        };
+
+        streams.push(StreamHeader {
+            name:   String::from_utf8_lossy(&name_buf[..name_length]).into_owned(),
+            offset: LittleEndian::read_u32(&buf[offset..]),
+            size:   LittleEndian::read_u32(&buf[offset + 4..]),
+        });
+
+        // the name is NULL-terminated and padded to 4 bytes.
+        offset += 8 + crate::util::align(name_length as u64 + 1, 4) as usize;
+    }
+
+    Ok((version, streams))
+}
+
+/// parse the metadata of the given .NET assembly.
+pub fn read_metadata(pe: &PE) -> Result<Option<Metadata>> {
+    let header = match read_cor20_header(pe)? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    let aspace = &pe.module.address_space;
+    let address = header.metadata.address;
+    let buf = aspace.read_bytes(address, header.metadata.size as usize)?;
+    let (version, streams) = read_metadata_root(&buf)?;
+    debug!("clr: metadata version: {}", version);
+
+    let read_stream = |name: &str| -> Result<Option<(VA, Vec<u8>)>> {
+        match streams.iter().find(|stream| stream.name == name) {
+            Some(stream) => {
+                let start = stream.offset as usize;
+                let end = start + stream.size as usize;
+                match buf.get(start..end) {
+                    Some(data) => Ok(Some((address + stream.offset as RVA, data.to_vec()))),
+                    None => Err(ClrError::MalformedMetadata("stream out of bounds").into()),
+                }
+            }
+            None => Ok(None),
+        }
+    };
+
+    // `#-` is the uncompressed (edit-and-continue) form of the tables stream.
+    let (tables_address, tables) = match read_stream("#~")? {
+        Some(tables) => tables,
+        None => match read_stream("#-")? {
+            Some(tables) => tables,
+            None => return Err(ClrError::MalformedMetadata("no tables stream").into()),
+        },
+    };
+
+    let mut metadata = Metadata {
+        address,
+        version,
+        strings: read_stream("#Strings")?.map(|(_, buf)| buf).unwrap_or_default(),
+        user_strings: read_stream("#US")?.map(|(_, buf)| buf).unwrap_or_default(),
+        blobs: read_stream("#Blob")?.map(|(_, buf)| buf).unwrap_or_default(),
+        guids: read_stream("#GUID")?.map(|(_, buf)| buf).unwrap_or_default(),
+        streams,
+        tables: Tables::parse(tables)?,
+        tables_address,
+        type_defs: vec![],
+        method_defs: vec![],
+        member_refs: vec![],
+        module_refs: vec![],
+        impl_maps: vec![],
+    };
+
+    let mut type_defs = vec![];
+    for (token, row) in metadata.tables.read_rows(MetadataTable::TypeDef)?.into_iter() {
+        type_defs.push(TypeDef {
+            token,
+            flags: row[0],
+            name: metadata.get_string(row[1])?,
+            namespace: metadata.get_string(row[2])?,
+            extends: token_or_none(row[3]),
+            field_list: row[4],
+            method_list: row[5],
+        });
+    }
+
+    let mut method_defs = vec![];
+    for (token, row) in metadata.tables.read_rows(MetadataTable::MethodDef)?.into_iter() {
+        let rva = row[0] as RVA;
+        let impl_flags = row[1] as u16;
+
+        // abstract, runtime, and P/Invoke methods have no body.
+        // native methods (from mixed-mode assemblies) have x86 code, not IL.
+        let body = if rva != 0 && impl_flags & METHOD_IMPL_CODE_TYPE_MASK == METHOD_IMPL_CODE_TYPE_IL {
+            match read_method_body(pe, aspace.base_address + rva) {
+                Ok(body) => Some(body),
+                Err(e) => {
+                    debug!("clr: failed to read method body: {}: {}", token, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        method_defs.push(MethodDef {
+            token,
+            rva,
+            impl_flags,
+            flags: row[2] as u16,
+            name: metadata.get_string(row[3])?,
+            signature: metadata.get_blob(row[4])?.to_vec(),
+            param_list: row[5],
+            body,
+        });
+    }
+
+    let mut member_refs = vec![];
+    for (token, row) in metadata.tables.read_rows(MetadataTable::MemberRef)?.into_iter() {
+        member_refs.push(MemberRef {
+            token,
+            parent: token_or_none(row[0]),
+            name: metadata.get_string(row[1])?,
+            signature: metadata.get_blob(row[2])?.to_vec(),
+        });
+    }
+
+    let mut module_refs = vec![];
+    for (token, row) in metadata.tables.read_rows(MetadataTable::ModuleRef)?.into_iter() {
+        module_refs.push(ModuleRef {
+            token,
+            name: metadata.get_string(row[0])?,
+        });
+    }
+
+    let mut impl_maps = vec![];
+    for (token, row) in metadata.tables.read_rows(MetadataTable::ImplMap)?.into_iter() {
+        impl_maps.push(ImplMap {
+            token,
+            flags: row[0] as u16,
+            member_forwarded: token_or_none(row[1]),
+            import_name: metadata.get_string(row[2])?,
+            import_scope: if row[3] == 0 {
+                None
+            } else {
+                Some(Token::new(MetadataTable::ModuleRef, row[3]))
+            },
+        });
+    }
+
+    metadata.type_defs = type_defs;
+    metadata.method_defs = method_defs;
+    metadata.member_refs = member_refs;
+    metadata.module_refs = module_refs;
+    metadata.impl_maps = impl_maps;
+
+    Ok(Some(metadata))
+}
+
+/// collect the regions of a .NET assembly that hold managed data,
+/// rather than native code: the CLR header, metadata, resources,
+/// strong name signature, and IL method bodies.
+///
+/// `metadata` is from `read_metadata`, when it could be parsed,
+/// and provides the IL method bodies.
+pub fn get_managed_ranges(pe: &PE, metadata: Option<&Metadata>) -> Result<Vec<std::ops::Range<VA>>> {
+    let mut ranges = vec![];
+
+    let header = match read_cor20_header(pe)? {
+        Some(header) => header,
+        None => return Ok(ranges),
+    };
+
+    if let Some(directory) = pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR)? {
+        ranges.push(directory.address..directory.address + directory.size);
+    }
+
+    for directory in [Some(header.metadata), header.resources, header.strong_name_signature]
+        .iter()
+        .flatten()
+    {
+        ranges.push(directory.address..directory.address + directory.size);
+    }
+
+    if let Some(metadata) = metadata {
+        ranges.extend(
+            metadata
+                .method_defs
+                .iter()
+                .filter_map(|method_def| method_def.body.as_ref())
+                .map(|body| body.range.clone()),
+        );
+    }
+
+    ranges.sort_unstable_by_key(|range| range.start);
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::{loader::pe::clr::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert!(read_cor20_header(&pe)?.is_none());
+        assert!(read_metadata(&pe)?.is_none());
+        assert_eq!(0, get_managed_ranges(&pe, None)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tables() -> Result<()> {
+        // a tables stream with one MethodDef, one ModuleRef,
+        // and one ImplMap that forwards the method to the module.
+        let mut buf = vec![0u8; 0x18];
+        buf[0x4] = 2;
+        let valid: u64 = (1 << 0x06) | (1 << 0x1A) | (1 << 0x1C);
+        LittleEndian::write_u64(&mut buf[0x8..], valid);
+        // row counts
+        buf.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        // MethodDef: rva, impl flags, flags, name, signature, param list
+        buf.extend_from_slice(&[0, 0, 0, 0, 0x80, 0x00, 0x96, 0x20, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00]);
+        // ModuleRef: name
+        buf.extend_from_slice(&[0x10, 0x00]);
+        // ImplMap: flags, member forwarded (MethodDef 1), import name, import scope
+        buf.extend_from_slice(&[0x06, 0x01, 0x03, 0x00, 0x20, 0x00, 0x01, 0x00]);
+
+        let tables = Tables::parse(buf)?;
+        assert_eq!(14, tables.row_sizes[MetadataTable::MethodDef as usize]);
+        assert_eq!(Some(0x34), tables.row_offset(MetadataTable::ImplMap, 1));
+        assert_eq!(None, tables.row_offset(MetadataTable::ImplMap, 2));
+        assert_eq!(
+            vec![0x0106, 0x0600_0001, 0x20, 0x1],
+            tables.read_row(MetadataTable::ImplMap, 1)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dotnet() -> Result<()> {
+        let buf = get_buf(Rsrc::DOTNET);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let header = read_cor20_header(&pe)?.unwrap();
+        assert_eq!(2, header.major_runtime_version);
+        assert!(header.is_il_only());
+        assert_eq!(0x40_205C, header.metadata.address);
+        assert_eq!(0x190, header.metadata.size);
+
+        let metadata = read_metadata(&pe)?.unwrap();
+        assert_eq!("v1.1.4322", metadata.version);
+        assert_eq!(
+            vec!["#~", "#Strings", "#Blob", "#GUID"],
+            metadata
+                .streams
+                .iter()
+                .map(|stream| stream.name.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(1, metadata.get_row_count(MetadataTable::Module));
+        assert_eq!(1, metadata.get_row_count(MetadataTable::TypeRef));
+        assert_eq!(1, metadata.get_row_count(MetadataTable::Assembly));
+        assert_eq!(1, metadata.get_row_count(MetadataTable::AssemblyRef));
+
+        assert_eq!(2, metadata.type_defs.len());
+        assert_eq!("<Module>", metadata.type_defs[0].full_name());
+        assert_eq!("ConfTest", metadata.type_defs[1].full_name());
+        assert_eq!(Some(Token(0x0100_0001)), metadata.type_defs[1].extends);
+
+        assert_eq!(2, metadata.method_defs.len());
+        let main = &metadata.method_defs[0];
+        assert_eq!(Token(header.entry_point), main.token);
+        assert_eq!("Main", main.name);
+        assert_eq!(0x2050, main.rva);
+        assert_eq!("ConfTest", metadata.get_method_type(main).unwrap().name);
+        // tiny header, then: ret
+        assert_eq!(
+            Some(MethodBody {
+                range: 0x40_2050..0x40_2052,
+                code:  0x40_2051..0x40_2052,
+            }),
+            main.body
+        );
+
+        // tiny header, then: ldarg.0; call System.Object::.ctor; ret
+        let ctor = &metadata.method_defs[1];
+        assert_eq!(".ctor", ctor.name);
+        assert_eq!(
+            Some(MethodBody {
+                range: 0x40_2054..0x40_205C,
+                code:  0x40_2055..0x40_205C,
+            }),
+            ctor.body
+        );
+
+        assert_eq!(1, metadata.member_refs.len());
+        assert_eq!(".ctor", metadata.member_refs[0].name);
+        assert_eq!(Some(Token(0x0100_0001)), metadata.member_refs[0].parent);
+        assert_eq!(&[0x20, 0x00, 0x01], &metadata.member_refs[0].signature[..]);
+
+        assert_eq!(0, metadata.module_refs.len());
+        assert_eq!(0, metadata.impl_maps.len());
+
+        // the tables stream follows the stream headers,
+        // and the MethodDef table follows Module, TypeRef, and TypeDef.
+        assert_eq!(Some(0x40_211C), metadata.get_row_address(main.token));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,494 @@
+//! Parse the PE debug directory.
+//!
+//! The directory is an array of `IMAGE_DEBUG_DIRECTORY` entries, each of which
+//! describes a blob of debug data. The blob is typically found in a read-only
+//! section, though it may also be found only in the file (outside of any
+//! section), in which case we fall back to the file offset.
+//!
+//! We parse the following entry types:
+//!   - CodeView: the PDB GUID/signature, age and path, used to fetch symbols
+//!   - POGO: the names and ranges of the sections/groups seen by the linker
+//!   - VC_FEATURE: counts of objects compiled with various security features
+//!   - REPRO: the hash that replaces the timestamps of deterministic builds
+//!   - EX_DLLCHARACTERISTICS: extended DLL characteristics, like CET
+//!
+//! Other entry types are exposed as raw bytes.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#debug-directory-image-only
+//!   - https://github.com/dotnet/runtime/blob/main/docs/design/specs/PE-COFF.md
+//!   - http://www.godevtool.com/Other/pdb.htm
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::{
+    aspace::AddressSpace,
+    loader::pe::{IMAGE_DIRECTORY_ENTRY_DEBUG, PE},
+    RVA, VA,
+};
+
+pub const IMAGE_DEBUG_TYPE_UNKNOWN: u32 = 0;
+pub const IMAGE_DEBUG_TYPE_COFF: u32 = 1;
+pub const IMAGE_DEBUG_TYPE_CODEVIEW: u32 = 2;
+pub const IMAGE_DEBUG_TYPE_FPO: u32 = 3;
+pub const IMAGE_DEBUG_TYPE_MISC: u32 = 4;
+pub const IMAGE_DEBUG_TYPE_EXCEPTION: u32 = 5;
+pub const IMAGE_DEBUG_TYPE_FIXUP: u32 = 6;
+pub const IMAGE_DEBUG_TYPE_OMAP_TO_SRC: u32 = 7;
+pub const IMAGE_DEBUG_TYPE_OMAP_FROM_SRC: u32 = 8;
+pub const IMAGE_DEBUG_TYPE_BORLAND: u32 = 9;
+pub const IMAGE_DEBUG_TYPE_CLSID: u32 = 11;
+pub const IMAGE_DEBUG_TYPE_VC_FEATURE: u32 = 12;
+pub const IMAGE_DEBUG_TYPE_POGO: u32 = 13;
+pub const IMAGE_DEBUG_TYPE_ILTCG: u32 = 14;
+pub const IMAGE_DEBUG_TYPE_MPX: u32 = 15;
+pub const IMAGE_DEBUG_TYPE_REPRO: u32 = 16;
+pub const IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS: u32 = 20;
+
+const SIZEOF_IMAGE_DEBUG_DIRECTORY: usize = 0x1C;
+
+/// `RSDS`, PDB 7.0
+const CV_SIGNATURE_RSDS: u32 = 0x5344_5352;
+/// `NB10`, PDB 2.0
+const CV_SIGNATURE_NB10: u32 = 0x3031_424E;
+
+/// Don't read debug data blobs larger than this.
+/// Real world CodeView/POGO records are a few KB at most.
+const MAX_DEBUG_DATA_SIZE: u32 = 0x10_0000;
+
+/// A Windows GUID, as found in the CodeView record.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+impl Guid {
+    pub fn from_bytes(buf: &[u8]) -> Guid {
+        let mut data4 = [0u8; 8];
+        data4.copy_from_slice(&buf[0x8..0x10]);
+
+        Guid {
+            data1: LittleEndian::read_u32(&buf[0x0..]),
+            data2: LittleEndian::read_u16(&buf[0x4..]),
+            data3: LittleEndian::read_u16(&buf[0x6..]),
+            data4,
+        }
+    }
+
+    /// the bytes of the GUID, as found on disk.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut buf = [0u8; 16];
+        LittleEndian::write_u32(&mut buf[0x0..], self.data1);
+        LittleEndian::write_u16(&mut buf[0x4..], self.data2);
+        LittleEndian::write_u16(&mut buf[0x6..], self.data3);
+        buf[0x8..].copy_from_slice(&self.data4);
+        buf
+    }
+}
+
+/// like: `4CE9A0D8-AB3A-4B8D-8E7C-6E1C8F5C3A1B`
+impl std::fmt::Display for Guid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            self.data1, self.data2, self.data3, self.data4[0], self.data4[1]
+        )?;
+        for b in self.data4[2..].iter() {
+            write!(f, "{:02X}", b)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CodeView {
+    /// `RSDS` record, emitted by all modern Microsoft linkers.
+    Pdb70 { guid: Guid, age: u32, path: String },
+    /// `NB10` record, emitted by VC6-era linkers.
+    Pdb20 {
+        offset:    u32,
+        signature: u32,
+        age:       u32,
+        path:      String,
+    },
+}
+
+impl CodeView {
+    pub fn age(&self) -> u32 {
+        match self {
+            CodeView::Pdb70 { age, .. } => *age,
+            CodeView::Pdb20 { age, .. } => *age,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            CodeView::Pdb70 { path, .. } => path,
+            CodeView::Pdb20 { path, .. } => path,
+        }
+    }
+
+    /// the key used to fetch the PDB from a symbol server,
+    /// like `https://msdl.microsoft.com/download/symbols/kernel32.pdb/<key>/kernel32.pdb`.
+    pub fn symbol_server_key(&self) -> String {
+        match self {
+            CodeView::Pdb70 { guid, age, .. } => format!("{}{:X}", guid.to_string().replace('-', ""), age),
+            CodeView::Pdb20 { signature, age, .. } => format!("{:08X}{:X}", signature, age),
+        }
+    }
+}
+
+/// An entry in the POGO (profile guided optimization) record,
+/// describing a contiguous region of a section, like `.text$mn`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PogoEntry {
+    pub address: VA,
+    pub size:    u32,
+    pub name:    String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pogo {
+    /// typically `LTCG` or `PGU\0`.
+    pub signature: u32,
+    pub entries:   Vec<PogoEntry>,
+}
+
+/// Counts of object files compiled with various security features.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VcFeature {
+    pub pre_vc11:  u32,
+    pub c_and_cpp: u32,
+    /// compiled with `/GS`
+    pub gs:        u32,
+    /// compiled with `/sdl`
+    pub sdl:       u32,
+    /// compiled with `/guard:cf`
+    pub guard_n:   u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DebugData {
+    CodeView(CodeView),
+    Pogo(Pogo),
+    VcFeature(VcFeature),
+    /// the hash of the build inputs, which may be empty.
+    /// when present, the timestamps in the PE are derived from this hash,
+    /// rather than the time of the build.
+    Repro(Vec<u8>),
+    ExDllCharacteristics(u32),
+    /// an entry type we don't parse, or that failed to parse.
+    Raw(Vec<u8>),
+}
+
+/// IMAGE_DEBUG_DIRECTORY and its parsed data.
+#[derive(Clone, Debug)]
+pub struct DebugEntry {
+    pub characteristics:     u32,
+    pub time_date_stamp:     u32,
+    pub major_version:       u16,
+    pub minor_version:       u16,
+    /// IMAGE_DEBUG_TYPE_*
+    pub kind:                u32,
+    pub size_of_data:        u32,
+    pub address_of_raw_data: RVA,
+    pub pointer_to_raw_data: u32,
+    /// None when the data is not present in the file.
+    pub data:                Option<DebugData>,
+}
+
+impl DebugEntry {
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            IMAGE_DEBUG_TYPE_UNKNOWN => "UNKNOWN",
+            IMAGE_DEBUG_TYPE_COFF => "COFF",
+            IMAGE_DEBUG_TYPE_CODEVIEW => "CODEVIEW",
+            IMAGE_DEBUG_TYPE_FPO => "FPO",
+            IMAGE_DEBUG_TYPE_MISC => "MISC",
+            IMAGE_DEBUG_TYPE_EXCEPTION => "EXCEPTION",
+            IMAGE_DEBUG_TYPE_FIXUP => "FIXUP",
+            IMAGE_DEBUG_TYPE_OMAP_TO_SRC => "OMAP_TO_SRC",
+            IMAGE_DEBUG_TYPE_OMAP_FROM_SRC => "OMAP_FROM_SRC",
+            IMAGE_DEBUG_TYPE_BORLAND => "BORLAND",
+            IMAGE_DEBUG_TYPE_CLSID => "CLSID",
+            IMAGE_DEBUG_TYPE_VC_FEATURE => "VC_FEATURE",
+            IMAGE_DEBUG_TYPE_POGO => "POGO",
+            IMAGE_DEBUG_TYPE_ILTCG => "ILTCG",
+            IMAGE_DEBUG_TYPE_MPX => "MPX",
+            IMAGE_DEBUG_TYPE_REPRO => "REPRO",
+            IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS => "EX_DLLCHARACTERISTICS",
+            _ => "OTHER",
+        }
+    }
+}
+
+/// read a NULL-terminated string from the given buffer,
+/// returning the string and the number of bytes consumed (including the NULL).
+fn read_cstr(buf: &[u8]) -> (String, usize) {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    (
+        String::from_utf8_lossy(&buf[..len]).into_owned(),
+        std::cmp::min(len + 1, buf.len()),
+    )
+}
+
+fn parse_codeview(buf: &[u8]) -> Option<CodeView> {
+    if buf.len() < 4 {
+        return None;
+    }
+
+    match LittleEndian::read_u32(buf) {
+        CV_SIGNATURE_RSDS if buf.len() >= 0x18 => Some(CodeView::Pdb70 {
+            guid: Guid::from_bytes(&buf[0x4..0x14]),
+            age:  LittleEndian::read_u32(&buf[0x14..]),
+            path: read_cstr(&buf[0x18..]).0,
+        }),
+        CV_SIGNATURE_NB10 if buf.len() >= 0x10 => Some(CodeView::Pdb20 {
+            offset:    LittleEndian::read_u32(&buf[0x4..]),
+            signature: LittleEndian::read_u32(&buf[0x8..]),
+            age:       LittleEndian::read_u32(&buf[0xC..]),
+            path:      read_cstr(&buf[0x10..]).0,
+        }),
+        signature => {
+            debug!("debug: unsupported CodeView signature: {:#x}", signature);
+            None
+        }
+    }
+}
+
+// ```
+//   u32 signature
+//   repeated:
+//     u32 RVA
+//     u32 size
+//     ascii name, NULL terminated, padded to 4 bytes
+// ```
+fn parse_pogo(pe: &PE, buf: &[u8]) -> Option<Pogo> {
+    if buf.len() < 4 {
+        return None;
+    }
+
+    let base_address = pe.module.address_space.base_address;
+    let mut entries = vec![];
+    let mut offset = 4usize;
+    while offset + 8 < buf.len() {
+        let rva = LittleEndian::read_u32(&buf[offset..]) as RVA;
+        let size = LittleEndian::read_u32(&buf[offset + 4..]);
+        let (name, len) = read_cstr(&buf[offset + 8..]);
+        if name.is_empty() {
+            break;
+        }
+
+        entries.push(PogoEntry {
+            address: base_address + rva,
+            size,
+            name,
+        });
+
+        offset += crate::util::align((8 + len) as u64, 4) as usize;
+    }
+
+    Some(Pogo {
+        signature: LittleEndian::read_u32(buf),
+        entries,
+    })
+}
+
+fn parse_vc_feature(buf: &[u8]) -> Option<VcFeature> {
+    if buf.len() < 0x14 {
+        return None;
+    }
+
+    Some(VcFeature {
+        pre_vc11:  LittleEndian::read_u32(&buf[0x0..]),
+        c_and_cpp: LittleEndian::read_u32(&buf[0x4..]),
+        gs:        LittleEndian::read_u32(&buf[0x8..]),
+        sdl:       LittleEndian::read_u32(&buf[0xC..]),
+        guard_n:   LittleEndian::read_u32(&buf[0x10..]),
+    })
+}
+
+// ```
+//   u32 size
+//   u8[size] hash
+// ```
+fn parse_repro(buf: &[u8]) -> Option<Vec<u8>> {
+    if buf.len() < 4 {
+        // older linkers emit an empty REPRO entry.
+        return Some(vec![]);
+    }
+
+    let size = LittleEndian::read_u32(buf) as usize;
+    buf.get(4..4 + size).map(|hash| hash.to_vec())
+}
+
+fn parse_debug_data(pe: &PE, kind: u32, buf: Vec<u8>) -> DebugData {
+    let data = match kind {
+        IMAGE_DEBUG_TYPE_CODEVIEW => parse_codeview(&buf).map(DebugData::CodeView),
+        IMAGE_DEBUG_TYPE_POGO => parse_pogo(pe, &buf).map(DebugData::Pogo),
+        IMAGE_DEBUG_TYPE_VC_FEATURE => parse_vc_feature(&buf).map(DebugData::VcFeature),
+        IMAGE_DEBUG_TYPE_REPRO => parse_repro(&buf).map(DebugData::Repro),
+        IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS if buf.len() >= 4 => {
+            Some(DebugData::ExDllCharacteristics(LittleEndian::read_u32(&buf)))
+        }
+        _ => None,
+    };
+
+    data.unwrap_or(DebugData::Raw(buf))
+}
+
+/// read the data blob of a debug entry, preferring the mapped data,
+/// and falling back to the file offset.
+fn read_debug_data(pe: &PE, address_of_raw_data: RVA, pointer_to_raw_data: u32, size: u32) -> Option<Vec<u8>> {
+    if size == 0 {
+        return Some(vec![]);
+    }
+
+    if size > MAX_DEBUG_DATA_SIZE {
+        debug!("debug: data too large: {:#x}", size);
+        return None;
+    }
+
+    if address_of_raw_data != 0 {
+        if let Ok(buf) = pe
+            .module
+            .address_space
+            .relative
+            .read_bytes(address_of_raw_data, size as usize)
+        {
+            return Some(buf);
+        }
+    }
+
+    let start = pointer_to_raw_data as usize;
+    if start != 0 {
+        if let Some(buf) = pe.buf.get(start..start + size as usize) {
+            return Some(buf.to_vec());
+        }
+    }
+
+    debug!("debug: data not present: {:#x}", address_of_raw_data);
+    None
+}
+
+/// parse the entries of the debug directory.
+pub fn read_debug_entries(pe: &PE) -> Result<Vec<DebugEntry>> {
+    let mut entries = vec![];
+
+    let debug_directory = match pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_DEBUG)? {
+        Some(debug_directory) if debug_directory.size > 0 => debug_directory,
+        _ => return Ok(entries),
+    };
+    debug!("debug: directory: {:#x}", debug_directory.address);
+
+    let count = debug_directory.size as usize / SIZEOF_IMAGE_DEBUG_DIRECTORY;
+    let buf = pe
+        .module
+        .address_space
+        .read_bytes(debug_directory.address, count * SIZEOF_IMAGE_DEBUG_DIRECTORY)?;
+
+    for entry in buf.chunks_exact(SIZEOF_IMAGE_DEBUG_DIRECTORY) {
+        let kind = LittleEndian::read_u32(&entry[0xC..]);
+        let size_of_data = LittleEndian::read_u32(&entry[0x10..]);
+        let address_of_raw_data = LittleEndian::read_u32(&entry[0x14..]) as RVA;
+        let pointer_to_raw_data = LittleEndian::read_u32(&entry[0x18..]);
+
+        let data = read_debug_data(pe, address_of_raw_data, pointer_to_raw_data, size_of_data)
+            .map(|buf| parse_debug_data(pe, kind, buf));
+
+        let entry = DebugEntry {
+            characteristics: LittleEndian::read_u32(&entry[0x0..]),
+            time_date_stamp: LittleEndian::read_u32(&entry[0x4..]),
+            major_version: LittleEndian::read_u16(&entry[0x8..]),
+            minor_version: LittleEndian::read_u16(&entry[0xA..]),
+            kind,
+            size_of_data,
+            address_of_raw_data,
+            pointer_to_raw_data,
+            data,
+        };
+        debug!("debug: entry: {}", entry.kind_name());
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// find the first CodeView record in the debug directory, if any.
+pub fn read_codeview(pe: &PE) -> Result<Option<CodeView>> {
+    Ok(read_debug_entries(pe)?.into_iter().find_map(|entry| match entry.data {
+        Some(DebugData::CodeView(cv)) => Some(cv),
+        _ => None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::pe::debug::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let entries = read_debug_entries(&pe)?;
+        assert_eq!(
+            vec![IMAGE_DEBUG_TYPE_CODEVIEW, IMAGE_DEBUG_TYPE_POGO, IMAGE_DEBUG_TYPE_REPRO],
+            entries.iter().map(|entry| entry.kind).collect::<Vec<_>>()
+        );
+        assert!(entries.iter().all(|entry| entry.time_date_stamp == 0x5F48_8A51));
+
+        let cv = read_codeview(&pe)?.unwrap();
+        assert_eq!("kernel32.pdb", cv.path());
+        assert_eq!(1, cv.age());
+        assert_eq!("63816243EC704DC091BC31470BAC48A31", cv.symbol_server_key());
+        if let CodeView::Pdb70 { guid, .. } = cv {
+            assert_eq!("63816243-EC70-4DC0-91BC-31470BAC48A3", guid.to_string());
+        } else {
+            panic!("expected RSDS record");
+        }
+
+        if let Some(DebugData::Pogo(pogo)) = &entries[1].data {
+            assert_eq!(50, pogo.entries.len());
+            assert_eq!(0x1_8000_1000, pogo.entries[0].address);
+            assert_eq!(".text$lp00kernel32.dll!20_pri7", pogo.entries[0].name);
+        } else {
+            panic!("expected POGO record");
+        }
+
+        assert_eq!(Some(DebugData::Repro(vec![])), entries[2].data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tiny() -> Result<()> {
+        let buf = get_buf(Rsrc::TINY);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert_eq!(0, read_debug_entries(&pe)?.len());
+        assert!(read_codeview(&pe)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn nop() -> Result<()> {
+        let buf = get_buf(Rsrc::NOP);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let cv = read_codeview(&pe)?.unwrap();
+        assert_eq!("c:\\code\\citrix\\nop\\Release\\nop.pdb", cv.path());
+        assert_eq!("53A2B8822B2643F281499933CD022A461", cv.symbol_server_key());
+
+        Ok(())
+    }
+}
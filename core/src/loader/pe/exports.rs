@@ -0,0 +1,289 @@
+// we use identifier names from the C headers for PE structures,
+// which don't match the Rust style guide.
+// example: `IMAGE_EXPORT_DIRECTORY`
+// don't show compiler warnings when encountering these names.
+#![allow(non_snake_case)]
+#![allow(non_camel_case_types)]
+#![allow(non_upper_case_globals)]
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::{
+    aspace::AddressSpace,
+    loader::pe::{IMAGE_DIRECTORY_ENTRY_EXPORT, PE},
+    module::Permissions,
+    RVA, VA,
+};
+
+const sizeof_IMAGE_EXPORT_DIRECTORY: usize = 0x28;
+
+// ```
+//  IMAGE_EXPORT_DIRECTORY
+//  +-----------------------+
+//  | ...                   |
+//  | Name                  | --> dll-name (ascii)
+//  | Base                  |
+//  | NumberOfFunctions     |
+//  | NumberOfNames         |            0x0    u32
+//  | AddressOfFunctions    | ---------> +---------+
+//  | AddressOfNames        | ---+       | RVA     | --> code, data, or forwarder (ascii)
+//  | AddressOfNameOrdinals | -+ |       +---------+
+//  +-----------------------+  | |       | ...     |
+//                             | |       +---------+
+//                             | |
+//                             | +-----> u32 RVA --> export-name (ascii)
+//                             |         parallel arrays
+//                             +-------> u16 index into AddressOfFunctions
+// ```
+#[derive(Clone, Debug)]
+pub struct IMAGE_EXPORT_DIRECTORY {
+    pub characteristics:          u32,
+    pub time_date_stamp:          u32,
+    pub major_version:            u16,
+    pub minor_version:            u16,
+    pub name:                     RVA,
+    pub base:                     u32,
+    pub number_of_functions:      u32,
+    pub number_of_names:          u32,
+    pub address_of_functions:     RVA,
+    pub address_of_names:         RVA,
+    pub address_of_name_ordinals: RVA,
+}
+
+impl IMAGE_EXPORT_DIRECTORY {
+    /// read the name of the DLL into a String.
+    pub fn read_name(&self, pe: &PE) -> Result<String> {
+        pe.module.address_space.relative.read_ascii(self.name, 1)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ExportTarget {
+    /// the export is found in an executable section.
+    Code(VA),
+    /// the export is found in a non-executable section,
+    /// such as an exported global variable.
+    Data(VA),
+    /// the export is implemented by another module,
+    /// like `NTDLL.RtlAllocateHeap` or `NTDLL.#12`.
+    Forwarder(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Export {
+    pub ordinal: u32,
+    /// exports may be referenced by ordinal only, in which case there is no
+    /// name.
+    pub name:    Option<String>,
+    pub target:  ExportTarget,
+}
+
+impl std::fmt::Display for Export {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} (#{})", name, self.ordinal),
+            None => write!(f, "#{}", self.ordinal),
+        }
+    }
+}
+
+pub fn read_image_export_directory(pe: &PE, va: VA) -> Result<IMAGE_EXPORT_DIRECTORY> {
+    let buf = pe.module.address_space.read_bytes(va, sizeof_IMAGE_EXPORT_DIRECTORY)?;
+
+    Ok(IMAGE_EXPORT_DIRECTORY {
+        characteristics:          LittleEndian::read_u32(&buf[0x0..]),
+        time_date_stamp:          LittleEndian::read_u32(&buf[0x4..]),
+        major_version:            LittleEndian::read_u16(&buf[0x8..]),
+        minor_version:            LittleEndian::read_u16(&buf[0xA..]),
+        name:                     LittleEndian::read_u32(&buf[0xC..]) as RVA,
+        base:                     LittleEndian::read_u32(&buf[0x10..]),
+        number_of_functions:      LittleEndian::read_u32(&buf[0x14..]),
+        number_of_names:          LittleEndian::read_u32(&buf[0x18..]),
+        address_of_functions:     LittleEndian::read_u32(&buf[0x1C..]) as RVA,
+        address_of_names:         LittleEndian::read_u32(&buf[0x20..]) as RVA,
+        address_of_name_ordinals: LittleEndian::read_u32(&buf[0x24..]) as RVA,
+    })
+}
+
+/// parse the export directory into a list of exports, ordered by ordinal.
+/// an exported function with multiple names is reported once per name.
+/// an export whose forwarder can't be read is skipped.
+pub fn read_exports(pe: &PE) -> Result<Vec<Export>> {
+    let mut exports = vec![];
+
+    let export_directory = match pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_EXPORT)? {
+        Some(export_directory) if export_directory.size > 0 => export_directory,
+        _ => return Ok(exports),
+    };
+    debug!("exports: directory: {:#x}", export_directory.address);
+
+    let directory = read_image_export_directory(pe, export_directory.address)?;
+    let base_address = pe.module.address_space.base_address;
+    let address_space = &pe.module.address_space.relative;
+
+    // index into AddressOfFunctions -> names
+    let mut names: BTreeMap<u32, Vec<String>> = Default::default();
+    if directory.number_of_names > 0 {
+        let name_rvas = address_space.read_bytes(directory.address_of_names, directory.number_of_names as usize * 4)?;
+        let name_ordinals = address_space.read_bytes(
+            directory.address_of_name_ordinals,
+            directory.number_of_names as usize * 2,
+        )?;
+
+        for (name_rva, index) in name_rvas.chunks_exact(4).zip(name_ordinals.chunks_exact(2)) {
+            let name_rva = LittleEndian::read_u32(name_rva) as RVA;
+            let index = LittleEndian::read_u16(index) as u32;
+
+            match address_space.read_ascii(name_rva, 1) {
+                Ok(name) => names.entry(index).or_default().push(name),
+                Err(_) => debug!("exports: invalid name at {:#x}", base_address + name_rva),
+            }
+        }
+    }
+
+    let function_rvas = address_space.read_bytes(
+        directory.address_of_functions,
+        directory.number_of_functions as usize * 4,
+    )?;
+    let forwarder_range = export_directory.address..export_directory.address + export_directory.size;
+
+    for (index, rva) in function_rvas.chunks_exact(4).enumerate() {
+        let index = index as u32;
+        let rva = LittleEndian::read_u32(rva) as RVA;
+        if rva == 0x0 {
+            // unused slot in the ordinal range.
+            continue;
+        }
+
+        let ordinal = directory.base + index;
+        let va = base_address + rva;
+
+        let target = if forwarder_range.contains(&va) {
+            // forwarders are ASCII strings found within the export directory,
+            // like `NTDLL.RtlAllocateHeap`.
+            match address_space.read_ascii(rva, 1) {
+                Ok(forwarder) => ExportTarget::Forwarder(forwarder),
+                Err(_) => {
+                    debug!("exports: #{}: invalid forwarder at {:#x}", ordinal, va);
+                    continue;
+                }
+            }
+        } else if pe.module.probe_va(va, Permissions::X) {
+            ExportTarget::Code(va)
+        } else {
+            ExportTarget::Data(va)
+        };
+
+        match names.remove(&index) {
+            Some(names) => {
+                for name in names.into_iter() {
+                    debug!("exports: #{} {} -> {:?}", ordinal, name, target);
+                    exports.push(Export {
+                        ordinal,
+                        name: Some(name),
+                        target: target.clone(),
+                    });
+                }
+            }
+            None => {
+                debug!("exports: #{} -> {:?}", ordinal, target);
+                exports.push(Export {
+                    ordinal,
+                    name: None,
+                    target,
+                });
+            }
+        }
+    }
+
+    Ok(exports)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::pe::exports::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let exports = read_exports(&pe)?;
+        assert_eq!(1621, exports.len());
+
+        let forwarders: Vec<_> = exports
+            .iter()
+            .filter(|exp| matches!(exp.target, ExportTarget::Forwarder(_)))
+            .collect();
+        assert_eq!(176, forwarders.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32_invalid_forwarder() -> Result<()> {
+        let mut buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let exports = read_exports(&pe)?;
+        let forwarder = exports
+            .iter()
+            .find(|exp| matches!(exp.target, ExportTarget::Forwarder(_)))
+            .unwrap();
+        let names = exports.iter().filter(|exp| exp.ordinal == forwarder.ordinal).count();
+
+        // truncate the forwarder string, so it's empty.
+        let directory = pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_EXPORT)?.unwrap();
+        let directory = read_image_export_directory(&pe, directory.address)?;
+        let index = (forwarder.ordinal - directory.base) as RVA;
+        let rva = pe
+            .module
+            .address_space
+            .relative
+            .read_u32(directory.address_of_functions + index * 4)?;
+        let offset = pe
+            .module
+            .file_offset(pe.module.address_space.base_address + rva as RVA)?;
+        buf[offset] = 0;
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // only that export is lost.
+        let exports = read_exports(&pe)?;
+        assert_eq!(1621 - names, exports.len());
+        assert_eq!(
+            175,
+            exports
+                .iter()
+                .filter(|exp| matches!(exp.target, ExportTarget::Forwarder(_)))
+                .count()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn tiny() -> Result<()> {
+        let buf = get_buf(Rsrc::TINY);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert_eq!(0, read_exports(&pe)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert_eq!(0, read_exports(&pe)?.len());
+
+        Ok(())
+    }
+}
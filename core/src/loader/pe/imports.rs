@@ -9,9 +9,19 @@
 use anyhow::Result;
 use byteorder::{ByteOrder, LittleEndian};
 
-use crate::{aspace::AddressSpace, loader::pe::PE, module::Permissions, RVA, VA};
+use crate::{
+    aspace::AddressSpace,
+    loader::pe::{IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT, PE},
+    module::Permissions,
+    RVA, VA,
+};
 
 const sizeof_IMAGE_IMPORT_DESCRIPTOR: usize = 0x14;
+const sizeof_IMAGE_DELAYLOAD_DESCRIPTOR: usize = 0x20;
+
+/// the delay-load descriptor contains RVAs.
+/// when not set, as by VC6-era linkers, the descriptor contains VAs.
+const dlattrRva: u32 = 0x1;
 
 // ```
 //  0x0                    0x14
@@ -95,7 +105,10 @@ pub fn read_image_import_descriptor(pe: &PE, va: VA) -> Result<IMAGE_IMPORT_DESC
     let buf = pe.module.address_space.read_bytes(va, sizeof_IMAGE_IMPORT_DESCRIPTOR)?;
 
     // these fields are all u32, even on 64-bit
-    let entries: Vec<u32> = buf.chunks_exact(0x4).map(|b| LittleEndian::read_u32(b)).collect();
+    let mut entries: Vec<u32> = vec![];
+    for entry in buf.chunks_exact(0x4) {
+        entries.push(LittleEndian::read_u32(entry));
+    }
 
     Ok(IMAGE_IMPORT_DESCRIPTOR {
         original_first_thunk: entries[0] as RVA,
@@ -111,7 +124,7 @@ pub fn read_import_descriptors<'a>(
     import_directory: VA,
 ) -> Box<dyn Iterator<Item = IMAGE_IMPORT_DESCRIPTOR> + 'a> {
     Box::new(
-        (0..std::usize::MAX)
+        (0..usize::MAX)
             .map(move |i| import_directory + (i * sizeof_IMAGE_IMPORT_DESCRIPTOR) as RVA)
             .map(move |va| read_image_import_descriptor(pe, va))
             .take_while(|desc| match desc {
@@ -198,7 +211,7 @@ pub fn read_thunks<'a>(
     let psize = pe.module.arch.pointer_size();
 
     Box::new(
-        (0..std::usize::MAX)
+        (0..usize::MAX)
             .map(move |i| {
                 (
                     // the Original First Thunk (OFT) remains constant, and points to the
@@ -221,3 +234,131 @@ pub fn read_image_import_by_name(pe: &PE, va: VA) -> Result<IMAGE_IMPORT_BY_NAME
         name: pe.module.address_space.read_ascii(va + 2u64, 1)?,
     })
 }
+
+// ```
+//  0x0                         0x20
+//  +----------------------------+
+//  | IMAGE_DELAYLOAD_DESCRIPTOR | ---> dll-name (ascii)
+//  +----------------------------+ \
+//  | ...                        |  |  Import Address Table (IAT)
+//  +----------------------------+  +-> +------------------+
+//  | 00 00 00 00 00 00 000      |  |   | ptr to stub      |  --> code that resolves the import on first call
+//  +----------------------------+  |   +------------------+
+//                                  |
+//                                  |   Import Name Table (INT)
+//                                  +-> +------------------+
+//                                      | IMAGE_THUNK_DATA | ---> IMAGE_IMPORT_BY_NAME
+//                                      +------------------+
+// ```
+//
+// also known as `ImgDelayDescr`.
+#[derive(Clone)]
+pub struct IMAGE_DELAYLOAD_DESCRIPTOR {
+    pub attributes:                 u32,
+    pub dll_name:                   RVA,
+    pub module_handle:              RVA,
+    pub import_address_table:       RVA,
+    pub import_name_table:          RVA,
+    pub bound_import_address_table: RVA,
+    pub unload_information_table:   RVA,
+    pub time_date_stamp:            u32,
+}
+
+impl IMAGE_DELAYLOAD_DESCRIPTOR {
+    pub fn is_empty(&self) -> bool {
+        self.dll_name == 0x0 && self.import_address_table == 0x0 && self.import_name_table == 0x0
+    }
+
+    /// read the name of the DLL into a String.
+    pub fn read_name(&self, pe: &PE) -> Result<String> {
+        pe.module
+            .address_space
+            .read_ascii(pe.module.address_space.base_address + self.dll_name, 1)
+    }
+}
+
+impl std::fmt::Debug for IMAGE_DELAYLOAD_DESCRIPTOR {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "IMAGE_DELAYLOAD_DESCRIPTOR(IAT: {:#x} INT: {:#x} name: {:#x})",
+            self.import_address_table, self.import_name_table, self.dll_name
+        )
+    }
+}
+
+/// fetch the VA of the delay-load import directory, if it exists.
+pub fn get_delay_import_directory(pe: &PE) -> Result<Option<VA>> {
+    match pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT)? {
+        Some(delay_import_directory) if delay_import_directory.size > 0 => Ok(Some(delay_import_directory.address)),
+        _ => Ok(None),
+    }
+}
+
+/// read the delay-load descriptor at the given address.
+/// the addresses in the result are always RVAs, even for VA-based descriptors.
+pub fn read_image_delayload_descriptor(pe: &PE, va: VA) -> Result<IMAGE_DELAYLOAD_DESCRIPTOR> {
+    let buf = pe
+        .module
+        .address_space
+        .read_bytes(va, sizeof_IMAGE_DELAYLOAD_DESCRIPTOR)?;
+
+    // these fields are all u32, even on 64-bit
+    let mut entries: Vec<u32> = vec![];
+    for entry in buf.chunks_exact(0x4) {
+        entries.push(LittleEndian::read_u32(entry));
+    }
+
+    let attributes = entries[0];
+    let base_address = pe.module.address_space.base_address;
+    let to_rva = |v: u32| -> RVA {
+        if attributes & dlattrRva > 0 || v == 0x0 {
+            v as RVA
+        } else {
+            (v as VA).wrapping_sub(base_address)
+        }
+    };
+
+    Ok(IMAGE_DELAYLOAD_DESCRIPTOR {
+        attributes,
+        dll_name: to_rva(entries[1]),
+        module_handle: to_rva(entries[2]),
+        import_address_table: to_rva(entries[3]),
+        import_name_table: to_rva(entries[4]),
+        bound_import_address_table: to_rva(entries[5]),
+        unload_information_table: to_rva(entries[6]),
+        time_date_stamp: entries[7],
+    })
+}
+
+pub fn read_delay_import_descriptors<'a>(
+    pe: &'a PE,
+    delay_import_directory: VA,
+) -> Box<dyn Iterator<Item = IMAGE_DELAYLOAD_DESCRIPTOR> + 'a> {
+    Box::new(
+        (0..usize::MAX)
+            .map(move |i| delay_import_directory + (i * sizeof_IMAGE_DELAYLOAD_DESCRIPTOR) as RVA)
+            .map(move |va| read_image_delayload_descriptor(pe, va))
+            .take_while(|desc| match desc {
+                Ok(desc) => !desc.is_empty(),
+                Err(_) => false,
+            })
+            .map(|desc| desc.unwrap()),
+    )
+}
+
+/// read an entry from the Import Name Table of a delay-load descriptor.
+/// like the descriptor, the result is always an RVA, even for VA-based
+/// descriptors.
+pub fn read_delay_import_thunk_data(
+    pe: &PE,
+    descriptor: &IMAGE_DELAYLOAD_DESCRIPTOR,
+    va: VA,
+) -> Result<IMAGE_THUNK_DATA> {
+    match read_image_thunk_data(pe, va)? {
+        IMAGE_THUNK_DATA::Function(ptr) if descriptor.attributes & dlattrRva == 0 && ptr != 0x0 => Ok(
+            IMAGE_THUNK_DATA::Function(ptr.wrapping_sub(pe.module.address_space.base_address)),
+        ),
+        thunk => Ok(thunk),
+    }
+}
@@ -0,0 +1,437 @@
+//! Parse the PE Load Config directory (`IMAGE_LOAD_CONFIG_DIRECTORY32/64`).
+//!
+//! The structure has grown with nearly every release of Windows,
+//! and the leading `Size` field describes which version is present.
+//! Fields that don't fit within `Size` are `None`.
+//!
+//! Among other things, this directory references the tables used by
+//! exploit mitigations, like SafeSEH and Control Flow Guard (CFG).
+//! The CFG tables are arrays of entries that consist of:
+//!   u32       RVA (both x32 and x64)
+//!   variable  data
+//! The GuardFlags describe how big the entry data is (the "stride").
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#load-configuration-directory
+//!   - https://docs.microsoft.com/en-us/windows/win32/api/winnt/ns-winnt-image_load_config_directory32
+//!   - https://docs.microsoft.com/en-us/windows/win32/api/winnt/ns-winnt-image_load_config_directory64
+//!   - https://lucasg.github.io/2017/02/05/Control-Flow-Guard/
+use anyhow::Result;
+use bitflags::bitflags;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::{
+    arch::Arch,
+    aspace::AddressSpace,
+    loader::pe::{PEError, IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG, PE},
+    RVA, VA,
+};
+
+bitflags! {
+    pub struct GuardFlags: u32 {
+        /// module performs control flow integrity checks using system-supplied support.
+        const CF_INSTRUMENTED = 0x0000_0100;
+        /// module performs control flow and write integrity checks.
+        const CFW_INSTRUMENTED = 0x0000_0200;
+        /// module contains valid control flow target metadata.
+        const CF_FUNCTION_TABLE_PRESENT = 0x0000_0400;
+        /// module does not make use of the /GS security cookie.
+        const SECURITY_COOKIE_UNUSED = 0x0000_0800;
+        /// module supports read only delay load IAT.
+        const PROTECT_DELAYLOAD_IAT = 0x0000_1000;
+        /// delayload import table in its own .didat section (with nothing else in it) that can be freely reprotected.
+        const DELAYLOAD_IAT_IN_ITS_OWN_SECTION = 0x0000_2000;
+        /// module contains suppressed export information.
+        const CF_EXPORT_SUPPRESSION_INFO_PRESENT = 0x0000_4000;
+        /// module enables suppression of exports.
+        const CF_ENABLE_EXPORT_SUPPRESSION = 0x0000_8000;
+        /// module contains longjmp target information.
+        const CF_LONGJUMP_TABLE_PRESENT = 0x0001_0000;
+        /// module contains return flow instrumentation and metadata.
+        const RF_INSTRUMENTED = 0x0002_0000;
+        /// module requests that the OS enable return flow protection.
+        const RF_ENABLE = 0x0004_0000;
+        /// module requests that the OS enable return flow protection in strict mode.
+        const RF_STRICT = 0x0008_0000;
+        /// module was built with retpoline support.
+        const RETPOLINE_PRESENT = 0x0010_0000;
+        /// module contains EH continuation target information.
+        const EH_CONTINUATION_TABLE_PRESENT = 0x0040_0000;
+        /// module was built with xfg.
+        const XFG_ENABLED = 0x0080_0000;
+        /// the number of extra bytes in each entry of the guard tables.
+        const CF_FUNCTION_TABLE_SIZE_MASK = 0xF000_0000;
+    }
+}
+
+const IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT: u32 = 28;
+
+/// the largest directory we'll read, to guard against malformed `Size` fields.
+/// as of Windows 11, the structure is 0x140 bytes on x64.
+const MAX_LOAD_CONFIG_DIRECTORY_SIZE: u32 = 0x1000;
+
+impl GuardFlags {
+    /// the number of bytes of metadata that follow the RVA in each guard table
+    /// entry.
+    pub fn stride(&self) -> usize {
+        ((self.bits & GuardFlags::CF_FUNCTION_TABLE_SIZE_MASK.bits) >> IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT)
+            as usize
+    }
+}
+
+/// A reference to a table, as found in the Load Config directory.
+#[derive(Clone, Copy, Debug)]
+pub struct Table {
+    pub address: VA,
+    pub count:   u64,
+}
+
+/// `IMAGE_LOAD_CONFIG_CODE_INTEGRITY`
+#[derive(Clone, Copy, Debug)]
+pub struct CodeIntegrity {
+    pub flags:          u16,
+    pub catalog:        u16,
+    pub catalog_offset: u32,
+}
+
+/// `IMAGE_LOAD_CONFIG_DIRECTORY32` or `IMAGE_LOAD_CONFIG_DIRECTORY64`.
+///
+/// Pointer-sized fields are widened to u64.
+/// Fields beyond `edit_list` were added over time, so they're optional.
+#[derive(Clone, Debug)]
+pub struct LoadConfigDirectory {
+    pub size: u32,
+    pub time_date_stamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub global_flags_clear: u32,
+    pub global_flags_set: u32,
+    pub critical_section_default_timeout: u32,
+    pub decommit_free_block_threshold: u64,
+    pub decommit_total_free_threshold: u64,
+    pub lock_prefix_table: VA,
+    pub maximum_allocation_size: u64,
+    pub virtual_memory_threshold: u64,
+    pub process_affinity_mask: u64,
+    pub process_heap_flags: u32,
+    pub csd_version: u16,
+    pub dependent_load_flags: u16,
+    pub edit_list: VA,
+
+    /// the address of the /GS security cookie.
+    pub security_cookie: Option<VA>,
+
+    /// the SafeSEH table of u32 RVAs to exception handlers.
+    pub se_handler_table: Option<Table>,
+
+    pub guard_cf_check_function_pointer: Option<VA>,
+    pub guard_cf_dispatch_function_pointer: Option<VA>,
+    pub guard_cf_function_table: Option<Table>,
+    pub guard_flags: Option<GuardFlags>,
+
+    pub code_integrity: Option<CodeIntegrity>,
+
+    pub guard_address_taken_iat_entry_table: Option<Table>,
+    pub guard_long_jump_target_table: Option<Table>,
+    pub dynamic_value_reloc_table: Option<VA>,
+    /// on x32, this is `CHPEMetadataPointer`;
+    /// on x64, this is `HybridMetadataPointer` (ARM64X/ARM64EC).
+    pub chpe_metadata_pointer: Option<VA>,
+    pub guard_rf_failure_routine: Option<VA>,
+    pub guard_rf_failure_routine_function_pointer: Option<VA>,
+    pub dynamic_value_reloc_table_offset: Option<u32>,
+    pub dynamic_value_reloc_table_section: Option<u16>,
+    pub guard_rf_verify_stack_pointer_function_pointer: Option<VA>,
+    pub hot_patch_table_offset: Option<u32>,
+    pub enclave_configuration_pointer: Option<VA>,
+    pub volatile_metadata_pointer: Option<VA>,
+    pub guard_eh_continuation_table: Option<Table>,
+    pub guard_xfg_check_function_pointer: Option<VA>,
+    pub guard_xfg_dispatch_function_pointer: Option<VA>,
+    pub guard_xfg_table_dispatch_function_pointer: Option<VA>,
+    pub cast_guard_os_determined_failure_mode: Option<VA>,
+    pub guard_memcpy_function_pointer: Option<VA>,
+}
+
+/// reads fields from the raw directory,
+/// given offsets for x32 and x64, respectively.
+struct FieldReader<'a> {
+    buf:  &'a [u8],
+    arch: Arch,
+}
+
+impl<'a> FieldReader<'a> {
+    fn offset(&self, offset32: usize, offset64: usize) -> usize {
+        match self.arch {
+            Arch::X32 => offset32,
+            Arch::X64 => offset64,
+        }
+    }
+
+    fn u16(&self, offset32: usize, offset64: usize) -> Option<u16> {
+        let offset = self.offset(offset32, offset64);
+        self.buf.get(offset..offset + 2).map(LittleEndian::read_u16)
+    }
+
+    fn u32(&self, offset32: usize, offset64: usize) -> Option<u32> {
+        let offset = self.offset(offset32, offset64);
+        self.buf.get(offset..offset + 4).map(LittleEndian::read_u32)
+    }
+
+    fn pointer(&self, offset32: usize, offset64: usize) -> Option<u64> {
+        let offset = self.offset(offset32, offset64);
+        match self.arch {
+            Arch::X32 => self
+                .buf
+                .get(offset..offset + 4)
+                .map(|b| LittleEndian::read_u32(b) as u64),
+            Arch::X64 => self.buf.get(offset..offset + 8).map(LittleEndian::read_u64),
+        }
+    }
+
+    /// a table is a pointer followed by a pointer-sized count.
+    /// both must be present.
+    fn table(&self, offset32: usize, offset64: usize) -> Option<Table> {
+        let psize = self.arch.pointer_size();
+        match (
+            self.pointer(offset32, offset64),
+            self.pointer(offset32 + psize, offset64 + psize),
+        ) {
+            (Some(address), Some(count)) => Some(Table { address, count }),
+            _ => None,
+        }
+    }
+}
+
+/// parse the Load Config directory, if present.
+///
+/// the leading `Size` field determines how much of the structure is read,
+/// so the result is `None` if it's too small to contain the fields common to
+/// all versions.
+pub fn read_load_config_directory(pe: &PE) -> Result<Option<LoadConfigDirectory>> {
+    let load_config_directory = match pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG)? {
+        Some(load_config_directory) if load_config_directory.size > 0 => load_config_directory,
+        _ => return Ok(None),
+    };
+    debug!("load config directory: {:#x}", load_config_directory.address);
+
+    // according to IDA, the first DWORD is `Size` not `Characteristics` (unused).
+    let size = pe.module.address_space.read_u32(load_config_directory.address)?;
+    debug!("load config directory: size: {:#x}", size);
+
+    let buf = pe.module.address_space.read_bytes(
+        load_config_directory.address,
+        std::cmp::min(size, MAX_LOAD_CONFIG_DIRECTORY_SIZE) as usize,
+    )?;
+    let r = FieldReader {
+        buf:  &buf,
+        arch: pe.module.arch,
+    };
+
+    // the fields up to and including `EditList` are found in all versions.
+    let edit_list = match r.pointer(0x38, 0x50) {
+        Some(edit_list) => edit_list,
+        None => {
+            debug!("load config directory too small: {:#x}", size);
+            return Ok(None);
+        }
+    };
+
+    let code_integrity = match (
+        r.u16(0x5C, 0x94),
+        r.u16(0x5E, 0x96),
+        r.u32(0x60, 0x98),
+        r.u32(0x64, 0x9C),
+    ) {
+        // the final field is reserved, but must be present.
+        (Some(flags), Some(catalog), Some(catalog_offset), Some(_)) => Some(CodeIntegrity {
+            flags,
+            catalog,
+            catalog_offset,
+        }),
+        _ => None,
+    };
+
+    Ok(Some(LoadConfigDirectory {
+        size,
+        time_date_stamp: r.u32(0x4, 0x4).unwrap(),
+        major_version: r.u16(0x8, 0x8).unwrap(),
+        minor_version: r.u16(0xA, 0xA).unwrap(),
+        global_flags_clear: r.u32(0xC, 0xC).unwrap(),
+        global_flags_set: r.u32(0x10, 0x10).unwrap(),
+        critical_section_default_timeout: r.u32(0x14, 0x14).unwrap(),
+        decommit_free_block_threshold: r.pointer(0x18, 0x18).unwrap(),
+        decommit_total_free_threshold: r.pointer(0x1C, 0x20).unwrap(),
+        lock_prefix_table: r.pointer(0x20, 0x28).unwrap(),
+        maximum_allocation_size: r.pointer(0x24, 0x30).unwrap(),
+        virtual_memory_threshold: r.pointer(0x28, 0x38).unwrap(),
+        // note: these two fields are swapped between x32 and x64.
+        process_heap_flags: r.u32(0x2C, 0x48).unwrap(),
+        process_affinity_mask: r.pointer(0x30, 0x40).unwrap(),
+        csd_version: r.u16(0x34, 0x4C).unwrap(),
+        dependent_load_flags: r.u16(0x36, 0x4E).unwrap(),
+        edit_list,
+        security_cookie: r.pointer(0x3C, 0x58),
+        se_handler_table: r.table(0x40, 0x60),
+        guard_cf_check_function_pointer: r.pointer(0x48, 0x70),
+        guard_cf_dispatch_function_pointer: r.pointer(0x4C, 0x78),
+        guard_cf_function_table: r.table(0x50, 0x80),
+        guard_flags: r.u32(0x58, 0x90).map(GuardFlags::from_bits_truncate),
+        code_integrity,
+        guard_address_taken_iat_entry_table: r.table(0x68, 0xA0),
+        guard_long_jump_target_table: r.table(0x70, 0xB0),
+        dynamic_value_reloc_table: r.pointer(0x78, 0xC0),
+        chpe_metadata_pointer: r.pointer(0x7C, 0xC8),
+        guard_rf_failure_routine: r.pointer(0x80, 0xD0),
+        guard_rf_failure_routine_function_pointer: r.pointer(0x84, 0xD8),
+        dynamic_value_reloc_table_offset: r.u32(0x88, 0xE0),
+        dynamic_value_reloc_table_section: r.u16(0x8C, 0xE4),
+        guard_rf_verify_stack_pointer_function_pointer: r.pointer(0x90, 0xE8),
+        hot_patch_table_offset: r.u32(0x94, 0xF0),
+        enclave_configuration_pointer: r.pointer(0x9C, 0xF8),
+        volatile_metadata_pointer: r.pointer(0xA0, 0x100),
+        guard_eh_continuation_table: r.table(0xA4, 0x108),
+        guard_xfg_check_function_pointer: r.pointer(0xAC, 0x118),
+        guard_xfg_dispatch_function_pointer: r.pointer(0xB0, 0x120),
+        guard_xfg_table_dispatch_function_pointer: r.pointer(0xB4, 0x128),
+        cast_guard_os_determined_failure_mode: r.pointer(0xB8, 0x130),
+        guard_memcpy_function_pointer: r.pointer(0xBC, 0x138),
+    }))
+}
+
+/// read the raw bytes of a table with entries of the given size.
+///
+/// the count comes from the file, so the table must fit within the section
+/// that contains it, or it's an error.
+fn read_table(pe: &PE, table: &Table, entry_size: usize) -> Result<Vec<u8>> {
+    let size = table
+        .count
+        .checked_mul(entry_size as u64)
+        .and_then(|size| table.address.checked_add(size).map(|end| (size, end)));
+
+    let section = pe
+        .module
+        .sections
+        .iter()
+        .find(|section| section.virtual_range.contains(&table.address));
+
+    match (size, section) {
+        (Some((size, end)), Some(section)) if end <= section.virtual_range.end => {
+            Ok(pe.module.address_space.read_bytes(table.address, size as usize)?)
+        }
+        _ => Err(PEError::MalformedPEFile(format!(
+            "invalid table at {:#x} with {} entries",
+            table.address, table.count
+        ))
+        .into()),
+    }
+}
+
+/// read the entries of a CFG table (function table, address-taken IAT table,
+/// longjmp table, or EH continuation table), given the guard flags that
+/// describe the entry stride.
+///
+/// the table is read in one go, so this fails if any part is not mapped.
+pub fn read_guard_table(pe: &PE, table: &Table, guard_flags: GuardFlags) -> Result<Vec<VA>> {
+    if table.address == 0 || table.count == 0 {
+        return Ok(vec![]);
+    }
+
+    // read the table buffer once up front, then iterate slices over it with
+    // windows. this is at the expense of one allocation for the table.
+    // it be faster than doing pe.module.with_va().read_i32() on each offset, on
+    // large tables.
+    //
+    // 4 == sizeof(u32) RVA to function start, both x32 and x64
+    let entry_size: usize = 4 + guard_flags.stride();
+    let buf = read_table(pe, table, entry_size)?;
+
+    let base_address = pe.module.address_space.base_address;
+    Ok(buf
+        .chunks_exact(entry_size)
+        .map(|entry| base_address + LittleEndian::read_u32(entry) as RVA)
+        .collect())
+}
+
+/// read the entries of the SafeSEH table.
+///
+/// the table is read in one go, so this fails if any part is not mapped.
+pub fn read_se_handler_table(pe: &PE, table: &Table) -> Result<Vec<VA>> {
+    if table.address == 0 || table.count == 0 {
+        return Ok(vec![]);
+    }
+
+    // the table is an array of u32 RVAs.
+    let buf = read_table(pe, table, 4)?;
+
+    let base_address = pe.module.address_space.base_address;
+    Ok(buf
+        .chunks_exact(4)
+        .map(|entry| base_address + LittleEndian::read_u32(entry) as RVA)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::pe::load_config::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let load_config = read_load_config_directory(&pe)?.unwrap();
+        assert_eq!(0x100, load_config.size);
+        assert_eq!(Some(0x1_800A_81F0), load_config.security_cookie);
+
+        let guard_flags = load_config.guard_flags.unwrap();
+        assert!(guard_flags.contains(GuardFlags::CF_INSTRUMENTED));
+        assert!(guard_flags.contains(GuardFlags::CF_FUNCTION_TABLE_PRESENT));
+
+        let table = load_config.guard_cf_function_table.unwrap();
+        assert_eq!(1500, read_guard_table(&pe, &table, guard_flags)?.len());
+
+        // a count taken from the file that runs past the section.
+        let huge = Table {
+            address: table.address,
+            count:   0x1_0000_0000,
+        };
+        assert!(read_guard_table(&pe, &huge, guard_flags).is_err());
+        let huge = Table {
+            address: table.address,
+            count:   u64::MAX,
+        };
+        assert!(read_guard_table(&pe, &huge, guard_flags).is_err());
+        assert!(read_se_handler_table(&pe, &huge).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tiny() -> Result<()> {
+        let buf = get_buf(Rsrc::TINY);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert!(read_load_config_directory(&pe)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn nop() -> Result<()> {
+        let buf = get_buf(Rsrc::NOP);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let load_config = read_load_config_directory(&pe)?.unwrap();
+        assert_eq!(0x48, load_config.size);
+        assert!(load_config.guard_flags.is_none());
+
+        let table = load_config.se_handler_table.unwrap();
+        assert_eq!(2, read_se_handler_table(&pe, &table)?.len());
+
+        Ok(())
+    }
+}
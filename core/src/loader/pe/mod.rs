@@ -4,12 +4,23 @@ use anyhow::Result;
 use log::debug;
 use thiserror::Error;
 
+pub mod authenticode;
+pub mod clr;
+pub mod debug;
+pub mod exports;
 pub mod imports;
+pub mod load_config;
+pub mod patch;
+#[cfg(feature = "pdb")]
+pub mod pdb;
+pub mod reloc;
+pub mod rich;
 pub mod rsrc;
+pub mod tls;
 
 use crate::{
     arch::Arch,
-    aspace::RelativeAddressSpace,
+    aspace::{RelativeAddressSpace, SharedBuffer},
     module::{Module, Permissions, Section},
     util, RVA, VA,
 };
@@ -21,6 +32,9 @@ pub enum PEError {
 
     #[error("malformed PE file: {0}")]
     MalformedPEFile(String),
+
+    #[error("address not backed by file data: {0:#x}")]
+    NotFileBacked(VA),
 }
 
 // ref: https://docs.microsoft.com/en-us/windows/win32/api/dbghelp/nf-dbghelp-imagedirectoryentrytodata#parameters
@@ -41,23 +55,114 @@ pub const IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT: usize = 13;
 pub const IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR: usize = 14;
 pub const IMAGE_DIRECTORY_MAX: usize = IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR;
 
+#[derive(Clone, Copy, Debug)]
 pub struct DataDirectory {
     pub address: VA,
     pub size:    RVA,
 }
 
+/// How the sections of a PE image are laid out in a buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// as stored on disk: each section is found at its `PointerToRawData`.
+    File,
+    /// as mapped by the Windows loader, such as in a process memory dump:
+    /// each section is found at its `VirtualAddress`.
+    Mapped,
+}
+
 /// A parsed and loaded PE file.
 /// The `buf` field contains the raw data.
 /// The `module` field contains an address space as the PE would be loaded.
+///
+/// ```
+/// use lancelot::{loader::pe::PE, util};
+///
+/// let buf = util::read_file("resources/test/tiny.exe").unwrap();
+/// let pe = PE::from_bytes(&buf).unwrap();
+/// assert_eq!(&pe.buf[..2], b"MZ");
+/// let owned: Vec<u8> = pe.buf.to_vec();
+/// assert_eq!(owned, buf);
+/// ```
 pub struct PE {
-    pub buf:    Vec<u8>,
+    /// the raw data, shared with the address space when loaded via
+    /// `from_shared_buffer`.
+    pub buf:    SharedBuffer,
     pub module: Module,
     pub header: goblin::pe::header::Header,
+    /// the layout of `buf`.
+    pub layout: Layout,
 }
 
 impl PE {
     pub fn from_bytes(buf: &[u8]) -> Result<PE> {
-        load_pe(buf)
+        load_pe(buf.into(), Layout::File, Backing::Copied)
+    }
+
+    /// Load a PE image without copying its data:
+    /// the address space reads the sections directly from the given buffer,
+    /// such as a memory-mapped file, and only allocates pages when they're
+    /// written to, like when rebasing.
+    /// This is a good fit for very large inputs.
+    ///
+    /// ```
+    /// use lancelot::{aspace::{AddressSpace, SharedBuffer}, loader::pe::PE, util};
+    ///
+    /// let buf = util::map_file("resources/test/tiny.exe").unwrap();
+    /// let pe = PE::from_shared_buffer(buf).unwrap();
+    /// assert_eq!(pe.module.address_space.read_u16(0x400000).unwrap(), 0x5A4D);
+    /// ```
+    pub fn from_shared_buffer(buf: SharedBuffer) -> Result<PE> {
+        load_pe(buf, Layout::File, Backing::Shared)
+    }
+
+    /// Load a PE image dumped from process memory, with its sections found at
+    /// their RVAs rather than at their file offsets.
+    ///
+    /// Dumps often have pages that were never paged in, or that were zeroed,
+    /// and may be truncated, so regions that aren't present in the buffer are
+    /// mapped as zeros rather than treated as an error.
+    pub fn from_memory(buf: &[u8]) -> Result<PE> {
+        load_pe(buf.into(), Layout::Mapped, Backing::Copied)
+    }
+
+    /// Load a PE image with the given layout. See `detect_layout` to guess it.
+    pub fn from_bytes_with_layout(buf: &[u8], layout: Layout) -> Result<PE> {
+        load_pe(buf.into(), layout, Backing::Copied)
+    }
+
+    /// Load the PE at the given base address, rather than its preferred base
+    /// address, applying base relocations.
+    /// This is how the image would appear when moved by ASLR.
+    pub fn from_bytes_at(buf: &[u8], base_address: VA) -> Result<PE> {
+        let mut pe = load_pe(buf.into(), Layout::File, Backing::Copied)?;
+        pe.rebase(base_address)?;
+        Ok(pe)
+    }
+
+    /// Move this loaded PE to the given base address, applying base
+    /// relocations. See `reloc::rebase`.
+    pub fn rebase(&mut self, base_address: VA) -> Result<()> {
+        reloc::rebase(self, base_address)
+    }
+
+    /// Produce a copy of the raw file with the given patches applied,
+    /// like those recorded by `aspace::OverlayAddressSpace`.
+    /// See `patch::export`.
+    pub fn export_patched(&self, patches: &[crate::aspace::Patch]) -> Result<Vec<u8>> {
+        patch::export(self, patches)
+    }
+
+    /// Parse the debug directory, such as the CodeView record that references
+    /// the PDB. See `debug::read_debug_entries`.
+    pub fn get_debug_entries(&self) -> Result<Vec<debug::DebugEntry>> {
+        debug::read_debug_entries(self)
+    }
+
+    /// Parse the .NET metadata, if this is a managed assembly.
+    /// See `clr::read_metadata`.
+    pub fn get_clr_metadata(&self) -> Result<Option<clr::Metadata>> {
+        clr::read_metadata(self)
     }
 
     pub fn executable_sections<'b>(&'b self) -> Box<dyn Iterator<Item = &Section> + 'b> {
@@ -69,8 +174,13 @@ impl PE {
         )
     }
 
+    /// Parse the PE with goblin.
+    /// For a mapped image, RVAs are used as file offsets.
     pub fn pe(&self) -> Result<goblin::pe::PE> {
-        get_pe(&self.buf)
+        let opts = goblin::pe::options::ParseOptions {
+            resolve_rva: self.layout == Layout::File,
+        };
+        Ok(goblin::pe::PE::parse_with_opts(&self.buf, &opts)?)
     }
 
     pub fn get_data_directory(&self, data_directory: usize) -> Result<Option<DataDirectory>> {
@@ -92,18 +202,88 @@ impl PE {
 }
 
 fn get_pe(buf: &[u8]) -> Result<goblin::pe::PE> {
-    let pe = goblin::pe::PE::parse(buf)?;
-    if let Some(opt) = pe.header.optional_header {
-        if opt.data_directories.get_clr_runtime_header().is_some() {
-            return Err(PEError::FormatNotSupported(".NET assembly".to_string()).into());
+    Ok(goblin::pe::PE::parse(buf)?)
+}
+
+/// parse just the headers and section table,
+/// which are found at the same offsets in both layouts.
+///
+/// unlike `get_pe`, this doesn't parse the data directories,
+/// which may well be paged out or already processed in a memory dump.
+fn get_pe_headers(buf: &[u8]) -> Result<(goblin::pe::header::Header, Vec<goblin::pe::section_table::SectionTable>)> {
+    let header = goblin::pe::header::Header::parse(buf)?;
+    let mut offset = header.dos_header.pe_pointer as usize
+        + goblin::pe::header::SIZEOF_PE_MAGIC
+        + goblin::pe::header::SIZEOF_COFF_HEADER
+        + header.coff_header.size_of_optional_header as usize;
+    let sections = header.coff_header.sections(buf, &mut offset)?;
+    Ok((header, sections))
+}
+
+/// the number of bytes to inspect at the start of each section
+/// when guessing the layout.
+const LAYOUT_PROBE_SIZE: usize = 0x200;
+
+fn is_all_zero(buf: &[u8], offset: usize, size: usize) -> bool {
+    match buf.get(offset..std::cmp::min(offset + size, buf.len())) {
+        Some(region) => region.iter().all(|&b| b == 0),
+        None => true,
+    }
+}
+
+/// guess whether the PE image in the given buffer has the file layout,
+/// or the mapped layout, such as when dumped from memory.
+///
+/// in a mapped image, the section data is found at each section's RVA,
+/// and the file offsets typically point into padding,
+/// such as the zeros that follow the headers in the first page.
+/// so, for each section whose file offset and RVA differ,
+/// we check which of the two locations has content.
+/// when there's no evidence either way, the file layout is assumed.
+pub fn detect_layout(buf: &[u8]) -> Result<Layout> {
+    let (header, sections) = get_pe_headers(buf)?;
+
+    // a truncated file is more likely a dump that covers SizeOfImage.
+    let file_end = sections
+        .iter()
+        .map(|section| section.pointer_to_raw_data as usize + section.size_of_raw_data as usize)
+        .max()
+        .unwrap_or(0);
+    if let Some(opt) = header.optional_header {
+        if file_end > buf.len() && opt.windows_fields.size_of_image as usize <= buf.len() {
+            debug!("pe: layout: mapped: file layout exceeds buffer");
+            return Ok(Layout::Mapped);
+        }
+    }
+
+    let mut votes: i32 = 0;
+    for section in sections.iter() {
+        if section.size_of_raw_data == 0 || section.pointer_to_raw_data == section.virtual_address {
+            continue;
+        }
+
+        let size = std::cmp::min(section.size_of_raw_data as usize, LAYOUT_PROBE_SIZE);
+        let at_file_offset = is_all_zero(buf, section.pointer_to_raw_data as usize, size);
+        let at_rva = is_all_zero(buf, section.virtual_address as usize, size);
+
+        match (at_file_offset, at_rva) {
+            (true, false) => votes += 1,
+            (false, true) => votes -= 1,
+            _ => continue,
         }
     }
-    Ok(pe)
+    debug!("pe: layout: votes for mapped: {}", votes);
+
+    if votes > 0 {
+        Ok(Layout::Mapped)
+    } else {
+        Ok(Layout::File)
+    }
 }
 
 #[allow(clippy::unnecessary_wraps)]
-fn load_pe_header(buf: &[u8], pe: &goblin::pe::PE, base_address: VA) -> Result<Section> {
-    let hdr_raw_size = match pe.header.optional_header {
+fn load_pe_header(buf: &[u8], header: &goblin::pe::header::Header, base_address: VA) -> Result<Section> {
+    let hdr_raw_size = match header.optional_header {
         Some(opt) => opt.windows_fields.size_of_headers,
         // assumption: header is at most 0x200 bytes.
         _ => 0x200,
@@ -201,16 +381,45 @@ fn load_pe_section(
 }
 
 // lots of further detail here: https://github.com/corkami/docs/blob/master/PE/PE.md
-fn load_pe(buf: &[u8]) -> Result<PE> {
-    let pe = get_pe(buf)?;
+/// How the section data makes its way into the address space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Backing {
+    /// copied into the pages of the address space.
+    Copied,
+    /// read on demand from the shared buffer.
+    Shared,
+}
+
+fn load_pe(shared_buf: SharedBuffer, layout: Layout, backing: Backing) -> Result<PE> {
+    let buf: &[u8] = &shared_buf;
+
+    // for the file layout, parse the whole image up front, so that malformed
+    // files are rejected early.
+    // a mapped image is dumped after the loader has processed it,
+    // so only rely on the headers.
+    let (header, sections_table, is_64) = match layout {
+        Layout::File => {
+            let pe = get_pe(buf)?;
+            (pe.header, pe.sections, pe.is_64)
+        }
+        Layout::Mapped => {
+            let (header, sections) = get_pe_headers(buf)?;
+            let is_64 = match header.optional_header {
+                Some(opt) => opt.container()? == goblin::container::Container::Big,
+                None => false,
+            };
+            (header, sections, is_64)
+        }
+    };
+    debug!("pe: layout: {:?}", layout);
 
-    let arch = match pe.is_64 {
+    let arch = match is_64 {
         false => Arch::X32,
         true => Arch::X64,
     };
     debug!("pe: arch: {:?}", arch);
 
-    let (base_address, section_alignment) = match pe.header.optional_header {
+    let (base_address, section_alignment) = match header.optional_header {
         Some(opt) => (
             opt.windows_fields.image_base,
             opt.windows_fields.section_alignment as u64,
@@ -222,18 +431,46 @@ fn load_pe(buf: &[u8]) -> Result<PE> {
     };
     debug!("pe: base address: {:#x}", base_address);
 
-    let mut sections = vec![load_pe_header(buf, &pe, base_address)?];
-    for section in pe.sections.iter() {
-        sections.push(load_pe_section(base_address, section_alignment as u64, section)?);
+    let mut sections = vec![load_pe_header(buf, &header, base_address)?];
+    for section in sections_table.iter() {
+        let mut section = load_pe_section(base_address, section_alignment as u64, section)?;
+
+        if layout == Layout::Mapped {
+            // the section data is found at its RVA, and spans its virtual size.
+            section.physical_range = std::ops::Range {
+                start: section.virtual_range.start - base_address,
+                end:   section.virtual_range.end - base_address,
+            };
+        }
+
+        sections.push(section);
     }
 
     let max_address = sections.iter().map(|sec| sec.virtual_range.end).max().unwrap();
     let max_page_address = util::align(max_address, 0x1000) - base_address;
     debug!("pe: address space: capacity: {:#x}", max_page_address);
 
-    let mut address_space = RelativeAddressSpace::with_capacity(max_page_address);
+    let mut address_space = match backing {
+        Backing::Copied => RelativeAddressSpace::with_capacity(max_page_address),
+        Backing::Shared => RelativeAddressSpace::with_backing(max_page_address, shared_buf.clone()),
+    };
+
+    for section in sections.iter_mut() {
+        if section.physical_range.end as usize > buf.len() {
+            if layout == Layout::File {
+                return Err(PEError::MalformedPEFile(format!("section {} out of bounds", section.name)).into());
+            }
+
+            // tolerate truncated dumps:
+            // any data beyond the end of the buffer is treated as zeros.
+            debug!(
+                "pe: section: {}: truncated: {:#x} - {:#x}",
+                section.name, section.physical_range.start, section.physical_range.end
+            );
+            section.physical_range.end = buf.len() as u64;
+            section.physical_range.start = std::cmp::min(section.physical_range.start, section.physical_range.end);
+        }
 
-    for section in sections.iter() {
         let pstart = section.physical_range.start as usize;
         let pend = section.physical_range.end as usize;
         let psize = pend - pstart;
@@ -249,19 +486,29 @@ fn load_pe(buf: &[u8]) -> Result<PE> {
             section_alignment,
         );
         let vend = vstart + vsize;
-        let mut vbuf = vec![0u8; vsize as usize];
-
-        if vsize as usize >= psize {
-            // vsize > psize, so there will be NULL bytes padding the physical data.
-            let dest = &mut vbuf[0..psize as usize];
-            dest.copy_from_slice(pbuf);
-        } else {
-            // psize > vsize, but vsize wins, so we only read a subset of physical data.
-            let src = &pbuf[0..vsize as usize];
-            vbuf.copy_from_slice(src);
-        }
 
-        address_space.map.writezx(rstart, &vbuf)?;
+        match backing {
+            Backing::Copied => {
+                let mut vbuf = vec![0u8; vsize as usize];
+
+                if vsize as usize >= psize {
+                    // vsize > psize, so there will be NULL bytes padding the physical data.
+                    let dest = &mut vbuf[0..psize as usize];
+                    dest.copy_from_slice(pbuf);
+                } else {
+                    // psize > vsize, but vsize wins, so we only read a subset of physical data.
+                    let src = &pbuf[0..vsize as usize];
+                    vbuf.copy_from_slice(src);
+                }
+
+                address_space.map.writezx(rstart, &vbuf)?;
+            }
+            Backing::Shared => {
+                // as above, vsize wins, and any remainder is NULL bytes.
+                let length = std::cmp::min(psize, vsize as usize);
+                address_space.map_backed(rstart, pstart, length, vsize)?;
+            }
+        }
 
         debug!(
             "pe: address space: mapped {:#x} - {:#x} {:?}",
@@ -277,9 +524,10 @@ fn load_pe(buf: &[u8]) -> Result<PE> {
 
     debug!("pe: loaded");
     Ok(PE {
-        buf: buf.to_vec(),
+        buf: shared_buf,
         module,
-        header: pe.header,
+        header,
+        layout,
     })
 }
 
@@ -377,4 +625,155 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn detect_layout() -> Result<()> {
+        use crate::loader::pe::{detect_layout, Layout};
+
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        assert_eq!(Layout::File, detect_layout(&buf)?);
+        assert_eq!(Layout::Mapped, detect_layout(&crate::test::dump_pe(&pe))?);
+
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        assert_eq!(Layout::File, detect_layout(&buf)?);
+        assert_eq!(Layout::Mapped, detect_layout(&crate::test::dump_pe(&pe))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_memory() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        let mapped = crate::loader::pe::PE::from_memory(&crate::test::dump_pe(&pe))?;
+
+        assert_eq!(crate::loader::pe::Layout::Mapped, mapped.layout);
+        assert_eq!(0x1_8000_0000, mapped.module.address_space.base_address);
+        assert_eq!(pe.module.sections.len(), mapped.module.sections.len());
+
+        for (section, mapped_section) in pe.module.sections.iter().zip(mapped.module.sections.iter()) {
+            assert_eq!(section.virtual_range, mapped_section.virtual_range);
+            assert_eq!(section.permissions, mapped_section.permissions);
+
+            let size = (section.virtual_range.end - section.virtual_range.start) as usize;
+            assert_eq!(
+                pe.module.address_space.read_bytes(section.virtual_range.start, size)?,
+                mapped
+                    .module
+                    .address_space
+                    .read_bytes(section.virtual_range.start, size)?
+            );
+        }
+
+        // in a mapped image, file offsets are RVAs.
+        assert_eq!(0x1000, mapped.module.file_offset(0x1_8000_1000)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_memory_truncated() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        let mut buf = crate::test::dump_pe(&pe);
+
+        // page out the second page of .text,
+        // and drop the end of the image.
+        let text = pe
+            .module
+            .sections
+            .iter()
+            .find(|section| section.name == ".text")
+            .unwrap();
+        let page = (text.virtual_range.start - 0x1_8000_0000) as usize + 0x1000;
+        buf[page..page + 0x1000].fill(0);
+        buf.truncate(buf.len() / 2);
+
+        let mapped = crate::loader::pe::PE::from_memory(&buf)?;
+        assert_eq!(0x0, mapped.module.address_space.relative.read_u64(page as u64)?);
+
+        // the sections are still mapped in full, padded with zeros.
+        let last = mapped.module.sections.last().unwrap();
+        assert_eq!(pe.module.sections.last().unwrap().virtual_range, last.virtual_range);
+        assert_eq!(0x0, mapped.module.address_space.read_u8(last.virtual_range.end - 1)?);
+
+        // whereas a truncated file is malformed.
+        let buf = get_buf(Rsrc::K32);
+        assert!(crate::loader::pe::PE::from_bytes(&buf[..buf.len() / 2]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_buffer() -> Result<()> {
+        use crate::aspace::SharedBuffer;
+
+        for rsrc in [Rsrc::K32, Rsrc::MIMI] {
+            let buf = get_buf(rsrc);
+            let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+            let shared = crate::loader::pe::PE::from_shared_buffer(SharedBuffer::from(buf.clone()))?;
+
+            assert_eq!(pe.module.sections.len(), shared.module.sections.len());
+            for section in pe.module.sections.iter() {
+                // including the zero padding to the end of the page.
+                let size = crate::util::align(section.virtual_range.end - section.virtual_range.start, 0x1000);
+                assert_eq!(
+                    pe.module
+                        .address_space
+                        .read_bytes(section.virtual_range.start, size as usize)?,
+                    shared
+                        .module
+                        .address_space
+                        .read_bytes(section.virtual_range.start, size as usize)?
+                );
+            }
+
+            let max_address = pe
+                .module
+                .sections
+                .iter()
+                .map(|sec| sec.virtual_range.end)
+                .max()
+                .unwrap();
+            assert!(shared
+                .module
+                .address_space
+                .read_u8(crate::util::align(max_address, 0x1000))
+                .is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_buffer_rebase() -> Result<()> {
+        use crate::aspace::SharedBuffer;
+
+        let buf = get_buf(Rsrc::K32);
+        let rebased = crate::loader::pe::PE::from_bytes_at(&buf, 0x7FF8_0000_0000)?;
+
+        let mut shared = crate::loader::pe::PE::from_shared_buffer(SharedBuffer::from(buf.clone()))?;
+        shared.rebase(0x7FF8_0000_0000)?;
+
+        for section in rebased.module.sections.iter() {
+            let size = (section.virtual_range.end - section.virtual_range.start) as usize;
+            assert_eq!(
+                rebased
+                    .module
+                    .address_space
+                    .read_bytes(section.virtual_range.start, size)?,
+                shared
+                    .module
+                    .address_space
+                    .read_bytes(section.virtual_range.start, size)?
+            );
+        }
+
+        // the relocations are applied to copies of the pages, not the buffer.
+        assert_eq!(&buf[..], &shared.buf[..]);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,201 @@
+//! Write patches, like those recorded by `aspace::OverlayAddressSpace`,
+//! back into the raw PE file.
+//!
+//! Each patched address is translated to its file offset via
+//! `Module::file_offset`, so the patches must fall within data that's backed
+//! by the file. For example, you can't patch the zero padding at the end of a
+//! section whose virtual size exceeds its raw size.
+//!
+//! The checksum in the optional header is left as-is,
+//! since it's not verified for most images.
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    aspace::Patch,
+    loader::pe::{PEError, PE},
+    VA,
+};
+
+/// find the file offset of the given address,
+/// ensuring that the byte is actually found in the file.
+fn get_file_offset(pe: &PE, va: VA) -> Result<usize> {
+    let offset = match pe.module.file_offset(va) {
+        Ok(offset) => offset,
+        Err(_) => return Err(PEError::NotFileBacked(va).into()),
+    };
+
+    // the offset may be past the end of the section's raw data,
+    // in which case it maps back to some other address (or none at all).
+    if offset >= pe.buf.len() || pe.module.virtual_address(offset as u64).ok() != Some(va) {
+        return Err(PEError::NotFileBacked(va).into());
+    }
+
+    Ok(offset)
+}
+
+/// produce a copy of the raw PE file with the given patches applied, in order.
+///
+/// errors:
+///   - PEError::NotFileBacked: if a patched byte isn't found in the file.
+pub fn export(pe: &PE, patches: &[Patch]) -> Result<Vec<u8>> {
+    let mut buf = pe.buf.to_vec();
+
+    for patch in patches.iter() {
+        debug!("patch: {:#x}: {} bytes", patch.address, patch.patched.len());
+
+        for (i, &b) in patch.patched.iter().enumerate() {
+            let offset = get_file_offset(pe, patch.address + i as u64)?;
+            buf[offset] = b;
+        }
+    }
+
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        aspace::{AddressSpace, OverlayAddressSpace},
+        rsrc::*,
+    };
+    use anyhow::Result;
+
+    #[test]
+    fn overlay() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let mut aspace = OverlayAddressSpace::new(&pe.module.address_space);
+        // the start of .text
+        aspace.patch(0x1_8000_1010, b"\x90\x90\x90")?;
+        aspace.patch(0x1_8000_1011, b"\xCC")?;
+
+        assert_eq!(b"\x90\xCC\x90".to_vec(), aspace.read_bytes(0x1_8000_1010, 3)?);
+        assert_ne!(0x90, pe.module.address_space.read_u8(0x1_8000_1010)?);
+
+        assert_eq!(2, aspace.patches().len());
+        assert_eq!(vec![0x90], aspace.patches()[1].original);
+
+        // reverting the first patch leaves the second in place.
+        let first = aspace.revert(0).unwrap();
+        assert_eq!(pe.module.address_space.read_bytes(0x1_8000_1010, 3)?, first.original);
+        assert_eq!(first.original[0], aspace.read_u8(0x1_8000_1010)?);
+        assert_eq!(0xCC, aspace.read_u8(0x1_8000_1011)?);
+
+        assert!(aspace.undo().is_some());
+        assert!(aspace.undo().is_none());
+        assert_eq!(
+            pe.module.address_space.read_bytes(0x1_8000_1010, 3)?,
+            aspace.read_bytes(0x1_8000_1010, 3)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_ascii() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // the DOS stub: "This program cannot be run in DOS mode."
+        let mut aspace = OverlayAddressSpace::new(&pe.module.address_space);
+        assert_eq!(
+            "This program cannot be run in DOS mode.\r\r\n$",
+            aspace.read_ascii(0x1_8000_004E, 1)?
+        );
+
+        aspace.patch(0x1_8000_004E, b"That")?;
+        assert!(aspace.read_ascii(0x1_8000_004E, 1)?.starts_with("That program"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_module() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let mut aspace = OverlayAddressSpace::new(&pe.module.address_space);
+        aspace.patch(0x1_8000_1010, b"\xC3")?;
+
+        let module = aspace.to_module(&pe.module)?;
+        assert_eq!(0xC3, module.address_space.read_u8(0x1_8000_1010)?);
+        assert_ne!(0xC3, pe.module.address_space.read_u8(0x1_8000_1010)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn export() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let mut aspace = OverlayAddressSpace::new(&pe.module.address_space);
+        aspace.patch(0x1_8000_1010, b"\x90\x90")?;
+        // in the header.
+        aspace.patch(0x1_8000_004E, b"That")?;
+
+        let patched = pe.export_patched(aspace.patches())?;
+        assert_eq!(buf.len(), patched.len());
+
+        let offset = pe.module.file_offset(0x1_8000_1010)?;
+        assert_eq!(b"\x90\x90", &patched[offset..offset + 2]);
+        assert_eq!(b"That", &patched[0x4E..0x52]);
+
+        // reloading the patched file shows the patches.
+        let repe = crate::loader::pe::PE::from_bytes(&patched)?;
+        assert_eq!(
+            aspace.read_bytes(0x1_8000_1000, 0x100)?,
+            repe.module.address_space.read_bytes(0x1_8000_1000, 0x100)?
+        );
+
+        // the original is intact.
+        assert_eq!(&buf[..], &pe.buf[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_not_file_backed() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // find the zero padding at the end of a section
+        // whose virtual size exceeds its raw size, like .data.
+        let section = pe
+            .module
+            .sections
+            .iter()
+            .find(|section| {
+                (section.virtual_range.end - section.virtual_range.start)
+                    > (section.physical_range.end - section.physical_range.start)
+            })
+            .unwrap();
+
+        let mut aspace = OverlayAddressSpace::new(&pe.module.address_space);
+        aspace.patch(section.virtual_range.end - 1, b"\x01")?;
+        assert!(pe.export_patched(aspace.patches()).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "disassembler")]
+    #[test]
+    fn cfg() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // replace the start of a function with `ret`,
+        // so the CFG is just the one instruction.
+        let mut aspace = OverlayAddressSpace::new(&pe.module.address_space);
+        aspace.patch(0x1_8000_1010, b"\xC3")?;
+        let module = aspace.to_module(&pe.module)?;
+
+        let cfg = crate::analysis::cfg::build_cfg(&module, 0x1_8000_1010)?;
+        assert_eq!(1, cfg.basic_blocks.len());
+        assert_eq!(1, cfg.basic_blocks.values().next().unwrap().length);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+//! Load symbols from a PDB file that matches a PE.
+//!
+//! The PDB is matched against the CodeView record found in the debug directory
+//! of the PE (see `loader::pe::debug`): both the GUID and age must agree,
+//! otherwise the symbols would describe some other build.
+//!
+//! We extract:
+//!   - public symbols, like `kernel32!CreateFileW`, which name code and data,
+//!   - procedures from the module streams, which have a name and a size,
+//!   - global (and file static) data.
+//!
+//! All addresses are translated into VAs relative to the module's current base
+//! address, so they can be used with the `Module` directly.
+//!
+//! references:
+//!   - https://llvm.org/docs/PDB/index.html
+//!   - https://github.com/willglynn/pdb
+use std::{collections::BTreeMap, fs::File, path::Path};
+
+use anyhow::Result;
+use log::debug;
+use pdb::FallibleIterator;
+use thiserror::Error;
+
+use crate::{
+    loader::pe::{
+        debug::{read_codeview, CodeView, Guid},
+        PE,
+    },
+    VA,
+};
+
+#[derive(Error, Debug)]
+pub enum PdbError {
+    #[error("the PE has no CodeView record")]
+    NoCodeView,
+
+    #[error("the PE references a PDB 2.0 file, which is not supported")]
+    NotSupported,
+
+    #[error("the PDB does not match the PE: expected {expected}, found {found}")]
+    Mismatch { expected: String, found: String },
+}
+
+/// A public or global data symbol.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Symbol {
+    pub address: VA,
+    pub name:    String,
+}
+
+/// A public symbol, as found in the global symbol stream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PublicSymbol {
+    pub address:     VA,
+    pub name:        String,
+    /// the symbol references a function, rather than data.
+    pub is_function: bool,
+}
+
+/// A procedure, as found in the module (compiland) symbol streams.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Procedure {
+    /// from the start of the procedure through the end of its last instruction.
+    pub range:  std::ops::Range<VA>,
+    pub name:   String,
+    /// the procedure is visible outside its compiland, rather than `static`.
+    pub global: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    pub publics:    Vec<PublicSymbol>,
+    pub procedures: Vec<Procedure>,
+    pub globals:    Vec<Symbol>,
+}
+
+impl Symbols {
+    /// collect a name for each address, preferring procedure names to public
+    /// (typically decorated) names.
+    pub fn names(&self) -> BTreeMap<VA, String> {
+        let mut names: BTreeMap<VA, String> = Default::default();
+
+        for global in self.globals.iter() {
+            names.insert(global.address, global.name.clone());
+        }
+        for public in self.publics.iter() {
+            names.insert(public.address, public.name.clone());
+        }
+        for procedure in self.procedures.iter() {
+            names.insert(procedure.range.start, procedure.name.clone());
+        }
+
+        names
+    }
+}
+
+/// fetch the GUID and age of the PDB referenced by the PE's CodeView record.
+fn get_pdb_reference(pe: &PE) -> Result<(Guid, u32)> {
+    match read_codeview(pe)? {
+        Some(CodeView::Pdb70 { guid, age, .. }) => Ok((guid, age)),
+        Some(CodeView::Pdb20 { .. }) => Err(PdbError::NotSupported.into()),
+        None => Err(PdbError::NoCodeView.into()),
+    }
+}
+
+/// ensure the PDB was produced by the same build as the PE,
+/// by comparing its GUID and age with the PE's CodeView record.
+fn check_pdb_matches<'s, S: pdb::Source<'s> + 's>(guid: Guid, age: u32, pdb: &mut pdb::PDB<'s, S>) -> Result<()> {
+    let info = pdb.pdb_information()?;
+    // the age in the PDB information stream is incremented on each incremental
+    // link, while the DBI stream holds the age that is written into the PE.
+    let pdb_age = pdb.debug_information()?.age().unwrap_or(info.age);
+
+    let (data1, data2, data3, data4) = info.guid.as_fields();
+    let pdb_guid = Guid {
+        data1,
+        data2,
+        data3,
+        data4: *data4,
+    };
+
+    if pdb_guid != guid || pdb_age != age {
+        return Err(PdbError::Mismatch {
+            expected: format!("{} age {}", guid, age),
+            found:    format!("{} age {}", pdb_guid, pdb_age),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// read the symbols from the given PDB source, such as an open file,
+/// which must match the given PE.
+pub fn read_pdb_symbols<'s, S: pdb::Source<'s> + 's>(pe: &PE, source: S) -> Result<Symbols> {
+    let (guid, age) = get_pdb_reference(pe)?;
+
+    let mut pdb = pdb::PDB::open(source)?;
+    check_pdb_matches(guid, age, &mut pdb)?;
+
+    let base_address = pe.module.address_space.base_address;
+    let address_map = pdb.address_map()?;
+    let mut symbols: Symbols = Default::default();
+
+    let global_symbols = pdb.global_symbols()?;
+    let mut iter = global_symbols.iter();
+    while let Some(symbol) = iter.next()? {
+        match symbol.parse() {
+            Ok(pdb::SymbolData::Public(data)) => {
+                if let Some(rva) = data.offset.to_rva(&address_map) {
+                    symbols.publics.push(PublicSymbol {
+                        address:     base_address + rva.0 as VA,
+                        name:        data.name.to_string().into_owned(),
+                        is_function: data.function,
+                    });
+                }
+            }
+            Ok(pdb::SymbolData::Data(data)) => {
+                if let Some(rva) = data.offset.to_rva(&address_map) {
+                    symbols.globals.push(Symbol {
+                        address: base_address + rva.0 as VA,
+                        name:    data.name.to_string().into_owned(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    debug!("pdb: found {} public symbols", symbols.publics.len());
+    debug!("pdb: found {} global data symbols", symbols.globals.len());
+
+    let debug_information = pdb.debug_information()?;
+    let mut modules = debug_information.modules()?;
+    while let Some(module) = modules.next()? {
+        let module_info = match pdb.module_info(&module)? {
+            Some(module_info) => module_info,
+            None => continue,
+        };
+
+        let mut iter = module_info.symbols()?;
+        while let Some(symbol) = iter.next()? {
+            if let Ok(pdb::SymbolData::Procedure(data)) = symbol.parse() {
+                if let Some(rva) = data.offset.to_rva(&address_map) {
+                    let start = base_address + rva.0 as VA;
+                    symbols.procedures.push(Procedure {
+                        range:  start..start + data.len as VA,
+                        name:   data.name.to_string().into_owned(),
+                        global: data.global,
+                    });
+                }
+            }
+        }
+    }
+    debug!("pdb: found {} procedures", symbols.procedures.len());
+
+    symbols.publics.sort_unstable_by_key(|public| public.address);
+    symbols.globals.sort_unstable_by_key(|global| global.address);
+    symbols
+        .procedures
+        .sort_unstable_by_key(|procedure| procedure.range.start);
+
+    Ok(symbols)
+}
+
+/// read the symbols from the local PDB file, which must match the given PE.
+pub fn load_pdb_symbols<P: AsRef<Path>>(pe: &PE, path: P) -> Result<Symbols> {
+    read_pdb_symbols(pe, File::open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::pe::pdb::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // k32 references kernel32.pdb, but this isn't a PDB file.
+        assert!(read_pdb_symbols(&pe, std::io::Cursor::new(vec![0u8; 0x1000])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tiny() -> Result<()> {
+        let buf = get_buf(Rsrc::TINY);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // there's no CodeView record, so no PDB could ever match.
+        let err = read_pdb_symbols(&pe, std::io::Cursor::new(vec![0u8; 0x1000])).unwrap_err();
+        assert!(matches!(err.downcast_ref::<PdbError>(), Some(PdbError::NoCodeView)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn nop() -> Result<()> {
+        let buf = get_buf(Rsrc::NOP);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let symbols = load_pdb_symbols(&pe, get_path(Rsrc::PDB))?;
+
+        assert_eq!(4, symbols.publics.len());
+        assert_eq!(
+            PublicSymbol {
+                address:     0x401000,
+                name:        String::from("_main"),
+                is_function: true,
+            },
+            symbols.publics[0]
+        );
+        assert_eq!(
+            PublicSymbol {
+                address:     0x408050,
+                name:        String::from("__iob"),
+                is_function: false,
+            },
+            symbols.publics[3]
+        );
+
+        assert_eq!(
+            vec![Symbol {
+                address: 0x408050,
+                name:    String::from("_iob"),
+            }],
+            symbols.globals
+        );
+
+        // the procedures come from each of the modules, sorted by address.
+        assert_eq!(3, symbols.procedures.len());
+        assert_eq!(
+            Procedure {
+                range:  0x401000..0x40102B,
+                name:   String::from("main"),
+                global: true,
+            },
+            symbols.procedures[0]
+        );
+        assert_eq!(0x40102B..0x40105C, symbols.procedures[1].range);
+        assert_eq!("_amsg_exit", symbols.procedures[2].name);
+
+        // procedure names are preferred to public names.
+        let names = symbols.names();
+        assert_eq!("main", names[&0x401000]);
+        assert_eq!("_mainCRTStartup", names[&0x401081]);
+        assert_eq!("__iob", names[&0x408050]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn nop_mismatch() -> Result<()> {
+        let mut buf = get_buf(Rsrc::NOP);
+        let pdb = get_buf(Rsrc::PDB);
+
+        // bump the age in the CodeView record, which follows the signature and GUID.
+        let offset = buf.windows(4).position(|w| w == b"RSDS").unwrap();
+        buf[offset + 20] += 1;
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let err = read_pdb_symbols(&pe, std::io::Cursor::new(pdb)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PdbError>(),
+            Some(PdbError::Mismatch { .. })
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+//! Parse the PE base relocation directory (`.reloc`), and use it to rebase a
+//! loaded PE to an arbitrary base address.
+//!
+//! The directory is a sequence of blocks, each covering one 4KB page:
+//!
+//! ```text
+//!  0x0                      0x8
+//!  +------------------------+-------+-------+-----+
+//!  | IMAGE_BASE_RELOCATION  | u16   | u16   | ... |
+//!  |   VirtualAddress u32   | entry | entry |     |
+//!  |   SizeOfBlock u32      |       |       |     |
+//!  +------------------------+-------+-------+-----+
+//! ```
+//!
+//! Each entry is `type << 12 | offset`, where `offset` is relative to the
+//! block's `VirtualAddress`.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#the-reloc-section-image-only
+
+// we use identifier names from the C headers for PE structures,
+// which don't match the Rust style guide.
+#![allow(non_upper_case_globals)]
+
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::{
+    aspace::AddressSpace,
+    loader::pe::{PEError, IMAGE_DIRECTORY_ENTRY_BASERELOC, PE},
+    RVA, VA,
+};
+
+const sizeof_IMAGE_BASE_RELOCATION: u64 = 0x8;
+
+const IMAGE_REL_BASED_ABSOLUTE: u16 = 0;
+const IMAGE_REL_BASED_HIGH: u16 = 1;
+const IMAGE_REL_BASED_LOW: u16 = 2;
+const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+const IMAGE_REL_BASED_HIGHADJ: u16 = 4;
+const IMAGE_REL_BASED_DIR64: u16 = 10;
+
+/// Relocation information was stripped from the file,
+/// so the file must be loaded at its preferred base address.
+const IMAGE_FILE_RELOCS_STRIPPED: u16 = 0x0001;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BaseRelocationType {
+    /// fixup the high 16 bits of a 32-bit field.
+    High,
+    /// fixup the low 16 bits of a 32-bit field.
+    Low,
+    /// fixup a 32-bit field.
+    HighLow,
+    /// fixup the high 16 bits of a 32-bit field,
+    /// given the low 16 bits found in the following entry.
+    HighAdj(u16),
+    /// fixup a 64-bit field.
+    Dir64,
+    /// a relocation type that doesn't apply to x86/x64, such as for MIPS or
+    /// ARM.
+    Other(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BaseRelocation {
+    /// the address of the field to fix up.
+    pub address: VA,
+    pub kind:    BaseRelocationType,
+}
+
+/// parse the base relocation directory, if present.
+///
+/// padding (`IMAGE_REL_BASED_ABSOLUTE`) entries are not returned.
+/// parsing stops, without error, at the first malformed block.
+pub fn read_base_relocations(pe: &PE) -> Result<Vec<BaseRelocation>> {
+    let mut relocs = vec![];
+
+    let reloc_directory = match pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_BASERELOC)? {
+        Some(reloc_directory) if reloc_directory.size > 0 => reloc_directory,
+        _ => return Ok(relocs),
+    };
+    debug!("reloc: directory: {:#x}", reloc_directory.address);
+
+    let base_address = pe.module.address_space.base_address;
+    let buf = match pe
+        .module
+        .address_space
+        .read_bytes(reloc_directory.address, reloc_directory.size as usize)
+    {
+        Ok(buf) => buf,
+        Err(_) => {
+            debug!("reloc: directory not mapped");
+            return Ok(relocs);
+        }
+    };
+
+    let mut offset = 0usize;
+    while offset as u64 + sizeof_IMAGE_BASE_RELOCATION <= buf.len() as u64 {
+        let page_rva = LittleEndian::read_u32(&buf[offset..]) as RVA;
+        let block_size = LittleEndian::read_u32(&buf[offset + 4..]) as usize;
+
+        if (block_size as u64) < sizeof_IMAGE_BASE_RELOCATION || offset + block_size > buf.len() {
+            debug!("reloc: invalid block size: {:#x}", block_size);
+            break;
+        }
+
+        let entries: Vec<u16> = buf[offset + sizeof_IMAGE_BASE_RELOCATION as usize..offset + block_size]
+            .chunks_exact(2)
+            .map(LittleEndian::read_u16)
+            .collect();
+
+        let mut entries = entries.into_iter();
+        while let Some(entry) = entries.next() {
+            let address = base_address + page_rva + (entry & 0xFFF) as RVA;
+            let kind = match entry >> 12 {
+                IMAGE_REL_BASED_ABSOLUTE => continue,
+                IMAGE_REL_BASED_HIGH => BaseRelocationType::High,
+                IMAGE_REL_BASED_LOW => BaseRelocationType::Low,
+                IMAGE_REL_BASED_HIGHLOW => BaseRelocationType::HighLow,
+                // the following entry is the low 16 bits of the target, not a relocation.
+                IMAGE_REL_BASED_HIGHADJ => match entries.next() {
+                    Some(low) => BaseRelocationType::HighAdj(low),
+                    None => {
+                        debug!("reloc: HIGHADJ entry missing parameter at {:#x}", address);
+                        break;
+                    }
+                },
+                IMAGE_REL_BASED_DIR64 => BaseRelocationType::Dir64,
+                ty => BaseRelocationType::Other(ty as u8),
+            };
+
+            relocs.push(BaseRelocation { address, kind });
+        }
+
+        offset += block_size;
+    }
+
+    debug!("reloc: found {} relocations", relocs.len());
+    Ok(relocs)
+}
+
+/// apply the given relocation to the loaded module, given the difference
+/// between the new and old base addresses.
+fn apply_base_relocation(pe: &mut PE, reloc: &BaseRelocation, delta: u64) -> Result<()> {
+    let rva = reloc.address - pe.module.address_space.base_address;
+    let address_space = &mut pe.module.address_space.relative;
+
+    match reloc.kind {
+        BaseRelocationType::High => {
+            let v = ((address_space.read_u16(rva)? as u32) << 16).wrapping_add(delta as u32);
+            address_space.write_into(rva, &((v >> 16) as u16).to_le_bytes())?;
+        }
+        BaseRelocationType::Low => {
+            let v = address_space.read_u16(rva)?.wrapping_add(delta as u16);
+            address_space.write_into(rva, &v.to_le_bytes())?;
+        }
+        BaseRelocationType::HighLow => {
+            let v = address_space.read_u32(rva)?.wrapping_add(delta as u32);
+            address_space.write_into(rva, &v.to_le_bytes())?;
+        }
+        BaseRelocationType::HighAdj(low) => {
+            let v = ((address_space.read_u16(rva)? as u32) << 16)
+                .wrapping_add(low as i16 as i32 as u32)
+                .wrapping_add(delta as u32)
+                // round, as the low half is sign extended when its used.
+                .wrapping_add(0x8000);
+            address_space.write_into(rva, &((v >> 16) as u16).to_le_bytes())?;
+        }
+        BaseRelocationType::Dir64 => {
+            let v = address_space.read_u64(rva)?.wrapping_add(delta);
+            address_space.write_into(rva, &v.to_le_bytes())?;
+        }
+        BaseRelocationType::Other(ty) => {
+            debug!("reloc: unsupported relocation type {} at {:#x}", ty, reloc.address);
+        }
+    }
+
+    Ok(())
+}
+
+/// move the loaded PE to the given base address,
+/// applying base relocations to `module.address_space`,
+/// and updating the section addresses and image base in the headers to match.
+///
+/// errors:
+///   - PEError::FormatNotSupported: if the relocations have been stripped.
+pub fn rebase(pe: &mut PE, base_address: VA) -> Result<()> {
+    let old_base_address = pe.module.address_space.base_address;
+    if old_base_address == base_address {
+        return Ok(());
+    }
+
+    if pe.header.coff_header.characteristics & IMAGE_FILE_RELOCS_STRIPPED > 0 {
+        return Err(PEError::FormatNotSupported("relocations stripped".to_string()).into());
+    }
+
+    // modular arithmetic takes care of moving the module to a lower address.
+    let delta = base_address.wrapping_sub(old_base_address);
+    debug!("reloc: rebase {:#x} -> {:#x}", old_base_address, base_address);
+
+    for reloc in read_base_relocations(pe)?.iter() {
+        if let Err(e) = apply_base_relocation(pe, reloc, delta) {
+            debug!("reloc: failed to apply relocation at {:#x}: {}", reloc.address, e);
+        }
+    }
+
+    // like the Windows loader, update the ImageBase field in the mapped headers.
+    // PE signature (4) + IMAGE_FILE_HEADER (0x14) + offset of ImageBase
+    if let Some(opt) = pe.header.optional_header.as_mut() {
+        let address_space = &mut pe.module.address_space.relative;
+        let header_offset = pe.header.dos_header.pe_pointer as RVA + 0x4 + 0x14;
+        match pe.module.arch {
+            crate::arch::Arch::X32 => {
+                address_space.write_into(header_offset + 0x1C, &(base_address as u32).to_le_bytes())?;
+            }
+            crate::arch::Arch::X64 => {
+                address_space.write_into(header_offset + 0x18, &base_address.to_le_bytes())?;
+            }
+        }
+        opt.windows_fields.image_base = base_address;
+    }
+
+    for section in pe.module.sections.iter_mut() {
+        section.virtual_range = std::ops::Range {
+            start: section.virtual_range.start.wrapping_add(delta),
+            end:   section.virtual_range.end.wrapping_add(delta),
+        };
+    }
+    pe.module.address_space.base_address = base_address;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{aspace::AddressSpace, loader::pe::reloc::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let relocs = read_base_relocations(&pe)?;
+        assert_eq!(276, relocs.len());
+        assert!(relocs.iter().all(|reloc| reloc.kind == BaseRelocationType::Dir64));
+
+        Ok(())
+    }
+
+    #[test]
+    fn tiny() -> Result<()> {
+        let buf = get_buf(Rsrc::TINY);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert_eq!(0, read_base_relocations(&pe)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let relocs = read_base_relocations(&pe)?;
+        assert_eq!(11457, relocs.len());
+        assert!(relocs.iter().all(|reloc| reloc.kind == BaseRelocationType::HighLow));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rebase_k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+        let rebased = crate::loader::pe::PE::from_bytes_at(&buf, 0x7FF8_0000_0000)?;
+
+        assert_eq!(0x7FF8_0000_0000, rebased.module.address_space.base_address);
+        assert_eq!(
+            0x7FF8_0000_0000,
+            rebased.header.optional_header.unwrap().windows_fields.image_base
+        );
+        assert_eq!(0x7FF8_0000_0000, rebased.module.sections[0].virtual_range.start);
+
+        // each relocated pointer is shifted by the difference between base addresses.
+        let delta = 0x7FF8_0000_0000 - 0x1_8000_0000;
+        for reloc in read_base_relocations(&pe)?.iter() {
+            let rva = reloc.address - pe.module.address_space.base_address;
+            assert_eq!(
+                pe.module.address_space.relative.read_u64(rva)? + delta,
+                rebased.module.address_space.relative.read_u64(rva)?
+            );
+        }
+
+        // and everything else is unchanged.
+        assert_eq!(0x4d, rebased.module.address_space.read_u8(0x7FF8_0000_0000)?);
+
+        Ok(())
+    }
+}
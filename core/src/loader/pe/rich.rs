@@ -0,0 +1,560 @@
+//! Decode the Rich header.
+//!
+//! The undocumented Rich header is emitted by Microsoft linkers between the
+//! DOS stub and the `IMAGE_NT_HEADERS`. It records the tools (compiler,
+//! assembler, linker, etc.) that produced the objects linked into the image:
+//! each entry is a product ID, a build number, and the number of objects.
+//!
+//! The header is XOR-encoded with a key that is also a checksum over the DOS
+//! header and the entries, so we can tell if it has been modified.
+//!
+//! ```text
+//!   "DanS" ^ key
+//!   0 ^ key        (padding)
+//!   0 ^ key
+//!   0 ^ key
+//!   repeated:
+//!     (prodid << 16 | build) ^ key
+//!     count ^ key
+//!   "Rich"
+//!   key
+//! ```
+//!
+//! Note: this is found in the file header, so we work with file offsets,
+//! rather than RVAs.
+//!
+//! references:
+//!   - http://bytepointer.com/articles/the_microsoft_rich_header.htm
+//!   - https://github.com/dishather/richprint
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::loader::pe::PE;
+
+/// `Rich`
+const RICH_SIGNATURE: u32 = 0x6863_6952;
+/// `DanS`
+const DANS_SIGNATURE: u32 = 0x536E_6144;
+
+/// offset of `IMAGE_DOS_HEADER.e_lfanew`, which is skipped by the checksum.
+const OFFSET_E_LFANEW: usize = 0x3C;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RichEntry {
+    /// the product ID of the tool, like `Linker1400`.
+    pub prodid: u16,
+    /// the build number of the tool, like `25711`.
+    pub build:  u16,
+    /// the number of objects produced by this tool.
+    pub count:  u32,
+}
+
+impl RichEntry {
+    /// the value that encodes the tool in the header: `prodid << 16 | build`.
+    pub fn comp_id(&self) -> u32 {
+        (self.prodid as u32) << 16 | self.build as u32
+    }
+
+    /// the name of the product, like `Utc1900_CPP`, if known.
+    pub fn product_name(&self) -> Option<&'static str> {
+        get_product_name(self.prodid)
+    }
+
+    /// the name of the toolchain release that shipped the product,
+    /// like `Visual Studio 2008`, if known.
+    pub fn toolchain(&self) -> Option<&'static str> {
+        match self.prodid {
+            0x003D..=0x0045 => Some("Visual Studio .NET 2002"),
+            0x0046..=0x0055 | 0x005A..=0x006C => Some("Visual Studio .NET 2003"),
+            0x006D..=0x0082 => Some("Visual Studio 2005"),
+            0x0083..=0x0096 => Some("Visual Studio 2008"),
+            0x0098..=0x00B4 => Some("Visual Studio 2010"),
+            0x00B5..=0x00C6 => Some("Visual Studio 2010 SP1"),
+            0x00C7..=0x00D8 => Some("Visual Studio 2012"),
+            0x00D9..=0x00FC => Some("Visual Studio 2013"),
+            // Visual Studio 2015 and later share product IDs,
+            // so distinguish them by build number.
+            0x00FD..=0x010E => match self.build {
+                0..=24999 => Some("Visual Studio 2015"),
+                25000..=27499 => Some("Visual Studio 2017"),
+                27500..=30699 => Some("Visual Studio 2019"),
+                _ => Some("Visual Studio 2022"),
+            },
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RichEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.product_name() {
+            Some(name) => write!(f, "{} build {} count {}", name, self.build, self.count),
+            None => write!(
+                f,
+                "prodid {:#06x} build {} count {}",
+                self.prodid, self.build, self.count
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RichHeader {
+    /// the file offset of the start of the header (`DanS`).
+    pub start:    usize,
+    /// the file offset of the end of the header (following the key).
+    pub end:      usize,
+    /// the XOR key found after `Rich`.
+    pub key:      u32,
+    /// the checksum computed over the DOS header and the entries.
+    /// the header is intact if this matches the key.
+    pub checksum: u32,
+    pub entries:  Vec<RichEntry>,
+}
+
+impl RichHeader {
+    pub fn is_checksum_valid(&self) -> bool {
+        self.key == self.checksum
+    }
+}
+
+/// the name of the Microsoft product with the given ID, like `Linker1400`.
+pub fn get_product_name(prodid: u16) -> Option<&'static str> {
+    Some(match prodid {
+        0x0000 => "Unknown",
+        0x0001 => "Import0",
+        0x0002 => "Linker510",
+        0x0003 => "Cvtomf510",
+        0x0004 => "Linker600",
+        0x0005 => "Cvtomf600",
+        0x0006 => "Cvtres500",
+        0x0007 => "Utc11_Basic",
+        0x0008 => "Utc11_C",
+        0x0009 => "Utc12_Basic",
+        0x000A => "Utc12_C",
+        0x000B => "Utc12_CPP",
+        0x000C => "AliasObj60",
+        0x000D => "VisualBasic60",
+        0x000E => "Masm613",
+        0x000F => "Masm710",
+        0x0010 => "Linker511",
+        0x0011 => "Cvtomf511",
+        0x0012 => "Masm614",
+        0x0013 => "Linker512",
+        0x0014 => "Cvtomf512",
+        0x0015 => "Utc12_C_Std",
+        0x0016 => "Utc12_CPP_Std",
+        0x0017 => "Utc12_C_Book",
+        0x0018 => "Utc12_CPP_Book",
+        0x0019 => "Implib700",
+        0x001A => "Cvtomf700",
+        0x001B => "Utc13_Basic",
+        0x001C => "Utc13_C",
+        0x001D => "Utc13_CPP",
+        0x001E => "Linker610",
+        0x001F => "Cvtomf610",
+        0x0020 => "Linker601",
+        0x0021 => "Cvtomf601",
+        0x0022 => "Utc12_1_Basic",
+        0x0023 => "Utc12_1_C",
+        0x0024 => "Utc12_1_CPP",
+        0x0025 => "Linker620",
+        0x0026 => "Cvtomf620",
+        0x0027 => "AliasObj70",
+        0x0028 => "Linker621",
+        0x0029 => "Cvtomf621",
+        0x002A => "Masm615",
+        0x002B => "Utc13_LTCG_C",
+        0x002C => "Utc13_LTCG_CPP",
+        0x002D => "Masm620",
+        0x002E => "ILAsm100",
+        0x002F => "Utc12_2_Basic",
+        0x0030 => "Utc12_2_C",
+        0x0031 => "Utc12_2_CPP",
+        0x0032 => "Utc12_2_C_Std",
+        0x0033 => "Utc12_2_CPP_Std",
+        0x0034 => "Utc12_2_C_Book",
+        0x0035 => "Utc12_2_CPP_Book",
+        0x0036 => "Implib622",
+        0x0037 => "Cvtomf622",
+        0x0038 => "Cvtres501",
+        0x0039 => "Utc13_C_Std",
+        0x003A => "Utc13_CPP_Std",
+        0x003B => "Cvtpgd1300",
+        0x003C => "Linker622",
+        0x003D => "Linker700",
+        0x003E => "Export622",
+        0x003F => "Export700",
+        0x0040 => "Masm700",
+        0x0041 => "Utc13_POGO_I_C",
+        0x0042 => "Utc13_POGO_I_CPP",
+        0x0043 => "Utc13_POGO_O_C",
+        0x0044 => "Utc13_POGO_O_CPP",
+        0x0045 => "Cvtres700",
+        0x0046 => "Cvtres710p",
+        0x0047 => "Linker710p",
+        0x0048 => "Cvtomf710p",
+        0x0049 => "Export710p",
+        0x004A => "Implib710p",
+        0x004B => "Masm710p",
+        0x004C => "Utc1310p_C",
+        0x004D => "Utc1310p_CPP",
+        0x004E => "Utc1310p_C_Std",
+        0x004F => "Utc1310p_CPP_Std",
+        0x0050 => "Utc1310p_LTCG_C",
+        0x0051 => "Utc1310p_LTCG_CPP",
+        0x0052 => "Utc1310p_POGO_I_C",
+        0x0053 => "Utc1310p_POGO_I_CPP",
+        0x0054 => "Utc1310p_POGO_O_C",
+        0x0055 => "Utc1310p_POGO_O_CPP",
+        0x0056 => "Linker624",
+        0x0057 => "Cvtomf624",
+        0x0058 => "Export624",
+        0x0059 => "Implib624",
+        0x005A => "Linker710",
+        0x005B => "Cvtomf710",
+        0x005C => "Export710",
+        0x005D => "Implib710",
+        0x005E => "Cvtres710",
+        0x005F => "Utc1310_C",
+        0x0060 => "Utc1310_CPP",
+        0x0061 => "Utc1310_C_Std",
+        0x0062 => "Utc1310_CPP_Std",
+        0x0063 => "Utc1310_LTCG_C",
+        0x0064 => "Utc1310_LTCG_CPP",
+        0x0065 => "Utc1310_POGO_I_C",
+        0x0066 => "Utc1310_POGO_I_CPP",
+        0x0067 => "Utc1310_POGO_O_C",
+        0x0068 => "Utc1310_POGO_O_CPP",
+        0x0069 => "AliasObj710",
+        0x006A => "AliasObj710p",
+        0x006B => "Cvtpgd1310",
+        0x006C => "Cvtpgd1310p",
+        0x006D => "Utc1400_C",
+        0x006E => "Utc1400_CPP",
+        0x006F => "Utc1400_C_Std",
+        0x0070 => "Utc1400_CPP_Std",
+        0x0071 => "Utc1400_LTCG_C",
+        0x0072 => "Utc1400_LTCG_CPP",
+        0x0073 => "Utc1400_POGO_I_C",
+        0x0074 => "Utc1400_POGO_I_CPP",
+        0x0075 => "Utc1400_POGO_O_C",
+        0x0076 => "Utc1400_POGO_O_CPP",
+        0x0077 => "Cvtpgd1400",
+        0x0078 => "Linker800",
+        0x0079 => "Cvtomf800",
+        0x007A => "Export800",
+        0x007B => "Implib800",
+        0x007C => "Cvtres800",
+        0x007D => "Masm800",
+        0x007E => "AliasObj800",
+        0x007F => "PhoenixPrerelease",
+        0x0080 => "Utc1400_CVTCIL_C",
+        0x0081 => "Utc1400_CVTCIL_CPP",
+        0x0082 => "Utc1400_LTCG_MSIL",
+        0x0083 => "Utc1500_C",
+        0x0084 => "Utc1500_CPP",
+        0x0085 => "Utc1500_C_Std",
+        0x0086 => "Utc1500_CPP_Std",
+        0x0087 => "Utc1500_CVTCIL_C",
+        0x0088 => "Utc1500_CVTCIL_CPP",
+        0x0089 => "Utc1500_LTCG_C",
+        0x008A => "Utc1500_LTCG_CPP",
+        0x008B => "Utc1500_LTCG_MSIL",
+        0x008C => "Utc1500_POGO_I_C",
+        0x008D => "Utc1500_POGO_I_CPP",
+        0x008E => "Utc1500_POGO_O_C",
+        0x008F => "Utc1500_POGO_O_CPP",
+        0x0090 => "Cvtpgd1500",
+        0x0091 => "Linker900",
+        0x0092 => "Export900",
+        0x0093 => "Implib900",
+        0x0094 => "Cvtres900",
+        0x0095 => "Masm900",
+        0x0096 => "AliasObj900",
+        0x0097 => "Resource",
+        0x0098 => "AliasObj1000",
+        0x0099 => "Cvtpgd1600",
+        0x009A => "Cvtres1000",
+        0x009B => "Export1000",
+        0x009C => "Implib1000",
+        0x009D => "Linker1000",
+        0x009E => "Masm1000",
+        0x009F => "Phx1600_C",
+        0x00A0 => "Phx1600_CPP",
+        0x00A1 => "Phx1600_CVTCIL_C",
+        0x00A2 => "Phx1600_CVTCIL_CPP",
+        0x00A3 => "Phx1600_LTCG_C",
+        0x00A4 => "Phx1600_LTCG_CPP",
+        0x00A5 => "Phx1600_LTCG_MSIL",
+        0x00A6 => "Phx1600_POGO_I_C",
+        0x00A7 => "Phx1600_POGO_I_CPP",
+        0x00A8 => "Phx1600_POGO_O_C",
+        0x00A9 => "Phx1600_POGO_O_CPP",
+        0x00AA => "Utc1600_C",
+        0x00AB => "Utc1600_CPP",
+        0x00AC => "Utc1600_CVTCIL_C",
+        0x00AD => "Utc1600_CVTCIL_CPP",
+        0x00AE => "Utc1600_LTCG_C",
+        0x00AF => "Utc1600_LTCG_CPP",
+        0x00B0 => "Utc1600_LTCG_MSIL",
+        0x00B1 => "Utc1600_POGO_I_C",
+        0x00B2 => "Utc1600_POGO_I_CPP",
+        0x00B3 => "Utc1600_POGO_O_C",
+        0x00B4 => "Utc1600_POGO_O_CPP",
+        0x00B5 => "AliasObj1010",
+        0x00B6 => "Cvtpgd1610",
+        0x00B7 => "Cvtres1010",
+        0x00B8 => "Export1010",
+        0x00B9 => "Implib1010",
+        0x00BA => "Linker1010",
+        0x00BB => "Masm1010",
+        0x00BC => "Utc1610_C",
+        0x00BD => "Utc1610_CPP",
+        0x00BE => "Utc1610_CVTCIL_C",
+        0x00BF => "Utc1610_CVTCIL_CPP",
+        0x00C0 => "Utc1610_LTCG_C",
+        0x00C1 => "Utc1610_LTCG_CPP",
+        0x00C2 => "Utc1610_LTCG_MSIL",
+        0x00C3 => "Utc1610_POGO_I_C",
+        0x00C4 => "Utc1610_POGO_I_CPP",
+        0x00C5 => "Utc1610_POGO_O_C",
+        0x00C6 => "Utc1610_POGO_O_CPP",
+        0x00C7 => "AliasObj1100",
+        0x00C8 => "Cvtpgd1700",
+        0x00C9 => "Cvtres1100",
+        0x00CA => "Export1100",
+        0x00CB => "Implib1100",
+        0x00CC => "Linker1100",
+        0x00CD => "Masm1100",
+        0x00CE => "Utc1700_C",
+        0x00CF => "Utc1700_CPP",
+        0x00D0 => "Utc1700_CVTCIL_C",
+        0x00D1 => "Utc1700_CVTCIL_CPP",
+        0x00D2 => "Utc1700_LTCG_C",
+        0x00D3 => "Utc1700_LTCG_CPP",
+        0x00D4 => "Utc1700_LTCG_MSIL",
+        0x00D5 => "Utc1700_POGO_I_C",
+        0x00D6 => "Utc1700_POGO_I_CPP",
+        0x00D7 => "Utc1700_POGO_O_C",
+        0x00D8 => "Utc1700_POGO_O_CPP",
+        0x00D9 => "AliasObj1200",
+        0x00DA => "Cvtpgd1800",
+        0x00DB => "Cvtres1200",
+        0x00DC => "Export1200",
+        0x00DD => "Implib1200",
+        0x00DE => "Linker1200",
+        0x00DF => "Masm1200",
+        0x00E0 => "Utc1800_C",
+        0x00E1 => "Utc1800_CPP",
+        0x00E2 => "Utc1800_CVTCIL_C",
+        0x00E3 => "Utc1800_CVTCIL_CPP",
+        0x00E4 => "Utc1800_LTCG_C",
+        0x00E5 => "Utc1800_LTCG_CPP",
+        0x00E6 => "Utc1800_LTCG_MSIL",
+        0x00E7 => "Utc1800_POGO_I_C",
+        0x00E8 => "Utc1800_POGO_I_CPP",
+        0x00E9 => "Utc1800_POGO_O_C",
+        0x00EA => "Utc1800_POGO_O_CPP",
+        0x00EB => "AliasObj1210",
+        0x00EC => "Cvtpgd1810",
+        0x00ED => "Cvtres1210",
+        0x00EE => "Export1210",
+        0x00EF => "Implib1210",
+        0x00F0 => "Linker1210",
+        0x00F1 => "Masm1210",
+        0x00F2 => "Utc1810_C",
+        0x00F3 => "Utc1810_CPP",
+        0x00F4 => "Utc1810_CVTCIL_C",
+        0x00F5 => "Utc1810_CVTCIL_CPP",
+        0x00F6 => "Utc1810_LTCG_C",
+        0x00F7 => "Utc1810_LTCG_CPP",
+        0x00F8 => "Utc1810_LTCG_MSIL",
+        0x00F9 => "Utc1810_POGO_I_C",
+        0x00FA => "Utc1810_POGO_I_CPP",
+        0x00FB => "Utc1810_POGO_O_C",
+        0x00FC => "Utc1810_POGO_O_CPP",
+        0x00FD => "AliasObj1400",
+        0x00FE => "Cvtpgd1900",
+        0x00FF => "Cvtres1400",
+        0x0100 => "Export1400",
+        0x0101 => "Implib1400",
+        0x0102 => "Linker1400",
+        0x0103 => "Masm1400",
+        0x0104 => "Utc1900_C",
+        0x0105 => "Utc1900_CPP",
+        0x0106 => "Utc1900_CVTCIL_C",
+        0x0107 => "Utc1900_CVTCIL_CPP",
+        0x0108 => "Utc1900_LTCG_C",
+        0x0109 => "Utc1900_LTCG_CPP",
+        0x010A => "Utc1900_LTCG_MSIL",
+        0x010B => "Utc1900_POGO_I_C",
+        0x010C => "Utc1900_POGO_I_CPP",
+        0x010D => "Utc1900_POGO_O_C",
+        0x010E => "Utc1900_POGO_O_CPP",
+        _ => return None,
+    })
+}
+
+fn compute_checksum(buf: &[u8], start: usize, entries: &[RichEntry]) -> u32 {
+    let mut checksum = start as u32;
+
+    for (i, &b) in buf[..start].iter().enumerate() {
+        if (OFFSET_E_LFANEW..OFFSET_E_LFANEW + 4).contains(&i) {
+            continue;
+        }
+        checksum = checksum.wrapping_add((b as u32).rotate_left(i as u32));
+    }
+
+    for entry in entries.iter() {
+        checksum = checksum.wrapping_add(entry.comp_id().rotate_left(entry.count));
+    }
+
+    checksum
+}
+
+/// decode the Rich header from the file data, if present.
+pub fn parse_rich_header(buf: &[u8]) -> Option<RichHeader> {
+    if buf.len() < OFFSET_E_LFANEW + 4 {
+        return None;
+    }
+    let e_lfanew = std::cmp::min(LittleEndian::read_u32(&buf[OFFSET_E_LFANEW..]) as usize, buf.len());
+
+    // the header ends with `Rich` and the key,
+    // dword aligned and found before the NT headers.
+    let rich = (0x40..e_lfanew.saturating_sub(7))
+        .step_by(4)
+        .find(|&offset| LittleEndian::read_u32(&buf[offset..]) == RICH_SIGNATURE)?;
+    let key = LittleEndian::read_u32(&buf[rich + 4..]);
+
+    // walk backwards to find the start `DanS`.
+    let start = (0x40..rich)
+        .step_by(4)
+        .rev()
+        .find(|&offset| LittleEndian::read_u32(&buf[offset..]) ^ key == DANS_SIGNATURE)?;
+    debug!("rich: header: {:#x}-{:#x}", start, rich + 8);
+
+    // skip the signature and padding.
+    // both markers come from the file, so they may be too close or misaligned.
+    let entries = buf.get(start + 0x10..rich)?;
+    if entries.len() % 8 != 0 {
+        debug!("rich: invalid header size: {:#x}", rich - start);
+        return None;
+    }
+
+    let entries: Vec<RichEntry> = entries
+        .chunks_exact(8)
+        .map(|entry| {
+            let comp_id = LittleEndian::read_u32(&entry[0x0..]) ^ key;
+            let count = LittleEndian::read_u32(&entry[0x4..]) ^ key;
+            RichEntry {
+                prodid: (comp_id >> 16) as u16,
+                build: (comp_id & 0xFFFF) as u16,
+                count,
+            }
+        })
+        .collect();
+
+    let checksum = compute_checksum(buf, start, &entries);
+    if checksum != key {
+        debug!("rich: invalid checksum: {:#x} (expected {:#x})", checksum, key);
+    }
+
+    Some(RichHeader {
+        start,
+        end: rich + 8,
+        key,
+        checksum,
+        entries,
+    })
+}
+
+/// decode the Rich header of the given PE, if present.
+pub fn read_rich_header(pe: &PE) -> Option<RichHeader> {
+    parse_rich_header(&pe.buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{loader::pe::rich::*, rsrc::*};
+
+    #[test]
+    fn k32() {
+        let buf = get_buf(Rsrc::K32);
+
+        let rich = parse_rich_header(&buf).unwrap();
+        assert_eq!(0x80, rich.start);
+        assert_eq!(0xE0, rich.end);
+        assert_eq!(0xDC12_C398, rich.key);
+        assert!(rich.is_checksum_valid());
+        assert_eq!(9, rich.entries.len());
+
+        let linker = rich.entries[8];
+        assert_eq!(0x102, linker.prodid);
+        assert_eq!(25711, linker.build);
+        assert_eq!(1, linker.count);
+        assert_eq!(Some("Linker1400"), linker.product_name());
+        assert_eq!(Some("Visual Studio 2017"), linker.toolchain());
+        assert_eq!("Linker1400 build 25711 count 1", linker.to_string());
+    }
+
+    #[test]
+    fn tiny() {
+        let buf = get_buf(Rsrc::TINY);
+
+        assert!(parse_rich_header(&buf).is_none());
+    }
+
+    #[test]
+    fn nop() {
+        let buf = get_buf(Rsrc::NOP);
+
+        let rich = parse_rich_header(&buf).unwrap();
+        assert!(rich.is_checksum_valid());
+        assert_eq!(6, rich.entries.len());
+        assert_eq!(Some("Linker710"), rich.entries[5].product_name());
+        assert_eq!(Some("Visual Studio .NET 2003"), rich.entries[5].toolchain());
+    }
+
+    #[test]
+    fn mimi() {
+        let mut buf = get_buf(Rsrc::MIMI);
+
+        let rich = parse_rich_header(&buf).unwrap();
+        assert!(rich.is_checksum_valid());
+        assert_eq!(15, rich.entries.len());
+
+        // tamper with the DOS stub, which is covered by the checksum.
+        buf[0x50] ^= 0xFF;
+        let rich = parse_rich_header(&buf).unwrap();
+        assert!(!rich.is_checksum_valid());
+    }
+
+    #[test]
+    fn truncated() {
+        // `DanS` (with a key of zero) immediately followed by `Rich`,
+        // with no room for the padding.
+        let mut buf = vec![0u8; 0x100];
+        LittleEndian::write_u32(&mut buf[OFFSET_E_LFANEW..], 0xC0);
+        LittleEndian::write_u32(&mut buf[0x80..], DANS_SIGNATURE);
+        LittleEndian::write_u32(&mut buf[0x88..], RICH_SIGNATURE);
+
+        assert!(parse_rich_header(&buf).is_none());
+
+        // room for the padding, but not a whole entry.
+        let mut buf = vec![0u8; 0x100];
+        LittleEndian::write_u32(&mut buf[OFFSET_E_LFANEW..], 0xC0);
+        LittleEndian::write_u32(&mut buf[0x80..], DANS_SIGNATURE);
+        LittleEndian::write_u32(&mut buf[0x94..], RICH_SIGNATURE);
+
+        assert!(parse_rich_header(&buf).is_none());
+
+        // `Rich` at the end of the data, with no room for the key.
+        let mut buf = vec![0u8; 0x4B];
+        buf[..2].copy_from_slice(b"MZ");
+        LittleEndian::write_u32(&mut buf[OFFSET_E_LFANEW..], 0xFFFF);
+        LittleEndian::write_u32(&mut buf[0x44..], RICH_SIGNATURE);
+
+        assert!(parse_rich_header(&buf).is_none());
+    }
+}
@@ -0,0 +1,224 @@
+//! Rebuild `.ico` files from the icon resources (`RT_GROUP_ICON` and
+//! `RT_ICON`).
+//!
+//! An `.ico` file is a directory of images followed by the image data.
+//! When compiled into resources, the directory (`GRPICONDIR`) is stored as a
+//! `RT_GROUP_ICON` resource, and each image as a separate `RT_ICON` resource.
+//! The group directory entries reference the images by resource ID,
+//! rather than file offset, so we have to fix those up:
+//!
+//! ```text
+//! GRPICONDIR                       ICONDIR
+//!   reserved    u16                  reserved    u16
+//!   type        u16 (1)              type        u16 (1)
+//!   count       u16                  count       u16
+//!   entries     [GRPICONDIRENTRY]    entries     [ICONDIRENTRY]
+//!     ...                              ...
+//!     bytes     u32                    bytes     u32
+//!     id        u16                    offset    u32
+//!                                    image data...
+//! ```
+//!
+//! references:
+//!   - https://devblogs.microsoft.com/oldnewthing/20120720-00/?p=7083
+//!   - https://docs.microsoft.com/en-us/previous-versions/ms997538(v=msdn.10)
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::loader::pe::{
+    rsrc::{NodeIdentifier, Resource, ResourceDataType, ResourceError, ResourceSectionData},
+    PE,
+};
+
+const ICON_TYPE_ICON: u16 = 1;
+
+const SIZEOF_ICONDIR: usize = 0x6;
+const SIZEOF_GRPICONDIRENTRY: usize = 0xE;
+const SIZEOF_ICONDIRENTRY: usize = 0x10;
+
+/// `GRPICONDIRENTRY`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GroupIconEntry {
+    /// zero means 256 pixels.
+    pub width:       u8,
+    /// zero means 256 pixels.
+    pub height:      u8,
+    pub color_count: u8,
+    pub planes:      u16,
+    pub bit_count:   u16,
+    pub size:        u32,
+    /// the ID of the `RT_ICON` resource with the image data.
+    pub id:          u16,
+}
+
+/// parse the raw data of an `RT_GROUP_ICON` resource.
+pub fn parse_group_icon(buf: &[u8]) -> Result<Vec<GroupIconEntry>> {
+    if buf.len() < SIZEOF_ICONDIR {
+        return Err(ResourceError::MalformedResource("GRPICONDIR too small").into());
+    }
+
+    if LittleEndian::read_u16(&buf[0x2..]) != ICON_TYPE_ICON {
+        return Err(ResourceError::MalformedResource("invalid GRPICONDIR type").into());
+    }
+
+    let count = LittleEndian::read_u16(&buf[0x4..]) as usize;
+    if buf.len() < SIZEOF_ICONDIR + count * SIZEOF_GRPICONDIRENTRY {
+        return Err(ResourceError::MalformedResource("GRPICONDIR entries out of bounds").into());
+    }
+
+    Ok(buf[SIZEOF_ICONDIR..SIZEOF_ICONDIR + count * SIZEOF_GRPICONDIRENTRY]
+        .chunks_exact(SIZEOF_GRPICONDIRENTRY)
+        .map(|entry| GroupIconEntry {
+            width:       entry[0x0],
+            height:      entry[0x1],
+            color_count: entry[0x2],
+            planes:      LittleEndian::read_u16(&entry[0x4..]),
+            bit_count:   LittleEndian::read_u16(&entry[0x6..]),
+            size:        LittleEndian::read_u32(&entry[0x8..]),
+            id:          LittleEndian::read_u16(&entry[0xC..]),
+        })
+        .collect())
+}
+
+/// build an `.ico` file from the given directory entries and their image data.
+/// the image sizes are taken from the data, not the entries.
+pub fn build_ico(images: &[(GroupIconEntry, Vec<u8>)]) -> Vec<u8> {
+    let mut buf = vec![0u8; SIZEOF_ICONDIR + images.len() * SIZEOF_ICONDIRENTRY];
+
+    LittleEndian::write_u16(&mut buf[0x2..], ICON_TYPE_ICON);
+    LittleEndian::write_u16(&mut buf[0x4..], images.len() as u16);
+
+    let mut offset = buf.len();
+    for (i, (entry, data)) in images.iter().enumerate() {
+        let e = &mut buf[SIZEOF_ICONDIR + i * SIZEOF_ICONDIRENTRY..];
+        e[0x0] = entry.width;
+        e[0x1] = entry.height;
+        e[0x2] = entry.color_count;
+        LittleEndian::write_u16(&mut e[0x4..], entry.planes);
+        LittleEndian::write_u16(&mut e[0x6..], entry.bit_count);
+        LittleEndian::write_u32(&mut e[0x8..], data.len() as u32);
+        LittleEndian::write_u32(&mut e[0xC..], offset as u32);
+
+        offset += data.len();
+    }
+
+    for (_, data) in images.iter() {
+        buf.extend_from_slice(data);
+    }
+
+    buf
+}
+
+/// An icon, rebuilt from an `RT_GROUP_ICON` resource and its images.
+#[derive(Clone, Debug)]
+pub struct Icon {
+    /// the name of the `RT_GROUP_ICON` resource.
+    pub name:     NodeIdentifier,
+    pub language: u32,
+    pub entries:  Vec<GroupIconEntry>,
+    /// the contents of an `.ico` file.
+    pub ico:      Vec<u8>,
+}
+
+/// find the `RT_ICON` resource with the given ID,
+/// preferring the given language.
+fn find_icon_image(images: &[Resource], id: u16, language: u32) -> Option<&Resource> {
+    let name = NodeIdentifier::ID(id as u32);
+
+    images
+        .iter()
+        .find(|image| image.name == name && image.language == language)
+        .or_else(|| images.iter().find(|image| image.name == name))
+}
+
+/// read the icons of the given PE, one per `RT_GROUP_ICON` resource.
+/// images that can't be found are left out of the `.ico` file.
+pub fn read_icons(pe: &PE) -> Result<Vec<Icon>> {
+    let mut icons = vec![];
+
+    let rsrc = match ResourceSectionData::from_pe(pe)? {
+        Some(rsrc) => rsrc,
+        None => return Ok(icons),
+    };
+
+    let images = rsrc.get_resources(ResourceDataType::RT_ICON)?;
+    for group in rsrc.get_resources(ResourceDataType::RT_GROUP_ICON)?.into_iter() {
+        let mut entries = vec![];
+        let mut datas = vec![];
+
+        for entry in parse_group_icon(&group.descriptor.data(pe)?)?.into_iter() {
+            match find_icon_image(&images, entry.id, group.language) {
+                Some(image) => {
+                    datas.push((entry, image.descriptor.data(pe)?));
+                    entries.push(entry);
+                }
+                None => {
+                    debug!("icon: image not found: {:#x}", entry.id);
+                }
+            }
+        }
+
+        icons.push(Icon {
+            name: group.name,
+            language: group.language,
+            entries,
+            ico: build_ico(&datas),
+        });
+    }
+
+    Ok(icons)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::{loader::pe::rsrc::icon::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert_eq!(0, read_icons(&pe)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let icons = read_icons(&pe)?;
+        assert_eq!(1, icons.len());
+
+        let icon = &icons[0];
+        assert_eq!(NodeIdentifier::ID(100), icon.name);
+        assert_eq!(1033, icon.language);
+        assert_eq!(3, icon.entries.len());
+        assert_eq!(48, icon.entries[0].width);
+        assert_eq!(32, icon.entries[0].bit_count);
+        assert_eq!(0x25A8, icon.entries[0].size);
+        assert_eq!(1, icon.entries[0].id);
+
+        let ico = &icon.ico;
+        assert_eq!(0x6 + 3 * 0x10 + 0x25A8 + 0x10A8 + 0x468, ico.len());
+        assert_eq!(&[0x00, 0x00, 0x01, 0x00, 0x03, 0x00], &ico[..6]);
+
+        // the first image follows the directory,
+        // and starts with a BITMAPINFOHEADER.
+        let offset = LittleEndian::read_u32(&ico[0x6 + 0xC..]) as usize;
+        assert_eq!(0x36, offset);
+        assert_eq!(0x28, LittleEndian::read_u32(&ico[offset..]));
+
+        // the last image ends at the end of the file.
+        let size = LittleEndian::read_u32(&ico[0x6 + 2 * 0x10 + 0x8..]) as usize;
+        let offset = LittleEndian::read_u32(&ico[0x6 + 2 * 0x10 + 0xC..]) as usize;
+        assert_eq!(ico.len(), offset + size);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+//! Parse the application manifest resource (`RT_MANIFEST`).
+//!
+//! The manifest is an XML document, typically UTF-8 encoded.
+//! We don't parse the full document, only pull out the interesting elements,
+//! like the `requestedExecutionLevel`:
+//!
+//! ```xml
+//! <trustInfo xmlns="urn:schemas-microsoft-com:asm.v3">
+//!   <security>
+//!     <requestedPrivileges>
+//!       <requestedExecutionLevel level="asInvoker" uiAccess="false"/>
+//!     </requestedPrivileges>
+//!   </security>
+//! </trustInfo>
+//! ```
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/sbscs/application-manifests
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::loader::pe::{
+    rsrc::{NodeIdentifier, ResourceDataType, ResourceSectionData},
+    PE,
+};
+
+/// the manifest used when creating the process, for executables.
+pub const CREATEPROCESS_MANIFEST_RESOURCE_ID: u32 = 1;
+/// the manifest used when loading the module, for DLLs.
+pub const ISOLATIONAWARE_MANIFEST_RESOURCE_ID: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExecutionLevel {
+    AsInvoker,
+    HighestAvailable,
+    RequireAdministrator,
+    Other(String),
+}
+
+impl ExecutionLevel {
+    fn from_str(s: &str) -> ExecutionLevel {
+        match s {
+            "asInvoker" => ExecutionLevel::AsInvoker,
+            "highestAvailable" => ExecutionLevel::HighestAvailable,
+            "requireAdministrator" => ExecutionLevel::RequireAdministrator,
+            _ => ExecutionLevel::Other(s.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for ExecutionLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionLevel::AsInvoker => write!(f, "asInvoker"),
+            ExecutionLevel::HighestAvailable => write!(f, "highestAvailable"),
+            ExecutionLevel::RequireAdministrator => write!(f, "requireAdministrator"),
+            ExecutionLevel::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestedExecutionLevel {
+    pub level:     ExecutionLevel,
+    pub ui_access: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Manifest {
+    /// the resource name, typically `CREATEPROCESS_MANIFEST_RESOURCE_ID`
+    /// or `ISOLATIONAWARE_MANIFEST_RESOURCE_ID`.
+    pub name: NodeIdentifier,
+    pub xml:  String,
+}
+
+lazy_static! {
+    // like: `<requestedExecutionLevel level="asInvoker" uiAccess="false"/>`
+    // the element may be namespaced, like `<ms_asmv3:requestedExecutionLevel ...>`.
+    static ref EXECUTION_LEVEL_RE: Regex = Regex::new(r"<(?:[\w.-]+:)?requestedExecutionLevel\s([^>]*)>").unwrap();
+    static ref LEVEL_RE: Regex = Regex::new(r#"(?:^|\s)level\s*=\s*["']([^"']*)["']"#).unwrap();
+    static ref UI_ACCESS_RE: Regex = Regex::new(r#"(?:^|\s)uiAccess\s*=\s*["']([^"']*)["']"#).unwrap();
+}
+
+impl Manifest {
+    /// fetch the `requestedExecutionLevel`, if present.
+    pub fn requested_execution_level(&self) -> Option<RequestedExecutionLevel> {
+        let attributes = EXECUTION_LEVEL_RE.captures(&self.xml)?.get(1)?.as_str();
+
+        let level = LEVEL_RE.captures(attributes)?.get(1)?.as_str();
+        let ui_access = UI_ACCESS_RE
+            .captures(attributes)
+            .and_then(|captures| captures.get(1))
+            .map(|ui_access| ui_access.as_str().eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        Some(RequestedExecutionLevel {
+            level: ExecutionLevel::from_str(level),
+            ui_access,
+        })
+    }
+}
+
+/// decode the raw data of an `RT_MANIFEST` resource into a string.
+/// manifests are UTF-8, though we also accept UTF-16 with a byte order mark.
+pub fn decode_manifest(buf: &[u8]) -> String {
+    if let Some(buf) = buf.strip_prefix(b"\xEF\xBB\xBF") {
+        String::from_utf8_lossy(buf).into_owned()
+    } else if let Some(buf) = buf.strip_prefix(b"\xFF\xFE") {
+        let chars: Vec<u16> = buf.chunks_exact(2).map(LittleEndian::read_u16).collect();
+        String::from_utf16_lossy(&chars)
+    } else {
+        String::from_utf8_lossy(buf).into_owned()
+    }
+}
+
+/// read the manifests of the given PE.
+pub fn read_manifests(pe: &PE) -> Result<Vec<Manifest>> {
+    let rsrc = match ResourceSectionData::from_pe(pe)? {
+        Some(rsrc) => rsrc,
+        None => return Ok(vec![]),
+    };
+
+    rsrc.get_resources(ResourceDataType::RT_MANIFEST)?
+        .into_iter()
+        .map(|resource| {
+            Ok(Manifest {
+                xml:  decode_manifest(&resource.descriptor.data(pe)?),
+                name: resource.name,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::pe::rsrc::manifest::*, rsrc::*};
+
+    fn manifest(xml: &str) -> Manifest {
+        Manifest {
+            name: NodeIdentifier::ID(CREATEPROCESS_MANIFEST_RESOURCE_ID),
+            xml:  decode_manifest(xml.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn execution_level() {
+        let m = manifest(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<assembly xmlns="urn:schemas-microsoft-com:asm.v1" manifestVersion="1.0">
+  <trustInfo xmlns="urn:schemas-microsoft-com:asm.v3">
+    <security>
+      <requestedPrivileges>
+        <requestedExecutionLevel level="requireAdministrator" uiAccess="false"></requestedExecutionLevel>
+      </requestedPrivileges>
+    </security>
+  </trustInfo>
+</assembly>"#,
+        );
+        assert_eq!(
+            Some(RequestedExecutionLevel {
+                level:     ExecutionLevel::RequireAdministrator,
+                ui_access: false,
+            }),
+            m.requested_execution_level()
+        );
+
+        let m = manifest(
+            r#"<ms_asmv3:requestedExecutionLevel uiAccess='true' level='asInvoker' xmlns:ms_asmv3="urn:schemas-microsoft-com:asm.v3"/>"#,
+        );
+        assert_eq!(
+            Some(RequestedExecutionLevel {
+                level:     ExecutionLevel::AsInvoker,
+                ui_access: true,
+            }),
+            m.requested_execution_level()
+        );
+
+        let m = manifest(r#"<assembly xmlns="urn:schemas-microsoft-com:asm.v1" manifestVersion="1.0"></assembly>"#);
+        assert_eq!(None, m.requested_execution_level());
+    }
+
+    #[test]
+    fn bom() {
+        assert_eq!("<assembly/>", decode_manifest(b"\xEF\xBB\xBF<assembly/>"));
+        assert_eq!("<a/>", decode_manifest(b"\xFF\xFE<\x00a\x00/\x00>\x00"));
+    }
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert_eq!(0, read_manifests(&pe)?.len());
+
+        Ok(())
+    }
+}
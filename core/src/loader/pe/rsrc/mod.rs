@@ -11,32 +11,45 @@
 use anyhow::Result;
 use byteorder::{ByteOrder, LittleEndian};
 use log::debug;
+use thiserror::Error;
 
 use crate::{aspace::AddressSpace, loader::pe::PE, RVA};
 
+pub mod icon;
+pub mod manifest;
+pub mod version;
+
+#[derive(Error, Debug)]
+pub enum ResourceError {
+    #[error("malformed resource: {0}")]
+    MalformedResource(&'static str),
+}
+
 pub struct ResourceSectionData {
     buf: Vec<u8>,
 }
 
 impl ResourceSectionData {
-    #[allow(clippy::unnecessary_wraps)]
+    /// fetch the bytes at the given offset into the resource section,
+    /// failing if they're out of bounds, such as due to a corrupt offset or
+    /// truncated section.
+    fn get(&self, offset: usize, length: usize) -> Result<&[u8]> {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.buf.get(offset..end))
+            .ok_or_else(|| ResourceError::MalformedResource("offset out of bounds").into())
+    }
+
     fn read_u16(&self, offset: usize) -> Result<u16> {
-        // TODO: bounds check
-        let buf = &self.buf[offset..offset + 2];
-        Ok(LittleEndian::read_u16(buf))
+        Ok(LittleEndian::read_u16(self.get(offset, 2)?))
     }
 
-    #[allow(clippy::unnecessary_wraps)]
     fn read_u32(&self, offset: usize) -> Result<u32> {
-        // TODO: bounds check
-        let buf = &self.buf[offset..offset + 4];
-        Ok(LittleEndian::read_u32(buf))
+        Ok(LittleEndian::read_u32(self.get(offset, 4)?))
     }
 
-    #[allow(clippy::unnecessary_wraps)]
     fn read_buf(&self, offset: usize, length: usize) -> Result<Vec<u8>> {
-        // TODO: bounds check
-        Ok(self.buf[offset..offset + length].to_vec())
+        Ok(self.get(offset, length)?.to_vec())
     }
 
     pub fn root(&self) -> Result<ResourceNode> {
@@ -68,6 +81,39 @@ impl ResourceSectionData {
 
         Ok(Some(ResourceSectionData { buf }))
     }
+
+    /// collect the resources of the given type, like `RT_VERSION`,
+    /// across all names and languages.
+    pub fn get_resources(&self, ty: ResourceDataType) -> Result<Vec<Resource>> {
+        let mut resources = vec![];
+
+        let names = match self.root()?.get_child_by_id(self, ty as u32)? {
+            Some(NodeChild::Node(names)) => names,
+            _ => return Ok(resources),
+        };
+
+        for (name, languages) in names.children(self)?.into_iter() {
+            let languages = match languages {
+                NodeChild::Node(languages) => languages,
+                NodeChild::Data(_) => continue,
+            };
+
+            for (language, data) in languages.children(self)?.into_iter() {
+                if let NodeChild::Data(descriptor) = data {
+                    resources.push(Resource {
+                        name: name.id(self)?,
+                        language: match language.id(self)? {
+                            NodeIdentifier::ID(id) => id,
+                            NodeIdentifier::Name(_) => 0,
+                        },
+                        descriptor,
+                    });
+                }
+            }
+        }
+
+        Ok(resources)
+    }
 }
 
 struct ResourceNodeHeader {
@@ -210,7 +256,7 @@ impl ResourceNode {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResourceDataType {
     RT_CURSOR       = 1,
     RT_BITMAP       = 2,
@@ -264,6 +310,7 @@ impl ResourceDataType {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum NodeIdentifier {
     Name(String),
     ID(u32),
@@ -312,7 +359,68 @@ impl ResourceDataDescriptor {
     }
 }
 
+/// A resource found at `type/name/language` in the resource tree.
+pub struct Resource {
+    pub name:       NodeIdentifier,
+    pub language:   u32,
+    pub descriptor: ResourceDataDescriptor,
+}
+
 pub enum NodeChild {
     Node(ResourceNode),
     Data(ResourceDataDescriptor),
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::{loader::pe::rsrc::*, rsrc::*};
+
+    #[test]
+    fn mimi() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let rsrc = ResourceSectionData::from_pe(&pe)?.unwrap();
+        assert_eq!(1, rsrc.get_resources(ResourceDataType::RT_VERSION)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi_truncated() -> Result<()> {
+        let mut buf = get_buf(Rsrc::MIMI);
+        // shrink the resource directory to just the header of the root node,
+        // so its entries are out of bounds.
+        LittleEndian::write_u32(&mut buf[0x1A4..], 0x10);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let rsrc = ResourceSectionData::from_pe(&pe)?.unwrap();
+        assert!(rsrc.root().is_err());
+        assert!(version::read_version_info(&pe).is_err());
+        assert!(manifest::read_manifests(&pe).is_err());
+        assert!(icon::read_icons(&pe).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi_invalid_offset() -> Result<()> {
+        let mut buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // point the first entry of the root node past the end of the resource section.
+        let offset = pe.module.file_offset(0x4B_B000)?;
+        let named_entry_count = LittleEndian::read_u16(&buf[offset + 12..]) as usize;
+        let entry = offset + 16 + 8 * named_entry_count;
+        LittleEndian::write_u32(&mut buf[entry + 4..], 0x8000_0000 | 0x7FFF_FFF0);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let rsrc = ResourceSectionData::from_pe(&pe)?.unwrap();
+        assert!(rsrc.root()?.children(&rsrc).is_err());
+
+        Ok(())
+    }
+}
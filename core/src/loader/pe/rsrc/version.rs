@@ -0,0 +1,404 @@
+//! Parse the version information resource (`RT_VERSION`).
+//!
+//! The resource is a tree of nodes, each with a length, a UTF-16 key,
+//! an optional value, and children, all aligned to 4 bytes:
+//!
+//! ```text
+//! VS_VERSIONINFO
+//!   VS_FIXEDFILEINFO (value)
+//!   StringFileInfo
+//!     StringTable "040904B0"
+//!       String "CompanyName" = "Microsoft Corporation"
+//!       ...
+//!   VarFileInfo
+//!     Var "Translation" = [(0x409, 0x4B0)]
+//! ```
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/menurc/vs-versioninfo
+//!   - https://docs.microsoft.com/en-us/windows/win32/api/verrsrc/ns-verrsrc-vs_fixedfileinfo
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use log::debug;
+
+use crate::{
+    loader::pe::{
+        rsrc::{ResourceDataType, ResourceError, ResourceSectionData},
+        PE,
+    },
+    util,
+};
+
+pub const VS_FFI_SIGNATURE: u32 = 0xFEEF_04BD;
+
+/// the node's value is binary data.
+const VALUE_TYPE_BINARY: u16 = 0;
+/// the node's value is a UTF-16 string, and its length is in characters.
+const VALUE_TYPE_TEXT: u16 = 1;
+
+const SIZEOF_NODE_HEADER: usize = 0x6;
+
+/// the deepest node that's parsed.
+/// well formed resources are four levels deep, like `String` above.
+const MAX_NODE_DEPTH: usize = 16;
+const SIZEOF_VS_FIXEDFILEINFO: usize = 0x34;
+
+/// A four part version number, like `10.0.16299.15`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major:    u16,
+    pub minor:    u16,
+    pub build:    u16,
+    pub revision: u16,
+}
+
+impl Version {
+    fn from_dwords(ms: u32, ls: u32) -> Version {
+        Version {
+            major:    (ms >> 16) as u16,
+            minor:    (ms & 0xFFFF) as u16,
+            build:    (ls >> 16) as u16,
+            revision: (ls & 0xFFFF) as u16,
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.major, self.minor, self.build, self.revision)
+    }
+}
+
+/// `VS_FIXEDFILEINFO`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FixedFileInfo {
+    pub struc_version:   u32,
+    pub file_version:    Version,
+    pub product_version: Version,
+    pub file_flags_mask: u32,
+    /// VS_FF_*
+    pub file_flags:      u32,
+    /// VOS_*
+    pub file_os:         u32,
+    /// VFT_*
+    pub file_type:       u32,
+    /// VFT2_*
+    pub file_subtype:    u32,
+    pub file_date:       u64,
+}
+
+impl FixedFileInfo {
+    fn parse(buf: &[u8]) -> Result<FixedFileInfo> {
+        if buf.len() < SIZEOF_VS_FIXEDFILEINFO {
+            return Err(ResourceError::MalformedResource("VS_FIXEDFILEINFO too small").into());
+        }
+
+        if LittleEndian::read_u32(buf) != VS_FFI_SIGNATURE {
+            return Err(ResourceError::MalformedResource("invalid VS_FIXEDFILEINFO signature").into());
+        }
+
+        let dword = |offset: usize| LittleEndian::read_u32(&buf[offset..]);
+        Ok(FixedFileInfo {
+            struc_version:   dword(0x4),
+            file_version:    Version::from_dwords(dword(0x8), dword(0xC)),
+            product_version: Version::from_dwords(dword(0x10), dword(0x14)),
+            file_flags_mask: dword(0x18),
+            file_flags:      dword(0x1C),
+            file_os:         dword(0x20),
+            file_type:       dword(0x24),
+            file_subtype:    dword(0x28),
+            file_date:       ((dword(0x2C) as u64) << 32) | dword(0x30) as u64,
+        })
+    }
+}
+
+/// A `StringTable`, which holds the strings for one language and code page.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StringTable {
+    /// the language and code page, as eight hex digits, like `040904B0`.
+    pub key:     String,
+    /// pairs of (key, value), like `("CompanyName", "Microsoft Corporation")`.
+    pub strings: Vec<(String, String)>,
+}
+
+impl StringTable {
+    pub fn language(&self) -> Option<u16> {
+        self.key.get(..4).and_then(|s| u16::from_str_radix(s, 16).ok())
+    }
+
+    pub fn codepage(&self) -> Option<u16> {
+        self.key.get(4..8).and_then(|s| u16::from_str_radix(s, 16).ok())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.strings.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+/// An entry from the `Translation` value of the `VarFileInfo`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Translation {
+    pub language: u16,
+    pub codepage: u16,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VersionInfo {
+    pub fixed:         Option<FixedFileInfo>,
+    pub string_tables: Vec<StringTable>,
+    pub translations:  Vec<Translation>,
+}
+
+impl VersionInfo {
+    /// fetch the string with the given key, like `CompanyName`,
+    /// from the first string table that has it.
+    pub fn get_string(&self, key: &str) -> Option<&str> {
+        self.string_tables.iter().find_map(|table| table.get(key))
+    }
+
+    pub fn company_name(&self) -> Option<&str> {
+        self.get_string("CompanyName")
+    }
+
+    pub fn product_name(&self) -> Option<&str> {
+        self.get_string("ProductName")
+    }
+
+    pub fn original_filename(&self) -> Option<&str> {
+        self.get_string("OriginalFilename")
+    }
+
+    pub fn file_description(&self) -> Option<&str> {
+        self.get_string("FileDescription")
+    }
+}
+
+/// A generic node in the version information tree.
+struct Node<'a> {
+    key:      String,
+    value:    &'a [u8],
+    children: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    /// interpret the value as a NULL-terminated UTF-16 string.
+    fn value_string(&self) -> String {
+        read_utf16z(self.value).0
+    }
+}
+
+/// read a NULL-terminated UTF-16 string.
+/// returns the string and the number of bytes consumed, including the NULL.
+fn read_utf16z(buf: &[u8]) -> (String, usize) {
+    let chars: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(LittleEndian::read_u16)
+        .take_while(|&c| c != 0)
+        .collect();
+
+    let size = std::cmp::min(buf.len() & !1, 2 * (chars.len() + 1));
+    (String::from_utf16_lossy(&chars), size)
+}
+
+fn align4(offset: usize) -> usize {
+    util::align(offset as u64, 4) as usize
+}
+
+/// parse the node at the given offset, which is relative to the start of the
+/// resource, since alignment is relative to there, too.
+fn parse_node(buf: &[u8], offset: usize, depth: usize) -> Result<Node<'_>> {
+    if depth > MAX_NODE_DEPTH {
+        return Err(ResourceError::MalformedResource("version node nested too deeply").into());
+    }
+
+    if offset + SIZEOF_NODE_HEADER > buf.len() {
+        return Err(ResourceError::MalformedResource("version node header out of bounds").into());
+    }
+
+    let length = LittleEndian::read_u16(&buf[offset..]) as usize;
+    let value_length = LittleEndian::read_u16(&buf[offset + 0x2..]) as usize;
+    let value_type = LittleEndian::read_u16(&buf[offset + 0x4..]);
+
+    let end = offset + length;
+    if length < SIZEOF_NODE_HEADER || end > buf.len() {
+        return Err(ResourceError::MalformedResource("invalid version node length").into());
+    }
+
+    let (key, key_size) = read_utf16z(&buf[offset + SIZEOF_NODE_HEADER..end]);
+
+    let value_offset = std::cmp::min(align4(offset + SIZEOF_NODE_HEADER + key_size), end);
+    let value_size = match value_type {
+        VALUE_TYPE_TEXT => value_length * 2,
+        VALUE_TYPE_BINARY => value_length,
+        _ => {
+            debug!("version: unexpected value type: {:#x}", value_type);
+            value_length
+        }
+    };
+    // some linkers write the length of text values in bytes, rather than
+    // characters, so don't read past the end of the node.
+    let value_end = std::cmp::min(value_offset + value_size, end);
+    let value = &buf[value_offset..value_end];
+
+    let mut children = vec![];
+    let mut child_offset = align4(value_end);
+    while child_offset + SIZEOF_NODE_HEADER <= end {
+        let child = parse_node(&buf[..end], child_offset, depth + 1)?;
+        let child_length = LittleEndian::read_u16(&buf[child_offset..]) as usize;
+        children.push(child);
+        child_offset = align4(child_offset + child_length);
+    }
+
+    Ok(Node { key, value, children })
+}
+
+/// parse the raw data of an `RT_VERSION` resource.
+pub fn parse_version_info(buf: &[u8]) -> Result<VersionInfo> {
+    let root = parse_node(buf, 0x0, 0)?;
+    if root.key != "VS_VERSION_INFO" {
+        return Err(ResourceError::MalformedResource("invalid VS_VERSIONINFO key").into());
+    }
+
+    let mut version_info: VersionInfo = Default::default();
+
+    if !root.value.is_empty() {
+        version_info.fixed = Some(FixedFileInfo::parse(root.value)?);
+    }
+
+    for child in root.children.iter() {
+        match child.key.as_str() {
+            "StringFileInfo" => {
+                for table in child.children.iter() {
+                    version_info.string_tables.push(StringTable {
+                        key:     table.key.clone(),
+                        strings: table
+                            .children
+                            .iter()
+                            .map(|string| (string.key.clone(), string.value_string()))
+                            .collect(),
+                    });
+                }
+            }
+            "VarFileInfo" => {
+                for var in child.children.iter().filter(|var| var.key == "Translation") {
+                    for translation in var.value.chunks_exact(4) {
+                        version_info.translations.push(Translation {
+                            language: LittleEndian::read_u16(translation),
+                            codepage: LittleEndian::read_u16(&translation[2..]),
+                        });
+                    }
+                }
+            }
+            key => {
+                debug!("version: unexpected node: {}", key);
+            }
+        }
+    }
+
+    Ok(version_info)
+}
+
+/// read the version information of the given PE, if present.
+/// if there are multiple `RT_VERSION` resources, use the first one.
+pub fn read_version_info(pe: &PE) -> Result<Option<VersionInfo>> {
+    let rsrc = match ResourceSectionData::from_pe(pe)? {
+        Some(rsrc) => rsrc,
+        None => return Ok(None),
+    };
+
+    match rsrc.get_resources(ResourceDataType::RT_VERSION)?.first() {
+        Some(resource) => Ok(Some(parse_version_info(&resource.descriptor.data(pe)?)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{loader::pe::rsrc::version::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let version_info = read_version_info(&pe)?.unwrap();
+
+        let fixed = version_info.fixed.unwrap();
+        assert_eq!("10.0.17134.1", fixed.file_version.to_string());
+        assert_eq!("10.0.17134.1", fixed.product_version.to_string());
+
+        assert_eq!(1, version_info.string_tables.len());
+        assert_eq!("040904B0", version_info.string_tables[0].key);
+        assert_eq!(Some(0x409), version_info.string_tables[0].language());
+        assert_eq!(Some(0x4B0), version_info.string_tables[0].codepage());
+
+        assert_eq!(Some("Microsoft Corporation"), version_info.company_name());
+        assert_eq!(
+            Some("Microsoft® Windows® Operating System"),
+            version_info.product_name()
+        );
+        assert_eq!(Some("kernel32"), version_info.original_filename());
+
+        assert_eq!(
+            vec![Translation {
+                language: 0x409,
+                codepage: 0x4B0,
+            }],
+            version_info.translations
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn tiny() -> Result<()> {
+        let buf = get_buf(Rsrc::TINY);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert!(read_version_info(&pe)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mimi() -> Result<()> {
+        let buf = get_buf(Rsrc::MIMI);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let version_info = read_version_info(&pe)?.unwrap();
+        assert_eq!(Some("gentilkiwi (Benjamin DELPY)"), version_info.company_name());
+        assert_eq!(Some("mimikatz"), version_info.product_name());
+        assert_eq!(Some("mimikatz.exe"), version_info.original_filename());
+        assert_eq!(
+            Some("Build with love for POC only"),
+            version_info.get_string("PrivateBuild")
+        );
+
+        Ok(())
+    }
+
+    /// build nodes with empty keys and no values, each the only child of the
+    /// one before.
+    fn nested_nodes(depth: usize) -> Vec<u8> {
+        let mut buf = vec![];
+        for i in 0..depth {
+            let length = 0x8 * (depth - i);
+            buf.extend_from_slice(&(length as u16).to_le_bytes());
+            buf.extend_from_slice(&[0x0; 6]);
+        }
+        buf
+    }
+
+    #[test]
+    fn nested() -> Result<()> {
+        assert!(parse_node(&nested_nodes(MAX_NODE_DEPTH + 1), 0x0, 0).is_ok());
+        assert!(parse_node(&nested_nodes(MAX_NODE_DEPTH + 2), 0x0, 0).is_err());
+
+        // as deep as the 16-bit node length allows.
+        assert!(parse_version_info(&nested_nodes(0xFFFF / 0x8)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! Parse the PE Thread Local Storage (TLS) directory.
+//!
+//! The directory describes the template data used to initialize each thread's
+//! TLS slot, as well as an array of callbacks that the loader invokes on
+//! process and thread attach/detach, before the entry point runs.
+//!
+//! Unlike most other directories, the fields contain VAs rather than RVAs.
+//!
+//! references:
+//!   - https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#the-tls-section
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    aspace::AddressSpace,
+    loader::pe::{IMAGE_DIRECTORY_ENTRY_TLS, PE},
+    VA,
+};
+
+/// IMAGE_TLS_DIRECTORY32 or IMAGE_TLS_DIRECTORY64
+#[derive(Clone, Debug)]
+pub struct TlsDirectory {
+    /// the template data copied into each new thread's TLS slot.
+    /// note: this does not include the zero-fill region.
+    pub raw_data:             std::ops::Range<VA>,
+    /// the location to which the loader writes the TLS index.
+    pub address_of_index:     VA,
+    /// the location of the NULL-terminated array of TLS callbacks.
+    pub address_of_callbacks: VA,
+    /// the number of zero bytes that follow the template data.
+    pub size_of_zero_fill:    u32,
+    pub characteristics:      u32,
+}
+
+/// parse the TLS directory, if present.
+pub fn read_tls_directory(pe: &PE) -> Result<Option<TlsDirectory>> {
+    let tls_directory = match pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_TLS)? {
+        Some(tls_directory) if tls_directory.size > 0 => tls_directory,
+        _ => return Ok(None),
+    };
+    debug!("tls: directory: {:#x}", tls_directory.address);
+
+    let directory = pe.module.address_space.slice(tls_directory.address)?;
+    let arch = pe.module.arch;
+    let psize = arch.pointer_size() as u64;
+
+    Ok(Some(TlsDirectory {
+        raw_data:             std::ops::Range {
+            start: directory.read_pointer(arch, 0x0)?,
+            end:   directory.read_pointer(arch, psize)?,
+        },
+        address_of_index:     directory.read_pointer(arch, 2 * psize)?,
+        address_of_callbacks: directory.read_pointer(arch, 3 * psize)?,
+        size_of_zero_fill:    directory.read_u32(4 * psize)?,
+        characteristics:      directory.read_u32(4 * psize + 4)?,
+    }))
+}
+
+/// read the TLS callback array, stopping at the NULL terminator or
+/// the first entry that can't be read.
+pub fn read_tls_callbacks(pe: &PE, tls_directory: &TlsDirectory) -> Result<Vec<VA>> {
+    let mut callbacks = vec![];
+
+    if tls_directory.address_of_callbacks == 0 {
+        return Ok(callbacks);
+    }
+
+    let psize = pe.module.arch.pointer_size() as u64;
+    let mut offset = tls_directory.address_of_callbacks;
+    loop {
+        match pe.module.read_va_at_va(offset) {
+            Ok(0) => break,
+            Ok(callback) => callbacks.push(callback),
+            Err(_) => {
+                debug!("tls: callback array not mapped: {:#x}", offset);
+                break;
+            }
+        }
+
+        offset += psize;
+    }
+
+    Ok(callbacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::{loader::pe::tls::*, rsrc::*};
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        assert!(read_tls_directory(&pe)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn synthetic() -> Result<()> {
+        // none of the test resources have a TLS directory, so add one to k32,
+        // placing the structures at the end of the .data section (file offset 0xA5E00).
+        let mut buf = get_buf(Rsrc::K32);
+
+        // IMAGE_DIRECTORY_ENTRY_TLS
+        LittleEndian::write_u32(&mut buf[0x1B8..], 0xA8500);
+        LittleEndian::write_u32(&mut buf[0x1BC..], 0x28);
+
+        // IMAGE_TLS_DIRECTORY64 at 0x1800A8500
+        let directory = 0xA5E00 + 0x500;
+        LittleEndian::write_u64(&mut buf[directory..], 0x1_800A_8580); // StartAddressOfRawData
+        LittleEndian::write_u64(&mut buf[directory + 0x8..], 0x1_800A_8590); // EndAddressOfRawData
+        LittleEndian::write_u64(&mut buf[directory + 0x10..], 0x1_800A_8560); // AddressOfIndex
+        LittleEndian::write_u64(&mut buf[directory + 0x18..], 0x1_800A_8540); // AddressOfCallBacks
+        LittleEndian::write_u32(&mut buf[directory + 0x20..], 0x10); // SizeOfZeroFill
+        LittleEndian::write_u32(&mut buf[directory + 0x24..], 0x0); // Characteristics
+
+        // callbacks at 0x1800A8540: _report_gsfailure, then NULL terminator.
+        LittleEndian::write_u64(&mut buf[directory + 0x40..], 0x1_8002_02B0);
+        LittleEndian::write_u64(&mut buf[directory + 0x48..], 0x0);
+
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        let tls_directory = read_tls_directory(&pe)?.unwrap();
+        assert_eq!(0x1_800A_8580..0x1_800A_8590, tls_directory.raw_data);
+        assert_eq!(0x1_800A_8560, tls_directory.address_of_index);
+        assert_eq!(0x1_800A_8540, tls_directory.address_of_callbacks);
+        assert_eq!(0x10, tls_directory.size_of_zero_fill);
+
+        assert_eq!(vec![0x1_8002_02B0], read_tls_callbacks(&pe, &tls_directory)?);
+        assert_eq!(
+            vec![0x1_8002_02B0],
+            crate::analysis::pe::tls_callbacks::find_pe_tls_callbacks(&pe)?
+        );
+
+        Ok(())
+    }
+}
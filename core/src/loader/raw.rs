@@ -0,0 +1,162 @@
+//! Load raw bytes, like shellcode or a carved memory region, as a module.
+//!
+//! There's no file format to describe the layout,
+//! so the caller provides the architecture, the address at which to map the
+//! bytes, their permissions, and optionally, where execution starts.
+//! The bytes are mapped as a single section named `raw`.
+use anyhow::Result;
+use log::debug;
+use thiserror::Error;
+
+use crate::{
+    arch::Arch,
+    aspace::RelativeAddressSpace,
+    module::{Module, Permissions, Section},
+    RVA, VA,
+};
+
+#[derive(Error, Debug)]
+pub enum RawError {
+    #[error("empty buffer")]
+    EmptyBuffer,
+
+    #[error("invalid base address: {0:#x}")]
+    InvalidBaseAddress(VA),
+
+    #[error("invalid entry point: {0:#x}")]
+    InvalidEntryPoint(VA),
+}
+
+/// Raw bytes loaded at a given address.
+/// The `buf` field contains the raw data.
+/// The `module` field contains an address space with the data mapped.
+pub struct Raw {
+    pub buf:         Vec<u8>,
+    pub module:      Module,
+    /// where execution starts, such as the first instruction of shellcode.
+    pub entry_point: Option<VA>,
+}
+
+impl Raw {
+    /// map the given bytes at `base_address`.
+    ///
+    /// ```
+    /// use lancelot::{arch::Arch, loader::raw::Raw, module::Permissions};
+    ///
+    /// let raw = Raw::from_bytes(b"\xEB\xFE", Arch::X32, 0x401000, Permissions::RX, Some(0x401000)).unwrap();
+    /// assert_eq!(raw.module.address_space.base_address, 0x401000);
+    /// assert_eq!(raw.module.sections[0].virtual_range, 0x401000..0x401002);
+    ///
+    /// // the entry point must be within the mapped bytes.
+    /// assert!(Raw::from_bytes(b"\xEB\xFE", Arch::X32, 0x401000, Permissions::RX, Some(0x0)).is_err());
+    /// ```
+    pub fn from_bytes(
+        buf: &[u8],
+        arch: Arch,
+        base_address: VA,
+        permissions: Permissions,
+        entry_point: Option<VA>,
+    ) -> Result<Raw> {
+        load_raw(buf, arch, base_address, permissions, entry_point)
+    }
+
+    pub fn executable_sections<'b>(&'b self) -> Box<dyn Iterator<Item = &'b Section> + 'b> {
+        Box::new(
+            self.module
+                .sections
+                .iter()
+                .filter(|section| section.permissions.intersects(Permissions::X)),
+        )
+    }
+}
+
+fn load_raw(
+    buf: &[u8],
+    arch: Arch,
+    base_address: VA,
+    permissions: Permissions,
+    entry_point: Option<VA>,
+) -> Result<Raw> {
+    if buf.is_empty() {
+        return Err(RawError::EmptyBuffer.into());
+    }
+
+    let end_address = match base_address.checked_add(buf.len() as RVA) {
+        Some(end_address) => end_address,
+        None => return Err(RawError::InvalidBaseAddress(base_address).into()),
+    };
+
+    if let Some(entry_point) = entry_point {
+        if !(base_address..end_address).contains(&entry_point) {
+            return Err(RawError::InvalidEntryPoint(entry_point).into());
+        }
+    }
+
+    let address_space = RelativeAddressSpace::from_buf(buf);
+    debug!(
+        "raw: address space: mapped {:#x} - {:#x} {:?}",
+        base_address, end_address, permissions
+    );
+
+    let module = Module {
+        arch,
+        sections: vec![Section {
+            physical_range: 0x0..buf.len() as RVA,
+            virtual_range: base_address..end_address,
+            permissions,
+            name: "raw".to_string(),
+        }],
+        address_space: address_space.into_absolute(base_address)?,
+    };
+
+    debug!("raw: loaded");
+    Ok(Raw {
+        buf: buf.to_vec(),
+        module,
+        entry_point,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{arch::Arch, aspace::AddressSpace, loader::raw::*, module::Permissions};
+
+    #[test]
+    fn base_address() -> Result<()> {
+        // call $+5; pop eax; ret
+        let buf = b"\xE8\x00\x00\x00\x00\x58\xC3";
+        let raw = Raw::from_bytes(buf, Arch::X32, 0x10_0000, Permissions::RWX, Some(0x10_0000))?;
+
+        assert_eq!(0x10_0000, raw.module.address_space.base_address);
+        assert_eq!(0xE8, raw.module.address_space.read_u8(0x10_0000)?);
+        assert_eq!(0xC3, raw.module.address_space.read_u8(0x10_0006)?);
+        assert_eq!(0x6, raw.module.file_offset(0x10_0006)?);
+        assert!(raw.module.probe_va(0x10_0006, Permissions::X));
+        assert!(!raw.module.probe_va(0x10_0007, Permissions::X));
+        assert_eq!(1, raw.executable_sections().count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn permissions() -> Result<()> {
+        // a carved data region isn't executable.
+        let raw = Raw::from_bytes(&[0u8; 0x1800], Arch::X64, 0x7FF0_0000, Permissions::RW, None)?;
+        assert_eq!(0, raw.executable_sections().count());
+        assert!(raw.module.probe_va(0x7FF0_17FF, Permissions::W));
+        assert_eq!(None, raw.entry_point);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<()> {
+        assert!(Raw::from_bytes(b"", Arch::X64, 0x0, Permissions::RWX, None).is_err());
+        assert!(Raw::from_bytes(b"\x90", Arch::X64, u64::MAX, Permissions::RWX, None).is_err());
+        assert!(Raw::from_bytes(b"\x90", Arch::X64, 0x1000, Permissions::RWX, Some(0x1001)).is_err());
+
+        Ok(())
+    }
+}
@@ -8,7 +8,7 @@ use super::RVA;
 // these are usize so that they're easy to work with for indexing within this
 // module. generally, this module should work with RVA/u64 as its public
 // interface.
-const PAGE_SIZE: usize = 0x1000;
+pub(crate) const PAGE_SIZE: usize = 0x1000;
 const PAGE_SHIFT: usize = 12;
 const PAGE_MASK: usize = 0xFFF;
 
@@ -54,9 +54,10 @@ impl<T: Default + Copy> Default for Page<T> {
 /// contiguous indices. At the moment, indices are `RVA`.
 ///
 /// Lookups should be quick, as they boil down to just a couple dereferences.
+/// Pages are allocated on demand, so a large, sparsely mapped region is cheap.
 #[derive(Clone)]
 pub struct PageMap<T: Default + Copy> {
-    pages: Vec<Option<Page<T>>>,
+    pages: Vec<Option<Box<Page<T>>>>,
 }
 
 impl<T: Default + Copy> PageMap<T> {
@@ -90,7 +91,7 @@ impl<T: Default + Copy> PageMap<T> {
             return Err(PageMapError::NotMapped.into());
         }
 
-        self.pages[page(rva)] = Some(Page::new(items));
+        self.pages[page(rva)] = Some(Box::new(Page::new(items)));
 
         Ok(())
     }
@@ -233,6 +234,46 @@ impl<T: Default + Copy> PageMap<T> {
         Some(&mut page.elements[page_offset(rva)])
     }
 
+    /// overwrite the items found at the given address with the given items.
+    /// unlike `write`, the region must already be mapped,
+    /// though it need not be page aligned.
+    ///
+    /// errors:
+    ///   - PageMapError::NotMapped: if any requested address is not mapped. in
+    ///     this case, no items are modified.
+    ///
+    /// ```
+    /// use lancelot::pagemap::PageMap;
+    ///
+    /// let mut d: PageMap<u32> = PageMap::with_capacity(0x2000);
+    /// d.map_empty(0x0, 0x1000).expect("failed to map");
+    ///
+    /// d.update(0xFFE, &[0x1, 0x2]).expect("failed to update");
+    /// assert_eq!(d.slice(0xFFE, 0x1000).unwrap(), [0x1, 0x2]);
+    ///
+    /// assert!(d.update(0xFFF, &[0x3, 0x4]).is_err(), "spans unmapped page");
+    /// assert_eq!(d.get(0xFFF), Some(0x2));
+    /// ```
+    pub fn update(&mut self, start: RVA, items: &[T]) -> Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let end = start + items.len() as u64 - 1;
+        for page in page(start)..=page(end) {
+            if !self.probe((page * PAGE_SIZE) as RVA) {
+                return Err(PageMapError::NotMapped.into());
+            }
+        }
+
+        for (i, &item) in items.iter().enumerate() {
+            // safety: we've checked above that each page is mapped.
+            *self.get_mut(start + i as u64).expect("update: page not mapped") = item;
+        }
+
+        Ok(())
+    }
+
     /// handle the simple slice case: when start and end fall within the same
     /// page. for example, reading a dword from address 0x10.
     ///
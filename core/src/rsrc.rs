@@ -15,6 +15,26 @@ pub enum Rsrc {
     NOP,
     /// from: https://github.com/gentilkiwi/mimikatz/releases/tag/2.2.0-20190512
     MIMI,
+    /// from: https://www.gnu.org/software/gettext/ `build-aux/csharpexec-test.exe`
+    /// a minimal .NET assembly, with a single class and `Main` method.
+    DOTNET,
+    /// built from `hello.c` with `gcc -O1 -fno-inline -rdynamic` (gcc 12.2,
+    /// Debian), then stripped. a 64-bit position independent ELF
+    /// executable, with a few imports from libc.
+    HELLO,
+    /// built by `hello.macho.py`, since there's no macOS toolchain around.
+    /// a universal (fat) binary with a small x86-64 executable and an empty
+    /// arm64 slice.
+    MACHO,
+    /// assembled from `hello.coff.s` with `llvm-mc`.
+    /// a small x86-64 COFF object with a few functions and relocations.
+    COFF,
+    /// `hello.obj` and a short import library for kernel32,
+    /// combined by `llvm-lib`. see `hello.coff.s`.
+    LIB,
+    /// built by `nop.pdb.py`, since the original isn't available.
+    /// a small PDB that matches `nop.exe`, with a few symbols and procedures.
+    PDB,
 }
 
 /// Fetch the file system name of the given resource.
@@ -24,6 +44,12 @@ pub fn get_name(rsrc: Rsrc) -> String {
         Rsrc::TINY => String::from("tiny.exe"),
         Rsrc::NOP => String::from("nop.exe"),
         Rsrc::MIMI => String::from("mimikatz.exe_"),
+        Rsrc::DOTNET => String::from("csharpexec-test.exe_"),
+        Rsrc::HELLO => String::from("hello.elf"),
+        Rsrc::MACHO => String::from("hello.macho"),
+        Rsrc::COFF => String::from("hello.obj"),
+        Rsrc::LIB => String::from("hello.lib"),
+        Rsrc::PDB => String::from("nop.pdb"),
     }
 }
 
@@ -54,6 +80,24 @@ pub fn get_buf(rsrc: Rsrc) -> Vec<u8> {
         Rsrc::MIMI => {
             // pass
         }
+        Rsrc::DOTNET => {
+            // pass
+        }
+        Rsrc::HELLO => {
+            // pass
+        }
+        Rsrc::MACHO => {
+            // pass
+        }
+        Rsrc::COFF => {
+            // pass
+        }
+        Rsrc::LIB => {
+            // pass
+        }
+        Rsrc::PDB => {
+            // pass
+        }
     }
     buf
 }
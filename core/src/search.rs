@@ -0,0 +1,401 @@
+//! Search address spaces and modules for byte patterns with wildcards,
+//! using the syntax of YARA hex strings:
+//!
+//!   - `4D 5A`: literal bytes, with optional whitespace,
+//!   - `??`: any byte,
+//!   - `4?` and `?D`: any byte with the given high or low nibble,
+//!   - `[4]`: any four bytes,
+//!   - `[2-4]`, `[2-]`, `[-]`: between two and four, at least two, or any
+//!     number of bytes,
+//!   - `( 55 8B EC | 8B FF )`: any of the alternatives, which may be nested.
+//!
+//! For example, `E8 ?? ?? ?? ?? 85 C0 [2-6] C3`.
+//!
+//! Patterns are compiled into a byte regex, like the hand-written ones in
+//! `analysis::pe::patterns`.
+//! Matches may overlap, and don't span the gaps between sections.
+//!
+//! references:
+//!   - https://yara.readthedocs.io/en/stable/writingrules.html#hexadecimal-strings
+use std::{iter::Peekable, ops::Range, str::Chars};
+
+use anyhow::Result;
+use log::debug;
+use regex::bytes::Regex;
+use thiserror::Error;
+
+use crate::{
+    aspace::AddressSpace,
+    module::{Module, Permissions, Section},
+    VA,
+};
+
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+}
+
+/// A compiled byte pattern.
+///
+/// ```
+/// use lancelot::search::Pattern;
+///
+/// let pattern = Pattern::new("55 8B EC [0-2] 8? ?C").unwrap();
+/// let matches: Vec<usize> = pattern.find_iter(b"\x90\x55\x8B\xEC\x00\x83\xEC").collect();
+/// assert_eq!(matches, vec![0x1]);
+///
+/// assert!(Pattern::new("55 8B E").is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    /// the pattern as provided by the user.
+    pub source: String,
+    regex:      Regex,
+}
+
+fn invalid(msg: &str) -> anyhow::Error {
+    SearchError::InvalidPattern(msg.to_string()).into()
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// parse the digits of a jump, like the `2` in `[2-4]`, if any.
+fn parse_jump_bound(chars: &mut Peekable<Chars>) -> Result<Option<usize>> {
+    skip_whitespace(chars);
+
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(c);
+    }
+    skip_whitespace(chars);
+
+    if digits.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(digits.parse().map_err(|_| invalid("jump too large"))?))
+    }
+}
+
+/// parse a jump, like `[2-4]`, after the opening bracket,
+/// into a regex repetition of any byte.
+fn parse_jump(chars: &mut Peekable<Chars>) -> Result<String> {
+    let low = parse_jump_bound(chars)?;
+
+    let re = match chars.next() {
+        Some(']') => match low {
+            Some(low) => format!(".{{{}}}", low),
+            None => return Err(invalid("empty jump")),
+        },
+        Some('-') => {
+            let high = parse_jump_bound(chars)?;
+            if chars.next() != Some(']') {
+                return Err(invalid("unterminated jump"));
+            }
+
+            match (low, high) {
+                (Some(low), Some(high)) if low > high => return Err(invalid("jump bounds out of order")),
+                (Some(low), Some(high)) => format!(".{{{},{}}}", low, high),
+                (Some(low), None) => format!(".{{{},}}", low),
+                (None, Some(high)) => format!(".{{0,{}}}", high),
+                (None, None) => ".*".to_string(),
+            }
+        }
+        _ => return Err(invalid("unterminated jump")),
+    };
+
+    Ok(re)
+}
+
+/// parse a byte, like `4D`, `4?`, `?D`, or `??`, into a regex.
+fn parse_byte(high: char, chars: &mut Peekable<Chars>) -> Result<String> {
+    let low = chars.next().ok_or_else(|| invalid("incomplete byte"))?;
+
+    let nibble = |c: char| -> Result<Option<u8>> {
+        match c {
+            '?' => Ok(None),
+            c => match c.to_digit(16) {
+                Some(v) => Ok(Some(v as u8)),
+                None => Err(invalid(&format!("invalid character: {}", c))),
+            },
+        }
+    };
+
+    let re = match (nibble(high)?, nibble(low)?) {
+        (Some(high), Some(low)) => format!("\\x{:02X}", high << 4 | low),
+        (Some(high), None) => format!("[\\x{:02X}-\\x{:02X}]", high << 4, high << 4 | 0xF),
+        (None, Some(low)) => format!(
+            "[{}]",
+            (0..0x10u8)
+                .map(|high| format!("\\x{:02X}", high << 4 | low))
+                .collect::<Vec<_>>()
+                .join("")
+        ),
+        (None, None) => ".".to_string(),
+    };
+
+    Ok(re)
+}
+
+/// parse a sequence of bytes, jumps, and alternatives into a regex,
+/// until the end of the pattern or the given terminator.
+/// returns the regex and the terminator found, if any.
+fn parse_sequence(chars: &mut Peekable<Chars>, depth: usize) -> Result<(String, Option<char>)> {
+    let mut re = String::new();
+    let mut is_empty = true;
+
+    loop {
+        skip_whitespace(chars);
+
+        let c = match chars.next() {
+            None => break,
+            Some(c) => c,
+        };
+
+        match c {
+            '|' | ')' if depth > 0 => {
+                if is_empty {
+                    return Err(invalid("empty alternative"));
+                }
+                return Ok((re, Some(c)));
+            }
+            '|' | ')' => return Err(invalid(&format!("unexpected: {}", c))),
+            '[' => {
+                if is_empty {
+                    return Err(invalid("pattern can't start with a jump"));
+                }
+                re.push_str(&parse_jump(chars)?);
+            }
+            '(' => {
+                let mut alternatives = vec![];
+                loop {
+                    let (alternative, terminator) = parse_sequence(chars, depth + 1)?;
+                    alternatives.push(alternative);
+                    match terminator {
+                        Some('|') => continue,
+                        Some(')') => break,
+                        _ => return Err(invalid("unterminated alternative")),
+                    }
+                }
+                re.push_str(&format!("(?:{})", alternatives.join("|")));
+                is_empty = false;
+            }
+            c => {
+                re.push_str(&parse_byte(c, chars)?);
+                is_empty = false;
+            }
+        }
+    }
+
+    if depth > 0 {
+        return Err(invalid("unterminated alternative"));
+    }
+
+    if is_empty {
+        return Err(invalid("empty pattern"));
+    }
+
+    Ok((re, None))
+}
+
+impl Pattern {
+    /// compile the given YARA-style hex pattern.
+    ///
+    /// errors:
+    ///   - SearchError::InvalidPattern: if the pattern can't be parsed.
+    pub fn new(pattern: &str) -> Result<Pattern> {
+        let (re, _) = parse_sequence(&mut pattern.chars().peekable(), 0)?;
+        if re.ends_with('}') || re.ends_with(".*") {
+            return Err(invalid("pattern can't end with a jump"));
+        }
+
+        // disable unicode, so that we match raw bytes,
+        // and let `.` match newlines, so that it matches any byte.
+        let re = format!("(?s-u){}", re);
+        debug!("search: pattern: {} -> {}", pattern, re);
+
+        Ok(Pattern {
+            source: pattern.to_string(),
+            regex:  Regex::new(&re).map_err(|e| invalid(&e.to_string()))?,
+        })
+    }
+
+    /// find the offsets of the matches in the given buffer.
+    /// matches may overlap.
+    pub fn find_iter<'a>(&'a self, buf: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            if offset > buf.len() {
+                return None;
+            }
+
+            let m = self.regex.find_at(buf, offset)?;
+            offset = m.start() + 1;
+            Some(m.start())
+        })
+    }
+
+    /// find the addresses of the matches within the given range of an
+    /// address space, which must be entirely mapped.
+    pub fn search<A: AddressSpace<VA>>(&self, aspace: &A, range: Range<VA>) -> Result<Vec<VA>> {
+        let buf = aspace.read_bytes(range.start, (range.end - range.start) as usize)?;
+        Ok(self.find_iter(&buf).map(|offset| range.start + offset as u64).collect())
+    }
+}
+
+/// The lazy iterator of matches returned by `Module::search`,
+/// which reads each section as its reached.
+pub struct Matches<'a> {
+    module:   &'a Module,
+    pattern:  &'a Pattern,
+    sections: std::vec::IntoIter<&'a Section>,
+    /// the address and contents of the current section.
+    current:  Option<(VA, Vec<u8>)>,
+    offset:   usize,
+}
+
+impl<'a> Iterator for Matches<'a> {
+    type Item = VA;
+
+    fn next(&mut self) -> Option<VA> {
+        loop {
+            if let Some((address, buf)) = &self.current {
+                if self.offset <= buf.len() {
+                    if let Some(m) = self.pattern.regex.find_at(buf, self.offset) {
+                        self.offset = m.start() + 1;
+                        return Some(address + m.start() as u64);
+                    }
+                }
+            }
+
+            let section = self.sections.next()?;
+            let size = (section.virtual_range.end - section.virtual_range.start) as usize;
+            match self.module.address_space.read_bytes(section.virtual_range.start, size) {
+                Ok(buf) => {
+                    self.current = Some((section.virtual_range.start, buf));
+                    self.offset = 0;
+                }
+                Err(e) => {
+                    debug!("search: {}: failed to read section: {}", section.name, e);
+                    self.current = None;
+                }
+            }
+        }
+    }
+}
+
+impl Module {
+    /// find the addresses of the matches of the given pattern in the sections
+    /// with (at least) the given permissions, like `Permissions::X` for code,
+    /// or `Permissions::empty()` for all sections.
+    ///
+    /// ```
+    /// use lancelot::{arch::Arch, loader::raw::Raw, module::Permissions, search::Pattern};
+    ///
+    /// let raw = Raw::from_bytes(b"\x55\x8B\xEC\xC3\x55\x8B\xEC", Arch::X32, 0x1000, Permissions::RX, None).unwrap();
+    /// let pattern = Pattern::new("55 8B EC").unwrap();
+    /// let matches: Vec<_> = raw.module.search(&pattern, Permissions::X).collect();
+    /// assert_eq!(matches, vec![0x1000, 0x1004]);
+    ///
+    /// assert_eq!(raw.module.search(&pattern, Permissions::W).count(), 0);
+    /// ```
+    pub fn search<'a>(&'a self, pattern: &'a Pattern, permissions: Permissions) -> Matches<'a> {
+        let sections: Vec<&Section> = self
+            .sections
+            .iter()
+            .filter(|section| section.permissions.contains(permissions))
+            .collect();
+
+        Matches {
+            module: self,
+            pattern,
+            sections: sections.into_iter(),
+            current: None,
+            offset: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{module::Permissions, rsrc::*, search::*};
+    use anyhow::Result;
+
+    #[test]
+    fn syntax() -> Result<()> {
+        let find =
+            |pattern: &str, buf: &[u8]| -> Vec<usize> { Pattern::new(pattern).unwrap().find_iter(buf).collect() };
+
+        assert_eq!(vec![0], find("4D 5A", b"MZ"));
+        assert_eq!(vec![0], find("4d5a", b"MZ"));
+        assert_eq!(vec![1], find("5A", b"MZ"));
+        assert_eq!(vec![0, 1], find("??", b"MZ"));
+        assert_eq!(vec![0, 1], find("?A", b"\x0A\xFA\xAB"));
+        assert_eq!(vec![2], find("A?", b"\x0A\xFA\xAB"));
+        // `.` matches newlines, too.
+        assert_eq!(vec![0], find("00 ?? 00", b"\x00\x0A\x00"));
+
+        // jumps
+        assert_eq!(vec![0], find("01 [2] 04", b"\x01\x02\x03\x04"));
+        assert!(find("01 [1] 04", b"\x01\x02\x03\x04").is_empty());
+        assert_eq!(vec![0], find("01 [1-2] 04", b"\x01\x02\x03\x04"));
+        assert_eq!(vec![0], find("01 [1-] 04", b"\x01\x02\x03\x04"));
+        assert_eq!(vec![0], find("01 [-] 04", b"\x01\x04"));
+        assert_eq!(vec![0], find("01 [-2] 04", b"\x01\x02\x03\x04"));
+
+        // alternatives
+        assert_eq!(vec![0, 2], find("( 01 | 03 ) ??", b"\x01\x02\x03\x04"));
+        assert_eq!(vec![0], find("01 ( 02 ( 03 | FF ) | FF ) 04", b"\x01\x02\x03\x04"));
+
+        // overlapping matches
+        assert_eq!(vec![0, 1, 2], find("90 90", b"\x90\x90\x90\x90"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<()> {
+        assert!(Pattern::new("").is_err());
+        assert!(Pattern::new("   ").is_err());
+        assert!(Pattern::new("4").is_err());
+        assert!(Pattern::new("4G").is_err());
+        assert!(Pattern::new("[2] 01").is_err());
+        assert!(Pattern::new("01 [2]").is_err());
+        assert!(Pattern::new("01 [2").is_err());
+        assert!(Pattern::new("01 [] 02").is_err());
+        assert!(Pattern::new("01 [4-2] 02").is_err());
+        assert!(Pattern::new("( 01 | 02").is_err());
+        assert!(Pattern::new("( 01 | ) 02").is_err());
+        assert!(Pattern::new("01 ) 02").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn k32() -> Result<()> {
+        let buf = get_buf(Rsrc::K32);
+        let pe = crate::loader::pe::PE::from_bytes(&buf)?;
+
+        // the DOS header is only found in the headers, which aren't executable.
+        let pattern = Pattern::new("4D 5A 90 00")?;
+        assert_eq!(
+            vec![0x1_8000_0000],
+            pe.module.search(&pattern, Permissions::empty()).collect::<Vec<_>>()
+        );
+        assert_eq!(0, pe.module.search(&pattern, Permissions::X).count());
+
+        // the module and address space searches agree.
+        let pattern = Pattern::new("48 89 5C 24 ?? 48 89 74 24 ??")?;
+        let text = pe.executable_sections().next().unwrap();
+        let matches: Vec<_> = pe.module.search(&pattern, Permissions::X).collect();
+        assert!(!matches.is_empty());
+        assert_eq!(
+            matches,
+            pattern.search(&pe.module.address_space, text.virtual_range.clone())?
+        );
+
+        Ok(())
+    }
+}
@@ -1,9 +1,9 @@
 //! Helpers that are useful for tests and doctests.
 use crate::{
     arch::Arch,
-    aspace::{AddressSpace, RelativeAddressSpace},
-    module::{Module, Permissions, Section},
-    RVA, VA,
+    aspace::AddressSpace,
+    module::{Module, Permissions},
+    VA,
 };
 
 /// configure a global logger at level==DEBUG.
@@ -32,25 +32,9 @@ pub fn init_logging() {
 
 /// this is for testing, so will panic on error.
 pub fn load_shellcode(arch: Arch, buf: &[u8]) -> Module {
-    let mut address_space = RelativeAddressSpace::with_capacity(buf.len() as u64);
-    address_space.map.writezx(0x0, buf).unwrap();
-
-    Module {
-        arch,
-        sections: vec![Section {
-            name:           "shellcode".to_string(),
-            permissions:    Permissions::RWX,
-            physical_range: std::ops::Range {
-                start: 0x0,
-                end:   buf.len() as RVA,
-            },
-            virtual_range:  std::ops::Range {
-                start: 0x0,
-                end:   buf.len() as RVA,
-            },
-        }],
-        address_space: address_space.into_absolute(0x0).unwrap(),
-    }
+    crate::loader::raw::Raw::from_bytes(buf, arch, 0x0, Permissions::RWX, Some(0x0))
+        .unwrap()
+        .module
 }
 
 /// this is for testing, so will panic on error.
@@ -63,6 +47,27 @@ pub fn load_shellcode64(buf: &[u8]) -> Module {
     load_shellcode(Arch::X64, buf)
 }
 
+/// lay out the loaded image as it would be found in a process memory dump.
+/// this is for testing, so will panic on error.
+pub fn dump_pe(pe: &crate::loader::pe::PE) -> Vec<u8> {
+    let base_address = pe.module.address_space.base_address;
+    let size_of_image = pe.header.optional_header.unwrap().windows_fields.size_of_image as usize;
+    let mut buf = vec![0u8; size_of_image];
+
+    for section in pe.module.sections.iter() {
+        let start = (section.virtual_range.start - base_address) as usize;
+        let size = (section.virtual_range.end - section.virtual_range.start) as usize;
+        let data = pe
+            .module
+            .address_space
+            .read_bytes(section.virtual_range.start, size)
+            .unwrap();
+        buf[start..start + size].copy_from_slice(&data);
+    }
+
+    buf
+}
+
 /// this is for testing, so will panic on error.
 #[cfg(feature = "disassembler")]
 pub fn read_insn(module: &Module, va: VA) -> zydis::DecodedInstruction {
@@ -7,6 +7,8 @@ use log::{debug, error};
 use regex::bytes::Regex;
 use thiserror::Error;
 
+use crate::aspace::SharedBuffer;
+
 #[derive(Debug, Error)]
 pub enum UtilError {
     #[error("insufficient file access")]
@@ -71,6 +73,67 @@ pub fn align(i: u64, b: u64) -> u64 {
     }
 }
 
+/// Decode the unsigned LEB128 value at the given offset,
+/// advancing the offset past it.
+/// Returns `None` if the buffer ends before the value does.
+///
+/// # Examples
+///
+/// ```
+/// use lancelot::util::*;
+/// let mut offset = 0;
+/// assert_eq!(Some(624485), read_uleb128(&[0xE5, 0x8E, 0x26], &mut offset));
+/// assert_eq!(3, offset);
+/// assert_eq!(None, read_uleb128(&[0xE5, 0x8E], &mut 0));
+/// ```
+pub fn read_uleb128(buf: &[u8], offset: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let b = *buf.get(*offset)?;
+        *offset += 1;
+        if shift < 64 {
+            value |= ((b & 0x7F) as u64) << shift;
+        }
+        shift += 7;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+/// Decode the signed LEB128 value at the given offset,
+/// advancing the offset past it.
+/// Returns `None` if the buffer ends before the value does.
+///
+/// # Examples
+///
+/// ```
+/// use lancelot::util::*;
+/// assert_eq!(Some(-123456), read_sleb128(&[0xC0, 0xBB, 0x78], &mut 0));
+/// assert_eq!(Some(-8), read_sleb128(&[0x78], &mut 0));
+/// assert_eq!(Some(63), read_sleb128(&[0x3F], &mut 0));
+/// ```
+pub fn read_sleb128(buf: &[u8], offset: &mut usize) -> Option<i64> {
+    let mut value = 0i64;
+    let mut shift = 0;
+    loop {
+        let b = *buf.get(*offset)?;
+        *offset += 1;
+        if shift < 64 {
+            value |= ((b & 0x7F) as i64) << shift;
+        }
+        shift += 7;
+        if b & 0x80 == 0 {
+            if shift < 64 && b & 0x40 != 0 {
+                // sign extend
+                value |= -1i64 << shift;
+            }
+            return Some(value);
+        }
+    }
+}
+
 pub fn hexdump_ascii(b: u8) -> char {
     if b.is_ascii_graphic() || b == b' ' {
         b as char
@@ -175,6 +238,40 @@ pub fn read_file(filename: &str) -> Result<Vec<u8>> {
     Ok(buf)
 }
 
+/// Map the given file into memory, read-only, rather than reading it.
+/// The pages are loaded by the OS when accessed.
+///
+/// The file shouldn't be modified while it's mapped.
+pub fn map_file(filename: &str) -> Result<SharedBuffer> {
+    debug!("map_file: {:?}", filename);
+
+    let f = match fs::File::open(filename) {
+        Ok(f) => f,
+        Err(_) => {
+            error!("failed to open file: {}", filename);
+            return Err(UtilError::FileAccess.into());
+        }
+    };
+
+    // safety: the mapping is read-only, and we assume the file isn't modified
+    // by another process while its mapped.
+    let mmap = match unsafe { memmap2::Mmap::map(&f) } {
+        Ok(mmap) => mmap,
+        Err(_) => {
+            error!("failed to map file: {}", filename);
+            return Err(UtilError::FileAccess.into());
+        }
+    };
+    debug!("mapped {} bytes", mmap.len());
+
+    if mmap.len() < 0x10 {
+        error!("file too small: {}", filename);
+        return Err(UtilError::FileFormat.into());
+    }
+
+    Ok(SharedBuffer::new(mmap))
+}
+
 pub fn find_ascii_strings<'a>(buf: &'a [u8]) -> Box<dyn Iterator<Item = (Range<usize>, String)> + 'a> {
     lazy_static! {
         static ref ASCII_RE: Regex = Regex::new("[ -~]{4,}").unwrap();
@@ -3,15 +3,19 @@
 
 use anyhow::Error;
 use lancelot::{
-    arch::Arch,
+    arch::{Arch, ArchError},
     aspace::AddressSpace,
-    loader::pe::{PEError, PE as lPE},
-    module::{ModuleError, Permissions},
+    loader::{
+        pe::{PEError, PE as lPE},
+        raw::{Raw as lRaw, RawError},
+    },
+    module::{Module, ModuleError, Permissions},
     pagemap::PageMapError,
+    search::{Pattern, SearchError},
     util::UtilError,
     VA,
 };
-use pyo3::{self, prelude::*, types::*, wrap_pyfunction, PyNumberProtocol, PyObjectProtocol};
+use pyo3::{self, prelude::*, types::*, wrap_pyfunction, PyNumberProtocol, PyObjectProtocol, PySequenceProtocol};
 
 /// ValueError -> "you're doing something wrong"
 fn to_value_error(e: anyhow::Error) -> PyErr {
@@ -22,6 +26,20 @@ fn to_py_err(e: Error) -> PyErr {
     match e.downcast_ref::<PEError>() {
         Some(PEError::FormatNotSupported(_)) => return to_value_error(e),
         Some(PEError::MalformedPEFile(_)) => return to_value_error(e),
+        Some(PEError::NotFileBacked(_)) => return to_value_error(e),
+        None => (),
+    };
+
+    match e.downcast_ref::<RawError>() {
+        Some(RawError::EmptyBuffer) => return to_value_error(e),
+        Some(RawError::InvalidBaseAddress(_)) => return to_value_error(e),
+        Some(RawError::InvalidEntryPoint(_)) => return to_value_error(e),
+        None => (),
+    };
+
+    #[allow(clippy::single_match)]
+    match e.downcast_ref::<ArchError>() {
+        Some(ArchError::UnsupportedArchitecture(_)) => return to_value_error(e),
         None => (),
     };
 
@@ -43,6 +61,12 @@ fn to_py_err(e: Error) -> PyErr {
         None => (),
     };
 
+    #[allow(clippy::single_match)]
+    match e.downcast_ref::<SearchError>() {
+        Some(SearchError::InvalidPattern(_)) => return to_value_error(e),
+        None => (),
+    };
+
     to_value_error(e)
 }
 
@@ -63,6 +87,56 @@ pub fn from_bytes(buf: &PyBytes) -> PyResult<PE> {
     })
 }
 
+/// load raw bytes, like shellcode or a carved memory region,
+/// as a single section at the given address.
+///
+/// Args:
+///   buf (bytes): the raw bytes.
+///   arch (str): the architecture, either "x32" or "x64".
+///   base_address (int): the address at which to map the bytes. default: 0x0.
+///   permissions (int): the combination of `PERMISSION_*` flags with which to
+///     map the bytes. default: read, write, and execute.
+///   entry_point (Optional[int]): the address at which execution starts.
+///     default: None.
+///
+/// Raises:
+///   ValueError - if the architecture is unsupported, or the entry point
+///     isn't within the bytes.
+///
+/// Returns: Raw
+#[pyfunction(
+    buf,
+    arch,
+    base_address = "0x0",
+    permissions = "PERMISSION_READ | PERMISSION_WRITE | PERMISSION_EXECUTE",
+    entry_point = "None"
+)]
+pub fn from_raw_bytes(
+    buf: &PyBytes,
+    arch: &str,
+    base_address: VA,
+    permissions: u8,
+    entry_point: Option<VA>,
+) -> PyResult<Raw> {
+    use lancelot::analysis::dis;
+
+    let arch: Arch = arch.parse().map_err(|e: ArchError| to_py_err(e.into()))?;
+
+    let raw = lRaw::from_bytes(
+        buf.as_bytes(),
+        arch,
+        base_address,
+        to_permissions(permissions),
+        entry_point,
+    )
+    .map_err(to_py_err)?;
+    let dec = dis::get_disassembler(&raw.module).map_err(to_py_err)?;
+    Ok(Raw {
+        inner:   raw,
+        decoder: dec,
+    })
+}
+
 /// Control Flow Graph (CFG) is the result of disassembling from a given
 /// address. The result is broken up into regions of non-branching instructions
 /// ("basic blocks").
@@ -105,6 +179,105 @@ pub struct CallGraph {
     /// type: Dict[int, List[int]]
     #[pyo3(get)]
     pub call_instruction_functions: Py<PyDict>,
+
+    /// map from a call instruction to its leading arguments, in order,
+    /// with None for an argument that isn't known.
+    /// on x32, these are the top slots of the stack,
+    /// so they may include values that aren't arguments to the callee.
+    /// an argument is a tuple (argument type, value, string).
+    /// use the `ARGUMENT_(TYPE|VALUE|STRING)` constants to index into this
+    /// tuple. the argument type is one of the `ARGUMENT_TYPE_*` constants,
+    /// such as `ARGUMENT_TYPE_CONSTANT`.
+    /// type: Dict[int, List[Optional[Tuple[int, int, Optional[str]]]]]
+    #[pyo3(get)]
+    pub call_arguments: Py<PyDict>,
+}
+
+const ARGUMENT_CONSTANT: u8 = 0;
+const ARGUMENT_LOAD: u8 = 1;
+const ARGUMENT_STACK: u8 = 2;
+
+fn argument_to_tuple(py: Python, argument: &lancelot::analysis::dataflow::Argument) -> Py<PyTuple> {
+    // we use a tuple for performance.
+    use lancelot::analysis::dataflow::Value;
+    let (ty, value): (u8, PyObject) = match argument.value {
+        Value::Constant(v) => (ARGUMENT_CONSTANT, v.into_py(py)),
+        Value::Load(va) => (ARGUMENT_LOAD, va.into_py(py)),
+        Value::Stack(offset) => (ARGUMENT_STACK, offset.into_py(py)),
+    };
+    PyTuple::new(py, &[ty.into_py(py), value, argument.string.clone().into_py(py)]).into()
+}
+
+/// an index of the cross-references among the code and data of a module.
+/// an xref is a tuple (from virtual address, to virtual address, xref type).
+/// use the `XREF_(FROM|TO|TYPE)` constants to index into this tuple.
+/// the xref type is one of the `XREF_TYPE_*` constants,
+/// such as `XREF_TYPE_READ`.
+#[pyclass]
+pub struct Xrefs {
+    inner: lancelot::analysis::xrefs::Xrefs,
+}
+
+const XREF_FALLTHROUGH: u8 = 0;
+const XREF_CALL: u8 = 1;
+const XREF_UNCONDITIONAL_JUMP: u8 = 2;
+const XREF_CONDITIONAL_JUMP: u8 = 3;
+const XREF_READ: u8 = 4;
+const XREF_WRITE: u8 = 5;
+const XREF_ADDRESS: u8 = 6;
+const XREF_POINTER: u8 = 7;
+
+fn xrefs_to_list(py: Python, xrefs: &[lancelot::analysis::xrefs::Xref]) -> PyResult<Py<PyList>> {
+    // we use tuples for performance.
+    use lancelot::analysis::xrefs::XrefKind;
+    let ret = PyList::empty(py);
+    for xref in xrefs.iter() {
+        let ty = match xref.kind {
+            XrefKind::Fallthrough => XREF_FALLTHROUGH,
+            XrefKind::Call => XREF_CALL,
+            XrefKind::UnconditionalJump => XREF_UNCONDITIONAL_JUMP,
+            XrefKind::ConditionalJump => XREF_CONDITIONAL_JUMP,
+            XrefKind::Read => XREF_READ,
+            XrefKind::Write => XREF_WRITE,
+            XrefKind::Address => XREF_ADDRESS,
+            XrefKind::Pointer => XREF_POINTER,
+        };
+        let triple: [u64; 3] = [xref.from, xref.to, ty as u64];
+        ret.append(PyTuple::new(py, triple.iter()))?;
+    }
+    Ok(ret.into())
+}
+
+#[pymethods]
+impl Xrefs {
+    /// fetch the xrefs to the given address,
+    /// such as the instructions that read a global variable.
+    ///
+    /// Args:
+    ///   va (int): the referenced address.
+    ///
+    /// Returns: List[Tuple[int, int, int]]
+    pub fn get_xrefs_to(&self, py: Python, va: VA) -> PyResult<Py<PyList>> {
+        xrefs_to_list(py, self.inner.to(va))
+    }
+
+    /// fetch the xrefs from the given address,
+    /// such as the targets of an instruction or a pointer.
+    ///
+    /// Args:
+    ///   va (int): the address of the instruction or pointer.
+    ///
+    /// Returns: List[Tuple[int, int, int]]
+    pub fn get_xrefs_from(&self, py: Python, va: VA) -> PyResult<Py<PyList>> {
+        xrefs_to_list(py, self.inner.from(va))
+    }
+}
+
+#[pyproto]
+impl PySequenceProtocol for Xrefs {
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
 }
 
 const FLOW_FALLTHROUGH: u8 = 0;
@@ -336,10 +509,307 @@ impl PyNumberProtocol for Instruction {
     }
 }
 
+/// An entry in the export table of a PE.
+#[pyclass]
+pub struct Export {
+    /// the ordinal of the export.
+    #[pyo3(get)]
+    pub ordinal: u32,
+
+    /// the name of the export, or None when exported by ordinal only.
+    /// type: Optional[str]
+    #[pyo3(get)]
+    pub name: Option<String>,
+
+    /// the address of the exported code or data, or None when forwarded.
+    /// type: Optional[int]
+    #[pyo3(get)]
+    pub address: Option<u64>,
+
+    /// the forwarded symbol, like `NTDLL.RtlAllocateHeap`,
+    /// or None when the export is implemented by this module.
+    /// type: Optional[str]
+    #[pyo3(get)]
+    pub forwarder: Option<String>,
+
+    /// True when the export points into a non-executable section,
+    /// such as an exported global variable.
+    #[pyo3(get)]
+    pub is_data: bool,
+}
+
+impl From<lancelot::loader::pe::exports::Export> for Export {
+    fn from(export: lancelot::loader::pe::exports::Export) -> Export {
+        use lancelot::loader::pe::exports::ExportTarget;
+
+        let (address, forwarder, is_data) = match export.target {
+            ExportTarget::Code(va) => (Some(va), None, false),
+            ExportTarget::Data(va) => (Some(va), None, true),
+            ExportTarget::Forwarder(forwarder) => (None, Some(forwarder), false),
+        };
+
+        Export {
+            ordinal: export.ordinal,
+            name: export.name,
+            address,
+            forwarder,
+            is_data,
+        }
+    }
+}
+
+/// An entry in the debug directory of a PE.
+#[pyclass]
+pub struct DebugEntry {
+    /// the type of the entry, like "CODEVIEW", "POGO", or "REPRO".
+    #[pyo3(get)]
+    pub kind: &'static str,
+
+    #[pyo3(get)]
+    pub time_date_stamp: u32,
+
+    /// the GUID of the referenced PDB, like
+    /// `63816243-EC70-4DC0-91BC-31470BAC48A3`, or None when this is not a
+    /// PDB 7.0 CodeView entry. type: Optional[str]
+    #[pyo3(get)]
+    pub pdb_guid: Option<String>,
+
+    /// type: Optional[int]
+    #[pyo3(get)]
+    pub pdb_age: Option<u32>,
+
+    /// type: Optional[str]
+    #[pyo3(get)]
+    pub pdb_path: Option<String>,
+
+    /// the key used to fetch the PDB from a symbol server.
+    /// type: Optional[str]
+    #[pyo3(get)]
+    pub pdb_key: Option<String>,
+
+    /// the regions described by a POGO entry.
+    /// type: Optional[List[Tuple[int, int, str]]]
+    #[pyo3(get)]
+    pub pogo: Option<Vec<(u64, u32, String)>>,
+}
+
+impl From<lancelot::loader::pe::debug::DebugEntry> for DebugEntry {
+    fn from(entry: lancelot::loader::pe::debug::DebugEntry) -> DebugEntry {
+        use lancelot::loader::pe::debug::{CodeView, DebugData};
+
+        let mut ret = DebugEntry {
+            kind:            entry.kind_name(),
+            time_date_stamp: entry.time_date_stamp,
+            pdb_guid:        None,
+            pdb_age:         None,
+            pdb_path:        None,
+            pdb_key:         None,
+            pogo:            None,
+        };
+
+        match entry.data {
+            Some(DebugData::CodeView(cv)) => {
+                if let CodeView::Pdb70 { guid, .. } = &cv {
+                    ret.pdb_guid = Some(guid.to_string());
+                }
+                ret.pdb_age = Some(cv.age());
+                ret.pdb_path = Some(cv.path().to_string());
+                ret.pdb_key = Some(cv.symbol_server_key());
+            }
+            Some(DebugData::Pogo(pogo)) => {
+                ret.pogo = Some(
+                    pogo.entries
+                        .into_iter()
+                        .map(|entry| (entry.address, entry.size, entry.name))
+                        .collect(),
+                );
+            }
+            _ => {}
+        }
+
+        ret
+    }
+}
+
 const PERMISSION_READ: u8 = 0b001;
 const PERMISSION_WRITE: u8 = 0b010;
 const PERMISSION_EXECUTE: u8 = 0b100;
 
+fn to_permissions(permissions: u8) -> Permissions {
+    let mut perms = Permissions::empty();
+    if permissions & PERMISSION_READ > 0 {
+        perms.insert(Permissions::R);
+    }
+    if permissions & PERMISSION_WRITE > 0 {
+        perms.insert(Permissions::W);
+    }
+    if permissions & PERMISSION_EXECUTE > 0 {
+        perms.insert(Permissions::X);
+    }
+    perms
+}
+
+// the following routines operate on the loaded module,
+// so they're shared by the `PE` and `Raw` classes.
+
+fn module_arch(module: &Module) -> &'static str {
+    match module.arch {
+        Arch::X32 => "x32",
+        Arch::X64 => "x64",
+    }
+}
+
+fn module_build_cfg(py: Python, module: &Module, va: VA) -> PyResult<CFG> {
+    let basic_blocks = PyDict::new(py);
+    let cfg = lancelot::analysis::cfg::build_cfg(module, va).map_err(to_py_err)?;
+
+    for (bbva, bb) in cfg.basic_blocks.iter() {
+        let bb: PyObject = BasicBlock::from_basic_block(py, bb)?.into_py(py);
+        basic_blocks.set_item(bbva, bb)?;
+    }
+
+    Ok(CFG {
+        address:      va,
+        basic_blocks: basic_blocks.into(),
+    })
+}
+
+/// build the CFG for each of the given functions,
+/// not disassembling past calls to the given no-return functions.
+fn module_build_cfgs(
+    module: &Module,
+    functions: &[VA],
+    noret: &std::collections::BTreeSet<VA>,
+) -> std::collections::BTreeMap<VA, lancelot::analysis::cfg::CFG> {
+    use lancelot::analysis::cfg;
+    use std::collections::BTreeMap;
+
+    let mut cfgs: BTreeMap<VA, cfg::CFG> = Default::default();
+    for &function in functions.iter() {
+        if let Ok(cfg) = cfg::build_cfg_with_noret(module, function, noret) {
+            cfgs.insert(function, cfg);
+        }
+    }
+
+    cfgs
+}
+
+fn module_build_call_graph(
+    py: Python,
+    module: &Module,
+    functions: &[VA],
+    noret: &std::collections::BTreeSet<VA>,
+) -> PyResult<CallGraph> {
+    use lancelot::analysis::{call_graph, dataflow};
+
+    let cfgs = module_build_cfgs(module, functions, noret);
+    let annotations = dataflow::analyze_functions(module, &cfgs).map_err(to_py_err)?;
+    let cg = call_graph::build_call_graph_with_annotations(module, &cfgs, &annotations).map_err(to_py_err)?;
+
+    let calls_to: PyObject = cg.calls_to.into_py(py);
+    let calls_to: Py<PyDict> = calls_to.extract(py)?;
+
+    let calls_from: PyObject = cg.calls_from.into_py(py);
+    let calls_from: Py<PyDict> = calls_from.extract(py)?;
+
+    let function_call_instructions: PyObject = cg.function_call_instructions.into_py(py);
+    let function_call_instructions: Py<PyDict> = function_call_instructions.extract(py)?;
+
+    let call_instruction_functions: PyObject = cg.call_instruction_functions.into_py(py);
+    let call_instruction_functions: Py<PyDict> = call_instruction_functions.extract(py)?;
+
+    let call_arguments = PyDict::new(py);
+    for (va, arguments) in annotations.arguments.iter() {
+        let arguments = PyList::new(
+            py,
+            arguments
+                .iter()
+                .map(|argument| argument.as_ref().map(|argument| argument_to_tuple(py, argument))),
+        );
+        call_arguments.set_item(va, arguments)?;
+    }
+
+    Ok(CallGraph {
+        calls_to,
+        calls_from,
+        function_call_instructions,
+        call_instruction_functions,
+        call_arguments: call_arguments.into(),
+    })
+}
+
+fn module_build_xrefs(module: &Module, functions: &[VA], noret: &std::collections::BTreeSet<VA>) -> PyResult<Xrefs> {
+    use lancelot::analysis::{dataflow, xrefs};
+
+    let cfgs = module_build_cfgs(module, functions, noret);
+    let annotations = dataflow::analyze_functions(module, &cfgs).map_err(to_py_err)?;
+    let xrefs = xrefs::build_xrefs_with_annotations(module, &cfgs, &annotations).map_err(to_py_err)?;
+    Ok(Xrefs { inner: xrefs })
+}
+
+fn module_read_bytes(py: Python, module: &Module, va: VA, length: usize) -> PyResult<Py<PyBytes>> {
+    module
+        .address_space
+        .read_bytes(va, length)
+        .map(|buf| PyBytes::new(py, &buf).into())
+        .map_err(to_py_err)
+}
+
+fn module_read_insn(module: &Module, decoder: &zydis::Decoder, va: VA) -> PyResult<Instruction> {
+    let mut insn_buf = [0u8; 16];
+    module.address_space.read_into(va, &mut insn_buf).map_err(to_py_err)?;
+
+    if let Ok(Some(insn)) = decoder.decode(&insn_buf) {
+        Ok(Instruction {
+            address: va,
+            inner:   insn,
+        })
+    } else {
+        Err(pyo3::exceptions::PyValueError::new_err("invalid instruction"))
+    }
+}
+
+fn module_search(module: &Module, pattern: &str, permissions: u8) -> PyResult<Vec<VA>> {
+    let pattern = Pattern::new(pattern).map_err(to_py_err)?;
+    Ok(module.search(&pattern, to_permissions(permissions)).collect())
+}
+
+fn module_probe(module: &Module, va: i128) -> u8 {
+    // probe should be pretty relaxed about what it accepts
+    // so that it is easy to use.
+    // therefore, do extra validation here.
+    if va < 0 {
+        return 0x0;
+    }
+    if va > u64::MAX as i128 {
+        return 0x0;
+    }
+    let va = va as u64;
+
+    match module
+        .sections
+        .iter()
+        .find(|section| section.virtual_range.contains(&va))
+    {
+        None => 0x0,
+        Some(sec) => {
+            let mut ret = 0;
+            if sec.permissions.intersects(Permissions::R) {
+                ret |= PERMISSION_READ;
+            }
+
+            if sec.permissions.intersects(Permissions::W) {
+                ret |= PERMISSION_WRITE;
+            }
+
+            if sec.permissions.intersects(Permissions::X) {
+                ret |= PERMISSION_EXECUTE;
+            }
+            ret
+        }
+    }
+}
+
 #[pyclass]
 pub struct PE {
     inner:   lPE,
@@ -354,10 +824,7 @@ impl PE {
     /// Returns: str
     #[getter]
     pub fn arch(&self) -> &'static str {
-        match self.inner.module.arch {
-            Arch::X32 => "x32",
-            Arch::X64 => "x64",
-        }
+        module_arch(&self.inner.module)
     }
 
     /// fetch the module base address.
@@ -376,13 +843,16 @@ impl PE {
     ///   - function prologue pattern matches
     ///   - targets of pointers to executable sections
     ///   - control flow and safeseh table entries
+    ///   - TLS callbacks
     ///
+    /// calls in the dead code after calls to no-return functions are ignored.
     /// the result is a list of virtual addresses where disassembly could start.
     ///
     /// Returns: List[int]
     pub fn get_functions(&self) -> PyResult<Vec<u64>> {
-        Ok(lancelot::analysis::pe::find_functions(&self.inner)
+        Ok(lancelot::analysis::pe::find_functions_and_noret(&self.inner)
             .map_err(to_py_err)?
+            .0
             .into_iter()
             .filter(|f| matches!(f, lancelot::analysis::pe::Function::Local(_)))
             .map(|f| match f {
@@ -393,8 +863,9 @@ impl PE {
     }
 
     pub fn get_thunks(&self) -> PyResult<Vec<u64>> {
-        Ok(lancelot::analysis::pe::find_functions(&self.inner)
+        Ok(lancelot::analysis::pe::find_functions_and_noret(&self.inner)
             .map_err(to_py_err)?
+            .0
             .into_iter()
             .filter(|f| matches!(f, lancelot::analysis::pe::Function::Thunk(_)))
             .map(|f| match f {
@@ -404,6 +875,31 @@ impl PE {
             .collect())
     }
 
+    /// parse the export table, including forwarded and data exports.
+    ///
+    /// Returns: List[Export]
+    pub fn get_exports(&self) -> PyResult<Vec<Export>> {
+        Ok(lancelot::loader::pe::exports::read_exports(&self.inner)
+            .map_err(to_py_err)?
+            .into_iter()
+            .map(Export::from)
+            .collect())
+    }
+
+    /// parse the debug directory, such as the CodeView entry that references
+    /// the PDB.
+    ///
+    /// Returns: List[DebugEntry]
+    pub fn get_debug_entries(&self) -> PyResult<Vec<DebugEntry>> {
+        Ok(self
+            .inner
+            .get_debug_entries()
+            .map_err(to_py_err)?
+            .into_iter()
+            .map(DebugEntry::from)
+            .collect())
+    }
+
     /// disassemble from the given virtual address,
     /// collecting ranges of non-branching instructions ("basic blocks").
     /// typically, you'd invoke `PE.build_cfg` on the address of a function
@@ -417,56 +913,178 @@ impl PE {
     ///
     /// Returns: CFG
     pub fn build_cfg(&self, py: Python, va: VA) -> PyResult<CFG> {
-        let basic_blocks = PyDict::new(py);
-        let cfg = lancelot::analysis::cfg::build_cfg(&self.inner.module, va).map_err(to_py_err)?;
-
-        for (bbva, bb) in cfg.basic_blocks.iter() {
-            let bb: PyObject = BasicBlock::from_basic_block(py, bb)?.into_py(py);
-            basic_blocks.set_item(bbva, bb)?;
-        }
-
-        Ok(CFG {
-            address:      va,
-            basic_blocks: basic_blocks.into(),
-        })
+        module_build_cfg(py, &self.inner.module, va)
     }
 
     /// construct and index the call graph among instructions and functions.
     /// this routine will implicitly find all functions and build a CFG for
-    /// each.
+    /// each, which doesn't continue past calls to functions that don't return.
+    /// indirect calls, like `call eax`, are resolved with local dataflow,
+    /// which also recovers the arguments to calls.
     ///
     /// Returns: CallGraph
     pub fn build_call_graph(&self, py: Python) -> PyResult<CallGraph> {
-        use lancelot::analysis::{call_graph, cfg, pe};
-        use std::collections::BTreeMap;
+        let (functions, noret) = lancelot::analysis::pe::find_functions_and_noret(&self.inner).map_err(to_py_err)?;
+        let functions = lancelot::analysis::pe::get_function_starts(functions);
+        module_build_call_graph(py, &self.inner.module, &functions, &noret)
+    }
 
-        let mut cfgs: BTreeMap<VA, cfg::CFG> = Default::default();
-        for &function in pe::find_function_starts(&self.inner).map_err(to_py_err)?.iter() {
-            if let Ok(cfg) = cfg::build_cfg(&self.inner.module, function) {
-                cfgs.insert(function, cfg);
-            }
-        }
+    /// construct and index the cross-references among code and data.
+    /// this routine will implicitly find all functions and build a CFG for
+    /// each, which doesn't continue past calls to functions that don't return.
+    /// indirect calls and jumps, like `call eax`, are resolved with local
+    /// dataflow.
+    ///
+    /// Returns: Xrefs
+    pub fn build_xrefs(&self) -> PyResult<Xrefs> {
+        let (functions, noret) = lancelot::analysis::pe::find_functions_and_noret(&self.inner).map_err(to_py_err)?;
+        let functions = lancelot::analysis::pe::get_function_starts(functions);
+        module_build_xrefs(&self.inner.module, &functions, &noret)
+    }
 
-        let cg = call_graph::build_call_graph(&self.inner.module, &cfgs).map_err(to_py_err)?;
+    /// read a sequence of bytes at the given virtual address.
+    ///
+    /// Args:
+    ///   va (int): the virtual address at which to read data.
+    ///   length (int): the number of bytes to read.
+    ///
+    /// Raises:
+    ///   ValueError - if the address is invalid.
+    ///
+    /// Returns: bytes
+    pub fn read_bytes(&self, py: Python, va: VA, length: usize) -> PyResult<Py<PyBytes>> {
+        module_read_bytes(py, &self.inner.module, va, length)
+    }
 
-        let calls_to: PyObject = cg.calls_to.into_py(py);
-        let calls_to: Py<PyDict> = calls_to.extract(py)?;
+    /// disassemble an instruction at the given virtual address.
+    ///
+    /// Args:
+    ///   va (int): the virtual address at which to disassemble.
+    ///
+    /// Raises:
+    ///   ValueError - if the address or instruction is invalid.
+    ///
+    /// Returns: Instruction
+    pub fn read_insn(&self, va: VA) -> PyResult<Instruction> {
+        module_read_insn(&self.inner.module, &self.decoder, va)
+    }
 
-        let calls_from: PyObject = cg.calls_from.into_py(py);
-        let calls_from: Py<PyDict> = calls_from.extract(py)?;
+    /// read a pointer at the given virtual address.
+    ///
+    /// Args:
+    ///   va (int): the virtual address at which to read the pointer.
+    ///
+    /// Raises:
+    ///   ValueError - if the address is invalid.
+    ///
+    /// Returns: int
+    pub fn read_pointer(&self, va: VA) -> PyResult<u64> {
+        self.inner.module.read_va_at_va(va).map_err(to_py_err)
+    }
 
-        let function_call_instructions: PyObject = cg.function_call_instructions.into_py(py);
-        let function_call_instructions: Py<PyDict> = function_call_instructions.extract(py)?;
+    pub fn probe(&self, va: i128) -> u8 {
+        module_probe(&self.inner.module, va)
+    }
 
-        let call_instruction_functions: PyObject = cg.call_instruction_functions.into_py(py);
-        let call_instruction_functions: Py<PyDict> = call_instruction_functions.extract(py)?;
+    /// find the addresses that match the given byte pattern,
+    /// like `"55 8B EC ?? [2-4] C3"`, in the sections with (at least)
+    /// the given `PERMISSION_*` flags.
+    ///
+    /// the pattern uses the syntax of YARA hex strings:
+    /// `??` matches any byte, `4?` any byte with the given high nibble,
+    /// `[2-4]` between two and four bytes, and `( 01 | 02 )` either
+    /// alternative.
+    ///
+    /// Args:
+    ///   pattern (str): the byte pattern.
+    ///   permissions (int): the permissions of the sections to search.
+    ///     default: 0, all sections.
+    ///
+    /// Raises:
+    ///   ValueError - if the pattern is invalid.
+    ///
+    /// Returns: List[int]
+    #[args(permissions = "0")]
+    pub fn search(&self, pattern: &str, permissions: u8) -> PyResult<Vec<VA>> {
+        module_search(&self.inner.module, pattern, permissions)
+    }
+}
 
-        Ok(CallGraph {
-            calls_to,
-            calls_from,
-            function_call_instructions,
-            call_instruction_functions,
-        })
+#[pyclass]
+pub struct Raw {
+    inner:   lRaw,
+    decoder: zydis::Decoder,
+}
+
+#[pymethods]
+impl Raw {
+    /// fetch the architecture of the bytes as a string.
+    /// either "x32" or "x64"
+    ///
+    /// Returns: str
+    #[getter]
+    pub fn arch(&self) -> &'static str {
+        module_arch(&self.inner.module)
+    }
+
+    /// fetch the address at which the bytes are mapped.
+    ///
+    /// Returns: int
+    #[getter]
+    pub fn base_address(&self) -> u64 {
+        self.inner.module.address_space.base_address
+    }
+
+    /// fetch the address at which execution starts, if provided.
+    ///
+    /// Returns: Optional[int]
+    #[getter]
+    pub fn entry_point(&self) -> Option<u64> {
+        self.inner.entry_point
+    }
+
+    /// find the functions reachable from the entry point via `call`
+    /// instructions, including the entry point itself.
+    ///
+    /// Returns: List[int]
+    pub fn get_functions(&self) -> PyResult<Vec<u64>> {
+        lancelot::analysis::raw::find_function_starts(&self.inner).map_err(to_py_err)
+    }
+
+    /// disassemble from the given virtual address,
+    /// collecting ranges of non-branching instructions ("basic blocks").
+    ///
+    /// does follow jumps, but
+    /// does not follow call instructions.
+    ///
+    /// Args:
+    ///   va (int): the address from which to disassemble.
+    ///
+    /// Returns: CFG
+    pub fn build_cfg(&self, py: Python, va: VA) -> PyResult<CFG> {
+        module_build_cfg(py, &self.inner.module, va)
+    }
+
+    /// construct and index the call graph among instructions and the
+    /// functions found by `Raw.get_functions`.
+    /// indirect calls, like `call eax`, are resolved with local dataflow,
+    /// which also recovers the arguments to calls.
+    ///
+    /// Returns: CallGraph
+    pub fn build_call_graph(&self, py: Python) -> PyResult<CallGraph> {
+        let functions = lancelot::analysis::raw::find_function_starts(&self.inner).map_err(to_py_err)?;
+        module_build_call_graph(py, &self.inner.module, &functions, &Default::default())
+    }
+
+    /// construct and index the cross-references among code and data,
+    /// from the functions found by `Raw.get_functions`.
+    /// indirect calls and jumps, like `call eax`, are resolved with local
+    /// dataflow.
+    ///
+    /// Returns: Xrefs
+    pub fn build_xrefs(&self) -> PyResult<Xrefs> {
+        let functions = lancelot::analysis::raw::find_function_starts(&self.inner).map_err(to_py_err)?;
+        module_build_xrefs(&self.inner.module, &functions, &Default::default())
     }
 
     /// read a sequence of bytes at the given virtual address.
@@ -480,12 +1098,7 @@ impl PE {
     ///
     /// Returns: bytes
     pub fn read_bytes(&self, py: Python, va: VA, length: usize) -> PyResult<Py<PyBytes>> {
-        self.inner
-            .module
-            .address_space
-            .read_bytes(va, length)
-            .map(|buf| PyBytes::new(py, &buf).into())
-            .map_err(to_py_err)
+        module_read_bytes(py, &self.inner.module, va, length)
     }
 
     /// disassemble an instruction at the given virtual address.
@@ -498,21 +1111,7 @@ impl PE {
     ///
     /// Returns: Instruction
     pub fn read_insn(&self, va: VA) -> PyResult<Instruction> {
-        let mut insn_buf = [0u8; 16];
-        self.inner
-            .module
-            .address_space
-            .read_into(va, &mut insn_buf)
-            .map_err(to_py_err)?;
-
-        if let Ok(Some(insn)) = self.decoder.decode(&insn_buf) {
-            Ok(Instruction {
-                address: va,
-                inner:   insn,
-            })
-        } else {
-            Err(pyo3::exceptions::PyValueError::new_err("invalid instruction"))
-        }
+        module_read_insn(&self.inner.module, &self.decoder, va)
     }
 
     /// read a pointer at the given virtual address.
@@ -529,48 +1128,42 @@ impl PE {
     }
 
     pub fn probe(&self, va: i128) -> u8 {
-        // probe should be pretty relaxed about what it accepts
-        // so that it is easy to use.
-        // therefore, do extra validation here.
-        if va < 0 {
-            return 0x0;
-        }
-        if va > u64::MAX as i128 {
-            return 0x0;
-        }
-        let va = va as u64;
-
-        match self
-            .inner
-            .module
-            .sections
-            .iter()
-            .find(|section| section.virtual_range.contains(&va))
-        {
-            None => 0x0,
-            Some(sec) => {
-                let mut ret = 0;
-                if sec.permissions.intersects(Permissions::R) {
-                    ret |= PERMISSION_READ;
-                }
-
-                if sec.permissions.intersects(Permissions::W) {
-                    ret |= PERMISSION_WRITE;
-                }
+        module_probe(&self.inner.module, va)
+    }
 
-                if sec.permissions.intersects(Permissions::X) {
-                    ret |= PERMISSION_EXECUTE;
-                }
-                ret
-            }
-        }
+    /// find the addresses that match the given byte pattern,
+    /// like `"55 8B EC ?? [2-4] C3"`, in the sections with (at least)
+    /// the given `PERMISSION_*` flags.
+    ///
+    /// the pattern uses the syntax of YARA hex strings:
+    /// `??` matches any byte, `4?` any byte with the given high nibble,
+    /// `[2-4]` between two and four bytes, and `( 01 | 02 )` either
+    /// alternative.
+    ///
+    /// Args:
+    ///   pattern (str): the byte pattern.
+    ///   permissions (int): the permissions of the sections to search.
+    ///     default: 0, all sections.
+    ///
+    /// Raises:
+    ///   ValueError - if the pattern is invalid.
+    ///
+    /// Returns: List[int]
+    #[args(permissions = "0")]
+    pub fn search(&self, pattern: &str, permissions: u8) -> PyResult<Vec<VA>> {
+        module_search(&self.inner.module, pattern, permissions)
     }
 }
 
 #[pymodule]
 fn lancelot(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(from_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(from_raw_bytes, m)?)?;
     m.add_class::<PE>()?;
+    m.add_class::<Raw>()?;
+    m.add_class::<Export>()?;
+    m.add_class::<DebugEntry>()?;
+    m.add_class::<Xrefs>()?;
 
     // indices into a flow tuple
     m.add("FLOW_VA", 0)?;
@@ -584,6 +1177,32 @@ fn lancelot(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add("FLOW_TYPE_CONDITIONAL_JUMP", FLOW_CONDITIONAL_JUMP)?;
     m.add("FLOW_TYPE_CONDITIONAL_MOVE", FLOW_CONDITIONAL_MOVE)?;
 
+    // indices into an xref tuple
+    m.add("XREF_FROM", 0)?;
+    m.add("XREF_TO", 1)?;
+    m.add("XREF_TYPE", 2)?;
+
+    // xref types
+    // we use int constants for performance
+    m.add("XREF_TYPE_FALLTHROUGH", XREF_FALLTHROUGH)?;
+    m.add("XREF_TYPE_CALL", XREF_CALL)?;
+    m.add("XREF_TYPE_UNCONDITIONAL_JUMP", XREF_UNCONDITIONAL_JUMP)?;
+    m.add("XREF_TYPE_CONDITIONAL_JUMP", XREF_CONDITIONAL_JUMP)?;
+    m.add("XREF_TYPE_READ", XREF_READ)?;
+    m.add("XREF_TYPE_WRITE", XREF_WRITE)?;
+    m.add("XREF_TYPE_ADDRESS", XREF_ADDRESS)?;
+    m.add("XREF_TYPE_POINTER", XREF_POINTER)?;
+
+    // indices into an argument tuple
+    m.add("ARGUMENT_TYPE", 0)?;
+    m.add("ARGUMENT_VALUE", 1)?;
+    m.add("ARGUMENT_STRING", 2)?;
+
+    // argument types
+    m.add("ARGUMENT_TYPE_CONSTANT", ARGUMENT_CONSTANT)?;
+    m.add("ARGUMENT_TYPE_LOAD", ARGUMENT_LOAD)?;
+    m.add("ARGUMENT_TYPE_STACK", ARGUMENT_STACK)?;
+
     // indices into an operand tuple
     m.add("OPERAND_TYPE", OPERAND_TYPE)?;
     m.add("OPERAND_SIZE", OPERAND_SIZE)?;
@@ -48,6 +48,45 @@ def test_functions(k32):
     assert 0x180020250 in functions
 
 
+def test_exports(k32):
+    ws = lancelot.from_bytes(k32)
+
+    assert "Returns: List[Export]" in ws.get_exports.__doc__
+    exports = ws.get_exports()
+    assert len(exports) == 1621
+
+    by_name = {export.name: export for export in exports}
+
+    forwarded = by_name["AcquireSRWLockExclusive"]
+    assert forwarded.ordinal == 1
+    assert forwarded.address is None
+    assert forwarded.forwarder == "NTDLL.RtlAcquireSRWLockExclusive"
+
+    local = by_name["ActivateActCtx"]
+    assert local.ordinal == 3
+    assert local.address is not None
+    assert local.forwarder is None
+    assert not local.is_data
+
+
+def test_debug_entries(k32):
+    ws = lancelot.from_bytes(k32)
+
+    assert "Returns: List[DebugEntry]" in ws.get_debug_entries.__doc__
+    entries = ws.get_debug_entries()
+    assert [entry.kind for entry in entries] == ["CODEVIEW", "POGO", "REPRO"]
+
+    cv = entries[0]
+    assert cv.pdb_guid == "63816243-EC70-4DC0-91BC-31470BAC48A3"
+    assert cv.pdb_age == 1
+    assert cv.pdb_path == "kernel32.pdb"
+    assert cv.pdb_key == "63816243EC704DC091BC31470BAC48A31"
+
+    pogo = entries[1]
+    assert len(pogo.pogo) == 50
+    assert pogo.pogo[0] == (0x180001000, 0xB50, ".text$lp00kernel32.dll!20_pri7")
+
+
 def test_flow_const():
     assert lancelot.FLOW_TYPE_FALLTHROUGH == 0
     assert lancelot.FLOW_TYPE_CALL == 1
@@ -88,6 +127,38 @@ def test_call_graph(k32):
     assert 0x180060504 in cg.function_call_instructions[0x1800602C0]
 
 
+def test_call_graph_dataflow():
+    # 0x0:  a1 20 00 00 00       mov    eax, [0x20]
+    # 0x5:  ff d0                call   eax
+    # 0x7:  68 24 00 00 00       push   0x24
+    # 0xC:  b9 1c 00 00 00       mov    ecx, 0x1C
+    # 0x11: ff d1                call   ecx
+    # 0x13: 83 c4 04             add    esp, 4
+    # 0x16: c3                   ret
+    # 0x17: 90 90 90 90 90       nop
+    # 0x1C: c3 90 90 90          ret
+    # 0x20: 1c 00 00 00          dd     0x1C
+    # 0x24: 61 62 63 64 00       db     "abcd", 0
+    buf = (
+        b"\xA1\x20\x00\x00\x00\xFF\xD0\x68\x24\x00\x00\x00\xB9\x1C\x00\x00\x00\xFF\xD1\x83\xC4\x04"
+        b"\xC3\x90\x90\x90\x90\x90\xC3\x90\x90\x90\x1C\x00\x00\x00\x61\x62\x63\x64\x00"
+    )
+    ws = lancelot.from_raw_bytes(buf, "x32", base_address=0x0, entry_point=0x0)
+
+    cg = ws.build_call_graph()
+    assert 0x1C in cg.calls_from[0x5]
+    assert 0x1C in cg.calls_from[0x11]
+
+    assert 0x5 not in cg.call_arguments
+    (argument,) = cg.call_arguments[0x11]
+    assert argument[lancelot.ARGUMENT_TYPE] == lancelot.ARGUMENT_TYPE_CONSTANT
+    assert argument[lancelot.ARGUMENT_VALUE] == 0x24
+    assert argument[lancelot.ARGUMENT_STRING] == "abcd"
+
+    xrefs = ws.build_xrefs()
+    assert (0x5, 0x1C, lancelot.XREF_TYPE_CALL) in xrefs.get_xrefs_from(0x5)
+
+
 def test_read_insn(k32):
     ws = lancelot.from_bytes(k32)
 
@@ -157,3 +228,58 @@ def test_probe(k32):
 def test_insn_int(k32):
     ws = lancelot.from_bytes(k32)
     assert int(ws.read_insn(0x1800202B0)) == 0x1800202B0
+
+
+def test_search(k32):
+    ws = lancelot.from_bytes(k32)
+
+    # the DOS header is only found in the headers, which aren't executable.
+    assert ws.search("4D 5A 90 00") == [0x180000000]
+    assert ws.search("4D 5A 90 00", permissions=lancelot.PERMISSION_EXECUTE) == []
+
+    matches = ws.search("48 89 5C 24 ?? 48 89 74 24 ??", permissions=lancelot.PERMISSION_EXECUTE)
+    assert len(matches) > 0
+    assert all(ws.probe(va) & lancelot.PERMISSION_EXECUTE != 0 for va in matches)
+
+    with pytest.raises(ValueError):
+        ws.search("4D 5")
+
+
+def test_xrefs(k32):
+    ws = lancelot.from_bytes(k32)
+    xrefs = ws.build_xrefs()
+    assert len(xrefs) > 0
+
+    # .text:0000000180060561  mov     rax, qword ptr [rip + 0x48900]
+    assert (0x180060561, 0x1800A8E68, lancelot.XREF_TYPE_READ) in xrefs.get_xrefs_to(0x1800A8E68)
+
+    # .text:0000000180060504  call    sub_180001068
+    xref = next(x for x in xrefs.get_xrefs_from(0x180060504) if x[lancelot.XREF_TYPE] == lancelot.XREF_TYPE_CALL)
+    assert xref[lancelot.XREF_TO] == 0x180001068
+
+
+def test_raw():
+    # 0x1000: call 0x1007
+    # 0x1005: jmp  0x1005
+    # 0x1007: ret
+    buf = b"\xE8\x02\x00\x00\x00\xEB\xFE\xC3"
+    ws = lancelot.from_raw_bytes(buf, "x64", base_address=0x1000, entry_point=0x1000)
+
+    assert ws.arch == "x64"
+    assert ws.base_address == 0x1000
+    assert ws.entry_point == 0x1000
+    assert ws.get_functions() == [0x1000, 0x1007]
+    assert ws.read_bytes(0x1007, 1) == b"\xC3"
+    assert ws.read_insn(0x1000).mnemonic == "call"
+    assert ws.probe(0x1000) & lancelot.PERMISSION_EXECUTE != 0
+    assert ws.probe(0x1008) == 0
+
+    ws = lancelot.from_raw_bytes(buf, "x32", permissions=lancelot.PERMISSION_READ)
+    assert ws.entry_point is None
+    assert ws.probe(0x0) & lancelot.PERMISSION_EXECUTE == 0
+
+    with pytest.raises(ValueError):
+        lancelot.from_raw_bytes(buf, "arm64")
+
+    with pytest.raises(ValueError):
+        lancelot.from_raw_bytes(buf, "x64", base_address=0x1000, entry_point=0x0)